@@ -0,0 +1,159 @@
+use std::{fmt, str::FromStr, time::Duration};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// 時間長度：字串 "500ms" / "10s" / "1m30s" / "2h"，或整數（秒）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HumanDuration(pub Duration);
+
+impl HumanDuration {
+    pub const fn from_secs(secs: u64) -> Self {
+        HumanDuration(Duration::from_secs(secs))
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0.is_zero()
+    }
+}
+
+impl From<HumanDuration> for Duration {
+    fn from(d: HumanDuration) -> Self {
+        d.0
+    }
+}
+
+impl FromStr for HumanDuration {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err("empty duration".to_string());
+        }
+        if let Ok(secs) = s.parse::<u64>() {
+            return Ok(HumanDuration::from_secs(secs));
+        }
+
+        let mut total = Duration::ZERO;
+        let mut rest = s;
+        while !rest.is_empty() {
+            let digits = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            if digits == 0 {
+                return Err(format!("invalid duration '{}'", s));
+            }
+            let n: u64 = rest[..digits]
+                .parse()
+                .map_err(|_| format!("invalid duration '{}'", s))?;
+            rest = &rest[digits..];
+            let unit_len = rest
+                .find(|c: char| c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let part = match &rest[..unit_len] {
+                "ms" => Duration::from_millis(n),
+                "s" => Duration::from_secs(n),
+                "m" => Duration::from_secs(n * 60),
+                "h" => Duration::from_secs(n * 3600),
+                "" => return Err(format!("duration '{}' is missing a unit (ms, s, m, h)", s)),
+                u => return Err(format!("unknown duration unit '{}' in '{}'", u, s)),
+            };
+            total += part;
+            rest = &rest[unit_len..];
+        }
+        Ok(HumanDuration(total))
+    }
+}
+
+impl fmt::Display for HumanDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = self.0.as_millis();
+        if ms == 0 {
+            return write!(f, "0s");
+        }
+        if !ms.is_multiple_of(1000) {
+            return write!(f, "{}ms", ms);
+        }
+        let (h, m, s) = (ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60);
+        if h > 0 {
+            write!(f, "{}h", h)?;
+        }
+        if m > 0 {
+            write!(f, "{}m", m)?;
+        }
+        if s > 0 {
+            write!(f, "{}s", s)?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for HumanDuration {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for HumanDuration {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Secs(u64),
+            Text(String),
+        }
+        match Repr::deserialize(d)? {
+            Repr::Secs(n) => Ok(HumanDuration::from_secs(n)),
+            Repr::Text(s) => s.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Duration {
+        s.parse::<HumanDuration>().unwrap().0
+    }
+
+    #[test]
+    fn units() {
+        assert_eq!(parse("500ms"), Duration::from_millis(500));
+        assert_eq!(parse("10s"), Duration::from_secs(10));
+        assert_eq!(parse("1m30s"), Duration::from_secs(90));
+        assert_eq!(parse("2h"), Duration::from_secs(7200));
+        assert_eq!(parse(" 45 "), Duration::from_secs(45));
+        assert_eq!(parse("1s500ms"), Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn invalid() {
+        for (s, why) in [
+            ("", "empty"),
+            ("ms", "invalid duration"),
+            ("10x", "unknown duration unit"),
+            ("1m30", "missing a unit"),
+            ("-5s", "invalid duration"),
+        ] {
+            let e = s.parse::<HumanDuration>().unwrap_err();
+            assert!(e.contains(why), "{s:?}: {e}");
+        }
+    }
+
+    #[test]
+    fn display_roundtrips() {
+        for s in ["0s", "250ms", "1500ms", "45s", "1m30s", "2h", "1h1m1s"] {
+            assert_eq!(s.parse::<HumanDuration>().unwrap().to_string(), s);
+        }
+    }
+
+    #[test]
+    fn deserialize_secs_or_text() {
+        let d: HumanDuration = serde_yaml::from_str("30").unwrap();
+        assert_eq!(d, HumanDuration::from_secs(30));
+        let d: HumanDuration = serde_yaml::from_str("\"1m\"").unwrap();
+        assert_eq!(d, HumanDuration::from_secs(60));
+        assert!(serde_yaml::from_str::<HumanDuration>("\"soon\"").is_err());
+    }
+}
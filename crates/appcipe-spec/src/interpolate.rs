@@ -0,0 +1,392 @@
+use std::collections::HashMap;
+
+use serde_yaml::Value;
+
+/// 由 chefer-runtime 在啟動時代入的變數；`chefer build` 原樣保留，只能用在 service 的 env、cmd、handoff
+pub const RUNTIME_VARS: [&str; 3] = ["CHEFER_DATA_DIR", "CHEFER_EXE_DIR", "HOME"];
+
+/// 查詢變數的結果；`Keep` 連同整個 `${...}` 原樣輸出，留給下一次展開
+pub(crate) enum Var {
+    Set(String),
+    Unset,
+    Keep,
+}
+
+/// 展開 `${VAR}`、`${VAR:-default}`（未設定或空字串時用 default）、`${VAR:?error}`（未設定或空字串時報錯）。
+/// `$$` 是字面的 `$`；其餘的 `$`（包含 `$VAR`）原樣保留，留給容器內的 shell
+pub fn interpolate(s: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String, String> {
+    expand(s, false, &|name| {
+        Ok(lookup(name).map_or(Var::Unset, Var::Set))
+    })
+}
+
+/// `keep_escapes`：保留 `$$`（字串之後還會再展開一次）
+pub(crate) fn expand(
+    s: &str,
+    keep_escapes: bool,
+    lookup: &dyn Fn(&str) -> Result<Var, String>,
+) -> Result<String, String> {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('$') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if rest.starts_with("$$") {
+            out.push_str(if keep_escapes { "$$" } else { "$" });
+            rest = &rest[2..];
+            continue;
+        }
+        if !rest.starts_with("${") {
+            out.push('$');
+            rest = &rest[1..];
+            continue;
+        }
+        let end = closing_brace(rest).ok_or_else(|| format!("unterminated '${{' in '{}'", s))?;
+        let (whole, expr) = (&rest[..=end], &rest[2..end]);
+        rest = &rest[end + 1..];
+
+        let split = expr
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(expr.len());
+        let (name, op) = expr.split_at(split);
+        if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+            return Err(format!("invalid variable name in '{}'", whole));
+        }
+        let (default, required) = match op {
+            "" => (None, None),
+            _ if op.starts_with(":-") => (Some(&op[2..]), None),
+            _ if op.starts_with(":?") => (None, Some(&op[2..])),
+            _ => {
+                return Err(format!(
+                    "unsupported expression '{}' (use ${{VAR}}, ${{VAR:-default}} or ${{VAR:?error}})",
+                    whole
+                ));
+            }
+        };
+        match lookup(name)? {
+            Var::Keep => out.push_str(whole),
+            Var::Set(v) if !v.is_empty() || op.is_empty() => out.push_str(&v),
+            _ => {
+                if let Some(d) = default {
+                    out.push_str(&expand(d, keep_escapes, lookup)?);
+                } else if let Some(msg) = required {
+                    let msg = expand(msg, false, lookup)?;
+                    return Err(if msg.is_empty() {
+                        format!("variable '{}' is required", name)
+                    } else {
+                        format!("variable '{}' is required: {}", name, msg)
+                    });
+                } else {
+                    return Err(format!(
+                        "variable '{}' is not set (write ${{{}:-}} for an empty default)",
+                        name, name
+                    ));
+                }
+            }
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// `s` 以 "${" 開頭；回傳對應的 '}' 位置（default 內可再有 `${...}`）
+fn closing_brace(s: &str) -> Option<usize> {
+    let b = s.as_bytes();
+    let (mut depth, mut i) = (0usize, 0);
+    while i < b.len() {
+        match (b[i], b.get(i + 1)) {
+            (b'$', Some(b'$')) => i += 1,
+            (b'$', Some(b'{')) => {
+                depth += 1;
+                i += 1;
+            }
+            (b'}', _) => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// `chefer build` 時展開 appcipe.yml 中所有字串值：host 的環境變數優先，其次 appcipe.yml 旁的 `.env`。
+/// [`RUNTIME_VARS`] 在 env、cmd、handoff 內原樣保留（連同 `$$`），其他欄位不能使用。
+/// 回傳值被展開過的 service env（service 名稱, env 名稱），這些值來自 build 機器，輸出時遮蔽
+pub(crate) fn interpolate_yaml(
+    v: &mut Value,
+    dotenv: &HashMap<String, String>,
+) -> Result<Vec<(String, String)>, String> {
+    let mut host_env = Vec::new();
+    walk(v, &mut Vec::new(), dotenv, &mut host_env)?;
+    Ok(host_env)
+}
+
+fn walk(
+    v: &mut Value,
+    path: &mut Vec<String>,
+    dotenv: &HashMap<String, String>,
+    host_env: &mut Vec<(String, String)>,
+) -> Result<(), String> {
+    match v {
+        Value::String(s) => {
+            let runtime = runtime_field(path);
+            let lookup = |name: &str| {
+                if RUNTIME_VARS.contains(&name) {
+                    if runtime {
+                        return Ok(Var::Keep);
+                    }
+                    let hint = if is_mount(path) {
+                        "; mounts use anchors instead ($DATA_DIR/..., $EXE_DIR/..., $HOME/...)"
+                    } else {
+                        ""
+                    };
+                    return Err(format!(
+                        "'${{{}}}' is only known when the app starts; it can be used in a service's env, cmd and handoff{}",
+                        name, hint
+                    ));
+                }
+                Ok(std::env::var(name)
+                    .ok()
+                    .or_else(|| dotenv.get(name).cloned())
+                    .map_or(Var::Unset, Var::Set))
+            };
+            let expanded =
+                expand(s, runtime, &lookup).map_err(|e| format!("{}: {}", path.join("."), e))?;
+            if let [_, svc, f, key] = path.as_slice()
+                && runtime
+                && f == "env"
+                && expanded != *s
+            {
+                host_env.push((svc.clone(), key.clone()));
+            }
+            *s = expanded;
+        }
+        Value::Sequence(items) => {
+            for (i, item) in items.iter_mut().enumerate() {
+                path.push(i.to_string());
+                walk(item, path, dotenv, host_env)?;
+                path.pop();
+            }
+        }
+        Value::Mapping(m) => {
+            for (k, item) in m.iter_mut() {
+                path.push(
+                    k.as_str()
+                        .map_or_else(|| format!("{:?}", k), str::to_string),
+                );
+                walk(item, path, dotenv, host_env)?;
+                path.pop();
+            }
+        }
+        Value::Tagged(t) => walk(&mut t.value, path, dotenv, host_env)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// services.<name>.env / cmd / handoff：啟動時 runtime 會再展開一次
+fn runtime_field(path: &[String]) -> bool {
+    matches!(path, [s, _, f, ..] if s == "services" && matches!(f.as_str(), "env" | "cmd" | "handoff"))
+}
+
+fn is_mount(path: &[String]) -> bool {
+    matches!(path, [s, _, f, ..] if s == "services" && f == "mounts")
+}
+
+/// `.env` 與 env_file 的格式：每行 `KEY=VALUE`；空行與 `#` 開頭的行略過，可加 `export ` 前綴。
+/// 值可用單引號（原樣）或雙引號（支援 \n \t \" \\）包住；沒有引號時 ` #` 之後是註解
+pub fn parse_env_file(text: &str) -> Result<Vec<(String, String)>, String> {
+    let mut vars = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(format!("line {}: expected KEY=VALUE", n + 1));
+        };
+        let key = key.trim();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return Err(format!("line {}: invalid variable name '{}'", n + 1, key));
+        }
+        let value = value.trim();
+        let value = if let Some(v) = value.strip_prefix('\'') {
+            v.strip_suffix('\'')
+                .ok_or_else(|| format!("line {}: unterminated single quote", n + 1))?
+                .to_string()
+        } else if let Some(v) = value.strip_prefix('"') {
+            unescape(
+                v.strip_suffix('"')
+                    .ok_or_else(|| format!("line {}: unterminated double quote", n + 1))?,
+            )
+        } else {
+            value
+                .split_once(" #")
+                .map_or(value, |(v, _)| v.trim_end())
+                .to_string()
+        };
+        vars.push((key.to_string(), value));
+    }
+    Ok(vars)
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some(c @ ('"' | '\\' | '$')) => out.push(c),
+            Some(c) => {
+                out.push('\\');
+                out.push(c);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(name: &str) -> Option<String> {
+        match name {
+            "USER" => Some("alice".into()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn expands_variables() {
+        let cases = [
+            ("hi ${USER}", "hi alice"),
+            ("${MISSING:-guest}", "guest"),
+            ("${EMPTY:-fallback}", "fallback"),
+            ("[${EMPTY}]", "[]"),
+            ("${MISSING:-${USER}}", "alice"),
+            ("${MISSING:-}", ""),
+            ("$$HOME and $$${USER}", "$HOME and $alice"),
+            ("$PATH stays", "$PATH stays"),
+        ];
+        for (input, want) in cases {
+            assert_eq!(interpolate(input, vars).unwrap(), want, "{input}");
+        }
+    }
+
+    #[test]
+    fn reports_errors() {
+        let cases = [
+            ("${MISSING}", "is not set"),
+            ("${MISSING:?set it}", "is required: set it"),
+            ("${EMPTY:?}", "'EMPTY' is required"),
+            ("${USER", "unterminated"),
+            ("${1X}", "invalid variable name"),
+            ("${USER/x/y}", "unsupported expression"),
+        ];
+        for (input, why) in cases {
+            let e = interpolate(input, vars).unwrap_err();
+            assert!(e.contains(why), "{input}: {e}");
+        }
+    }
+
+    #[test]
+    fn env_file_format() {
+        let text = r#"
+# comment
+export A=1
+B = two words # trailing comment
+C='single $quoted # kept'
+D="line\nbreak \"q\""
+E=
+"#;
+        let vars = parse_env_file(text).unwrap();
+        let want = [
+            ("A", "1"),
+            ("B", "two words"),
+            ("C", "single $quoted # kept"),
+            ("D", "line\nbreak \"q\""),
+            ("E", ""),
+        ];
+        let got: Vec<(&str, &str)> = vars.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+        assert_eq!(got, want);
+
+        assert!(parse_env_file("NOVALUE").unwrap_err().contains("line 1"));
+        assert!(parse_env_file("A='x").unwrap_err().contains("unterminated"));
+        assert!(
+            parse_env_file("BAD KEY=1")
+                .unwrap_err()
+                .contains("invalid variable name")
+        );
+    }
+
+    const YAML: &str = r#"
+version: "0.1"
+name: App
+services:
+  web:
+    image: "${IMAGE_DIR}/web.tar"
+    cmd: "serve --root ${CHEFER_DATA_DIR} --cost $$5"
+    env_file: extra.env
+    env:
+      GREETING: "hello ${WHO:-world}"
+      DATA: "${CHEFER_DATA_DIR}/web"
+      LITERAL: plain
+"#;
+
+    #[test]
+    fn appcipe_yml_uses_dotenv_and_keeps_runtime_vars() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(".env"), "IMAGE_DIR=/images\n").unwrap();
+        std::fs::write(
+            dir.path().join("extra.env"),
+            "PRICE=$5\nLITERAL=from file\n",
+        )
+        .unwrap();
+        let app = crate::from_str_with_base(YAML, dir.path()).unwrap();
+        let web = &app.services["web"];
+
+        assert!(
+            matches!(&web.image, crate::ImageSourceOrPath::TarPath(p) if p == "/images/web.tar")
+        );
+        assert!(
+            matches!(&web.cmd, Some(crate::Cmd::String(c)) if c == "serve --root ${CHEFER_DATA_DIR} --cost $$5")
+        );
+        assert_eq!(web.env["GREETING"], "hello world");
+        assert_eq!(web.env["DATA"], "${CHEFER_DATA_DIR}/web");
+        // env 優先於 env_file；env_file 的值是字面值
+        assert_eq!(web.env["LITERAL"], "plain");
+        assert_eq!(web.env["PRICE"], "$$5");
+
+        // 值來自 build 機器的 env（輸出時遮蔽）
+        let mut host: Vec<&str> = web.host_env.iter().map(String::as_str).collect();
+        host.sort();
+        assert_eq!(host, ["GREETING", "PRICE"]);
+    }
+
+    #[test]
+    fn runtime_vars_only_in_runtime_fields() {
+        let yaml = YAML.replace("${IMAGE_DIR}", "${CHEFER_EXE_DIR}");
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("extra.env"), "").unwrap();
+        let e = crate::from_str_with_base(&yaml, dir.path())
+            .unwrap_err()
+            .to_string();
+        assert!(
+            e.contains("services.web.image") && e.contains("only known when the app starts"),
+            "{e}"
+        );
+    }
+}
@@ -1,6 +1,26 @@
+mod duration;
+mod interpolate;
+mod mount;
 mod parse;
+mod ports;
+mod secret;
+mod signal;
+mod size;
 mod types;
 mod validate;
 
+pub use duration::HumanDuration;
+pub use interpolate::{RUNTIME_VARS, interpolate, parse_env_file};
+pub use mount::{Mount, PathAnchor, path_anchor};
 pub use parse::*;
+pub use ports::{DEFAULT_BIND, PortMapping, Protocol, parse_ports};
+pub use secret::{
+    DEFAULT_SECRET_LENGTH, REDACTED, SECRETS_DIR, Secret, ServiceSecret, is_sensitive_env,
+};
+pub use signal::{DEFAULT_STOP_GRACE_PERIOD, DEFAULT_STOP_SIGNAL, normalize_signal, signal_number};
+pub use size::ByteSize;
 pub use types::*;
+pub use validate::{
+    dependency_order, validate_healthcheck, validate_level, validate_mounts, validate_ports,
+    validate_resources, validate_secrets, validate_service_secrets, validate_web,
+};
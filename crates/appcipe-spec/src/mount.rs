@@ -0,0 +1,239 @@
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// mounts 的一項：`"<host>:<container>[:ro][,create]"`，或 `{ source, target, read_only, create }`。
+/// host 路徑在啟動時才解析，可用錨點開頭（見 [`PathAnchor`]）；相對路徑以執行檔所在目錄為準
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Mount {
+    pub source: String,
+    pub target: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub read_only: bool,
+    /// host 路徑不存在時建立資料夾（否則啟動前報錯）
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub create: bool,
+}
+
+/// 使用者機器上路徑（mounts 的 host 路徑、secrets 的 file）開頭的錨點
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathAnchor {
+    /// `$EXE_DIR`：執行檔所在目錄（沒有錨點的相對路徑也是）
+    ExeDir,
+    /// `$DATA_DIR`：app 資料夾
+    DataDir,
+    /// `$HOME` 或 `~`：使用者的家目錄
+    Home,
+    /// `$CWD`：啟動時的工作目錄
+    Cwd,
+}
+
+const ANCHORS: [(&str, PathAnchor); 4] = [
+    ("$EXE_DIR", PathAnchor::ExeDir),
+    ("$DATA_DIR", PathAnchor::DataDir),
+    ("$HOME", PathAnchor::Home),
+    ("$CWD", PathAnchor::Cwd),
+];
+
+impl Mount {
+    /// host 路徑的錨點與其後的路徑，見 [`path_anchor`]
+    pub fn anchor(&self) -> Result<(Option<PathAnchor>, &str), String> {
+        path_anchor(&self.source).map_err(|e| format!("mount '{}': {}", self, e))
+    }
+}
+
+/// 拆出錨點與其後的路徑（不含開頭的分隔符號）；絕對路徑沒有錨點
+pub fn path_anchor(s: &str) -> Result<(Option<PathAnchor>, &str), String> {
+    if s == "~" || s.starts_with("~/") {
+        return Ok((Some(PathAnchor::Home), s[1..].trim_start_matches('/')));
+    }
+    if s.starts_with('$') {
+        let end = s.find(['/', '\\']).unwrap_or(s.len());
+        return match ANCHORS.iter().find(|(a, _)| *a == &s[..end]) {
+            Some((_, anchor)) => Ok((Some(*anchor), s[end..].trim_start_matches(['/', '\\']))),
+            None => Err(format!(
+                "unknown anchor '{}' (use $EXE_DIR, $DATA_DIR, $HOME or $CWD)",
+                &s[..end]
+            )),
+        };
+    }
+    if is_absolute(s) {
+        Ok((None, s))
+    } else {
+        Ok((Some(PathAnchor::ExeDir), s.trim_start_matches("./")))
+    }
+}
+
+/// 也接受 Windows 的 "C:\"、"C:/"
+fn is_absolute(s: &str) -> bool {
+    let b = s.as_bytes();
+    s.starts_with(['/', '\\'])
+        || (b.len() >= 3
+            && b[0].is_ascii_alphabetic()
+            && b[1] == b':'
+            && matches!(b[2], b'/' | b'\\'))
+}
+
+impl std::str::FromStr for Mount {
+    type Err = String;
+
+    /// 從右往左切，避免 Windows 的 "C:\"；最後一段全是選項（ro、rw、create）時當作選項
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "invalid mount '{}' (expected \"<host>:<container>[:ro][,create]\")",
+                s
+            )
+        };
+        let (mut rest, mut opts) = (s, "");
+        if let Some((r, last)) = s.rsplit_once(':')
+            && r.contains(':')
+            && last
+                .split(',')
+                .all(|o| matches!(o.trim(), "ro" | "rw" | "create"))
+        {
+            (rest, opts) = (r, last);
+        }
+        let (source, target) = rest.rsplit_once(':').ok_or_else(invalid)?;
+        if source.is_empty() || target.is_empty() {
+            return Err(invalid());
+        }
+        let mut m = Mount {
+            source: source.to_string(),
+            target: target.to_string(),
+            read_only: false,
+            create: false,
+        };
+        for o in opts.split(',').map(str::trim).filter(|o| !o.is_empty()) {
+            match o {
+                "ro" => m.read_only = true,
+                "rw" => m.read_only = false,
+                _ => m.create = true,
+            }
+        }
+        Ok(m)
+    }
+}
+
+impl fmt::Display for Mount {
+    /// 短寫法："./presets:/app/presets:ro,create"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.source, self.target)?;
+        match (self.read_only, self.create) {
+            (false, false) => Ok(()),
+            (true, false) => write!(f, ":ro"),
+            (false, true) => write!(f, ":create"),
+            (true, true) => write!(f, ":ro,create"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Mount {
+    /// 不用 untagged：長寫法缺欄位、拼錯欄位時保留 serde 的錯誤訊息
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Full {
+            source: String,
+            target: String,
+            #[serde(default)]
+            read_only: bool,
+            #[serde(default)]
+            create: bool,
+        }
+        struct V;
+        impl<'de> serde::de::Visitor<'de> for V {
+            type Value = Mount;
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str(
+                    "\"<host>:<container>[:ro][,create]\" or { source, target, read_only, create }",
+                )
+            }
+            fn visit_str<E: serde::de::Error>(self, s: &str) -> Result<Mount, E> {
+                s.parse().map_err(E::custom)
+            }
+            fn visit_map<A: serde::de::MapAccess<'de>>(self, map: A) -> Result<Mount, A::Error> {
+                let f = Full::deserialize(serde::de::value::MapAccessDeserializer::new(map))?;
+                Ok(Mount {
+                    source: f.source,
+                    target: f.target,
+                    read_only: f.read_only,
+                    create: f.create,
+                })
+            }
+        }
+        d.deserialize_any(V)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anchors() {
+        let cases = [
+            ("$DATA_DIR/db", Some(PathAnchor::DataDir), "db"),
+            ("$EXE_DIR", Some(PathAnchor::ExeDir), ""),
+            ("$HOME/Documents", Some(PathAnchor::Home), "Documents"),
+            ("~/Documents", Some(PathAnchor::Home), "Documents"),
+            ("~", Some(PathAnchor::Home), ""),
+            ("$CWD\\in", Some(PathAnchor::Cwd), "in"),
+            ("./presets", Some(PathAnchor::ExeDir), "presets"),
+            ("presets", Some(PathAnchor::ExeDir), "presets"),
+            ("/srv/data", None, "/srv/data"),
+            ("C:\\data", None, "C:\\data"),
+        ];
+        for (s, anchor, rest) in cases {
+            assert_eq!(path_anchor(s).unwrap(), (anchor, rest), "{s}");
+        }
+        let e = path_anchor("$TMP/x").unwrap_err();
+        assert!(e.contains("unknown anchor '$TMP'"), "{e}");
+        // `~user` 不是錨點，當成相對路徑
+        assert_eq!(
+            path_anchor("~bob").unwrap(),
+            (Some(PathAnchor::ExeDir), "~bob")
+        );
+    }
+
+    #[test]
+    fn short_form() {
+        let m: Mount = "./presets:/app/presets:ro,create".parse().unwrap();
+        assert_eq!(
+            m,
+            Mount {
+                source: "./presets".into(),
+                target: "/app/presets".into(),
+                read_only: true,
+                create: true,
+            }
+        );
+        assert_eq!(m.to_string(), "./presets:/app/presets:ro,create");
+
+        let m: Mount = "C:\\data:/data".parse().unwrap();
+        assert_eq!(
+            (m.source.as_str(), m.target.as_str()),
+            ("C:\\data", "/data")
+        );
+        assert!(!m.read_only);
+
+        for bad in ["/only", ":/x", "/x:"] {
+            assert!(
+                bad.parse::<Mount>().unwrap_err().contains("invalid mount"),
+                "{bad}"
+            );
+        }
+    }
+
+    #[test]
+    fn long_form() {
+        let m: Mount =
+            serde_yaml::from_str("{ source: $DATA_DIR/db, target: /var/lib/db, create: true }")
+                .unwrap();
+        assert_eq!(m.to_string(), "$DATA_DIR/db:/var/lib/db:create");
+        assert_eq!(m.anchor().unwrap(), (Some(PathAnchor::DataDir), "db"));
+        let e =
+            serde_yaml::from_str::<Mount>("{ source: a, target: b, readonly: true }").unwrap_err();
+        assert!(e.to_string().contains("unknown field `readonly`"), "{e}");
+    }
+}
@@ -1,6 +1,12 @@
+use crate::interpolate::{interpolate_yaml, parse_env_file};
 use crate::types::*;
+use anyhow::anyhow;
+use std::collections::HashMap;
 use std::path::Path;
 
+/// appcipe.yml 旁的變數檔（`${VAR}` 在 host 環境變數中找不到時的來源）
+pub const DOTENV_FILE: &str = ".env";
+
 pub fn from_file<P: AsRef<Path>>(path: P) -> anyhow::Result<AppCipe> {
     let path = path.as_ref();
     let base = path.parent().unwrap_or_else(|| Path::new("."));
@@ -14,13 +20,62 @@ pub fn from_str(yaml: &str) -> anyhow::Result<AppCipe> {
 }
 
 pub fn from_str_with_base<P: AsRef<Path>>(yaml: &str, base: P) -> anyhow::Result<AppCipe> {
-    let mut app: AppCipe = serde_yaml::from_str(yaml)?;
-    normalize_paths_in_place(&mut app, base.as_ref())?;
+    let base = base.as_ref();
+    // 沒有 `$` 就不必經過 Value（保留 serde_yaml 錯誤訊息中的行號）
+    let mut app: AppCipe = if yaml.contains('$') {
+        let mut value: serde_yaml::Value = serde_yaml::from_str(yaml)?;
+        let dotenv = read_dotenv(base)?;
+        let host_env = interpolate_yaml(&mut value, &dotenv)
+            .map_err(|e| anyhow!("Interpolation error: {e}"))?;
+        let mut app: AppCipe = serde_yaml::from_value(value)?;
+        for (name, key) in host_env {
+            if let Some(svc) = app.services.get_mut(&name) {
+                svc.host_env.insert(key);
+            }
+        }
+        app
+    } else {
+        serde_yaml::from_str(yaml)?
+    };
+    normalize_paths_in_place(&mut app, base)?;
+    merge_env_files(&mut app)?;
     app.validate()
         .map_err(|e| anyhow::anyhow!("Validation error: {e}"))?;
     Ok(app)
 }
 
+fn read_dotenv(base: &Path) -> anyhow::Result<HashMap<String, String>> {
+    let path = base.join(DOTENV_FILE);
+    if !path.is_file() {
+        return Ok(HashMap::new());
+    }
+    let text = std::fs::read_to_string(&path).map_err(|e| anyhow!("{}: {e}", path.display()))?;
+    let vars = parse_env_file(&text).map_err(|e| anyhow!("{}: {e}", path.display()))?;
+    Ok(vars.into_iter().collect())
+}
+
+/// env_file 依序讀入（後面的檔案覆蓋前面的），`env` 中同名的優先。
+/// 檔案內的值是字面值：`$` 轉成 `$$`，runtime 展開時不會當成變數；值來自 build 機器，輸出時遮蔽
+fn merge_env_files(app: &mut AppCipe) -> anyhow::Result<()> {
+    for (name, svc) in app.services.iter_mut() {
+        let mut vars = HashMap::new();
+        for f in svc.env_file.paths() {
+            let text = std::fs::read_to_string(f)
+                .map_err(|e| anyhow!("service '{name}' env_file {f}: {e}"))?;
+            vars.extend(
+                parse_env_file(&text).map_err(|e| anyhow!("service '{name}' env_file {f}: {e}"))?,
+            );
+        }
+        for (k, v) in vars {
+            if !svc.env.contains_key(&k) {
+                svc.env.insert(k.clone(), v.replace('$', "$$"));
+                svc.host_env.insert(k);
+            }
+        }
+    }
+    Ok(())
+}
+
 fn to_abs(base: &Path, p: &str) -> String {
     let pb = Path::new(p);
     if pb.is_absolute() {
@@ -31,10 +86,7 @@ fn to_abs(base: &Path, p: &str) -> String {
 }
 
 fn normalize_paths_in_place(app: &mut AppCipe, base: &Path) -> anyhow::Result<()> {
-    // data_dir（Host 路徑）
-    if let Some(dir) = &app.data_dir {
-        app.data_dir = Some(to_abs(base, dir));
-    }
+    // data_dir 是「使用者機器」上的路徑，原樣保留給 runtime 解析（相對路徑以 exe 所在目錄為準）
 
     for (_name, svc) in app.services.iter_mut() {
         // image.file（Host 路徑；僅 source=tar 或 TarPath）
@@ -49,24 +101,12 @@ fn normalize_paths_in_place(app: &mut AppCipe, base: &Path) -> anyhow::Result<()
             }
         }
 
-        // mounts：只轉左半邊（Host 路徑）。用 rsplitn(2, ':') 以相容 Windows 的 "C:\"
-        // 形如 "host_path:container_path"
-        for m in &mut svc.mounts {
-            if let Some((host, guest)) = split_mount(m) {
-                let new_host = to_abs(base, host);
-                *m = format!("{new_host}:{guest}");
-            }
+        match &mut svc.env_file {
+            EnvFiles::One(p) => *p = to_abs(base, p),
+            EnvFiles::Many(ps) => ps.iter_mut().for_each(|p| *p = to_abs(base, p)),
         }
 
-        // 注意：persist_path 是容器內路徑，不轉！
+        // 注意：persist_path 是容器內路徑，不轉！mounts 的 host 路徑在使用者機器上，由 runtime 啟動時解析
     }
     Ok(())
 }
-
-fn split_mount(s: &str) -> Option<(&str, &str)> {
-    // 從右往左找一次 ':'，避免 Windows drive "C:\"
-    let mut it = s.rsplitn(2, ':');
-    let right = it.next()?;
-    let left = it.next()?;
-    Some((left, right))
-}
@@ -0,0 +1,209 @@
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr},
+};
+
+/// 未指定綁定位址時只開在本機
+pub const DEFAULT_BIND: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+impl Protocol {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Protocol::Tcp => "tcp",
+            Protocol::Udp => "udp",
+        }
+    }
+}
+
+/// `ports` 的一項展開後的單一埠對應
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PortMapping {
+    /// host 上的綁定位址
+    pub bind: IpAddr,
+    /// host 埠；0 = 由系統挑一個空的
+    pub host: u16,
+    /// service 在容器內聽的埠
+    pub guest: u16,
+    pub proto: Protocol,
+}
+
+impl fmt::Display for PortMapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.bind {
+            IpAddr::V6(ip) => write!(f, "[{}]", ip)?,
+            IpAddr::V4(ip) => write!(f, "{}", ip)?,
+        }
+        write!(f, ":{}->{}/{}", self.host, self.guest, self.proto.as_str())
+    }
+}
+
+impl PortMapping {
+    /// 兩個對應是否會搶同一個 host 埠（0 不會衝突；0.0.0.0 / :: 與任何位址衝突）
+    pub fn conflicts_with(&self, other: &PortMapping) -> bool {
+        self.host != 0
+            && self.host == other.host
+            && self.proto == other.proto
+            && (self.bind == other.bind
+                || self.bind.is_unspecified()
+                || other.bind.is_unspecified())
+    }
+}
+
+/// 解析 `[ip:][host[-end]:]guest[-end][/tcp|udp]`：
+/// - "8080" → host 與容器同埠
+/// - "127.0.0.1::80" → host 埠由系統挑選
+/// - "[::1]:8080:80"、"0.0.0.0:9000-9001:9000-9001/udp"
+pub fn parse_ports(spec: &str) -> Result<Vec<PortMapping>, String> {
+    let err = |why: &str| format!("invalid port '{}': {}", spec, why);
+
+    let (rest, proto) = match spec.rsplit_once('/') {
+        Some((r, "tcp")) => (r, Protocol::Tcp),
+        Some((r, "udp")) => (r, Protocol::Udp),
+        Some((_, p)) => return Err(err(&format!("unknown protocol '{}' (tcp or udp)", p))),
+        None => (spec, Protocol::Tcp),
+    };
+
+    // IPv6 位址要加方括號，其餘以 ':' 切
+    let (bind, rest) = if let Some(v6) = rest.strip_prefix('[') {
+        let (ip, r) = v6.split_once(']').ok_or_else(|| err("missing ']'"))?;
+        let r = r
+            .strip_prefix(':')
+            .ok_or_else(|| err("expected ':' after the address"))?;
+        (Some(ip), r)
+    } else {
+        match rest.matches(':').count() {
+            0 | 1 => (None, rest),
+            2 => {
+                let (ip, r) = rest.split_once(':').unwrap();
+                (Some(ip), r)
+            }
+            _ => return Err(err("IPv6 addresses must be written in brackets")),
+        }
+    };
+    let bind = match bind {
+        Some(ip) => ip
+            .parse::<IpAddr>()
+            .map_err(|_| err(&format!("'{}' is not an IP address", ip)))?,
+        None => DEFAULT_BIND,
+    };
+
+    let (host, guest) = match rest.split_once(':') {
+        Some((h, g)) => (Some(h), g),
+        None => (None, rest),
+    };
+    let guest = range(guest).map_err(|e| err(&e))?;
+    let host = match host {
+        None => guest,
+        Some("") => (0, 0),
+        Some(h) => range(h).map_err(|e| err(&e))?,
+    };
+    let len = guest.1 - guest.0;
+    if host != (0, 0) && host.1 - host.0 != len {
+        return Err(err(
+            "host and container port ranges must be the same length",
+        ));
+    }
+
+    Ok((0..=len)
+        .map(|i| PortMapping {
+            bind,
+            host: if host.0 == 0 { 0 } else { host.0 + i },
+            guest: guest.0 + i,
+            proto,
+        })
+        .collect())
+}
+
+fn range(s: &str) -> Result<(u16, u16), String> {
+    let port = |p: &str| match p.parse::<u16>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!("'{}' is not a port between 1 and 65535", p)),
+    };
+    match s.split_once('-') {
+        Some((a, b)) => {
+            let (a, b) = (port(a)?, port(b)?);
+            if a > b {
+                return Err(format!("range '{}' is reversed", s));
+            }
+            Ok((a, b))
+        }
+        None => port(s).map(|p| (p, p)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn one(spec: &str) -> PortMapping {
+        let v = parse_ports(spec).unwrap();
+        assert_eq!(v.len(), 1, "{spec}");
+        v[0]
+    }
+
+    #[test]
+    fn short_forms() {
+        assert_eq!(one("8080").to_string(), "127.0.0.1:8080->8080/tcp");
+        assert_eq!(one("9000:80").to_string(), "127.0.0.1:9000->80/tcp");
+        assert_eq!(one("127.0.0.1::80").host, 0);
+        assert_eq!(one("53/udp").proto, Protocol::Udp);
+    }
+
+    #[test]
+    fn bind_addresses() {
+        assert_eq!(one("0.0.0.0:8080:80").to_string(), "0.0.0.0:8080->80/tcp");
+        assert_eq!(one("[::1]:8080:80").to_string(), "[::1]:8080->80/tcp");
+        assert!(parse_ports("::1:8080:80").unwrap_err().contains("brackets"));
+        assert!(
+            parse_ports("localhost:8080:80")
+                .unwrap_err()
+                .contains("not an IP")
+        );
+    }
+
+    #[test]
+    fn ranges() {
+        let v = parse_ports("0.0.0.0:9000-9002:7000-7002/udp").unwrap();
+        let pairs: Vec<(u16, u16)> = v.iter().map(|m| (m.host, m.guest)).collect();
+        assert_eq!(pairs, [(9000, 7000), (9001, 7001), (9002, 7002)]);
+        assert!(v.iter().all(|m| m.proto == Protocol::Udp));
+        // host 埠由系統挑選時每個都是 0
+        assert!(
+            parse_ports("127.0.0.1::80-81")
+                .unwrap()
+                .iter()
+                .all(|m| m.host == 0)
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        for (spec, why) in [
+            ("0", "between 1 and 65535"),
+            ("70000", "between 1 and 65535"),
+            ("80/sctp", "unknown protocol"),
+            ("90-80", "reversed"),
+            ("9000-9001:80", "same length"),
+            ("[::1:80", "missing ']'"),
+        ] {
+            let e = parse_ports(spec).unwrap_err();
+            assert!(e.contains(why), "{spec}: {e}");
+        }
+    }
+
+    #[test]
+    fn conflicts() {
+        let a = one("127.0.0.1:8080:80");
+        assert!(a.conflicts_with(&one("0.0.0.0:8080:81")));
+        assert!(!a.conflicts_with(&one("127.0.0.2:8080:80")));
+        assert!(!a.conflicts_with(&one("127.0.0.1:8080:80/udp")));
+        let any = one("127.0.0.1::80");
+        assert!(!any.conflicts_with(&any));
+    }
+}
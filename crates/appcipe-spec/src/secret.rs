@@ -0,0 +1,210 @@
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::AppCipe;
+
+/// 沒有指定 target 時，secret 以檔案掛在容器內的這個資料夾
+pub const SECRETS_DIR: &str = "/run/secrets";
+
+/// `generate` 的預設長度（英數字）
+pub const DEFAULT_SECRET_LENGTH: u32 = 32;
+
+/// 輸出時代替機密值的字串
+pub const REDACTED: &str = "***";
+
+/// env 名稱含這些字（不分大小寫）就當成機密
+const SENSITIVE_ENV_WORDS: [&str; 9] = [
+    "PASSWORD",
+    "PASSWD",
+    "PASSPHRASE",
+    "SECRET",
+    "TOKEN",
+    "CREDENTIAL",
+    "API_KEY",
+    "ACCESS_KEY",
+    "PRIVATE_KEY",
+];
+
+/// env 名稱看起來是機密（`POSTGRES_PASSWORD`、`GITHUB_TOKEN`…）；`*_FILE` 是機密檔案的路徑，不算
+pub fn is_sensitive_env(key: &str) -> bool {
+    let key = key.to_ascii_uppercase();
+    !key.ends_with("_FILE") && SENSITIVE_ENV_WORDS.iter().any(|w| key.contains(w))
+}
+
+/// 頂層 secrets 的一項：值的來源（generate、file、prompt 三擇一）。
+/// 值只在啟動時由 runtime 取得，不會寫進 bundle
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Secret {
+    /// 第一次啟動時產生隨機字串，存在 app 資料夾，之後沿用
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub generate: bool,
+
+    /// generate 的長度（預設 32）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub length: Option<u32>,
+
+    /// 使用者機器上的檔案；可用 mounts 的錨點（`$HOME/...`），相對路徑以執行檔所在目錄為準
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+
+    /// 第一次啟動時在終端機詢問（這段文字是提示），存在 app 資料夾，之後沿用
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+}
+
+impl fmt::Display for Secret {
+    /// 來源的簡短說明（"generated"、"file ~/key"、"prompt"）
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.file, &self.prompt) {
+            (Some(file), _) => write!(f, "file {}", file),
+            (_, Some(_)) => write!(f, "prompt"),
+            _ => write!(f, "generated"),
+        }
+    }
+}
+
+/// service 的 secrets 一項：名稱（檔案 `/run/secrets/<name>`），
+/// 或 `{ source, target }`（檔案掛在 target）、`{ source, env }`（設成環境變數）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ServiceSecret {
+    pub source: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env: Option<String>,
+}
+
+impl ServiceSecret {
+    /// 以檔案提供時在容器內的路徑；以 env 提供時為 None
+    pub fn file_target(&self) -> Option<String> {
+        match (&self.env, &self.target) {
+            (Some(_), _) => None,
+            (None, Some(t)) => Some(t.clone()),
+            (None, None) => Some(format!("{}/{}", SECRETS_DIR, self.source)),
+        }
+    }
+}
+
+impl fmt::Display for ServiceSecret {
+    /// "db_password"、"db_password → /etc/pw"、"db_password → $POSTGRES_PASSWORD"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.env, &self.target) {
+            (Some(env), _) => write!(f, "{} → ${}", self.source, env),
+            (None, Some(t)) => write!(f, "{} → {}", self.source, t),
+            (None, None) => write!(f, "{}", self.source),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ServiceSecret {
+    /// 同 Mount：不用 untagged，保留長寫法的錯誤訊息
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Full {
+            source: String,
+            #[serde(default)]
+            target: Option<String>,
+            #[serde(default)]
+            env: Option<String>,
+        }
+        struct V;
+        impl<'de> serde::de::Visitor<'de> for V {
+            type Value = ServiceSecret;
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a secret name, { source, target } or { source, env }")
+            }
+            fn visit_str<E: serde::de::Error>(self, s: &str) -> Result<ServiceSecret, E> {
+                Ok(ServiceSecret {
+                    source: s.to_string(),
+                    target: None,
+                    env: None,
+                })
+            }
+            fn visit_map<A: serde::de::MapAccess<'de>>(
+                self,
+                map: A,
+            ) -> Result<ServiceSecret, A::Error> {
+                let f = Full::deserialize(serde::de::value::MapAccessDeserializer::new(map))?;
+                Ok(ServiceSecret {
+                    source: f.source,
+                    target: f.target,
+                    env: f.env,
+                })
+            }
+        }
+        d.deserialize_any(V)
+    }
+}
+
+impl AppCipe {
+    /// 給人看或複製進 bundle 的 appcipe：env 中名稱像機密的、值來自 build 機器的（`${VAR}`、env_file）換成 `***`
+    pub fn redacted(&self) -> serde_yaml::Result<serde_yaml::Value> {
+        let mut v = serde_yaml::to_value(self)?;
+        for (name, svc) in &self.services {
+            let Some(env) = v["services"][name.as_str()]
+                .get_mut("env")
+                .and_then(|e| e.as_mapping_mut())
+            else {
+                continue;
+            };
+            for (k, val) in env.iter_mut() {
+                if k.as_str().is_some_and(|k| svc.hides_env(k)) {
+                    *val = REDACTED.into();
+                }
+            }
+        }
+        Ok(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YAML: &str = r#"
+version: "0.1"
+name: App
+services:
+  db:
+    image: db.tar
+    env:
+      POSTGRES_PASSWORD: pw
+      POSTGRES_PASSWORD_FILE: /run/secrets/pw
+      DB_USER: "${APPCIPE_TEST_UNSET_USER:-bob}"
+      DATA: "${CHEFER_DATA_DIR}/db"
+      PLAIN: plain
+"#;
+
+    #[test]
+    fn sensitive_env_names() {
+        for k in [
+            "POSTGRES_PASSWORD",
+            "github_token",
+            "AWS_SECRET_ACCESS_KEY",
+            "STRIPE_API_KEY",
+            "db_passwd",
+        ] {
+            assert!(is_sensitive_env(k), "{k}");
+        }
+        for k in ["POSTGRES_PASSWORD_FILE", "PATH", "DB_USER", "KEYBOARD"] {
+            assert!(!is_sensitive_env(k), "{k}");
+        }
+    }
+
+    #[test]
+    fn redacted_hides_sensitive_and_interpolated_env() {
+        let app = crate::from_str_with_base(YAML, "/nonexistent").unwrap();
+        let v = app.redacted().unwrap();
+        let env = &v["services"]["db"]["env"];
+        assert_eq!(env["POSTGRES_PASSWORD"], REDACTED);
+        assert_eq!(env["DB_USER"], REDACTED);
+        assert_eq!(env["POSTGRES_PASSWORD_FILE"], "/run/secrets/pw");
+        assert_eq!(env["DATA"], "${CHEFER_DATA_DIR}/db");
+        assert_eq!(env["PLAIN"], "plain");
+        // 只遮蔽輸出，runtime 用的值不變
+        assert_eq!(app.services["db"].env["DB_USER"], "bob");
+    }
+}
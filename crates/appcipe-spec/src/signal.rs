@@ -0,0 +1,54 @@
+use crate::HumanDuration;
+
+/// 可當 stop_signal 的訊號（Linux 編號）；runtime 會一路轉送到 app
+const SIGNALS: &[(&str, i32)] = &[
+    ("SIGHUP", 1),
+    ("SIGINT", 2),
+    ("SIGQUIT", 3),
+    ("SIGKILL", 9),
+    ("SIGUSR1", 10),
+    ("SIGUSR2", 12),
+    ("SIGTERM", 15),
+    ("SIGWINCH", 28),
+    ("SIGPWR", 30),
+];
+
+pub const DEFAULT_STOP_SIGNAL: &str = "SIGTERM";
+pub const DEFAULT_STOP_GRACE_PERIOD: HumanDuration = HumanDuration::from_secs(10);
+
+/// "SIGTERM" / "TERM" / "sigterm" / "15" → "SIGTERM"
+pub fn normalize_signal(s: &str) -> Result<String, String> {
+    let s = s.trim();
+    let found = match s.parse::<i32>() {
+        Ok(n) => SIGNALS.iter().find(|(_, num)| *num == n),
+        Err(_) => {
+            let upper = s.to_ascii_uppercase();
+            let name = if upper.starts_with("SIG") {
+                upper
+            } else {
+                format!("SIG{}", upper)
+            };
+            SIGNALS.iter().find(|(n, _)| *n == name)
+        }
+    };
+    match found {
+        Some((name, _)) => Ok(name.to_string()),
+        None => Err(format!(
+            "unsupported signal '{}' (expected one of {})",
+            s,
+            SIGNALS
+                .iter()
+                .map(|(n, _)| *n)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+/// 已正規化的名稱 → 訊號編號
+pub fn signal_number(name: &str) -> Option<i32> {
+    SIGNALS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, num)| *num)
+}
@@ -0,0 +1,144 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// 容量：字串 "512m" / "512MiB" / "1.5g" / "2GB"，或整數（bytes）；單位一律以 1024 為底
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ByteSize(pub u64);
+
+const UNITS: [(&str, u64); 5] = [
+    ("", 1),
+    ("k", 1 << 10),
+    ("m", 1 << 20),
+    ("g", 1 << 30),
+    ("t", 1 << 40),
+];
+
+impl ByteSize {
+    pub const fn mib(n: u64) -> Self {
+        ByteSize(n << 20)
+    }
+}
+
+impl FromStr for ByteSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err("empty size".to_string());
+        }
+        let split = s
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(s.len());
+        let (num, unit) = (&s[..split], s[split..].trim().to_ascii_lowercase());
+        let n: f64 = num.parse().map_err(|_| format!("invalid size '{}'", s))?;
+        // "m"、"mb"、"mib" 都是 MiB；"b" 是 bytes
+        let unit = unit
+            .strip_suffix("ib")
+            .or_else(|| unit.strip_suffix('b'))
+            .unwrap_or(&unit);
+        let Some((_, mult)) = UNITS.iter().find(|(u, _)| *u == unit) else {
+            return Err(format!(
+                "unknown size unit '{}' in '{}' (b, k, m, g, t)",
+                &s[split..],
+                s
+            ));
+        };
+        let bytes = n * *mult as f64;
+        if !bytes.is_finite() || bytes >= u64::MAX as f64 {
+            return Err(format!("size '{}' is too large", s));
+        }
+        Ok(ByteSize(bytes.round() as u64))
+    }
+}
+
+impl fmt::Display for ByteSize {
+    /// 整除的最大單位（"512MiB"、"2GiB"），否則 bytes
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (unit, mult) = UNITS
+            .iter()
+            .rev()
+            .find(|(_, m)| self.0 != 0 && self.0.is_multiple_of(*m))
+            .copied()
+            .unwrap_or(("", 1));
+        match unit {
+            "" => write!(f, "{}B", self.0),
+            u => write!(f, "{}{}iB", self.0 / mult, u.to_ascii_uppercase()),
+        }
+    }
+}
+
+impl Serialize for ByteSize {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteSize {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Bytes(u64),
+            Text(String),
+        }
+        match Repr::deserialize(d)? {
+            Repr::Bytes(n) => Ok(ByteSize(n)),
+            Repr::Text(s) => s.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> u64 {
+        s.parse::<ByteSize>().unwrap().0
+    }
+
+    #[test]
+    fn units_are_binary() {
+        assert_eq!(parse("512"), 512);
+        assert_eq!(parse("100b"), 100);
+        assert_eq!(parse("4k"), 4096);
+        assert_eq!(parse("512m"), 512 << 20);
+        assert_eq!(parse("512MiB"), 512 << 20);
+        assert_eq!(parse("2GB"), 2 << 30);
+        assert_eq!(parse("1.5g"), 3 << 29);
+        assert_eq!(parse("1 t"), 1 << 40);
+    }
+
+    #[test]
+    fn invalid() {
+        for (s, why) in [
+            ("", "empty"),
+            ("m", "invalid size"),
+            ("1.2.3m", "invalid size"),
+            ("5x", "unknown size unit"),
+            ("5pb", "unknown size unit"),
+            ("99999999999t", "too large"),
+        ] {
+            let e = s.parse::<ByteSize>().unwrap_err();
+            assert!(e.contains(why), "{s:?}: {e}");
+        }
+    }
+
+    #[test]
+    fn display_uses_largest_exact_unit() {
+        assert_eq!(ByteSize(0).to_string(), "0B");
+        assert_eq!(ByteSize(1000).to_string(), "1000B");
+        assert_eq!(ByteSize::mib(512).to_string(), "512MiB");
+        assert_eq!(ByteSize(3 << 29).to_string(), "1536MiB");
+        assert_eq!(ByteSize(2 << 30).to_string(), "2GiB");
+    }
+
+    #[test]
+    fn deserialize_bytes_or_text() {
+        let s: ByteSize = serde_yaml::from_str("1048576").unwrap();
+        assert_eq!(s, ByteSize::mib(1));
+        let s: ByteSize = serde_yaml::from_str("\"256m\"").unwrap();
+        assert_eq!(s, ByteSize::mib(256));
+    }
+}
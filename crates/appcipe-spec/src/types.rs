@@ -1,7 +1,9 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use serde::{Deserialize, Serialize};
 
+use crate::{ByteSize, HumanDuration, Mount, Secret, ServiceSecret};
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AppCipe {
     pub version: String,
@@ -18,6 +20,25 @@ pub struct AppCipe {
 
     #[serde(default)]
     pub crash: CrashPolicy,
+
+    /// 哪些 service 結束時整個 app 跟著結束；省略時有主要 service 為 primary，否則為 all
+    #[serde(default)]
+    pub lifetime: Option<Lifetime>,
+
+    /// 所有 service 的預設重啟策略；service 自己的 `restart` 優先
+    #[serde(default)]
+    pub restart: Option<RestartConfig>,
+
+    #[serde(default)]
+    pub update: Option<UpdateConfig>,
+
+    #[serde(default)]
+    pub compression: Option<CompressionConfig>,
+
+    /// 機密值：名稱 → 來源；service 以 `secrets` 引用，值在啟動時才取得，不寫進 bundle
+    #[serde(default)]
+    pub secrets: HashMap<String, Secret>,
+
     pub services: HashMap<String, Service>,
 }
 
@@ -31,23 +52,155 @@ pub struct Service {
     #[serde(default)]
     pub workdir:  Option<String>,
 
+    /// 值可用 `${VAR}`；`${CHEFER_DATA_DIR}` 等 runtime 變數在啟動時才代入
     #[serde(default)]
     pub env: HashMap<String, String>,
 
+    /// 讀入 env 的檔案（每行 KEY=VALUE），單一路徑或清單；相對於 appcipe.yml，`env` 中同名的優先
+    #[serde(default)]
+    pub env_file: EnvFiles,
+
     #[serde(default)]
     pub persist_path: Option<String>,
 
     #[serde(default)]
     pub ports: Vec<String>,
 
+    /// "<host>:<container>[:ro][,create]" 或 `{ source, target, read_only, create }`；host 路徑在啟動時解析
     #[serde(default)]
-    pub mounts: Vec<String>,
+    pub mounts: Vec<Mount>,
 
     #[serde(default)]
     pub interface_mode: InterfaceMode,
 
+    /// 清單（`[db]`）或對應表（`db: { condition: service_healthy }`）
+    #[serde(default)]
+    pub depends_on: DependsOn,
+
+    #[serde(default)]
+    pub restart: Option<RestartConfig>,
+
+    /// 省略時沿用 image 的 HEALTHCHECK
+    #[serde(default)]
+    pub healthcheck: Option<Healthcheck>,
+
+    /// 停止時送的訊號（"SIGINT"、"TERM"、"15"）；省略時沿用 image 的 StopSignal，再沒有則 SIGTERM
+    #[serde(default)]
+    pub stop_signal: Option<String>,
+
+    /// 送出 stop_signal 後等多久仍未結束就 SIGKILL（預設 10s）
+    #[serde(default)]
+    pub stop_grace_period: Option<HumanDuration>,
+
+    /// 主要 service：結束碼即 app 的結束碼，執行檔的命令列參數也交給它（至多一個）
     #[serde(default)]
-    pub depends_on: Vec<String>,
+    pub primary: bool,
+
+    /// app 已在執行時再次啟動：在這個 service 的容器內執行此指令，後面接上第二次啟動的參數
+    /// （開檔、叫出視窗等）。只能設在主要 service
+    #[serde(default)]
+    pub handoff: Option<Cmd>,
+
+    /// CPU 上限（核心數，可為小數：0.5、2）
+    #[serde(default)]
+    pub cpus: Option<f64>,
+
+    /// 記憶體上限（"512m"、"2g"）；超過時容器內的行程被 OOM kill
+    #[serde(default)]
+    pub memory: Option<ByteSize>,
+
+    /// 行程（含 thread）數上限
+    #[serde(default)]
+    pub pids_limit: Option<u32>,
+
+    /// I/O 權重（1..=10000，預設 100）
+    #[serde(default)]
+    pub io_weight: Option<u16>,
+
+    /// 引用頂層 secrets：名稱（檔案 /run/secrets/<name>）、`{ source, target }` 或 `{ source, env }`
+    #[serde(default)]
+    pub secrets: Vec<ServiceSecret>,
+
+    /// 值來自 build 機器（`${VAR}` 展開、env_file）的 env 名稱；不序列化，輸出時遮蔽
+    #[serde(skip)]
+    pub host_env: HashSet<String>,
+}
+
+impl Service {
+    /// 輸出（chefer check、bundle 內的 appcipe.yml、--chefer-manifest）時不顯示這個 env 的值
+    pub fn hides_env(&self, key: &str) -> bool {
+        crate::is_sensitive_env(key) || self.host_env.contains(key)
+    }
+
+    pub fn resources(&self) -> Resources {
+        Resources { cpus: self.cpus, memory: self.memory, pids_limit: self.pids_limit, io_weight: self.io_weight }
+    }
+}
+
+/// service 的資源上限；都是選填，未設定的不限制
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Resources {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpus: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<ByteSize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pids_limit: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub io_weight: Option<u16>,
+}
+
+impl Resources {
+    pub fn is_empty(&self) -> bool {
+        *self == Resources::default()
+    }
+}
+
+/// "cpus 0.5, memory 512MiB, pids 100, io weight 50"；沒有上限時為 "-"
+impl std::fmt::Display for Resources {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(c) = self.cpus {
+            parts.push(format!("cpus {}", c));
+        }
+        if let Some(m) = self.memory {
+            parts.push(format!("memory {}", m));
+        }
+        if let Some(p) = self.pids_limit {
+            parts.push(format!("pids {}", p));
+        }
+        if let Some(w) = self.io_weight {
+            parts.push(format!("io weight {}", w));
+        }
+        if parts.is_empty() {
+            return f.write_str("-");
+        }
+        f.write_str(&parts.join(", "))
+    }
+}
+
+impl AppCipe {
+    /// 主要 service：`primary: true` 的；沒有時為接終端機的，再沒有則為唯一一個 gui 的，最後為 web 的
+    pub fn primary_service(&self) -> Option<&str> {
+        let find = |f: &dyn Fn(&Service) -> bool| {
+            let mut found = self.services.iter().filter(|(_, s)| f(s));
+            match (found.next(), found.next()) {
+                (Some((name, _)), None) => Some(name.as_str()),
+                _ => None,
+            }
+        };
+        find(&|s| s.primary)
+            .or_else(|| find(&|s| s.interface_mode.uses_terminal()))
+            .or_else(|| find(&|s| s.interface_mode == InterfaceMode::Gui))
+            .or_else(|| find(&|s| s.interface_mode.web().is_some()))
+    }
+
+    pub fn resolved_lifetime(&self) -> Lifetime {
+        self.lifetime.unwrap_or(match self.primary_service() {
+            Some(_) => Lifetime::Primary,
+            None => Lifetime::All,
+        })
+    }
 }
 
 
@@ -75,23 +228,19 @@ pub enum ImageSourceType {
     Image, // 直接拉現有 image
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum ImageFormat {
+    #[default]
     Auto,
     DockerArchive,
     OciArchive,
 }
 
-impl Default for ImageFormat {
-    fn default() -> Self {
-        ImageFormat::Auto
-    }
-}
-
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum ImagePlatform {
+    #[default]
     #[serde(rename = "linux/amd64", alias = "linux_amd64", alias = "amd64", alias = "x86_64", alias = "x86-64")]
     LinuxAmd64,
 
@@ -103,42 +252,494 @@ pub enum ImagePlatform {
     WindowsAmd64,
 }
 
-impl Default for ImagePlatform {
+/// service 失敗且不再重啟時，整個 app 怎麼反應
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CrashPolicy {
+    /// 停止所有 service，app 以錯誤結束
+    #[default]
+    FailFast,
+    /// 其他 service 繼續執行（降級運作）
+    Degrade,
+}
+
+/// 哪些 service 結束（且不再重啟）時停止整個 app
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Lifetime {
+    /// 主要 service 結束就停止其他 service
+    Primary,
+    /// 所有 service 都結束才結束
+    #[default]
+    All,
+    /// 任何一個 service 結束就停止其他 service
+    Any,
+}
+
+impl Lifetime {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Lifetime::Primary => "primary",
+            Lifetime::All => "all",
+            Lifetime::Any => "any",
+        }
+    }
+}
+
+/// service 結束後是否重新啟動
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    #[default]
+    No,
+    /// exit code ≠ 0 才重啟
+    OnFailure,
+    /// 不論 exit code 都重啟
+    Always,
+    /// 同 always，但使用者手動停止的 service 不重啟
+    UnlessStopped,
+}
+
+impl RestartPolicy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RestartPolicy::No => "no",
+            RestartPolicy::OnFailure => "on-failure",
+            RestartPolicy::Always => "always",
+            RestartPolicy::UnlessStopped => "unless-stopped",
+        }
+    }
+}
+
+impl std::str::FromStr for RestartPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "no" => Ok(RestartPolicy::No),
+            "on-failure" => Ok(RestartPolicy::OnFailure),
+            "always" => Ok(RestartPolicy::Always),
+            "unless-stopped" => Ok(RestartPolicy::UnlessStopped),
+            other => Err(format!(
+                "unknown restart policy '{}' (expected no, on-failure, always or unless-stopped)",
+                other
+            )),
+        }
+    }
+}
+
+/// 重啟策略：短寫 `restart: on-failure` / `restart: "on-failure:5"`，或完整的對應表。
+/// 第 n 次重啟前等待 `backoff × 2^(n-1)`（上限 `max_backoff`）；
+/// 連續執行超過 `reset_after` 才結束的話，次數與等待時間重新計算。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "RestartRepr")]
+pub struct RestartConfig {
+    pub policy: RestartPolicy,
+    /// 最多連續重啟幾次；省略表示不限
+    pub max_retries: Option<u32>,
+    pub backoff: HumanDuration,
+    pub max_backoff: HumanDuration,
+    pub reset_after: HumanDuration,
+}
+
+impl Default for RestartConfig {
     fn default() -> Self {
-        ImagePlatform::LinuxAmd64
+        RestartConfig {
+            policy: RestartPolicy::No,
+            max_retries: None,
+            backoff: HumanDuration::from_secs(1),
+            max_backoff: HumanDuration::from_secs(60),
+            reset_after: HumanDuration::from_secs(60),
+        }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
-pub enum CrashPolicy {
-    FailFast,
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RestartRepr {
+    Short(String),
+    Full {
+        /// 字串自己解析，錯誤訊息才會指出是哪個值
+        policy: String,
+        #[serde(default)]
+        max_retries: Option<u32>,
+        #[serde(default)]
+        backoff: Option<HumanDuration>,
+        #[serde(default)]
+        max_backoff: Option<HumanDuration>,
+        #[serde(default)]
+        reset_after: Option<HumanDuration>,
+    },
+}
+
+impl TryFrom<RestartRepr> for RestartConfig {
+    type Error = String;
+
+    fn try_from(r: RestartRepr) -> Result<Self, Self::Error> {
+        let d = RestartConfig::default();
+        match r {
+            RestartRepr::Short(s) => {
+                let (policy, retries) = match s.split_once(':') {
+                    Some((p, n)) => {
+                        let n = n
+                            .parse()
+                            .map_err(|_| format!("invalid max retries in restart '{}'", s))?;
+                        (p, Some(n))
+                    }
+                    None => (s.as_str(), None),
+                };
+                Ok(RestartConfig {
+                    policy: policy.parse()?,
+                    max_retries: retries,
+                    ..d
+                })
+            }
+            RestartRepr::Full { policy, max_retries, backoff, max_backoff, reset_after } => Ok(RestartConfig {
+                policy: policy.parse()?,
+                max_retries,
+                backoff: backoff.unwrap_or(d.backoff),
+                max_backoff: max_backoff.unwrap_or(d.max_backoff),
+                reset_after: reset_after.unwrap_or(d.reset_after),
+            }),
+        }
+    }
+}
+
+/// env_file：`.env` 或 `[a.env, b.env]`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum EnvFiles {
+    One(String),
+    Many(Vec<String>),
 }
 
-impl Default for CrashPolicy {
+impl Default for EnvFiles {
     fn default() -> Self {
-        CrashPolicy::FailFast
+        EnvFiles::Many(Vec::new())
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl EnvFiles {
+    pub fn paths(&self) -> &[String] {
+        match self {
+            EnvFiles::One(p) => std::slice::from_ref(p),
+            EnvFiles::Many(ps) => ps,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum Cmd {
     String(String),
     Array(Vec<String>),
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// YAML 寫法為 `gui` 等字串，或 `web: { port: 8080, path: / }`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum InterfaceMode {
     Gui,
     Terminal,
     Both,
+    /// web UI：容器內的 `port`（需在 ports 發布）接受連線後，以瀏覽器開啟 `path`
+    Web { port: u16, path: String },
+    #[default]
     None, // 如果要顯式表示沒有
 }
 
-impl Default for InterfaceMode {
+impl<'de> Deserialize<'de> for InterfaceMode {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Web {
+            port: u16,
+            #[serde(default = "InterfaceMode::default_web_path")]
+            path: String,
+        }
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Name(String),
+            Web { web: Web },
+        }
+        use serde::de::Error;
+        match Repr::deserialize(d)? {
+            Repr::Name(name) => match name.as_str() {
+                "gui" => Ok(InterfaceMode::Gui),
+                "terminal" => Ok(InterfaceMode::Terminal),
+                "both" => Ok(InterfaceMode::Both),
+                "none" => Ok(InterfaceMode::None),
+                "web" => Err(D::Error::custom("interface_mode web needs a port, e.g. `web: { port: 8080 }`")),
+                other => Err(D::Error::custom(format!(
+                    "unknown interface_mode '{}' (expected terminal, gui, both, none or web)", other
+                ))),
+            },
+            Repr::Web { web } => Ok(InterfaceMode::Web { port: web.port, path: web.path }),
+        }
+    }
+}
+
+impl InterfaceMode {
+    fn default_web_path() -> String {
+        "/".to_string()
+    }
+
+    /// 是否接上使用者的終端機（terminal 或 both）；一個 app 只能有一個
+    pub fn uses_terminal(&self) -> bool {
+        matches!(self, InterfaceMode::Terminal | InterfaceMode::Both)
+    }
+
+    /// 是否共用 host 的顯示（gui 或 both）
+    pub fn uses_display(&self) -> bool {
+        matches!(self, InterfaceMode::Gui | InterfaceMode::Both)
+    }
+
+    /// web 模式的 (容器內的埠, 路徑)
+    pub fn web(&self) -> Option<(u16, &str)> {
+        match self {
+            InterfaceMode::Web { port, path } => Some((*port, path)),
+            _ => None,
+        }
+    }
+}
+
+/// service 的相依：清單寫法等同每項 `condition: service_started`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DependsOn {
+    List(Vec<String>),
+    Map(HashMap<String, DependsOnEntry>),
+}
+
+impl Default for DependsOn {
     fn default() -> Self {
-        InterfaceMode::None
+        DependsOn::List(Vec::new())
+    }
+}
+
+impl DependsOn {
+    /// (相依的 service, 條件)，依名稱排序
+    pub fn entries(&self) -> Vec<(String, DependencyCondition)> {
+        let mut out: Vec<_> = match self {
+            DependsOn::List(names) => names
+                .iter()
+                .map(|n| (n.clone(), DependencyCondition::default()))
+                .collect(),
+            DependsOn::Map(map) => map.iter().map(|(n, e)| (n.clone(), e.condition)).collect(),
+        };
+        out.sort();
+        out
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.entries().into_iter().map(|(n, _)| n).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self {
+            DependsOn::List(v) => v.is_empty(),
+            DependsOn::Map(m) => m.is_empty(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DependsOnEntry {
+    #[serde(default)]
+    pub condition: DependencyCondition,
+}
+
+/// 啟動相依的 service 前要等到的狀態
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyCondition {
+    /// 行程已啟動
+    #[default]
+    #[serde(alias = "started")]
+    ServiceStarted,
+    /// healthcheck 通過
+    #[serde(alias = "healthy")]
+    ServiceHealthy,
+}
+
+impl DependencyCondition {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DependencyCondition::ServiceStarted => "service_started",
+            DependencyCondition::ServiceHealthy => "service_healthy",
+        }
+    }
+}
+
+/// 健康檢查：`cmd`（在容器內執行，exit 0 為健康）、`tcp`（連得上該埠）、
+/// `http`（GET 回 2xx/3xx）三擇一。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Healthcheck {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cmd: Option<Cmd>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tcp: Option<u16>,
+
+    /// 例如 "http://localhost:8080/health"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http: Option<String>,
+
+    #[serde(default = "Healthcheck::default_interval")]
+    pub interval: HumanDuration,
+
+    #[serde(default = "Healthcheck::default_timeout")]
+    pub timeout: HumanDuration,
+
+    /// 連續失敗幾次才算 unhealthy
+    #[serde(default = "Healthcheck::default_retries")]
+    pub retries: u32,
+
+    /// 啟動後這段時間內的失敗不計入 retries
+    #[serde(default)]
+    pub start_period: HumanDuration,
+
+    /// 停用（包含 image 的 HEALTHCHECK）
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub disable: bool,
+}
+
+impl Healthcheck {
+    fn default_interval() -> HumanDuration {
+        HumanDuration::from_secs(30)
+    }
+    fn default_timeout() -> HumanDuration {
+        HumanDuration::from_secs(30)
+    }
+    fn default_retries() -> u32 {
+        3
+    }
+
+    /// 只有 probe，其餘為預設值
+    pub fn with_cmd(cmd: Cmd) -> Self {
+        Healthcheck {
+            cmd: Some(cmd),
+            tcp: None,
+            http: None,
+            interval: Self::default_interval(),
+            timeout: Self::default_timeout(),
+            retries: Self::default_retries(),
+            start_period: HumanDuration::default(),
+            disable: false,
+        }
+    }
+
+    /// 顯示用："cmd" / "tcp:5432" / "http://…"
+    pub fn describe(&self) -> String {
+        match (&self.cmd, self.tcp, &self.http) {
+            (Some(_), _, _) => "cmd".to_string(),
+            (_, Some(port), _) => format!("tcp:{}", port),
+            (_, _, Some(url)) => url.clone(),
+            _ => "none".to_string(),
+        }
+    }
+}
+
+/// 打包後的應用自我更新設定
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateConfig {
+    /// 更新 feed（JSON）的 URL；http(s)
+    pub feed: String,
+
+    /// 發行者的 ed25519 公鑰（hex，64 字元）；由 `chefer keygen` 產生
+    pub public_key: String,
+
+    #[serde(default)]
+    pub check: UpdateCheck,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateCheck {
+    /// 每次啟動時在背景檢查並安裝，下次啟動生效
+    #[default]
+    Startup,
+    /// 只在執行 `--chefer-update` 時檢查
+    Manual,
+}
+
+/// 單檔 payload 的壓縮設定；每個 service 各自成一段壓縮
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompressionConfig {
+    /// 預設 codec（metadata 與未覆寫的 service）
+    #[serde(default)]
+    pub codec: Codec,
+
+    /// 壓縮等級；省略則用 codec 預設值（lz4 不支援）
+    #[serde(default)]
+    pub level: Option<i32>,
+
+    /// 以所有 service rootfs 的檔案訓練 zstd dictionary，供 zstd 區段共用
+    #[serde(default)]
+    pub dictionary: bool,
+
+    /// 依 service 覆寫 codec / level
+    #[serde(default)]
+    pub services: HashMap<String, SectionCompression>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SectionCompression {
+    pub codec: Codec,
+
+    #[serde(default)]
+    pub level: Option<i32>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Codec {
+    /// 不壓縮；全部區段都不壓縮時 runtime 可直接 FUSE 掛載
+    #[default]
+    None,
+    Zstd,
+    Xz,
+    Lz4,
+}
+
+impl Codec {
+    /// 可用的等級範圍；None 表示不接受 level
+    pub fn level_range(self) -> Option<(i32, i32)> {
+        match self {
+            Codec::Zstd => Some((1, 22)),
+            Codec::Xz => Some((0, 9)),
+            Codec::None | Codec::Lz4 => None,
+        }
+    }
+
+    pub fn default_level(self) -> Option<i32> {
+        match self {
+            Codec::Zstd => Some(19),
+            Codec::Xz => Some(6),
+            Codec::None | Codec::Lz4 => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Codec::None => "none",
+            Codec::Zstd => "zstd",
+            Codec::Xz => "xz",
+            Codec::Lz4 => "lz4",
+        }
+    }
+}
+
+impl std::str::FromStr for Codec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Codec::None),
+            "zstd" => Ok(Codec::Zstd),
+            "xz" => Ok(Codec::Xz),
+            "lz4" => Ok(Codec::Lz4),
+            other => Err(format!("unknown codec '{}' (expected none, zstd, xz or lz4)", other)),
+        }
     }
 }
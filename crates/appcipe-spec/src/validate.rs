@@ -1,3 +1,6 @@
+use crate::{ByteSize, Mount, Secret, ServiceSecret, path_anchor};
+use crate::ports::{PortMapping, Protocol, parse_ports};
+use crate::signal::normalize_signal;
 use crate::types::*;
 
 impl AppCipe {
@@ -8,6 +11,9 @@ impl AppCipe {
                 if !self.name.chars().all(|c| c.is_ascii_alphabetic() || c == '_') {
                     return Err("name can only contain English letters and underscores, and cannot have spaces".to_string());
                 }
+                if self.services.is_empty() {
+                    return Err("services: at least one service is required".to_string());
+                }
                 self.services.iter().try_for_each(|(name, _)| {
                     if name.is_empty() {
                         return Err("Service name cannot be empty".to_string());
@@ -17,9 +23,488 @@ impl AppCipe {
                     }
                     Ok(())
                 })?;
+                self.validate_depends_on()?;
+                if let Some(r) = &self.restart {
+                    validate_restart("restart", r)?;
+                }
+                let defined: Vec<&str> = self.secrets.keys().map(String::as_str).collect();
+                for (name, svc) in &self.services {
+                    if let Some(r) = &svc.restart {
+                        validate_restart(&format!("services.{}.restart", name), r)?;
+                    }
+                    if let Some(h) = &svc.healthcheck {
+                        validate_healthcheck(&format!("services.{}.healthcheck", name), h)?;
+                    }
+                    if let Some(sig) = &svc.stop_signal {
+                        normalize_signal(sig).map_err(|e| format!("services.{}.stop_signal: {}", name, e))?;
+                    }
+                    validate_resources(&format!("services.{}", name), &svc.resources())?;
+                    validate_mounts(&format!("services.{}.mounts", name), &svc.mounts)?;
+                    let env: Vec<&str> = svc.env.keys().map(String::as_str).collect();
+                    validate_service_secrets(&format!("services.{}.secrets", name), &svc.secrets, &defined, &env)?;
+                }
+                validate_secrets(self.secrets.iter().map(|(name, s)| (name.as_str(), s)))?;
+                validate_ports(self.services.iter().map(|(name, svc)| (name.as_str(), svc.ports.as_slice())))?;
+                let mut terminal: Vec<&str> = self.services.iter()
+                    .filter(|(_, svc)| svc.interface_mode.uses_terminal())
+                    .map(|(name, _)| name.as_str())
+                    .collect();
+                if terminal.len() > 1 {
+                    terminal.sort();
+                    return Err(format!(
+                        "only one service can use the terminal (interface_mode terminal or both), found: {}",
+                        terminal.join(", ")
+                    ));
+                }
+                validate_web(self.services.iter().map(|(name, svc)| (name.as_str(), &svc.interface_mode, svc.ports.as_slice())))?;
+                let mut primary: Vec<&str> = self.services.iter()
+                    .filter(|(_, svc)| svc.primary)
+                    .map(|(name, _)| name.as_str())
+                    .collect();
+                if primary.len() > 1 {
+                    primary.sort();
+                    return Err(format!("only one service can be primary, found: {}", primary.join(", ")));
+                }
+                for (name, svc) in &self.services {
+                    if svc.handoff.is_some() && self.primary_service() != Some(name.as_str()) {
+                        return Err(format!("services.{}.handoff: only the primary service can take over a second launch", name));
+                    }
+                }
+                if self.lifetime == Some(Lifetime::Primary) && self.primary_service().is_none() {
+                    return Err("lifetime: primary needs a service with `primary: true`".to_string());
+                }
+                if let Some(update) = &self.update {
+                    self.validate_update(update)?;
+                }
+                if let Some(compression) = &self.compression {
+                    self.validate_compression(compression)?;
+                }
                 Ok(())
             },
             other => Err(format!("Unsupported version: {}", other)),
         }
     }
+
+    fn validate_depends_on(&self) -> Result<(), String> {
+        let graph: Vec<(String, Vec<String>)> = self
+            .services
+            .iter()
+            .map(|(name, svc)| (name.clone(), svc.depends_on.names()))
+            .collect();
+        dependency_order(&graph).map(|_| ())
+    }
+
+    fn validate_update(&self, update: &UpdateConfig) -> Result<(), String> {
+        if !update.feed.starts_with("https://") && !is_loopback_http(&update.feed) {
+            return Err(format!("update.feed must be an https URL, got '{}'", update.feed));
+        }
+        if update.public_key.len() != 64 || !update.public_key.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err("update.public_key must be a 64-character hex ed25519 public key".to_string());
+        }
+        match &self.app_version {
+            None => return Err("update requires app_version so the runtime can compare versions".to_string()),
+            Some(v) if semver::Version::parse(v.trim_start_matches('v')).is_err() => {
+                return Err(format!("update requires a semver app_version (e.g. 2.3.1), got '{}'", v));
+            }
+            Some(_) => {}
+        }
+        Ok(())
+    }
+
+    fn validate_compression(&self, c: &CompressionConfig) -> Result<(), String> {
+        validate_level("compression", c.codec, c.level)?;
+        for (name, sc) in &c.services {
+            if !self.services.contains_key(name) {
+                return Err(format!("compression.services.{} does not match any service", name));
+            }
+            validate_level(&format!("compression.services.{}", name), sc.codec, sc.level)?;
+        }
+        if c.dictionary {
+            let any_zstd = c.codec == Codec::Zstd || c.services.values().any(|s| s.codec == Codec::Zstd);
+            if !any_zstd {
+                return Err("compression.dictionary requires at least one zstd section".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 只有本機測試用的 stand-in feed 可以走 http
+fn is_loopback_http(url: &str) -> bool {
+    let Some(rest) = url.strip_prefix("http://") else { return false };
+    let host = rest.split('/').next().unwrap_or("");
+    let host = host.rsplit_once(':').filter(|(h, p)| !h.is_empty() && p.chars().all(|c| c.is_ascii_digit())).map_or(host, |(h, _)| h);
+    matches!(host, "localhost" | "127.0.0.1" | "[::1]")
+}
+
+fn validate_restart(field: &str, r: &RestartConfig) -> Result<(), String> {
+    if r.policy == RestartPolicy::No && r.max_retries.is_some() {
+        return Err(format!("{}: max_retries has no effect with policy 'no'", field));
+    }
+    if r.backoff.is_zero() {
+        return Err(format!("{}.backoff must be greater than 0", field));
+    }
+    if r.max_backoff < r.backoff {
+        return Err(format!(
+            "{}.max_backoff ({}) must not be shorter than backoff ({})",
+            field, r.max_backoff, r.backoff
+        ));
+    }
+    Ok(())
+}
+
+pub fn validate_healthcheck(field: &str, h: &Healthcheck) -> Result<(), String> {
+    let probes = [h.cmd.is_some(), h.tcp.is_some(), h.http.is_some()]
+        .iter()
+        .filter(|p| **p)
+        .count();
+    if h.disable {
+        if probes > 0 {
+            return Err(format!("{}: disable cannot be combined with cmd, tcp or http", field));
+        }
+        return Ok(());
+    }
+    if probes != 1 {
+        return Err(format!("{}: set exactly one of cmd, tcp or http", field));
+    }
+    if matches!(&h.cmd, Some(Cmd::Array(a)) if a.is_empty()) {
+        return Err(format!("{}.cmd must not be empty", field));
+    }
+    if h.tcp == Some(0) {
+        return Err(format!("{}.tcp must be a port between 1 and 65535", field));
+    }
+    if let Some(url) = &h.http
+        && !url.starts_with("http://")
+        && !url.starts_with("https://")
+    {
+        return Err(format!("{}.http must be an http(s) URL, got '{}'", field, url));
+    }
+    if h.interval.is_zero() || h.timeout.is_zero() {
+        return Err(format!("{}: interval and timeout must be greater than 0", field));
+    }
+    if h.retries == 0 {
+        return Err(format!("{}.retries must be at least 1", field));
+    }
+    Ok(())
+}
+
+/// cgroup 能設定的範圍；`field` 是 service 的路徑（欄位名接在後面）
+pub fn validate_resources(field: &str, r: &Resources) -> Result<(), String> {
+    if let Some(cpus) = r.cpus
+        && !(cpus.is_finite() && cpus >= MIN_CPUS)
+    {
+        return Err(format!("{}.cpus must be at least {}, got {}", field, MIN_CPUS, cpus));
+    }
+    if let Some(memory) = r.memory
+        && memory < MIN_MEMORY
+    {
+        return Err(format!("{}.memory must be at least {}, got {}", field, MIN_MEMORY, memory));
+    }
+    if r.pids_limit == Some(0) {
+        return Err(format!("{}.pids_limit must be at least 1", field));
+    }
+    if let Some(w) = r.io_weight
+        && !(1..=10000).contains(&w)
+    {
+        return Err(format!("{}.io_weight must be between 1 and 10000, got {}", field, w));
+    }
+    Ok(())
+}
+
+/// host 路徑的錨點要認得；容器內路徑不可有 ".."（相對路徑視為從根開始）
+pub fn validate_mounts(field: &str, mounts: &[Mount]) -> Result<(), String> {
+    for m in mounts {
+        if m.source.is_empty() || m.target.is_empty() {
+            return Err(format!("{}: '{}' needs both a host path and a container path", field, m));
+        }
+        m.anchor().map_err(|e| format!("{}: {}", field, e))?;
+        if m.target.split(['/', '\\']).any(|c| c == "..") {
+            return Err(format!("{}: container path '{}' must not contain '..'", field, m.target));
+        }
+    }
+    Ok(())
+}
+
+/// 每個 secret 恰有一個來源；名稱會成為檔名
+pub fn validate_secrets<'a>(secrets: impl IntoIterator<Item = (&'a str, &'a Secret)>) -> Result<(), String> {
+    for (name, s) in secrets {
+        if name.is_empty() || name.starts_with('.') || !name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.')) {
+            return Err(format!("secret name '{}' can only contain letters, digits, '_', '-' and '.', and cannot start with '.'", name));
+        }
+        let sources = [s.generate, s.file.is_some(), s.prompt.is_some()].iter().filter(|b| **b).count();
+        if sources != 1 {
+            return Err(format!("secrets.{}: set exactly one of generate, file or prompt", name));
+        }
+        if let Some(len) = s.length {
+            if !s.generate {
+                return Err(format!("secrets.{}.length only applies with generate", name));
+            }
+            if !(MIN_SECRET_LENGTH..=MAX_SECRET_LENGTH).contains(&len) {
+                return Err(format!("secrets.{}.length must be between {} and {}, got {}", name, MIN_SECRET_LENGTH, MAX_SECRET_LENGTH, len));
+            }
+        }
+        if let Some(file) = &s.file {
+            if file.is_empty() {
+                return Err(format!("secrets.{}.file is empty", name));
+            }
+            path_anchor(file).map_err(|e| format!("secrets.{}.file: {}", name, e))?;
+        }
+        if s.prompt.as_ref().is_some_and(|p| p.trim().is_empty()) {
+            return Err(format!("secrets.{}.prompt is empty", name));
+        }
+    }
+    Ok(())
+}
+
+const MIN_SECRET_LENGTH: u32 = 8;
+const MAX_SECRET_LENGTH: u32 = 4096;
+
+/// service 引用的 secret 要有定義；env 不可與 service 的 env 重複，檔案路徑不可有 ".."
+pub fn validate_service_secrets(field: &str, refs: &[ServiceSecret], defined: &[&str], env: &[&str]) -> Result<(), String> {
+    let mut seen_env = Vec::new();
+    let mut seen_target = Vec::new();
+    for r in refs {
+        if !defined.contains(&r.source.as_str()) {
+            return Err(format!("{}: unknown secret '{}' (define it under the top-level secrets)", field, r.source));
+        }
+        if r.env.is_some() && r.target.is_some() {
+            return Err(format!("{}: secret '{}' sets both env and target; use one", field, r.source));
+        }
+        if let Some(e) = &r.env {
+            if e.is_empty() || e.contains(['=', '\0']) || e.contains(char::is_whitespace) {
+                return Err(format!("{}: invalid env name '{}'", field, e));
+            }
+            if env.contains(&e.as_str()) {
+                return Err(format!("{}: env {} is also set in env; remove it there", field, e));
+            }
+            if seen_env.contains(e) {
+                return Err(format!("{}: env {} is set by more than one secret", field, e));
+            }
+            seen_env.push(e.clone());
+        }
+        if let Some(t) = r.file_target() {
+            if t.split('/').any(|c| c == "..") {
+                return Err(format!("{}: container path '{}' must not contain '..'", field, t));
+            }
+            if seen_target.contains(&t) {
+                return Err(format!("{}: more than one secret is mounted at {}", field, t));
+            }
+            seen_target.push(t);
+        }
+    }
+    Ok(())
+}
+
+/// cpu.max 的 quota 至少 1ms（period 100ms）
+const MIN_CPUS: f64 = 0.01;
+/// 再小連 shell 都跑不起來
+const MIN_MEMORY: ByteSize = ByteSize::mib(4);
+
+/// 每項都要能解析，且不同項目（含跨 service）不可搶同一個 host 埠
+pub fn validate_ports<'a>(services: impl IntoIterator<Item = (&'a str, &'a [String])>) -> Result<(), String> {
+    let mut seen: Vec<(&str, PortMapping)> = Vec::new();
+    for (name, ports) in services {
+        for spec in ports {
+            for m in parse_ports(spec).map_err(|e| format!("service '{}': {}", name, e))? {
+                if let Some((other, _)) = seen.iter().find(|(_, o)| o.conflicts_with(&m)) {
+                    return Err(format!(
+                        "host port {}/{} is published by both '{}' and '{}'",
+                        m.host, m.proto.as_str(), other, name
+                    ));
+                }
+                seen.push((name, m));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// interface_mode web：至多一個 service，path 以 / 開頭，port 需由該 service 以 tcp 發布
+pub fn validate_web<'a>(services: impl IntoIterator<Item = (&'a str, &'a InterfaceMode, &'a [String])>) -> Result<(), String> {
+    let mut web: Vec<&str> = Vec::new();
+    for (name, mode, ports) in services {
+        let Some((port, path)) = mode.web() else { continue };
+        web.push(name);
+        if !path.starts_with('/') {
+            return Err(format!("service '{}': interface_mode web path must start with '/', got '{}'", name, path));
+        }
+        let mut published = false;
+        for spec in ports {
+            published |= parse_ports(spec)
+                .map_err(|e| format!("service '{}': {}", name, e))?
+                .iter()
+                .any(|m| m.guest == port && m.proto == Protocol::Tcp);
+        }
+        if !published {
+            return Err(format!(
+                "service '{}': interface_mode web port {} must be published in ports (e.g. \"{}:{}\")",
+                name, port, port, port
+            ));
+        }
+    }
+    if web.len() > 1 {
+        web.sort();
+        return Err(format!("only one service can use interface_mode web, found: {}", web.join(", ")));
+    }
+    Ok(())
+}
+
+pub fn validate_level(field: &str, codec: Codec, level: Option<i32>) -> Result<(), String> {
+    let Some(level) = level else {
+        return Ok(());
+    };
+    match codec.level_range() {
+        Some((lo, hi)) if (lo..=hi).contains(&level) => Ok(()),
+        Some((lo, hi)) => Err(format!(
+            "{}.level {} is out of range for {} ({}..={})",
+            field, level, codec.as_str(), lo, hi
+        )),
+        None => Err(format!("{}: codec {} does not take a level", field, codec.as_str())),
+    }
+}
+
+/// 依 depends_on 排出啟動順序（相依的在前）；同一層依名稱排序，結果固定。
+/// 相依到不存在的 service、相依自己或有循環時回傳錯誤。
+pub fn dependency_order(graph: &[(String, Vec<String>)]) -> Result<Vec<String>, String> {
+    use std::collections::BTreeMap;
+
+    let deps: BTreeMap<&str, Vec<&str>> = graph
+        .iter()
+        .map(|(name, deps)| {
+            let mut deps: Vec<&str> = deps.iter().map(String::as_str).collect();
+            deps.sort();
+            (name.as_str(), deps)
+        })
+        .collect();
+    for (name, ds) in &deps {
+        for d in ds {
+            if d == name {
+                return Err(format!("service '{}' depends on itself", name));
+            }
+            if !deps.contains_key(d) {
+                return Err(format!("service '{}' depends on unknown service '{}'", name, d));
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark {
+        Visiting,
+        Done,
+    }
+    fn visit<'a>(
+        name: &'a str,
+        deps: &BTreeMap<&'a str, Vec<&'a str>>,
+        marks: &mut BTreeMap<&'a str, Mark>,
+        path: &mut Vec<&'a str>,
+        order: &mut Vec<String>,
+    ) -> Result<(), String> {
+        match marks.get(name) {
+            Some(Mark::Done) => return Ok(()),
+            Some(Mark::Visiting) => {
+                let start = path.iter().position(|n| *n == name).unwrap_or(0);
+                let mut cycle = path[start..].to_vec();
+                cycle.push(name);
+                return Err(format!("dependency cycle: {}", cycle.join(" -> ")));
+            }
+            None => {}
+        }
+        marks.insert(name, Mark::Visiting);
+        path.push(name);
+        for d in &deps[name] {
+            visit(d, deps, marks, path, order)?;
+        }
+        path.pop();
+        marks.insert(name, Mark::Done);
+        order.push(name.to_string());
+        Ok(())
+    }
+
+    let mut marks = BTreeMap::new();
+    let mut order = Vec::with_capacity(deps.len());
+    for name in deps.keys() {
+        visit(name, &deps, &mut marks, &mut Vec::new(), &mut order)?;
+    }
+    Ok(order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(edges: &[(&str, &[&str])]) -> Vec<(String, Vec<String>)> {
+        edges
+            .iter()
+            .map(|(name, deps)| (name.to_string(), deps.iter().map(|d| d.to_string()).collect()))
+            .collect()
+    }
+
+    fn check(yaml: &str) -> Result<(), String> {
+        crate::from_str_with_base(yaml, "/nonexistent")
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn needs_a_service() {
+        let err = check("version: \"0.1\"\nname: App\nservices: {}\n").unwrap_err();
+        assert!(err.contains("at least one service"), "{err}");
+    }
+
+    #[test]
+    fn rejects_self_and_unknown_dependencies() {
+        assert_eq!(
+            dependency_order(&graph(&[("a", &["a"])])),
+            Err("service 'a' depends on itself".to_string())
+        );
+        assert_eq!(
+            dependency_order(&graph(&[("a", &[]), ("b", &["a", "c"])])),
+            Err("service 'b' depends on unknown service 'c'".to_string())
+        );
+    }
+
+    #[test]
+    fn names_the_cycle() {
+        assert_eq!(
+            dependency_order(&graph(&[("a", &["b"]), ("b", &["a"])])),
+            Err("dependency cycle: a -> b -> a".to_string())
+        );
+        assert_eq!(
+            dependency_order(&graph(&[("a", &["b"]), ("b", &["c"]), ("c", &["a"])])),
+            Err("dependency cycle: a -> b -> c -> a".to_string())
+        );
+        // 只列出循環本身，不含走到循環之前的 service
+        assert_eq!(
+            dependency_order(&graph(&[("app", &["db"]), ("db", &["proxy"]), ("proxy", &["db"])])),
+            Err("dependency cycle: db -> proxy -> db".to_string())
+        );
+    }
+
+    #[test]
+    fn diamond_order_is_deterministic() {
+        let diamond: &[(&str, &[&str])] = &[("web", &["cache", "api"]), ("api", &["db"]), ("cache", &["db"]), ("db", &[])];
+        let want = ["db", "api", "cache", "web"];
+        assert_eq!(dependency_order(&graph(diamond)).unwrap(), want);
+        // 輸入的順序不影響結果
+        let mut reversed = diamond.to_vec();
+        reversed.reverse();
+        assert_eq!(dependency_order(&graph(&reversed)).unwrap(), want);
+        assert_eq!(dependency_order(&graph(&[("web", &["api", "cache"]), ("cache", &["db"]), ("db", &[]), ("api", &["db"])])).unwrap(), want);
+    }
+
+    #[test]
+    fn validate_checks_depends_on() {
+        let yaml = |deps_a: &str, deps_b: &str| {
+            format!(
+                "version: \"0.1\"\nname: App\nservices:\n  a:\n    image: a.tar\n    depends_on: {deps_a}\n  b:\n    image: b.tar\n    depends_on: {deps_b}\n"
+            )
+        };
+        let err = check(&yaml("[b]", "[a]")).unwrap_err();
+        assert!(err.contains("dependency cycle: a -> b -> a"), "{err}");
+        let err = check(&yaml("[]", "{ b: { condition: service_started } }")).unwrap_err();
+        assert!(err.contains("service 'b' depends on itself"), "{err}");
+        let err = check(&yaml("[c]", "[]")).unwrap_err();
+        assert!(err.contains("unknown service 'c'"), "{err}");
+        check(&yaml("[]", "{ a: { condition: service_started } }")).unwrap();
+    }
 }
@@ -0,0 +1,234 @@
+// src/assemble.rs
+use anyhow::{Context, Result, bail};
+use fs_err as fs;
+use sha2::{Digest, Sha256};
+use std::{
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+use tar::{Builder, EntryType, Header, HeaderMode};
+
+use appcipe_spec::CompressionConfig;
+
+use crate::{
+    elf::Elf,
+    footer::{
+        FLAG_ELF_SECTION, FLAG_SECTIONED, FOOTER_LEN, FOOTER_VERSION, FOOTER_VERSION_SECTIONED,
+        Footer, Placement,
+    },
+    section,
+};
+
+#[derive(Clone, Debug)]
+pub struct AssembleOptions {
+    /// chefer-runtime 執行檔（不可已帶 footer）
+    pub runtime: PathBuf,
+    /// chefer-pack 產出的 bundle 目錄（dist/<name>）
+    pub bundle_dir: PathBuf,
+    /// 輸出單檔
+    pub output: PathBuf,
+    /// payload 壓縮設定；預設不壓縮
+    pub compression: CompressionConfig,
+    /// payload 放在檔尾或 ELF `.chefer` section
+    pub placement: Placement,
+}
+
+/// 單檔 = runtime stub || payload || footer
+///
+/// 不壓縮時 payload 是單一 tar，runtime 才能直接以 FUSE 掛載；
+/// 有任何區段要壓縮時改用分段格式（見 section.rs），runtime 會解壓到暫存目錄。
+/// tar 內容依路徑排序、uid/gid 歸零，同樣的輸入會得到同樣的位元組，
+/// 差異更新才能以 service 為單位比對。
+///
+/// `Placement::ElfSection` 時 payload + footer 成為 ELF 的 `.chefer` section，
+/// 其後再接新的 section header table；strip / 簽章工具看得到它而不會當成垃圾資料。
+pub fn assemble(opts: &AssembleOptions) -> Result<Footer> {
+    if Footer::read_from_exe(&opts.runtime).is_ok() {
+        bail!(
+            "runtime {:?} already carries a payload; use a plain chefer-runtime build",
+            opts.runtime
+        );
+    }
+    chefer_manifest::Manifest::read(&opts.bundle_dir)
+        .with_context(|| format!("not a valid bundle dir: {:?}", opts.bundle_dir))?;
+
+    let partial = partial_path(&opts.output);
+    let mut out = BufWriter::new(fs::File::create(&partial)?);
+
+    // 1) runtime stub
+    let mut stub = fs::File::open(&opts.runtime)?;
+    let elf = match opts.placement {
+        Placement::Appended => None,
+        Placement::ElfSection => Some(Elf::read(&mut stub)?.with_context(|| {
+            format!(
+                "runtime {:?} is not a 64-bit little-endian ELF",
+                opts.runtime
+            )
+        })?),
+    };
+    stub.seek(SeekFrom::Start(0))?;
+    let offset = io::copy(&mut stub, &mut out)?;
+
+    // 2) payload
+    let sectioned = section::needs_sections(&opts.bundle_dir, &opts.compression)?;
+    let mut hw = HashWriter::new(&mut out);
+    let table_length = if sectioned {
+        section::write_sections(&opts.bundle_dir, &opts.compression, &mut hw)
+    } else {
+        write_bundle_tar(&opts.bundle_dir, &mut hw).map(|_| 0)
+    }
+    .with_context(|| format!("archive bundle {:?}", opts.bundle_dir))?;
+    let (length, sha256) = hw.finish();
+
+    // 3) footer
+    let mut ft = Footer {
+        version: if sectioned {
+            FOOTER_VERSION_SECTIONED
+        } else {
+            FOOTER_VERSION
+        },
+        flags: if sectioned { FLAG_SECTIONED } else { 0 },
+        offset,
+        length,
+        sha256,
+        table_length,
+    };
+    match &elf {
+        None => out.write_all(&ft.to_bytes())?,
+        Some(elf) => {
+            // section 內的 offset 是相對的，讓 strip 之類的工具搬動 section 也不會壞
+            let mut rel = ft;
+            rel.flags |= FLAG_ELF_SECTION;
+            rel.offset = 0;
+            out.write_all(&rel.to_bytes())?;
+            ft.flags = rel.flags;
+
+            let section_len = length + FOOTER_LEN;
+            let ehdr = elf.write_tables(&mut out, offset + section_len, offset, section_len)?;
+            out.flush()?;
+            let f = out.get_mut();
+            f.seek(SeekFrom::Start(0))?;
+            f.write_all(&ehdr)?;
+        }
+    }
+    out.flush()?;
+    drop(out);
+
+    set_executable(&partial)?;
+    fs::rename(&partial, &opts.output)?;
+    Ok(ft)
+}
+
+/// 以穩定順序把 bundle 目錄寫成 tar（路徑相對於 bundle 根目錄）
+pub fn write_bundle_tar<W: Write>(bundle_dir: &Path, w: W) -> Result<()> {
+    let mut b = Builder::new(w);
+    b.mode(HeaderMode::Complete);
+    b.follow_symlinks(false);
+    append_dir_sorted(&mut b, bundle_dir, Path::new(""))?;
+    b.finish()?;
+    Ok(())
+}
+
+fn append_dir_sorted<W: Write>(b: &mut Builder<W>, root: &Path, rel: &Path) -> Result<()> {
+    let mut entries: Vec<_> = fs::read_dir(root.join(rel))?.collect::<io::Result<_>>()?;
+    entries.sort_by_key(|e| e.file_name());
+
+    for e in entries {
+        append_path(b, root, &rel.join(e.file_name()), true)?;
+    }
+    Ok(())
+}
+
+/// 加入一個路徑；目錄在 recurse 時連同內容一起加入
+pub(crate) fn append_path<W: Write>(
+    b: &mut Builder<W>,
+    root: &Path,
+    rel_path: &Path,
+    recurse: bool,
+) -> Result<()> {
+    let abs = root.join(rel_path);
+    let meta = fs::symlink_metadata(&abs)?;
+
+    let mut h = Header::new_gnu();
+    h.set_metadata_in_mode(&meta, HeaderMode::Complete);
+    // 建置機器的擁有者沒有意義；目錄 mtime 受解壓順序影響，固定為 0
+    h.set_uid(0);
+    h.set_gid(0);
+    if meta.is_dir() {
+        h.set_mtime(0);
+    }
+
+    let ft = meta.file_type();
+    if ft.is_dir() {
+        h.set_entry_type(EntryType::Directory);
+        h.set_size(0);
+        b.append_data(&mut h, rel_path, io::empty())?;
+        if recurse {
+            append_dir_sorted(b, root, rel_path)?;
+        }
+    } else if ft.is_symlink() {
+        let target = fs::read_link(&abs)?;
+        h.set_entry_type(EntryType::Symlink);
+        h.set_size(0);
+        b.append_link(&mut h, rel_path, &target)?;
+    } else if ft.is_file() {
+        h.set_entry_type(EntryType::Regular);
+        let f = fs::File::open(&abs)?;
+        b.append_data(&mut h, rel_path, f)?;
+    } else {
+        tracing::warn!("skip special file: {}", abs.display());
+    }
+    Ok(())
+}
+
+pub(crate) fn partial_path(output: &Path) -> PathBuf {
+    let mut name = output.file_name().unwrap_or_default().to_os_string();
+    name.push(".partial");
+    output.with_file_name(name)
+}
+
+#[cfg(unix)]
+pub(crate) fn set_executable(p: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(p, std::fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn set_executable(_p: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// 一邊寫一邊計算長度與 sha256
+pub(crate) struct HashWriter<W> {
+    inner: W,
+    hasher: Sha256,
+    len: u64,
+}
+
+impl<W: Write> HashWriter<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        HashWriter {
+            inner,
+            hasher: Sha256::new(),
+            len: 0,
+        }
+    }
+
+    pub(crate) fn finish(self) -> (u64, [u8; 32]) {
+        (self.len, self.hasher.finalize().into())
+    }
+}
+
+impl<W: Write> Write for HashWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.len += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,522 @@
+// src/delta.rs
+//! 兩個單檔之間的差異更新。
+//!
+//! 單檔被切成有標籤的區段：runtime stub、每個 service 的 tar entries
+//! （services/<name>/…）、其餘 metadata、tar 結尾與 footer。
+//! 分段壓縮的單檔直接沿用 section table 的區段（壓縮是確定性的，
+//! 同樣的 rootfs 與設定會得到同樣的位元組；但 dictionary 一變，所有用它的區段都會變）。
+//! 新檔的每個區段若在舊檔找得到相同內容就只記錄「從舊檔複製」，
+//! 否則把新內容放進 patch；沒變的 service 因此不占任何 patch 空間。
+//!
+//! patch（.chd）格式：
+//!   "CHEFDLT1" || header 長度 (u64 LE) || header JSON || 依序排列的 data 區段
+use anyhow::{Context, Result, bail};
+use fs_err as fs;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Component, Path},
+};
+use tar::Archive;
+
+use crate::{
+    ExeRegion,
+    assemble::{HashWriter, partial_path, set_executable},
+    footer::{FOOTER_LEN, Footer, Located},
+    payload::verify_payload,
+    section::read_section_table,
+};
+
+pub const PATCH_MAGIC: &[u8; 8] = b"CHEFDLT1";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PatchHeader {
+    pub old: FileDigest,
+    pub new: FileDigest,
+    pub chunks: Vec<Chunk>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileDigest {
+    pub size: u64,
+    pub sha256: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Chunk {
+    /// 從舊檔 [offset, offset+length) 複製
+    Copy {
+        label: String,
+        offset: u64,
+        length: u64,
+    },
+    /// 內容跟在 header 後面
+    Data { label: String, length: u64 },
+}
+
+impl Chunk {
+    pub fn label(&self) -> &str {
+        match self {
+            Chunk::Copy { label, .. } | Chunk::Data { label, .. } => label,
+        }
+    }
+
+    pub fn length(&self) -> u64 {
+        match self {
+            Chunk::Copy { length, .. } | Chunk::Data { length, .. } => *length,
+        }
+    }
+
+    pub fn is_copy(&self) -> bool {
+        matches!(self, Chunk::Copy { .. })
+    }
+}
+
+/// 單檔中的一段連續位元組
+#[derive(Debug)]
+struct Segment {
+    label: String,
+    offset: u64,
+    length: u64,
+}
+
+/// 比較 old / new 兩個單檔，寫出 patch；回傳 patch header 供顯示摘要
+pub fn create_patch(old_exe: &Path, new_exe: &Path, patch_out: &Path) -> Result<PatchHeader> {
+    let old_segs = segments(old_exe).with_context(|| format!("scan {:?}", old_exe))?;
+    let new_segs = segments(new_exe).with_context(|| format!("scan {:?}", new_exe))?;
+
+    // 舊檔：內容 sha256 → offset
+    let mut old_file = File::open(old_exe)?;
+    let mut by_hash: HashMap<([u8; 32], u64), u64> = HashMap::new();
+    for s in &old_segs {
+        let h = hash_range(&mut old_file, s.offset, s.length)?;
+        by_hash.entry((h, s.length)).or_insert(s.offset);
+    }
+
+    let mut new_file = File::open(new_exe)?;
+    let mut chunks = Vec::with_capacity(new_segs.len());
+    for s in &new_segs {
+        let h = hash_range(&mut new_file, s.offset, s.length)?;
+        let chunk = match by_hash.get(&(h, s.length)) {
+            Some(&offset) => Chunk::Copy {
+                label: s.label.clone(),
+                offset,
+                length: s.length,
+            },
+            None => Chunk::Data {
+                label: s.label.clone(),
+                length: s.length,
+            },
+        };
+        chunks.push(chunk);
+    }
+
+    let header = PatchHeader {
+        old: digest_file(old_exe)?,
+        new: digest_file(new_exe)?,
+        chunks,
+    };
+
+    let partial = partial_path(patch_out);
+    let mut out = BufWriter::new(fs::File::create(&partial)?);
+    let json = serde_json::to_vec(&header)?;
+    out.write_all(PATCH_MAGIC)?;
+    out.write_all(&(json.len() as u64).to_le_bytes())?;
+    out.write_all(&json)?;
+    for (seg, chunk) in new_segs.iter().zip(&header.chunks) {
+        if !chunk.is_copy() {
+            copy_range(&mut new_file, seg.offset, seg.length, &mut out)?;
+        }
+    }
+    out.flush()?;
+    drop(out);
+    fs::rename(&partial, patch_out)?;
+    Ok(header)
+}
+
+/// 讀取 patch header（不套用）
+pub fn read_patch_header(patch: &Path) -> Result<PatchHeader> {
+    let f = fs::File::open(patch)?;
+    let size = f.metadata()?.len();
+    read_header(&mut BufReader::new(f), size)
+}
+
+/// 對 old_exe 套用 patch，輸出到 out；結果必須符合新檔 sha256 與新 footer 的 payload digest
+pub fn apply_patch(old_exe: &Path, patch: &Path, out_path: &Path) -> Result<Footer> {
+    let pf = fs::File::open(patch)?;
+    let patch_size = pf.metadata()?.len();
+    let mut pr = BufReader::new(pf);
+    let header =
+        read_header(&mut pr, patch_size).with_context(|| format!("read patch {:?}", patch))?;
+
+    let base = digest_file(old_exe)?;
+    if base != header.old {
+        bail!(
+            "patch was made for a different executable (expected sha256 {}, got {})",
+            header.old.sha256,
+            base.sha256
+        );
+    }
+
+    let mut old_file = File::open(old_exe)?;
+    let partial = partial_path(out_path);
+    let mut hw = HashWriter::new(BufWriter::new(fs::File::create(&partial)?));
+    for chunk in &header.chunks {
+        match chunk {
+            Chunk::Copy { offset, length, .. } => {
+                copy_range(&mut old_file, *offset, *length, &mut hw)?;
+            }
+            Chunk::Data { length, label } => {
+                let n = io::copy(&mut (&mut pr).take(*length), &mut hw)?;
+                if n != *length {
+                    bail!("patch truncated in chunk `{label}`");
+                }
+            }
+        }
+    }
+    hw.flush()?;
+    let (size, sha) = hw.finish();
+
+    let verify = || -> Result<Footer> {
+        if size != header.new.size || hex::encode(sha) != header.new.sha256 {
+            bail!("patched output does not match the expected new executable");
+        }
+        verify_payload(&partial).context("patched payload does not match the footer digest")
+    };
+    match verify() {
+        Ok(ft) => {
+            set_executable(&partial)?;
+            fs::rename(&partial, out_path)?;
+            Ok(ft)
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&partial);
+            Err(e)
+        }
+    }
+}
+
+/// `size` 為整個 patch 檔的大小；header 長度來自不可信的檔案，超過剩餘大小就拒絕，避免照著配置記憶體
+fn read_header<R: Read>(r: &mut R, size: u64) -> Result<PatchHeader> {
+    let mut magic = [0u8; 8];
+    r.read_exact(&mut magic)?;
+    if &magic != PATCH_MAGIC {
+        bail!("not a chefer patch (bad magic)");
+    }
+    let mut len = [0u8; 8];
+    r.read_exact(&mut len)?;
+    let len = u64::from_le_bytes(len);
+    let remaining = size.saturating_sub((PATCH_MAGIC.len() + 8) as u64);
+    if len > remaining {
+        bail!(
+            "patch header length {len} exceeds the remaining {remaining} bytes (truncated or corrupt patch)"
+        );
+    }
+    let mut json = vec![0u8; usize::try_from(len)?];
+    r.read_exact(&mut json)?;
+    Ok(serde_json::from_slice(&json)?)
+}
+
+/// 把單檔切成：runtime | tar entries（依 service 分組）| tar 結尾 | footer
+fn segments(exe: &Path) -> Result<Vec<Segment>> {
+    let loc = Footer::locate(exe)?;
+    let ft = loc.footer;
+    if ft.compressed_is_zstd() {
+        bail!("delta does not support whole-payload zstd; rebuild with per-section compression");
+    }
+    let mut segs = vec![Segment {
+        label: "runtime".into(),
+        offset: 0,
+        length: ft.offset,
+    }];
+
+    if let Some(table) = read_section_table(exe, &ft)? {
+        if let Some(d) = table.dictionary {
+            segs.push(Segment {
+                label: "zstd-dictionary".into(),
+                offset: ft.offset + d.offset,
+                length: d.length,
+            });
+        }
+        for s in &table.sections {
+            segs.push(Segment {
+                label: s.name.clone(),
+                offset: ft.offset + s.offset,
+                length: s.length,
+            });
+        }
+        segs.push(Segment {
+            label: "section-table".into(),
+            offset: ft.offset + ft.length - ft.table_length,
+            length: ft.table_length,
+        });
+        return finish_segments(segs, &loc);
+    }
+
+    let region = ExeRegion::new(File::open(exe)?, ft.offset, ft.length);
+    let mut ar = Archive::new(region);
+    // entry 的區段從上一個 entry 結尾開始，連同 longname/pax 等擴充 header 一起算
+    let mut prev_end = 0u64;
+    for entry in ar.entries_with_seek()? {
+        let entry = entry?;
+        let label = group_label(&entry.path()?);
+        let end = entry.raw_file_position() + entry.size().next_multiple_of(512);
+        match segs.last_mut() {
+            Some(last)
+                if last.label == label && last.offset + last.length == ft.offset + prev_end =>
+            {
+                last.length += end - prev_end;
+            }
+            _ => segs.push(Segment {
+                label,
+                offset: ft.offset + prev_end,
+                length: end - prev_end,
+            }),
+        }
+        prev_end = end;
+    }
+    if prev_end < ft.length {
+        segs.push(Segment {
+            label: "tar-trailer".into(),
+            offset: ft.offset + prev_end,
+            length: ft.length - prev_end,
+        });
+    }
+    finish_segments(segs, &loc)
+}
+
+/// 檢查區段首尾相接、涵蓋整個 payload，再補上 footer 與其後的資料
+/// （ELF section headers 或附加在檔尾的簽章等）
+fn finish_segments(mut segs: Vec<Segment>, loc: &Located) -> Result<Vec<Segment>> {
+    let ft = &loc.footer;
+    let mut at = 0;
+    for s in &segs {
+        if s.offset != at {
+            bail!("gap before segment `{}`", s.label);
+        }
+        at += s.length;
+    }
+    let footer_at = ft.offset + ft.length;
+    if at != footer_at {
+        bail!("segments do not cover the payload");
+    }
+    if loc.footer_at != footer_at {
+        bail!("unexpected data between payload and footer");
+    }
+    segs.push(Segment {
+        label: "footer".into(),
+        offset: footer_at,
+        length: FOOTER_LEN,
+    });
+    if loc.trailing > 0 {
+        segs.push(Segment {
+            label: "trailing".into(),
+            offset: footer_at + FOOTER_LEN,
+            length: loc.trailing,
+        });
+    }
+    Ok(segs)
+}
+
+/// services/<name>/… → "service:<name>"，其他 → "meta"
+fn group_label(p: &Path) -> String {
+    let mut comps = p
+        .components()
+        .filter(|c| !matches!(c, Component::CurDir | Component::RootDir));
+    match (comps.next(), comps.next()) {
+        (Some(first), Some(Component::Normal(name))) if first.as_os_str() == "services" => {
+            format!("service:{}", name.to_string_lossy())
+        }
+        _ => "meta".into(),
+    }
+}
+
+fn digest_file(p: &Path) -> Result<FileDigest> {
+    let mut f = File::open(p).with_context(|| format!("open {:?}", p))?;
+    let size = f.metadata()?.len();
+    let sha = hash_range(&mut f, 0, size)?;
+    Ok(FileDigest {
+        size,
+        sha256: hex::encode(sha),
+    })
+}
+
+fn hash_range(f: &mut File, offset: u64, length: u64) -> Result<[u8; 32]> {
+    let mut hw = HashWriter::new(io::sink());
+    copy_range(f, offset, length, &mut hw)?;
+    Ok(hw.finish().1)
+}
+
+fn copy_range<W: Write>(f: &mut File, offset: u64, length: u64, w: &mut W) -> Result<()> {
+    f.seek(SeekFrom::Start(offset))?;
+    let n = io::copy(&mut f.take(length), w)?;
+    if n != length {
+        bail!("unexpected end of file at offset {}", offset + n);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assemble::{AssembleOptions, assemble};
+    use appcipe_spec::{Codec, CompressionConfig};
+    use std::path::PathBuf;
+
+    const MANIFEST: &str = r#"{
+        "manifest_version": 3,
+        "app_name": "App",
+        "spec_version": "0.1",
+        "generated_at_utc": "2026-01-01T00:00:00Z",
+        "services": [
+            { "name": "a", "rootfs_rel": "services/a/rootfs" },
+            { "name": "b", "rootfs_rel": "services/b/rootfs" }
+        ]
+    }"#;
+
+    /// 內容相同、mtime 也相同（tar 保留檔案的 mtime），才會得到相同的位元組
+    fn write(path: &Path, content: &[u8]) {
+        fs::write(path, content).unwrap();
+        let mtime = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+    }
+
+    /// services a、b 各有一個檔案，內容分別為 a、b
+    fn build(dir: &Path, name: &str, a: &[u8], b: &[u8], codec: Codec) -> PathBuf {
+        let bundle = dir.join(name);
+        for (svc, content) in [("a", a), ("b", b)] {
+            let rootfs = bundle.join("services").join(svc).join("rootfs");
+            fs::create_dir_all(&rootfs).unwrap();
+            write(&rootfs.join("data"), content);
+        }
+        write(
+            &bundle.join(chefer_manifest::MANIFEST_FILE),
+            MANIFEST.as_bytes(),
+        );
+        let runtime = dir.join("runtime");
+        if !runtime.exists() {
+            fs::write(&runtime, b"runtime stub").unwrap();
+        }
+        let output = dir.join(format!("{name}.run"));
+        assemble(&AssembleOptions {
+            runtime,
+            bundle_dir: bundle,
+            output: output.clone(),
+            compression: CompressionConfig {
+                codec,
+                ..Default::default()
+            },
+            placement: crate::Placement::Appended,
+        })
+        .unwrap();
+        output
+    }
+
+    fn copied(header: &PatchHeader, label: &str) -> bool {
+        let mut chunks = header.chunks.iter().filter(|c| c.label() == label);
+        chunks.clone().next().is_some() && chunks.all(Chunk::is_copy)
+    }
+
+    fn roundtrip(codec: Codec) {
+        let dir = tempfile::tempdir().unwrap();
+        let big = vec![b'x'; 64 * 1024];
+        let old = build(dir.path(), "old", &big, b"old", codec);
+        let new = build(dir.path(), "new", &big, b"new", codec);
+        let patch = dir.path().join("patch.chd");
+        let header = create_patch(&old, &new, &patch).unwrap();
+
+        // 沒變的 service 與 runtime 只記錄複製，patch 遠小於 service a 的內容
+        assert!(copied(&header, "runtime"));
+        assert!(copied(&header, "service:a"), "{:?}", header.chunks);
+        assert!(!copied(&header, "service:b"));
+        assert!(fs::metadata(&patch).unwrap().len() < big.len() as u64 / 2);
+
+        let out = dir.path().join("patched.run");
+        apply_patch(&old, &patch, &out).unwrap();
+        assert_eq!(fs::read(&out).unwrap(), fs::read(&new).unwrap());
+    }
+
+    #[test]
+    fn plain_tar_patch_roundtrip() {
+        roundtrip(Codec::None);
+    }
+
+    #[test]
+    fn sectioned_patch_roundtrip() {
+        roundtrip(Codec::Zstd);
+    }
+
+    #[test]
+    fn apply_rejects_other_base() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = build(dir.path(), "old", b"a", b"old", Codec::None);
+        let new = build(dir.path(), "new", b"a", b"new", Codec::None);
+        let patch = dir.path().join("patch.chd");
+        create_patch(&old, &new, &patch).unwrap();
+
+        let out = dir.path().join("patched.run");
+        let e = apply_patch(&new, &patch, &out).unwrap_err();
+        assert!(e.to_string().contains("different executable"), "{e}");
+        assert!(!out.exists());
+    }
+
+    #[test]
+    fn apply_rejects_tampered_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = build(dir.path(), "old", b"a", b"old", Codec::None);
+        let new = build(dir.path(), "new", b"a", b"new", Codec::None);
+        let patch = dir.path().join("patch.chd");
+        create_patch(&old, &new, &patch).unwrap();
+
+        // 改掉 patch 中新內容的一個 byte
+        let mut bytes = fs::read(&patch).unwrap();
+        let at = bytes.windows(3).rposition(|w| w == b"new").unwrap();
+        bytes[at] = b'N';
+        fs::write(&patch, bytes).unwrap();
+
+        let out = dir.path().join("patched.run");
+        let e = apply_patch(&old, &patch, &out).unwrap_err();
+        assert!(e.to_string().contains("does not match"), "{e}");
+        assert!(!out.exists() && !partial_path(&out).exists());
+    }
+
+    #[test]
+    fn rejects_truncated_or_oversized_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = build(dir.path(), "old", b"a", b"old", Codec::None);
+        let new = build(dir.path(), "new", b"a", b"new", Codec::None);
+        let patch = dir.path().join("patch.chd");
+        create_patch(&old, &new, &patch).unwrap();
+        let bytes = fs::read(&patch).unwrap();
+        let json_len = u64::from_le_bytes(bytes[8..16].try_into().unwrap()) as usize;
+
+        // header 被截斷
+        let truncated = dir.path().join("truncated.chd");
+        fs::write(&truncated, &bytes[..16 + json_len / 2]).unwrap();
+        let e = read_patch_header(&truncated).unwrap_err();
+        assert!(e.to_string().contains("exceeds the remaining"), "{e}");
+
+        // 宣告的長度遠超過檔案大小：不能照著配置
+        let mut oversized = bytes.clone();
+        oversized[8..16].copy_from_slice(&u64::MAX.to_le_bytes());
+        let huge = dir.path().join("oversized.chd");
+        fs::write(&huge, oversized).unwrap();
+        let out = dir.path().join("patched.run");
+        let e = apply_patch(&old, &huge, &out).unwrap_err();
+        assert!(format!("{e:#}").contains("exceeds the remaining"), "{e:#}");
+        assert!(!out.exists());
+
+        // 連長度欄位都不完整
+        let short = dir.path().join("short.chd");
+        fs::write(&short, &bytes[..12]).unwrap();
+        assert!(read_patch_header(&short).is_err());
+    }
+}
@@ -0,0 +1,154 @@
+// src/elf.rs
+//! 把 payload 放進 ELF 的 `.chefer` section（不占 load segment，不影響執行）。
+//! 只處理 64-bit little-endian ELF；其他格式請用預設的附加在檔尾。
+use anyhow::{Result, bail};
+use std::io::{Read, Seek, SeekFrom, Write};
+
+pub const SECTION_NAME: &str = ".chefer";
+
+const EHDR_LEN: usize = 64;
+const SHDR_LEN: usize = 64;
+const SHT_PROGBITS: u32 = 1;
+const SHN_LORESERVE: u16 = 0xff00;
+
+/// ELF header 中用得到的欄位
+#[derive(Debug, Clone)]
+pub(crate) struct Elf {
+    ehdr: [u8; EHDR_LEN],
+    shnum: u16,
+    shstrndx: u16,
+    /// 原本的 section header table
+    shdrs: Vec<[u8; SHDR_LEN]>,
+    /// 原本的 section 名稱字串表
+    shstrtab: Vec<u8>,
+}
+
+fn u16_at(b: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes(b[at..at + 2].try_into().unwrap())
+}
+fn u32_at(b: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(b[at..at + 4].try_into().unwrap())
+}
+fn u64_at(b: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(b[at..at + 8].try_into().unwrap())
+}
+
+impl Elf {
+    /// 非 ELF64 LE 或沒有 section header 時回傳 None
+    pub(crate) fn read<R: Read + Seek>(f: &mut R) -> Result<Option<Self>> {
+        let size = f.seek(SeekFrom::End(0))?;
+        let mut ehdr = [0u8; EHDR_LEN];
+        if size < EHDR_LEN as u64 {
+            return Ok(None);
+        }
+        f.seek(SeekFrom::Start(0))?;
+        f.read_exact(&mut ehdr)?;
+        // \x7fELF, ELFCLASS64, ELFDATA2LSB
+        if &ehdr[0..4] != b"\x7fELF" || ehdr[4] != 2 || ehdr[5] != 1 {
+            return Ok(None);
+        }
+        let shoff = u64_at(&ehdr, 0x28);
+        let shentsize = u16_at(&ehdr, 0x3a);
+        let shnum = u16_at(&ehdr, 0x3c);
+        let shstrndx = u16_at(&ehdr, 0x3e);
+        if shoff == 0 || shnum == 0 {
+            return Ok(None);
+        }
+        if shentsize as usize != SHDR_LEN || shnum >= SHN_LORESERVE || shstrndx >= shnum {
+            bail!("unsupported ELF section header layout");
+        }
+        if shoff + shnum as u64 * SHDR_LEN as u64 > size {
+            bail!("ELF section header table out of range");
+        }
+
+        f.seek(SeekFrom::Start(shoff))?;
+        let mut shdrs = vec![[0u8; SHDR_LEN]; shnum as usize];
+        for h in &mut shdrs {
+            f.read_exact(h)?;
+        }
+        let strtab = &shdrs[shstrndx as usize];
+        let (str_off, str_len) = (u64_at(strtab, 0x18), u64_at(strtab, 0x20));
+        if str_off + str_len > size {
+            bail!("ELF .shstrtab out of range");
+        }
+        let mut shstrtab = vec![0u8; usize::try_from(str_len)?];
+        f.seek(SeekFrom::Start(str_off))?;
+        f.read_exact(&mut shstrtab)?;
+
+        Ok(Some(Elf {
+            ehdr,
+            shnum,
+            shstrndx,
+            shdrs,
+            shstrtab,
+        }))
+    }
+
+    fn section_name(&self, h: &[u8; SHDR_LEN]) -> &[u8] {
+        let start = (u32_at(h, 0) as usize).min(self.shstrtab.len());
+        let rest = &self.shstrtab[start..];
+        &rest[..rest.iter().position(|&b| b == 0).unwrap_or(rest.len())]
+    }
+
+    /// 找 section 的 (file offset, size)
+    pub(crate) fn find_section(&self, name: &str) -> Option<(u64, u64)> {
+        self.shdrs
+            .iter()
+            .find(|h| self.section_name(h) == name.as_bytes())
+            .map(|h| (u64_at(h, 0x18), u64_at(h, 0x20)))
+    }
+
+    /// 在 `section_at` 已寫好 `section_len` bytes 的 section 內容後，
+    /// 寫出新的 .shstrtab 與 section header table（多一個 `.chefer`），
+    /// 並回傳要覆寫回檔案開頭的新 ELF header。`pos` 是目前寫到的位置。
+    pub(crate) fn write_tables<W: Write>(
+        &self,
+        w: &mut W,
+        mut pos: u64,
+        section_at: u64,
+        section_len: u64,
+    ) -> Result<[u8; EHDR_LEN]> {
+        if self.find_section(SECTION_NAME).is_some() {
+            bail!("runtime already has a {SECTION_NAME} section");
+        }
+        if self.shnum + 1 >= SHN_LORESERVE {
+            bail!("too many ELF sections");
+        }
+
+        // 新字串表 = 舊的 + ".chefer\0"
+        let mut strtab = self.shstrtab.clone();
+        let name_off = strtab.len() as u32;
+        strtab.extend_from_slice(SECTION_NAME.as_bytes());
+        strtab.push(0);
+        let strtab_at = pos;
+        w.write_all(&strtab)?;
+        pos += strtab.len() as u64;
+
+        let pad = pos.next_multiple_of(8) - pos;
+        w.write_all(&vec![0u8; pad as usize])?;
+        pos += pad;
+        let shoff = pos;
+
+        let mut shdrs = self.shdrs.clone();
+        let strhdr = &mut shdrs[self.shstrndx as usize];
+        strhdr[0x18..0x20].copy_from_slice(&strtab_at.to_le_bytes());
+        strhdr[0x20..0x28].copy_from_slice(&(strtab.len() as u64).to_le_bytes());
+
+        let mut ours = [0u8; SHDR_LEN];
+        ours[0..4].copy_from_slice(&name_off.to_le_bytes());
+        ours[4..8].copy_from_slice(&SHT_PROGBITS.to_le_bytes());
+        // sh_flags 0：不 SHF_ALLOC，載入時不會被映射
+        ours[0x18..0x20].copy_from_slice(&section_at.to_le_bytes());
+        ours[0x20..0x28].copy_from_slice(&section_len.to_le_bytes());
+        ours[0x30..0x38].copy_from_slice(&1u64.to_le_bytes());
+        shdrs.push(ours);
+        for h in &shdrs {
+            w.write_all(h)?;
+        }
+
+        let mut ehdr = self.ehdr;
+        ehdr[0x28..0x30].copy_from_slice(&shoff.to_le_bytes());
+        ehdr[0x3c..0x3e].copy_from_slice(&(self.shnum + 1).to_le_bytes());
+        Ok(ehdr)
+    }
+}
@@ -0,0 +1,342 @@
+// src/footer.rs
+use anyhow::{Context, Result, bail};
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use crate::elf::{self, Elf};
+
+pub const FOOTER_LEN: u64 = 80;
+pub const MAGIC: &[u8; 8] = b"CHEFER\0\0";
+/// 未壓縮的單一 tar payload（可直接 FUSE 掛載）
+pub const FOOTER_VERSION: u8 = 1;
+/// 分段壓縮的 payload，帶 section table
+pub const FOOTER_VERSION_SECTIONED: u8 = 2;
+
+/// flags bit0：payload 為 zstd(tar)（舊格式，只讀不寫）
+pub const FLAG_ZSTD: u8 = 0b0000_0001;
+/// flags bit1：payload 為各自壓縮的區段 + 結尾的 section table（見 section.rs）
+pub const FLAG_SECTIONED: u8 = 0b0000_0010;
+/// flags bit2：payload + footer 放在 ELF 的 `.chefer` section，offset 相對於 section 起點
+pub const FLAG_ELF_SECTION: u8 = 0b0000_0100;
+
+/// 檔尾被附加資料（簽章、安裝器包裝等）時，往回找 footer 的最大範圍
+pub const SCAN_WINDOW: u64 = 8 * 1024 * 1024;
+
+/// 單檔結尾的 80 bytes：
+///   0..8   magic "CHEFER\0\0"
+///   8      version
+///   9      flags
+///   10..16 reserved
+///   16..24 payload offset (LE)
+///   24..32 payload length (LE)
+///   32..64 payload sha256
+///   64..72 section table 長度 (LE)；位於 payload 最後，version 2 才有
+///   72..76 reserved
+///   76..80 footer 前 76 bytes 的 CRC32 (LE)；0 表示舊版未填
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Footer {
+    pub version: u8,
+    pub flags: u8,
+    pub offset: u64,
+    pub length: u64,
+    pub sha256: [u8; 32],
+    pub table_length: u64,
+}
+
+/// footer 在檔案中的位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placement {
+    /// 附加在 runtime 之後（預設）
+    Appended,
+    /// 在 ELF 的 `.chefer` section 裡
+    ElfSection,
+}
+
+/// 找到的 footer；offset 已換算成檔案內的絕對位置
+#[derive(Debug, Clone, Copy)]
+pub struct Located {
+    pub footer: Footer,
+    pub placement: Placement,
+    /// footer 本身在檔案中的位置
+    pub footer_at: u64,
+    /// footer 之後被忽略的位元組數（ELF section 時為其後的 section headers 等）
+    pub trailing: u64,
+}
+
+impl Footer {
+    pub fn read_from_exe(exe: &Path) -> Result<Self> {
+        Ok(Self::locate(exe)?.footer)
+    }
+
+    /// 依序嘗試：檔尾 80 bytes → ELF `.chefer` section → 往回掃描 SCAN_WINDOW。
+    /// 後兩者都要求 footer checksum 正確，且 payload 緊接在 footer 之前。
+    pub fn locate(exe: &Path) -> Result<Located> {
+        let mut f = File::open(exe).with_context(|| format!("open exe {:?}", exe))?;
+        let size = f.metadata()?.len();
+        if size < FOOTER_LEN {
+            bail!("file too small, no footer");
+        }
+
+        let mut buf = [0u8; FOOTER_LEN as usize];
+        f.seek(SeekFrom::End(-(FOOTER_LEN as i64)))?;
+        f.read_exact(&mut buf)?;
+        if &buf[0..8] == MAGIC && buf[9] & FLAG_ELF_SECTION == 0 {
+            let footer = Self::parse(&buf, size)?;
+            return Ok(Located {
+                footer,
+                placement: Placement::Appended,
+                footer_at: size - FOOTER_LEN,
+                trailing: 0,
+            });
+        }
+
+        if let Some(found) = Self::locate_elf(&mut f, size)? {
+            return Ok(found);
+        }
+
+        let window = size.min(SCAN_WINDOW);
+        let mut tail = vec![0u8; window as usize];
+        f.seek(SeekFrom::Start(size - window))?;
+        f.read_exact(&mut tail)?;
+        let last = tail.len() - FOOTER_LEN as usize;
+        for i in (0..=last).rev() {
+            if &tail[i..i + 8] != MAGIC {
+                continue;
+            }
+            let cand: &[u8; FOOTER_LEN as usize] =
+                tail[i..i + FOOTER_LEN as usize].try_into().unwrap();
+            let footer_at = size - window + i as u64;
+            // runtime 本身也含有 magic 字串，checksum 與位置都對得上才算
+            if stored_checksum(cand) == 0 || cand[9] & FLAG_ELF_SECTION != 0 {
+                continue;
+            }
+            let Ok(footer) = Self::parse(cand, size) else {
+                continue;
+            };
+            if footer.offset + footer.length == footer_at {
+                return Ok(Located {
+                    footer,
+                    placement: Placement::Appended,
+                    footer_at,
+                    trailing: size - footer_at - FOOTER_LEN,
+                });
+            }
+        }
+        bail!("no chefer footer found (checked the last {} bytes)", window)
+    }
+
+    fn locate_elf(f: &mut File, size: u64) -> Result<Option<Located>> {
+        let Some(elf) = Elf::read(f)? else {
+            return Ok(None);
+        };
+        let Some((at, len)) = elf.find_section(elf::SECTION_NAME) else {
+            return Ok(None);
+        };
+        if len < FOOTER_LEN || at + len > size {
+            bail!("{} section out of range", elf::SECTION_NAME);
+        }
+        let footer_at = at + len - FOOTER_LEN;
+        let mut buf = [0u8; FOOTER_LEN as usize];
+        f.seek(SeekFrom::Start(footer_at))?;
+        f.read_exact(&mut buf)?;
+        if stored_checksum(&buf) == 0 || buf[9] & FLAG_ELF_SECTION == 0 {
+            bail!(
+                "{} section does not end with a chefer footer",
+                elf::SECTION_NAME
+            );
+        }
+        let mut footer = Self::parse(&buf, len)?;
+        footer.offset += at;
+        if footer.offset + footer.length != footer_at {
+            bail!("{} section layout mismatch", elf::SECTION_NAME);
+        }
+        Ok(Some(Located {
+            footer,
+            placement: Placement::ElfSection,
+            footer_at,
+            trailing: size - footer_at - FOOTER_LEN,
+        }))
+    }
+
+    /// 解析 footer；file_size 用來檢查 offset/length 是否越界
+    pub fn parse(buf: &[u8; FOOTER_LEN as usize], file_size: u64) -> Result<Self> {
+        if &buf[0..8] != MAGIC {
+            bail!("bad magic");
+        }
+        let stored = stored_checksum(buf);
+        if stored != 0 && stored != checksum(buf) {
+            bail!("footer checksum mismatch");
+        }
+        let version = buf[8];
+        let flags = buf[9];
+        // 10..16 reserved
+        let offset = u64::from_le_bytes(buf[16..24].try_into().unwrap());
+        let length = u64::from_le_bytes(buf[24..32].try_into().unwrap());
+        let mut sha256 = [0u8; 32];
+        sha256.copy_from_slice(&buf[32..64]);
+        let table_length = u64::from_le_bytes(buf[64..72].try_into().unwrap());
+        // 72..80 reserved
+
+        match version {
+            FOOTER_VERSION if flags & FLAG_SECTIONED == 0 => {}
+            FOOTER_VERSION_SECTIONED if flags & FLAG_SECTIONED != 0 => {
+                if table_length == 0 || table_length > length {
+                    bail!("section table length out of range: {}", table_length);
+                }
+            }
+            FOOTER_VERSION | FOOTER_VERSION_SECTIONED => {
+                bail!(
+                    "footer version {} does not match flags {:#04x}",
+                    version,
+                    flags
+                )
+            }
+            _ => bail!("unsupported footer version: {}", version),
+        }
+        // 基本檢查
+        if offset
+            .checked_add(length)
+            .filter(|&end| end <= file_size)
+            .is_none()
+        {
+            bail!(
+                "footer offset/length out of range (offset={}, len={}, file_size={})",
+                offset,
+                length,
+                file_size
+            );
+        }
+        Ok(Footer {
+            version,
+            flags,
+            offset,
+            length,
+            sha256,
+            table_length,
+        })
+    }
+
+    pub fn to_bytes(&self) -> [u8; FOOTER_LEN as usize] {
+        let mut buf = [0u8; FOOTER_LEN as usize];
+        buf[0..8].copy_from_slice(MAGIC);
+        buf[8] = self.version;
+        buf[9] = self.flags;
+        buf[16..24].copy_from_slice(&self.offset.to_le_bytes());
+        buf[24..32].copy_from_slice(&self.length.to_le_bytes());
+        buf[32..64].copy_from_slice(&self.sha256);
+        buf[64..72].copy_from_slice(&self.table_length.to_le_bytes());
+        let crc = checksum(&buf);
+        buf[76..80].copy_from_slice(&crc.to_le_bytes());
+        buf
+    }
+
+    pub fn compressed_is_zstd(&self) -> bool {
+        (self.flags & FLAG_ZSTD) != 0
+    }
+
+    pub fn in_elf_section(&self) -> bool {
+        (self.flags & FLAG_ELF_SECTION) != 0
+    }
+
+    pub fn is_sectioned(&self) -> bool {
+        (self.flags & FLAG_SECTIONED) != 0
+    }
+
+    /// payload 就是一個未壓縮的 tar（可直接掛載、做 delta）
+    pub fn is_plain_tar(&self) -> bool {
+        (self.flags & (FLAG_ZSTD | FLAG_SECTIONED)) == 0
+    }
+}
+
+fn checksum(buf: &[u8; FOOTER_LEN as usize]) -> u32 {
+    crc32fast::hash(&buf[..76])
+}
+
+fn stored_checksum(buf: &[u8; FOOTER_LEN as usize]) -> u32 {
+    u32::from_le_bytes(buf[76..80].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// runtime stub（內含 magic 字串，與真的 runtime 一樣）|| payload || footer || trailing
+    fn exe(payload: &[u8], trailing: &[u8]) -> (tempfile::NamedTempFile, Footer) {
+        let mut stub = b"\x7fELF-not-really ".to_vec();
+        stub.extend_from_slice(MAGIC);
+        stub.extend_from_slice(&[0u8; 100]);
+        let ft = Footer {
+            version: FOOTER_VERSION,
+            flags: 0,
+            offset: stub.len() as u64,
+            length: payload.len() as u64,
+            sha256: [7; 32],
+            table_length: 0,
+        };
+        let mut f = tempfile::NamedTempFile::new().unwrap();
+        f.write_all(&stub).unwrap();
+        f.write_all(payload).unwrap();
+        f.write_all(&ft.to_bytes()).unwrap();
+        f.write_all(trailing).unwrap();
+        (f, ft)
+    }
+
+    #[test]
+    fn roundtrip() {
+        let (_, ft) = exe(b"payload", b"");
+        let parsed = Footer::parse(&ft.to_bytes(), 1 << 20).unwrap();
+        assert_eq!(parsed, ft);
+        assert!(parsed.is_plain_tar());
+    }
+
+    #[test]
+    fn parse_rejects_corruption() {
+        let (_, ft) = exe(b"payload", b"");
+        let mut buf = ft.to_bytes();
+        buf[20] ^= 1;
+        let e = Footer::parse(&buf, 1 << 20).unwrap_err();
+        assert!(e.to_string().contains("checksum"), "{e}");
+
+        let e = Footer::parse(&ft.to_bytes(), ft.offset).unwrap_err();
+        assert!(e.to_string().contains("out of range"), "{e}");
+
+        let mut bad = ft;
+        bad.flags = FLAG_SECTIONED;
+        let e = Footer::parse(&bad.to_bytes(), 1 << 20).unwrap_err();
+        assert!(e.to_string().contains("does not match flags"), "{e}");
+    }
+
+    #[test]
+    fn locate_at_end() {
+        let (f, ft) = exe(b"payload", b"");
+        let loc = Footer::locate(f.path()).unwrap();
+        assert_eq!(loc.footer, ft);
+        assert_eq!(loc.placement, Placement::Appended);
+        assert_eq!(loc.footer_at, ft.offset + ft.length);
+        assert_eq!(loc.trailing, 0);
+    }
+
+    #[test]
+    fn locate_skips_trailing_data() {
+        // 附加的資料裡也有 magic（例如另一個被包進來的單檔），checksum 或位置不符就略過
+        let mut trailing = b"signature".to_vec();
+        trailing.extend_from_slice(MAGIC);
+        trailing.extend_from_slice(&[0u8; FOOTER_LEN as usize]);
+        let (f, ft) = exe(b"payload", &trailing);
+        let loc = Footer::locate(f.path()).unwrap();
+        assert_eq!(loc.footer, ft);
+        assert_eq!(loc.trailing, trailing.len() as u64);
+    }
+
+    #[test]
+    fn locate_without_footer() {
+        let mut f = tempfile::NamedTempFile::new().unwrap();
+        f.write_all(&[0u8; 4096]).unwrap();
+        let e = Footer::locate(f.path()).unwrap_err();
+        assert!(e.to_string().contains("no chefer footer"), "{e}");
+    }
+}
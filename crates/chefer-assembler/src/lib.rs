@@ -0,0 +1,13 @@
+mod assemble;
+pub mod delta;
+mod elf;
+pub mod footer;
+mod payload;
+mod region;
+pub mod section;
+pub mod update;
+
+pub use assemble::*;
+pub use footer::{Footer, Located, Placement};
+pub use payload::{BundleEntry, list_bundle, read_bundle_file, unpack_payload, verify_payload};
+pub use region::ExeRegion;
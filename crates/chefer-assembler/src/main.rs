@@ -1,3 +1,83 @@
-fn main() {
-    println!("Hello, world!");
+use anyhow::{Result, anyhow};
+use appcipe_spec::{Codec, CompressionConfig, SectionCompression};
+use chefer_assembler::Placement;
+use clap::Parser;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "chefer-assembler",
+    version,
+    about = "把 chefer-runtime 與 bundle 組裝成單一執行檔"
+)]
+struct Args {
+    /// chefer-runtime 執行檔
+    #[arg(long)]
+    runtime: PathBuf,
+
+    /// chefer-pack 產出的 bundle 目錄（dist/<name>）
+    #[arg(long)]
+    bundle: PathBuf,
+
+    /// 輸出檔案
+    #[arg(long, short)]
+    output: PathBuf,
+
+    /// payload codec：none | zstd | xz | lz4（預設 none，可直接掛載）
+    #[arg(long, default_value = "none")]
+    codec: Codec,
+
+    /// 壓縮等級（zstd 1..=22、xz 0..=9）
+    #[arg(long)]
+    level: Option<i32>,
+
+    /// 依 service 覆寫：<service>=<codec>[:<level>]，可重複
+    #[arg(long = "service-codec", value_name = "SERVICE=CODEC[:LEVEL]",
+          value_parser = chefer_assembler::section::parse_service_override)]
+    service_codec: Vec<(String, SectionCompression)>,
+
+    /// 以 service rootfs 訓練共用的 zstd dictionary
+    #[arg(long)]
+    zstd_dict: bool,
+
+    /// 把 payload 放進 ELF `.chefer` section，而不是附加在檔尾
+    #[arg(long)]
+    elf_section: bool,
+}
+
+fn main() -> Result<()> {
+    // 區段壓縮等警告印到 stderr
+    tracing_subscriber::fmt()
+        .with_max_level(tracing_subscriber::filter::LevelFilter::WARN)
+        .with_target(false)
+        .without_time()
+        .with_writer(std::io::stderr)
+        .init();
+    let args = Args::parse();
+    appcipe_spec::validate_level("--level", args.codec, args.level).map_err(|e| anyhow!(e))?;
+    let compression = CompressionConfig {
+        codec: args.codec,
+        level: args.level,
+        dictionary: args.zstd_dict,
+        services: args.service_codec.into_iter().collect(),
+    };
+    let ft = chefer_assembler::assemble(&chefer_assembler::AssembleOptions {
+        runtime: args.runtime,
+        bundle_dir: args.bundle,
+        output: args.output.clone(),
+        compression,
+        placement: if args.elf_section {
+            Placement::ElfSection
+        } else {
+            Placement::Appended
+        },
+    })?;
+    println!(
+        "assembled {} (payload {} bytes at offset {}, sha256={})",
+        args.output.display(),
+        ft.length,
+        ft.offset,
+        hex::encode(ft.sha256)
+    );
+    Ok(())
 }
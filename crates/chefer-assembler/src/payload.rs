@@ -0,0 +1,114 @@
+// src/payload.rs
+use anyhow::{Result, bail};
+use fs_err as fs;
+use std::{
+    fs::File,
+    io::Read,
+    path::{Component, Path},
+};
+use tar::Archive;
+
+use crate::{ExeRegion, assemble::HashWriter, footer::Footer, section::tar_streams};
+
+/// 不解壓整包，直接從單檔的 payload 讀出一個檔案（例如 manifest.json）
+pub fn read_bundle_file(exe: &Path, name: &str) -> Result<Option<Vec<u8>>> {
+    for stream in tar_streams(exe)? {
+        if let Some(buf) = find_in_tar(stream, name)? {
+            return Ok(Some(buf));
+        }
+    }
+    Ok(None)
+}
+
+/// 驗證 payload digest 後把整個 bundle 解到 out（不存在會自動建立）
+pub fn unpack_payload(exe: &Path, out: &Path) -> Result<Footer> {
+    let ft = verify_payload(exe)?;
+    fs::create_dir_all(out)?;
+    for stream in tar_streams(exe)? {
+        Archive::new(stream).unpack(out)?;
+    }
+    Ok(ft)
+}
+
+/// payload 中的一個項目（`--chefer-list` 用）
+#[derive(Debug, Clone)]
+pub struct BundleEntry {
+    pub path: String,
+    pub kind: char,
+    pub mode: u32,
+    pub size: u64,
+    pub link: Option<String>,
+}
+
+/// 列出 payload 內所有項目（依 tar 順序）
+pub fn list_bundle(exe: &Path) -> Result<Vec<BundleEntry>> {
+    let mut out = Vec::new();
+    for stream in tar_streams(exe)? {
+        out.extend(list_tar(stream)?);
+    }
+    Ok(out)
+}
+
+fn list_tar<R: Read>(reader: R) -> Result<Vec<BundleEntry>> {
+    let mut ar = Archive::new(reader);
+    let mut out = Vec::new();
+    for entry in ar.entries()? {
+        let entry = entry?;
+        let h = entry.header();
+        let ty = h.entry_type();
+        let kind = if ty.is_dir() {
+            'd'
+        } else if ty.is_symlink() {
+            'l'
+        } else if ty.is_hard_link() {
+            'h'
+        } else if ty.is_file() {
+            '-'
+        } else {
+            '?'
+        };
+        out.push(BundleEntry {
+            path: entry.path()?.to_string_lossy().into_owned(),
+            kind,
+            mode: h.mode().unwrap_or(0) & 0o7777,
+            size: entry.size(),
+            link: entry.link_name()?.map(|l| l.to_string_lossy().into_owned()),
+        });
+    }
+    Ok(out)
+}
+
+fn find_in_tar<R: Read>(reader: R, name: &str) -> Result<Option<Vec<u8>>> {
+    let want = Path::new(name);
+    let mut ar = Archive::new(reader);
+    for entry in ar.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?;
+        let rel: std::path::PathBuf = path
+            .components()
+            .filter(|c| matches!(c, Component::Normal(_)))
+            .collect();
+        if rel == want {
+            if !entry.header().entry_type().is_file() {
+                bail!("{name} in bundle is not a regular file");
+            }
+            let mut buf = Vec::with_capacity(entry.size() as usize);
+            entry.read_to_end(&mut buf)?;
+            return Ok(Some(buf));
+        }
+    }
+    Ok(None)
+}
+
+/// 重新計算 payload 的 sha256 並與 footer 比對
+pub fn verify_payload(exe: &Path) -> Result<Footer> {
+    let ft = Footer::read_from_exe(exe)?;
+    let mut region = ExeRegion::new(File::open(exe)?, ft.offset, ft.length);
+    let mut hw = HashWriter::new(std::io::sink());
+    std::io::copy(&mut region, &mut hw)?;
+    let (len, sha) = hw.finish();
+    if len != ft.length || sha != ft.sha256 {
+        bail!("payload sha256 mismatch");
+    }
+    Ok(ft)
+}
@@ -0,0 +1,58 @@
+// src/region.rs
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+};
+
+/// 把 exe 的一段位元組範圍 [start, start+len) 包成 Read + Seek
+pub struct ExeRegion {
+    file: File,
+    start: u64,
+    len: u64,
+    pos: u64,
+}
+
+impl ExeRegion {
+    pub fn new(file: File, start: u64, len: u64) -> Self {
+        ExeRegion {
+            file,
+            start,
+            len,
+            pos: 0,
+        }
+    }
+}
+
+impl Read for ExeRegion {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remain = self.len.saturating_sub(self.pos);
+        if remain == 0 {
+            return Ok(0);
+        }
+        let want = buf.len().min(remain as usize);
+        self.file.seek(SeekFrom::Start(self.start + self.pos))?;
+        let n = self.file.read(&mut buf[..want])?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for ExeRegion {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let next = match pos {
+            SeekFrom::Start(p) => Some(p),
+            SeekFrom::End(d) => self.len.checked_add_signed(d),
+            SeekFrom::Current(d) => self.pos.checked_add_signed(d),
+        };
+        match next {
+            Some(p) => {
+                self.pos = p;
+                Ok(p)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seek out of range",
+            )),
+        }
+    }
+}
@@ -0,0 +1,412 @@
+// src/section.rs
+//! 分段壓縮的 payload（footer version 2，FLAG_SECTIONED）。
+//!
+//! payload = [zstd dictionary] || 區段… || section table (JSON)
+//!
+//! 每個區段是一個獨立的 tar（各自帶結尾），各自用自己的 codec 壓縮：
+//! `meta` 放 bundle 根目錄的檔案與 `services/` 目錄本身，
+//! `service:<name>` 放 services/<name>/ 底下的全部內容。
+//! 依序解開全部區段即得到完整的 bundle 目錄。
+//! section table 的長度記在 footer，offset 都相對於 payload 起點。
+use anyhow::{Context, Result, anyhow, bail};
+use appcipe_spec::{Codec, CompressionConfig, SectionCompression};
+use fs_err as fs;
+use serde::{Deserialize, Serialize};
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::{self, BufReader, Read, Write},
+    path::{Path, PathBuf},
+};
+use tar::{Builder, HeaderMode};
+
+use crate::{ExeRegion, assemble::append_path, footer::Footer};
+
+/// dictionary 上限；zstd 建議 ~100 KiB
+const DICT_MAX_SIZE: usize = 112 * 1024;
+/// 每個樣本最多取前面這麼多 bytes，樣本總量另有上限
+const DICT_SAMPLE_MAX: usize = 128 * 1024;
+const DICT_SAMPLES_TOTAL: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SectionTable {
+    /// 共用的 zstd dictionary（未壓縮存放）
+    #[serde(default)]
+    pub dictionary: Option<Span>,
+    pub sections: Vec<Section>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Span {
+    pub offset: u64,
+    pub length: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Section {
+    /// "meta" 或 "service:<name>"
+    pub name: String,
+    pub codec: Codec,
+    #[serde(default)]
+    pub level: Option<i32>,
+    /// 是否以 table 的 dictionary 壓縮
+    #[serde(default)]
+    pub dictionary: bool,
+    pub offset: u64,
+    pub length: u64,
+    /// 解壓後（tar）的大小
+    pub raw_length: u64,
+}
+
+/// 解析 `<service>=<codec>[:<level>]`（命令列的 per-service 覆寫）
+pub fn parse_service_override(s: &str) -> Result<(String, SectionCompression), String> {
+    let (name, spec) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected <service>=<codec>[:<level>], got '{s}'"))?;
+    let (codec, level) = match spec.split_once(':') {
+        Some((c, l)) => (
+            c,
+            Some(l.parse::<i32>().map_err(|e| format!("level '{l}': {e}"))?),
+        ),
+        None => (spec, None),
+    };
+    let codec: Codec = codec.parse()?;
+    appcipe_spec::validate_level(name, codec, level)?;
+    Ok((name.to_string(), SectionCompression { codec, level }))
+}
+
+/// 一個區段要放哪些路徑（相對 bundle 根目錄）；bool 為是否遞迴
+struct Plan {
+    name: String,
+    paths: Vec<(PathBuf, bool)>,
+    codec: Codec,
+    level: Option<i32>,
+}
+
+/// 只要有任何區段需要壓縮就用分段格式；否則維持單一未壓縮 tar
+pub fn needs_sections(bundle_dir: &Path, cfg: &CompressionConfig) -> Result<bool> {
+    Ok(plan(bundle_dir, cfg)?
+        .iter()
+        .any(|p| p.codec != Codec::None))
+}
+
+/// 各區段的 codec / level；覆寫的 service 必須存在，level 必須適用於 codec（lz4 不接受 level）
+fn plan(bundle_dir: &Path, cfg: &CompressionConfig) -> Result<Vec<Plan>> {
+    let mut meta = Vec::new();
+    let mut services = Vec::new();
+    for name in sorted_names(bundle_dir)? {
+        let rel = PathBuf::from(&name);
+        if name == "services" && bundle_dir.join("services").is_dir() {
+            meta.push((rel, false));
+            for svc in sorted_names(&bundle_dir.join("services"))? {
+                let svc_name = svc.to_string_lossy().into_owned();
+                let over = cfg.services.get(&svc_name);
+                services.push(Plan {
+                    paths: vec![(Path::new("services").join(&svc), true)],
+                    codec: over.map_or(cfg.codec, |o| o.codec),
+                    level: over.map_or(cfg.level, |o| o.level),
+                    name: format!("service:{svc_name}"),
+                });
+            }
+        } else {
+            meta.push((rel, true));
+        }
+    }
+    let mut plans = vec![Plan {
+        name: "meta".into(),
+        paths: meta,
+        codec: cfg.codec,
+        level: cfg.level,
+    }];
+    plans.extend(services);
+
+    for name in cfg.services.keys() {
+        if !plans.iter().any(|p| p.name == format!("service:{name}")) {
+            bail!("compression override `{name}` does not match any service in the bundle");
+        }
+    }
+    for p in &plans {
+        appcipe_spec::validate_level(&p.name, p.codec, p.level).map_err(|e| anyhow!(e))?;
+    }
+    Ok(plans)
+}
+
+fn sorted_names(dir: &Path) -> Result<Vec<OsString>> {
+    let mut names = fs::read_dir(dir)?
+        .map(|e| Ok(e?.file_name()))
+        .collect::<Result<Vec<_>>>()?;
+    names.sort();
+    Ok(names)
+}
+
+/// 寫出全部區段與 section table；回傳 table 的長度
+pub(crate) fn write_sections<W: Write>(
+    bundle_dir: &Path,
+    cfg: &CompressionConfig,
+    out: &mut W,
+) -> Result<u64> {
+    let plans = plan(bundle_dir, cfg)?;
+    let mut pos = 0u64;
+
+    let dict = if cfg.dictionary {
+        train_dictionary(bundle_dir)?
+    } else {
+        None
+    };
+    let dictionary = match &dict {
+        Some(d) => {
+            out.write_all(d)?;
+            pos += d.len() as u64;
+            Some(Span {
+                offset: 0,
+                length: d.len() as u64,
+            })
+        }
+        None => None,
+    };
+
+    let mut sections = Vec::with_capacity(plans.len());
+    for p in plans {
+        let level = p.level.or(p.codec.default_level());
+        // dictionary 只用在 service 區段；meta 很小而且幾乎每版都會變
+        let use_dict = p.codec == Codec::Zstd && p.name != "meta" && dict.is_some();
+        let mut counted = Counter::new(&mut *out);
+        let mut enc = encoder(
+            p.codec,
+            level,
+            dict.as_deref().filter(|_| use_dict),
+            &mut counted,
+        )?;
+        let mut raw = Counter::new(&mut enc);
+        {
+            let mut b = Builder::new(&mut raw);
+            b.mode(HeaderMode::Complete);
+            b.follow_symlinks(false);
+            for (rel, recurse) in &p.paths {
+                append_path(&mut b, bundle_dir, rel, *recurse)?;
+            }
+            b.finish()?;
+        }
+        let raw_length = raw.count;
+        enc.finish_box()?;
+        let length = counted.count;
+        sections.push(Section {
+            name: p.name,
+            codec: p.codec,
+            level,
+            dictionary: use_dict,
+            offset: pos,
+            length,
+            raw_length,
+        });
+        pos += length;
+    }
+
+    let table = serde_json::to_vec(&SectionTable {
+        dictionary,
+        sections,
+    })?;
+    out.write_all(&table)?;
+    Ok(table.len() as u64)
+}
+
+/// 以所有 service rootfs 的檔案當樣本訓練 dictionary；樣本不足時略過
+fn train_dictionary(bundle_dir: &Path) -> Result<Option<Vec<u8>>> {
+    let mut samples = Vec::new();
+    let mut total = 0usize;
+    let services = bundle_dir.join("services");
+    if services.is_dir() {
+        for svc in sorted_names(&services)? {
+            collect_samples(&services.join(svc), &mut samples, &mut total)?;
+        }
+    }
+    if samples.is_empty() {
+        tracing::warn!("zstd dictionary: no samples, building without dictionary");
+        return Ok(None);
+    }
+    match zstd::dict::from_samples(&samples, DICT_MAX_SIZE) {
+        Ok(d) => Ok(Some(d)),
+        Err(e) => {
+            tracing::warn!("zstd dictionary: training failed ({e}), building without dictionary");
+            Ok(None)
+        }
+    }
+}
+
+fn collect_samples(dir: &Path, samples: &mut Vec<Vec<u8>>, total: &mut usize) -> Result<()> {
+    for name in sorted_names(dir)? {
+        if *total >= DICT_SAMPLES_TOTAL {
+            return Ok(());
+        }
+        let p = dir.join(name);
+        let meta = fs::symlink_metadata(&p)?;
+        if meta.is_dir() {
+            collect_samples(&p, samples, total)?;
+        } else if meta.is_file() && meta.len() > 0 {
+            let mut buf = Vec::new();
+            fs::File::open(&p)?
+                .take(DICT_SAMPLE_MAX as u64)
+                .read_to_end(&mut buf)?;
+            *total += buf.len();
+            samples.push(buf);
+        }
+    }
+    Ok(())
+}
+
+/// 讀 footer 指向的 section table；舊格式回傳 None
+pub fn read_section_table(exe: &Path, ft: &Footer) -> Result<Option<SectionTable>> {
+    if !ft.is_sectioned() {
+        return Ok(None);
+    }
+    let start = ft.length - ft.table_length;
+    let mut region = ExeRegion::new(File::open(exe)?, ft.offset + start, ft.table_length);
+    let mut buf = Vec::new();
+    region.read_to_end(&mut buf)?;
+    let table: SectionTable = serde_json::from_slice(&buf).context("parse section table")?;
+    for s in &table.sections {
+        if s.offset.checked_add(s.length).is_none_or(|end| end > start) {
+            bail!("section `{}` out of range", s.name);
+        }
+    }
+    Ok(Some(table))
+}
+
+/// 依序開啟每段 tar 的解壓 reader（舊格式只有一段）
+pub(crate) fn tar_streams(exe: &Path) -> Result<Vec<Box<dyn Read>>> {
+    let ft = Footer::read_from_exe(exe)?;
+    let region = |offset: u64, length: u64| -> Result<ExeRegion> {
+        Ok(ExeRegion::new(File::open(exe)?, ft.offset + offset, length))
+    };
+    let Some(table) = read_section_table(exe, &ft)? else {
+        let r = region(0, ft.length)?;
+        return Ok(vec![if ft.compressed_is_zstd() {
+            Box::new(zstd::stream::read::Decoder::new(r)?)
+        } else {
+            Box::new(r)
+        }]);
+    };
+
+    let dict = match table.dictionary {
+        Some(d) => {
+            let mut buf = Vec::new();
+            region(d.offset, d.length)?.read_to_end(&mut buf)?;
+            Some(buf)
+        }
+        None => None,
+    };
+    table
+        .sections
+        .iter()
+        .map(|s| {
+            let d = if s.dictionary {
+                Some(
+                    dict.as_deref()
+                        .context("section needs a dictionary but none is stored")?,
+                )
+            } else {
+                None
+            };
+            decoder(s.codec, d, region(s.offset, s.length)?)
+                .with_context(|| format!("open section `{}`", s.name))
+        })
+        .collect()
+}
+
+fn decoder<R: Read + 'static>(codec: Codec, dict: Option<&[u8]>, r: R) -> Result<Box<dyn Read>> {
+    Ok(match codec {
+        Codec::None => Box::new(r),
+        Codec::Zstd => match dict {
+            Some(d) => Box::new(zstd::stream::read::Decoder::with_dictionary(
+                BufReader::new(r),
+                d,
+            )?),
+            None => Box::new(zstd::stream::read::Decoder::new(r)?),
+        },
+        Codec::Xz => Box::new(xz2::read::XzDecoder::new(r)),
+        Codec::Lz4 => Box::new(lz4_flex::frame::FrameDecoder::new(r)),
+    })
+}
+
+/// 壓縮器結束時需要寫出 frame 結尾
+trait FinishWrite: Write {
+    fn finish_box(self: Box<Self>) -> io::Result<()>;
+}
+
+struct Plain<W>(W);
+
+impl<W: Write> Write for Plain<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl<W: Write> FinishWrite for Plain<W> {
+    fn finish_box(mut self: Box<Self>) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl<W: Write> FinishWrite for zstd::stream::write::Encoder<'_, W> {
+    fn finish_box(self: Box<Self>) -> io::Result<()> {
+        self.finish().map(drop)
+    }
+}
+
+impl<W: Write> FinishWrite for xz2::write::XzEncoder<W> {
+    fn finish_box(self: Box<Self>) -> io::Result<()> {
+        self.finish().map(drop)
+    }
+}
+
+impl<W: Write> FinishWrite for lz4_flex::frame::FrameEncoder<W> {
+    fn finish_box(self: Box<Self>) -> io::Result<()> {
+        self.finish().map(drop).map_err(io::Error::other)
+    }
+}
+
+fn encoder<'a, W: Write + 'a>(
+    codec: Codec,
+    level: Option<i32>,
+    dict: Option<&[u8]>,
+    w: W,
+) -> Result<Box<dyn FinishWrite + 'a>> {
+    Ok(match codec {
+        Codec::None => Box::new(Plain(w)),
+        Codec::Zstd => {
+            let level = level.unwrap_or(0);
+            match dict {
+                Some(d) => Box::new(zstd::stream::write::Encoder::with_dictionary(w, level, d)?),
+                None => Box::new(zstd::stream::write::Encoder::new(w, level)?),
+            }
+        }
+        Codec::Xz => Box::new(xz2::write::XzEncoder::new(w, level.unwrap_or(6) as u32)),
+        Codec::Lz4 => Box::new(lz4_flex::frame::FrameEncoder::new(w)),
+    })
+}
+
+/// 計算寫入的 bytes
+struct Counter<W> {
+    inner: W,
+    count: u64,
+}
+
+impl<W> Counter<W> {
+    fn new(inner: W) -> Self {
+        Counter { inner, count: 0 }
+    }
+}
+
+impl<W: Write> Write for Counter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,185 @@
+// src/update.rs
+//! 打包後應用的自我更新：feed 格式與 ed25519 簽章。
+//!
+//! feed（JSON）：
+//! ```json
+//! { "app": "StudioPro", "version": "2.3.2",
+//!   "platforms": { "linux-x86_64": { "url": "...", "size": 1, "sha256": "...", "signature": "..." } } }
+//! ```
+//! 簽章內容綁定 app 名稱、版本、平台與單檔 sha256，避免被換成其他 app 或舊版。
+use anyhow::{Context, Result, bail};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Feed {
+    pub app: String,
+    pub version: String,
+    #[serde(default)]
+    pub platforms: BTreeMap<String, Release>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Release {
+    /// 絕對 URL，或相對於 feed 的路徑
+    pub url: String,
+    pub size: u64,
+    /// 單檔 sha256（hex）
+    pub sha256: String,
+    /// ed25519 簽章（hex）
+    pub signature: String,
+}
+
+/// 目前平台的 key，例如 "linux-x86_64"
+pub fn platform_key() -> String {
+    format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH)
+}
+
+fn signing_message(app: &str, version: &str, platform: &str, sha256_hex: &str) -> Vec<u8> {
+    format!("chefer-update/1\n{app}\n{version}\n{platform}\n{sha256_hex}").into_bytes()
+}
+
+/// 產生新的金鑰對，回傳 (secret hex, public hex)
+pub fn generate_keypair() -> Result<(String, String)> {
+    let mut seed = [0u8; 32];
+    getrandom::fill(&mut seed).map_err(|e| anyhow::anyhow!("getrandom: {e}"))?;
+    let sk = SigningKey::from_bytes(&seed);
+    Ok((
+        hex::encode(seed),
+        hex::encode(sk.verifying_key().to_bytes()),
+    ))
+}
+
+pub fn sign(
+    secret_hex: &str,
+    app: &str,
+    version: &str,
+    platform: &str,
+    sha256_hex: &str,
+) -> Result<String> {
+    let seed: [u8; 32] = decode32(secret_hex).context("bad secret key")?;
+    let sk = SigningKey::from_bytes(&seed);
+    let sig = sk.sign(&signing_message(app, version, platform, sha256_hex));
+    Ok(hex::encode(sig.to_bytes()))
+}
+
+pub fn verify(
+    public_hex: &str,
+    app: &str,
+    version: &str,
+    platform: &str,
+    release: &Release,
+) -> Result<()> {
+    let pk = VerifyingKey::from_bytes(&decode32(public_hex).context("bad public key")?)?;
+    let sig_bytes: [u8; 64] = hex::decode(&release.signature)
+        .ok()
+        .and_then(|v| v.try_into().ok())
+        .context("bad signature encoding")?;
+    let msg = signing_message(app, version, platform, &release.sha256.to_ascii_lowercase());
+    pk.verify(&msg, &Signature::from_bytes(&sig_bytes))
+        .context("update signature verification failed")
+}
+
+/// candidate 是否比 current 新：兩者都必須是 semver，且 candidate 嚴格較大。
+/// 非 semver 一律不更新，避免重播簽章有效的舊 feed 造成降版
+pub fn is_newer(candidate: &str, current: &str) -> bool {
+    match (parse_version(candidate), parse_version(current)) {
+        (Some(c), Some(cur)) => c > cur,
+        _ => false,
+    }
+}
+
+/// 接受開頭的 `v`（"v2.3.1"）
+pub fn parse_version(v: &str) -> Option<semver::Version> {
+    semver::Version::parse(v.trim_start_matches('v')).ok()
+}
+
+/// 相對 URL 以 feed 所在目錄為基準
+pub fn resolve_url(feed_url: &str, url: &str) -> String {
+    if url.contains("://") {
+        return url.to_string();
+    }
+    match feed_url.rfind('/') {
+        Some(i) => format!("{}/{}", &feed_url[..i], url.trim_start_matches('/')),
+        None => url.to_string(),
+    }
+}
+
+fn decode32(s: &str) -> Result<[u8; 32]> {
+    let v = hex::decode(s.trim())?;
+    match v.try_into() {
+        Ok(a) => Ok(a),
+        Err(_) => bail!("expected 32 bytes"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA: &str = "ab12000000000000000000000000000000000000000000000000000000000000";
+
+    fn signed(secret: &str, version: &str) -> Release {
+        Release {
+            url: "app.run".into(),
+            size: 1,
+            sha256: SHA.into(),
+            signature: sign(secret, "App", version, "linux-x86_64", SHA).unwrap(),
+        }
+    }
+
+    #[test]
+    fn verify_accepts_matching_key() {
+        let (secret, public) = generate_keypair().unwrap();
+        let r = signed(&secret, "1.2.0");
+        verify(&public, "App", "1.2.0", "linux-x86_64", &r).unwrap();
+    }
+
+    #[test]
+    fn verify_rejects_other_key() {
+        let (secret, _) = generate_keypair().unwrap();
+        let (_, other) = generate_keypair().unwrap();
+        let r = signed(&secret, "1.2.0");
+        assert!(verify(&other, "App", "1.2.0", "linux-x86_64", &r).is_err());
+    }
+
+    #[test]
+    fn signature_is_bound_to_app_version_and_platform() {
+        let (secret, public) = generate_keypair().unwrap();
+        let r = signed(&secret, "1.2.0");
+        assert!(verify(&public, "App", "1.3.0", "linux-x86_64", &r).is_err());
+        assert!(verify(&public, "Other", "1.2.0", "linux-x86_64", &r).is_err());
+        assert!(verify(&public, "App", "1.2.0", "linux-aarch64", &r).is_err());
+    }
+
+    #[test]
+    fn is_newer_requires_strictly_greater_semver() {
+        assert!(is_newer("1.2.1", "1.2.0"));
+        assert!(is_newer("v2.0.0", "1.9.9"));
+        assert!(is_newer("1.2.0", "1.2.0-beta.1"));
+        assert!(!is_newer("1.2.0", "1.2.0"));
+        assert!(!is_newer("1.1.9", "1.2.0"));
+        assert!(!is_newer("nightly-2", "nightly-1"));
+        assert!(!is_newer("1.3.0", "nightly"));
+        assert!(!is_newer("", "1.0.0"));
+    }
+
+    #[test]
+    fn resolve_url_is_relative_to_feed() {
+        let feed = "https://example.com/app/feed.json";
+        assert_eq!(
+            resolve_url(feed, "app.run"),
+            "https://example.com/app/app.run"
+        );
+        assert_eq!(
+            resolve_url(feed, "/app.run"),
+            "https://example.com/app/app.run"
+        );
+        assert_eq!(
+            resolve_url(feed, "https://cdn.example.com/app.run"),
+            "https://cdn.example.com/app.run"
+        );
+        assert_eq!(resolve_url("feed.json", "app.run"), "app.run");
+    }
+}
@@ -46,6 +46,88 @@ enum Cmd {
         /// 只做檢查與前置，不輸出
         #[arg(long)]
         dry_run: bool,
+
+        /// chefer-runtime 執行檔；預設找 $CHEFER_RUNTIME 或與 chefer 同目錄的 chefer-runtime
+        #[arg(long, value_name = "PATH")]
+        runtime: Option<String>,
+
+        #[command(flatten)]
+        compression: CompressionArgs,
+
+        /// 把 payload 放進 ELF `.chefer` section（可承受簽章等工具在檔尾附加資料）
+        #[arg(long)]
+        elf_section: bool,
+    },
+
+    /// 比較兩個組裝好的單檔，產生差異更新檔（未變更的 service 不占空間）
+    Delta {
+        /// 舊版單檔
+        #[arg(value_name = "OLD")]
+        old: String,
+
+        /// 新版單檔
+        #[arg(value_name = "NEW")]
+        new: String,
+
+        /// 輸出的 patch 檔
+        #[arg(long, short, value_name = "PATCH")]
+        output: String,
+    },
+
+    /// 對舊版單檔套用差異更新檔，產生新版單檔並驗證
+    Patch {
+        /// 舊版單檔
+        #[arg(value_name = "OLD")]
+        old: String,
+
+        /// `chefer delta` 產生的 patch 檔
+        #[arg(value_name = "PATCH")]
+        patch: String,
+
+        /// 輸出的新版單檔
+        #[arg(long, short, value_name = "OUT")]
+        output: String,
+    },
+
+    /// 產生自我更新用的 ed25519 金鑰對
+    Keygen {
+        /// 私鑰輸出檔（請妥善保管，不要放進版本控制）
+        #[arg(long, short, default_value = "chefer-update.key")]
+        output: String,
+    },
+
+    /// 為組裝好的單檔簽章，並寫入（或更新）自我更新 feed
+    Sign {
+        /// 要發布的單檔
+        #[arg(value_name = "EXE")]
+        exe: String,
+
+        /// `chefer keygen` 產生的私鑰檔
+        #[arg(long)]
+        key: String,
+
+        /// 下載網址（絕對 URL，或相對於 feed 的路徑）
+        #[arg(long)]
+        url: String,
+
+        /// feed JSON 檔；已存在且版本相同時只加入 / 更新此平台
+        #[arg(long, default_value = "feed.json")]
+        feed: String,
+
+        /// 平台 key，預設為目前平台（例如 linux-x86_64）
+        #[arg(long)]
+        platform: Option<String>,
+    },
+
+    /// 控制執行中的 app：狀態、單一 service 的啟停、log、關閉
+    Ctl {
+        /// 單檔、app 的資料夾，或 app 名稱（使用預設資料夾）
+        #[arg(value_name = "APP")]
+        app: String,
+
+        /// 預設為 status
+        #[command(subcommand)]
+        action: Option<CtlCmd>,
     },
 
     /// 顯示 Chefer 與環境版本資訊
@@ -64,6 +146,32 @@ enum Cmd {
     },
 }
 
+#[derive(Subcommand, Debug)]
+enum CtlCmd {
+    /// app 與各 service 的狀態（PID、health、uptime、埠）
+    Status,
+    /// 啟動已停止或已結束的 service
+    Start { service: String },
+    /// 停止 service，之後不重啟（restart: unless-stopped 時下次開 app 也不啟動）
+    Stop { service: String },
+    /// 停止後再啟動 service
+    Restart { service: String },
+    /// 顯示 log（預設全部 service）
+    Logs {
+        service: Option<String>,
+
+        /// 每個 service 最後幾行
+        #[arg(long, short = 'n', default_value_t = chefer_manifest::control::DEFAULT_LOG_LINES)]
+        lines: usize,
+
+        /// 持續顯示新的輸出
+        #[arg(long, short)]
+        follow: bool,
+    },
+    /// 停止整個 app，等它結束
+    Shutdown,
+}
+
 #[derive(Copy, Clone, Debug, ValueEnum)]
 enum PrintFmt {
     Pretty,
@@ -72,16 +180,40 @@ enum PrintFmt {
 }
 
 fn main() -> Result<()> {
+    // 函式庫（chefer-assembler）的警告印到 stderr
+    tracing_subscriber::fmt()
+        .with_max_level(tracing_subscriber::filter::LevelFilter::WARN)
+        .with_target(false)
+        .without_time()
+        .with_writer(std::io::stderr)
+        .init();
     let cli = Cli::parse();
     match cli.cmd {
         Cmd::Check { file, format } => {
             let file = resolve_appcipe_path(file);
             cmd_check(&file, format)
         }
-        Cmd::Build { file, dry_run } => {
+        Cmd::Build {
+            file,
+            dry_run,
+            runtime,
+            compression,
+            elf_section,
+        } => {
             let file = resolve_appcipe_path(file);
-            cmd_build(&file, dry_run)
+            cmd_build(&file, dry_run, runtime.as_deref(), compression, elf_section)
         }
+        Cmd::Delta { old, new, output } => cmd_delta(&old, &new, &output),
+        Cmd::Patch { old, patch, output } => cmd_patch(&old, &patch, &output),
+        Cmd::Keygen { output } => cmd_keygen(&output),
+        Cmd::Sign {
+            exe,
+            key,
+            url,
+            feed,
+            platform,
+        } => cmd_sign(&exe, &key, &url, &feed, platform),
+        Cmd::Ctl { app, action } => cmd_ctl(&app, action.unwrap_or(CtlCmd::Status)),
         Cmd::Version => cmd_version(),
         Cmd::Upgrade {
             channel,
@@ -112,34 +244,92 @@ fn resolve_appcipe_path(file: Option<String>) -> String {
 fn cmd_check(file: &str, fmt: PrintFmt) -> Result<()> {
     let app = appcipe_spec::from_file(file).map_err(|e| anyhow!("{e}"))?;
     println!(
-        "{}  {}",
+        "{}  {} v{}",
         "✔ Verified".green().bold(),
-        format!("{} v{}", app.name.blue().bold(), app.version)
+        app.name.blue().bold(),
+        app.version
     );
     match fmt {
         PrintFmt::Pretty => {
             render_summary_table(&app);
         }
+        // 機密的 env 值（名稱像密碼的、來自 build 機器的）以 *** 顯示
         PrintFmt::Json => {
-            let s = serde_json::to_string_pretty(&app)?;
+            let s = serde_json::to_string_pretty(&app.redacted()?)?;
             println!("{s}");
         }
         PrintFmt::Yaml => {
-            let s = serde_yaml::to_string(&app)?;
+            let s = serde_yaml::to_string(&app.redacted()?)?;
             println!("{s}");
         }
     }
     Ok(())
 }
 
-fn cmd_build(file: &str, dry_run: bool) -> Result<()> {
+/// 覆寫 appcipe.yml 的 `compression:`
+#[derive(clap::Args, Debug)]
+struct CompressionArgs {
+    /// payload codec：none | zstd | xz | lz4
+    #[arg(long)]
+    codec: Option<appcipe_spec::Codec>,
+
+    /// 壓縮等級（zstd 1..=22、xz 0..=9）
+    #[arg(long)]
+    level: Option<i32>,
+
+    /// 依 service 覆寫：<service>=<codec>[:<level>]，可重複
+    #[arg(long = "service-codec", value_name = "SERVICE=CODEC[:LEVEL]",
+          value_parser = chefer_assembler::section::parse_service_override)]
+    service_codec: Vec<(String, appcipe_spec::SectionCompression)>,
+
+    /// 以 service rootfs 訓練共用的 zstd dictionary
+    #[arg(long)]
+    zstd_dict: bool,
+}
+
+impl CompressionArgs {
+    fn apply(self, app: &appcipe_spec::AppCipe) -> Result<appcipe_spec::CompressionConfig> {
+        // 與 yml 的 compression.services 相同：只能覆寫存在的 service
+        if let Some((name, _)) = self
+            .service_codec
+            .iter()
+            .find(|(n, _)| !app.services.contains_key(n))
+        {
+            return Err(anyhow!("--service-codec {name} does not match any service"));
+        }
+        let mut c = app.compression.clone().unwrap_or_default();
+        if let Some(codec) = self.codec {
+            // 換 codec 時 yml 的 level 不一定適用
+            c.codec = codec;
+            c.level = None;
+        }
+        if self.level.is_some() {
+            c.level = self.level;
+        }
+        c.services.extend(self.service_codec);
+        c.dictionary |= self.zstd_dict;
+        appcipe_spec::validate_level("--level", c.codec, c.level).map_err(|e| anyhow!(e))?;
+        Ok(c)
+    }
+}
+
+fn cmd_build(
+    file: &str,
+    dry_run: bool,
+    runtime: Option<&str>,
+    compression: CompressionArgs,
+    elf_section: bool,
+) -> Result<()> {
     let app = appcipe_spec::from_file(file).map_err(|e| anyhow!("{e}"))?;
+    let compression = compression.apply(&app)?;
     println!(
-        "{}  {}",
+        "{}  {} v{}",
         "🔧 Prepare to build".yellow().bold(),
-        format!("{} v{}", app.name.blue().bold(), app.version)
+        app.name.blue().bold(),
+        app.version
     );
     render_summary_table(&app);
+    warn_plaintext_env(&app);
 
     if dry_run {
         println!("{}", "（dry-run）僅前置檢查完成。".dimmed());
@@ -154,10 +344,537 @@ fn cmd_build(file: &str, dry_run: bool) -> Result<()> {
     };
     let res = chefer_pack::pack_all(&app, &opts)?;
     println!("📦 Bundle: {}", res.bundle_dir.display());
-    // todo: 這裡加入更多後續處理邏輯
+
+    let Some(runtime) = resolve_runtime(runtime)? else {
+        println!(
+            "{}",
+            "找不到 chefer-runtime，略過組裝單檔（可用 --runtime 指定）。".dimmed()
+        );
+        return Ok(());
+    };
+    let output = opts.out_dir.join(executable_name(&app.name));
+    let ft = chefer_assembler::assemble(&chefer_assembler::AssembleOptions {
+        runtime,
+        bundle_dir: res.bundle_dir,
+        output: output.clone(),
+        compression,
+        placement: if elf_section {
+            chefer_assembler::Placement::ElfSection
+        } else {
+            chefer_assembler::Placement::Appended
+        },
+    })?;
+    println!(
+        "🍱 Executable: {} ({})",
+        output.display(),
+        human_bytes(ft.offset + ft.length)
+    );
+    Ok(())
+}
+
+/// env 的值會以明文寫進執行檔（manifest.json）；名稱像機密的提醒改用 `secrets:`
+fn warn_plaintext_env(app: &appcipe_spec::AppCipe) {
+    let mut found: Vec<String> = app
+        .services
+        .iter()
+        .flat_map(|(name, svc)| {
+            svc.env
+                .keys()
+                .filter(|k| appcipe_spec::is_sensitive_env(k))
+                .map(move |k| format!("{name}.env.{k}"))
+        })
+        .collect();
+    found.sort();
+    for f in found {
+        println!(
+            "{}  {f} is stored in the executable in plain text; use `secrets:` for credentials",
+            "⚠ Warning".yellow().bold()
+        );
+    }
+}
+
+/// --runtime > $CHEFER_RUNTIME > 與 chefer 同目錄的 chefer-runtime
+fn resolve_runtime(flag: Option<&str>) -> Result<Option<std::path::PathBuf>> {
+    use std::path::PathBuf;
+    if let Some(p) = flag {
+        let p = PathBuf::from(p);
+        if !p.is_file() {
+            return Err(anyhow!("runtime not found: {}", p.display()));
+        }
+        return Ok(Some(p));
+    }
+    if let Some(p) = std::env::var_os("CHEFER_RUNTIME") {
+        return Ok(Some(PathBuf::from(p)));
+    }
+    let sibling = std::env::current_exe()?
+        .with_file_name(format!("chefer-runtime{}", std::env::consts::EXE_SUFFIX));
+    Ok(sibling.is_file().then_some(sibling))
+}
+
+/// dist/<name> 已是 bundle 目錄，單檔加上副檔名
+fn executable_name(app_name: &str) -> String {
+    match std::env::consts::EXE_SUFFIX {
+        "" => format!("{app_name}.run"),
+        suffix => format!("{app_name}{suffix}"),
+    }
+}
+
+fn cmd_delta(old: &str, new: &str, output: &str) -> Result<()> {
+    let header =
+        chefer_assembler::delta::create_patch(old.as_ref(), new.as_ref(), output.as_ref())?;
+    let patch_size = std::fs::metadata(output)?.len();
+
+    let cols = terminal::size().map(|(c, _)| c).unwrap_or(100);
+    let mut t = Table::new();
+    t.load_preset(UTF8_BORDERS_ONLY)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_width(cols);
+    t.set_header(vec![
+        Cell::new("Part")
+            .add_attribute(Attribute::Bold)
+            .fg(Color::Green),
+        Cell::new("Size")
+            .add_attribute(Attribute::Bold)
+            .fg(Color::Green),
+        Cell::new("Patch")
+            .add_attribute(Attribute::Bold)
+            .fg(Color::Green),
+    ]);
+    for c in &header.chunks {
+        let (state, color) = if c.is_copy() {
+            ("unchanged", Color::DarkGrey)
+        } else {
+            ("included", Color::Yellow)
+        };
+        t.add_row(vec![
+            Cell::new(c.label()).fg(Color::Cyan),
+            Cell::new(human_bytes(c.length())),
+            Cell::new(state).fg(color),
+        ]);
+    }
+
+    println!("\n{}", "▎Delta".bold());
+    println!("{t}");
+    println!(
+        "{}  {} ({} vs. {} full download)",
+        "✔ Patch written".green().bold(),
+        output,
+        human_bytes(patch_size),
+        human_bytes(header.new.size)
+    );
+    Ok(())
+}
+
+fn cmd_patch(old: &str, patch: &str, output: &str) -> Result<()> {
+    let ft = chefer_assembler::delta::apply_patch(old.as_ref(), patch.as_ref(), output.as_ref())?;
+    println!(
+        "{}  {} (payload sha256 {})",
+        "✔ Patched".green().bold(),
+        output,
+        hex_prefix(&ft.sha256)
+    );
+    Ok(())
+}
+
+fn cmd_keygen(output: &str) -> Result<()> {
+    use std::io::Write;
+    let (secret, public) = chefer_assembler::update::generate_keypair()?;
+
+    let mut opts = std::fs::OpenOptions::new();
+    opts.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut opts, 0o600);
+    let mut f = opts
+        .open(output)
+        .map_err(|e| anyhow!("cannot create {output}: {e}"))?;
+    writeln!(f, "{secret}")?;
+
+    println!("{}  {}", "✔ Secret key written".green().bold(), output);
+    println!("\nAdd this to appcipe.yml:\n");
+    println!("update:");
+    println!("  feed: https://example.com/{{name}}/feed.json");
+    println!("  public_key: \"{public}\"");
+    Ok(())
+}
+
+fn cmd_sign(
+    exe: &str,
+    key: &str,
+    url: &str,
+    feed_path: &str,
+    platform: Option<String>,
+) -> Result<()> {
+    use chefer_assembler::update::{Feed, Release, parse_version, platform_key, sign};
+
+    let mani = chefer_assembler::read_bundle_file(exe.as_ref(), chefer_manifest::MANIFEST_FILE)?
+        .ok_or_else(|| anyhow!("{exe} has no manifest.json; is it a chefer executable?"))?;
+    let mani = chefer_manifest::Manifest::from_slice(&mani)?;
+    let app = mani.app_name;
+    let version = mani
+        .app_version
+        .ok_or_else(|| anyhow!("{exe} has no app_version; set it in appcipe.yml"))?;
+    if parse_version(&version).is_none() {
+        return Err(anyhow!(
+            "app_version '{version}' is not semver; the runtime only installs strictly newer semver versions"
+        ));
+    }
+
+    chefer_assembler::verify_payload(exe.as_ref())?;
+    let mut f = std::fs::File::open(exe)?;
+    let size = f.metadata()?.len();
+    let sha256 = sha256_hex(&mut f)?;
+
+    let platform = platform.unwrap_or_else(platform_key);
+    let secret = std::fs::read_to_string(key).map_err(|e| anyhow!("read key {key}: {e}"))?;
+    let signature = sign(&secret, &app, &version, &platform, &sha256)?;
+
+    // 同版本：累加平台；不同版本：換成新的 feed
+    let mut feed: Feed = match std::fs::read(feed_path) {
+        Ok(b) => serde_json::from_slice(&b)?,
+        Err(_) => Feed::default(),
+    };
+    if feed.app != app || feed.version != version {
+        feed = Feed {
+            app: app.clone(),
+            version: version.clone(),
+            platforms: Default::default(),
+        };
+    }
+    feed.platforms.insert(
+        platform.clone(),
+        Release {
+            url: url.to_string(),
+            size,
+            sha256,
+            signature,
+        },
+    );
+    std::fs::write(feed_path, serde_json::to_vec_pretty(&feed)?)?;
+
+    println!(
+        "{}  {} v{} [{}] → {}",
+        "✔ Signed".green().bold(),
+        app.blue().bold(),
+        version,
+        platform,
+        feed_path
+    );
     Ok(())
 }
 
+fn sha256_hex<R: std::io::Read>(r: &mut R) -> Result<String> {
+    use sha2::{Digest, Sha256};
+    let mut hasher = Sha256::new();
+    std::io::copy(r, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|x| format!("{x:02x}"))
+        .collect())
+}
+
+fn hex_prefix(b: &[u8]) -> String {
+    b.iter().take(6).map(|x| format!("{x:02x}")).collect()
+}
+
+fn human_bytes(n: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut v = n as f64;
+    let mut i = 0;
+    while v >= 1024.0 && i + 1 < UNITS.len() {
+        v /= 1024.0;
+        i += 1;
+    }
+    if i == 0 {
+        format!("{n} B")
+    } else {
+        format!("{v:.1} {}", UNITS[i])
+    }
+}
+
+/// `chefer ctl shutdown` 等 app 結束的上限（各 service 的 stop_grace_period 之和通常遠小於此）
+#[cfg(unix)]
+const CTL_SHUTDOWN_WAIT: std::time::Duration = std::time::Duration::from_secs(120);
+
+#[cfg(not(unix))]
+fn cmd_ctl(_app: &str, _action: CtlCmd) -> Result<()> {
+    anyhow::bail!("chefer ctl is only supported on Unix")
+}
+
+#[cfg(unix)]
+fn cmd_ctl(app: &str, action: CtlCmd) -> Result<()> {
+    use chefer_manifest::control::{self, Request};
+    use std::time::Duration;
+
+    let data_dir = ctl_data_dir(app)?;
+    let socket = data_dir.join(chefer_manifest::CONTROL_SOCKET);
+    if !socket.exists() {
+        anyhow::bail!(
+            "{app} is not running (no {}); if it was started with --chefer-data-dir, pass that directory",
+            socket.display()
+        );
+    }
+    let send = |req: &Request| -> Result<control::Response> {
+        let r = control::request(&socket, req).map_err(|e| anyhow!("cannot reach {app}: {e:#}"))?;
+        if !r.ok {
+            anyhow::bail!("{}", r.message);
+        }
+        Ok(r)
+    };
+
+    match action {
+        CtlCmd::Status => {
+            let r = send(&Request::Status)?;
+            let status = r.status.ok_or_else(|| anyhow!("the app sent no status"))?;
+            render_status(&status);
+        }
+        CtlCmd::Start { service } => {
+            let r = send(&Request::Start { service })?;
+            println!("{} {}", "✔".green().bold(), r.message);
+        }
+        CtlCmd::Stop { service } => {
+            println!(
+                "{}",
+                send(&Request::Stop {
+                    service: service.clone()
+                })?
+                .message
+            );
+            wait_stopping(&socket, &service);
+        }
+        CtlCmd::Restart { service } => {
+            println!(
+                "{}",
+                send(&Request::Restart {
+                    service: service.clone()
+                })?
+                .message
+            );
+            wait_stopping(&socket, &service);
+        }
+        CtlCmd::Logs {
+            service,
+            lines,
+            follow,
+        } => {
+            let (r, mut reader) = control::connect(
+                &socket,
+                &Request::Logs {
+                    service,
+                    lines,
+                    follow,
+                },
+            )?;
+            if !r.ok {
+                anyhow::bail!("{}", r.message);
+            }
+            // follow 時一直讀到 Ctrl-C 或 app 結束
+            std::io::copy(&mut reader, &mut std::io::stdout().lock())?;
+        }
+        CtlCmd::Shutdown => {
+            println!("{}", send(&Request::Shutdown)?.message);
+            // 實例結束時移除 socket；被強制結束時 socket 留著但不再接受連線
+            let deadline = std::time::Instant::now() + CTL_SHUTDOWN_WAIT;
+            while socket.exists() {
+                let refused = std::os::unix::net::UnixStream::connect(&socket)
+                    .is_err_and(|e| e.kind() == std::io::ErrorKind::ConnectionRefused);
+                if refused {
+                    break;
+                }
+                if std::time::Instant::now() >= deadline {
+                    anyhow::bail!(
+                        "{app} did not stop within {}s; it may still be stopping its services",
+                        CTL_SHUTDOWN_WAIT.as_secs()
+                    );
+                }
+                std::thread::sleep(Duration::from_millis(200));
+            }
+            println!("{} stopped", "✔".green().bold());
+        }
+    }
+    Ok(())
+}
+
+/// 等 service 停下來（超過 stop_grace_period 由 app 砍掉），印出之後的狀態
+#[cfg(unix)]
+fn wait_stopping(socket: &std::path::Path, service: &str) {
+    use chefer_manifest::control::{self, Request};
+    let state = loop {
+        let Ok(r) = control::request(socket, &Request::Status) else {
+            break "gone (the app stopped)".to_string();
+        };
+        let svc = r
+            .status
+            .and_then(|s| s.services.into_iter().find(|s| s.name == service));
+        match svc {
+            Some(s) if s.state == "stopping" => {
+                std::thread::sleep(std::time::Duration::from_millis(200))
+            }
+            Some(s) => break s.state,
+            None => break "unknown".to_string(),
+        }
+    };
+    println!("{} service `{service}` is {state}", "✔".green().bold());
+}
+
+/// `chefer ctl` 的 APP：單檔（依它的 manifest 找資料夾）、資料夾，或 app 名稱
+#[cfg(unix)]
+fn ctl_data_dir(app: &str) -> Result<std::path::PathBuf> {
+    let path = std::path::Path::new(app);
+    if path.is_dir() {
+        return Ok(path.to_path_buf());
+    }
+    if path.is_file() {
+        let mani = chefer_assembler::read_bundle_file(path, chefer_manifest::MANIFEST_FILE)?
+            .ok_or_else(|| anyhow!("{app}: no manifest.json in the bundle"))?;
+        let mani = chefer_manifest::Manifest::from_slice(&mani)?;
+        let exe = std::fs::canonicalize(path)?;
+        let exe_dir = exe.parent().unwrap_or(std::path::Path::new("/"));
+        return Ok(chefer_manifest::DataDir::resolve(&mani, None, exe_dir)?.path);
+    }
+    chefer_manifest::default_data_dir(app)
+}
+
+#[cfg(unix)]
+fn render_status(status: &chefer_manifest::control::AppStatus) {
+    let cols = terminal::size().map(|(c, _)| c).unwrap_or(120);
+    let version = status
+        .version
+        .as_deref()
+        .map(|v| format!(" v{v}"))
+        .unwrap_or_default();
+    println!(
+        "{}{}  running (pid {}, up {})",
+        status.app.blue().bold(),
+        version,
+        status.pid,
+        fmt_uptime(status.uptime_secs)
+    );
+
+    // 有 service 設定資源上限時才顯示用量
+    let limited = status.services.iter().any(|s| !s.limits.is_empty());
+    let mut t = Table::new();
+    t.load_preset(UTF8_BORDERS_ONLY)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_width(cols);
+    let mut header = vec![
+        "Service", "State", "PID", "Health", "Uptime", "Restarts", "Ports",
+    ];
+    if limited {
+        header.extend(["CPU", "Memory", "PIDs", "IO Weight"]);
+    }
+    t.set_header(
+        header
+            .into_iter()
+            .map(|h| Cell::new(h).add_attribute(Attribute::Bold).fg(Color::Green)),
+    );
+    for s in &status.services {
+        let name = if s.primary {
+            format!("{} (primary)", s.name)
+        } else {
+            s.name.clone()
+        };
+        let state_color = match s.state.as_str() {
+            "running" => Color::Green,
+            "exited" | "backing-off" => Color::Red,
+            _ => Color::Yellow,
+        };
+        let state = match &s.exit {
+            Some(exit) => format!("{} ({exit})", s.state),
+            None => s.state.clone(),
+        };
+        let health_color = match s.health.as_deref() {
+            Some("healthy") => Color::Green,
+            Some("unhealthy") => Color::Red,
+            _ => Color::Yellow,
+        };
+        let mut row = vec![
+            Cell::new(name).fg(Color::Cyan),
+            Cell::new(state).fg(state_color),
+            Cell::new(s.pid.map(|p| p.to_string()).unwrap_or_else(|| "-".into())),
+            Cell::new(s.health.as_deref().unwrap_or("-")).fg(health_color),
+            Cell::new(s.uptime_secs.map(fmt_uptime).unwrap_or_else(|| "-".into())),
+            Cell::new(s.restarts),
+            Cell::new(if s.ports.is_empty() {
+                "-".to_string()
+            } else {
+                s.ports.join("\n")
+            }),
+        ];
+        if limited {
+            let usage = s.usage.as_ref();
+            // 「用量 / 上限」；沒有其中一個就只顯示另一個
+            let pair = |used: Option<String>, limit: Option<String>| match (used, limit) {
+                (Some(u), Some(l)) => format!("{u} / {l}"),
+                (Some(u), None) => u,
+                (None, Some(l)) => format!("- / {l}"),
+                (None, None) => "-".into(),
+            };
+            let limit_color = if s.limits.is_empty() || s.limits_enforced {
+                Color::Reset
+            } else {
+                Color::Yellow
+            };
+            row.extend([
+                Cell::new(pair(
+                    usage
+                        .and_then(|u| u.cpu_percent)
+                        .map(|p| format!("{p:.1}%")),
+                    s.limits.cpus.map(|c| format!("{:.0}%", c * 100.0)),
+                ))
+                .fg(limit_color),
+                Cell::new(pair(
+                    usage.and_then(|u| u.memory).map(human_bytes),
+                    s.limits.memory.map(|m| human_bytes(m.0)),
+                ))
+                .fg(limit_color),
+                Cell::new(pair(
+                    usage.and_then(|u| u.pids).map(|p| p.to_string()),
+                    s.limits.pids_limit.map(|p| p.to_string()),
+                ))
+                .fg(limit_color),
+                Cell::new(
+                    s.limits
+                        .io_weight
+                        .map(|w| w.to_string())
+                        .unwrap_or_else(|| "-".into()),
+                )
+                .fg(limit_color),
+            ]);
+        }
+        t.add_row(row);
+    }
+    println!("{t}");
+    let unenforced: Vec<&str> = status
+        .services
+        .iter()
+        .filter(|s| !s.limits.is_empty() && !s.limits_enforced)
+        .map(|s| s.name.as_str())
+        .collect();
+    if !unenforced.is_empty() {
+        println!(
+            "{} limits not enforced for {} (no delegated cgroup v2 controllers; see the app's log)",
+            "!".yellow().bold(),
+            unenforced.join(", ")
+        );
+    }
+}
+
+/// 1d 2h、3h 4m、5m 6s、7s
+#[cfg(unix)]
+fn fmt_uptime(secs: u64) -> String {
+    let (d, h, m, s) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60, secs % 60);
+    if d > 0 {
+        format!("{d}d {h}h")
+    } else if h > 0 {
+        format!("{h}h {m}m")
+    } else if m > 0 {
+        format!("{m}m {s}s")
+    } else {
+        format!("{s}s")
+    }
+}
+
 fn cmd_version() -> Result<()> {
     use comfy_table::{Table, presets::UTF8_BORDERS_ONLY};
 
@@ -208,14 +925,11 @@ fn cmd_version() -> Result<()> {
 fn cmd_upgrade(channel: &str, to: Option<&str>, check_only: bool) -> Result<()> {
     use self_update::Status;
     use self_update::backends::github::Update;
-    let repo_owner = "YourGitHubUser";
-    let repo_name = "chefer";
-
     let mut builder = Update::configure();
     builder
-        .repo_owner(repo_owner)
-        .repo_name(repo_name)
-        .bin_name("chefer")
+        .repo_owner(REPO_OWNER)
+        .repo_name(REPO_NAME)
+        .bin_name(BIN_NAME)
         .show_download_progress(true)
         .current_version(cargo_crate_version!());
 
@@ -292,6 +1006,12 @@ fn render_summary_table(app: &appcipe_spec::AppCipe) {
         Cell::new("Spec Version").fg(Color::Cyan),
         Cell::new(&app.version),
     ]);
+    if let Some(ver) = &app.app_version {
+        header.add_row(vec![
+            Cell::new("App Version").fg(Color::Cyan),
+            Cell::new(ver).fg(Color::Yellow),
+        ]);
+    }
     header.add_row(vec![
         Cell::new("Crash Policy").fg(Color::Cyan),
         Cell::new(format!("{:?}", app.crash)).fg(Color::Yellow),
@@ -305,6 +1025,45 @@ fn render_summary_table(app: &appcipe_spec::AppCipe) {
             Cell::new(app.old_names.join(", ")).fg(Color::Magenta),
         ]);
     }
+    if let Some(update) = &app.update {
+        header.add_row(vec![
+            Cell::new("Update Feed").fg(Color::Cyan),
+            Cell::new(format!("{} ({:?})", update.feed, update.check)).fg(Color::Blue),
+        ]);
+    }
+    if let Some(c) = &app.compression {
+        let mut desc = c.codec.as_str().to_string();
+        if let Some(level) = c.level {
+            desc.push_str(&format!(" -{level}"));
+        }
+        if c.dictionary {
+            desc.push_str(" +dict");
+        }
+        let mut overrides: Vec<_> = c.services.iter().collect();
+        overrides.sort_by_key(|(name, _)| name.as_str());
+        for (name, sc) in overrides {
+            desc.push_str(&format!(", {name}={}", sc.codec.as_str()));
+        }
+        header.add_row(vec![
+            Cell::new("Compression").fg(Color::Cyan),
+            Cell::new(desc).fg(Color::Blue),
+        ]);
+    }
+
+    if !app.secrets.is_empty() {
+        // 只列名稱與來源；值在啟動時才取得
+        let mut secrets: Vec<_> = app.secrets.iter().collect();
+        secrets.sort_by_key(|(name, _)| name.as_str());
+        let desc = secrets
+            .iter()
+            .map(|(name, s)| format!("{name} ({s})"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        header.add_row(vec![
+            Cell::new("Secrets").fg(Color::Cyan),
+            Cell::new(desc).fg(Color::Magenta),
+        ]);
+    }
 
     println!();
     println!("{}", "▎App Information".bold());
@@ -319,12 +1078,13 @@ fn render_summary_table(app: &appcipe_spec::AppCipe) {
         .set_width(cols)
         .set_constraints(vec![
             ColumnConstraint::Absolute(Width::Percentage(10)), // Service
-            ColumnConstraint::Absolute(Width::Percentage(30)), // Image
+            ColumnConstraint::Absolute(Width::Percentage(24)), // Image
             ColumnConstraint::Absolute(Width::Percentage(8)),  // Mode
-            ColumnConstraint::Absolute(Width::Percentage(16)), // Persist
+            ColumnConstraint::Absolute(Width::Percentage(14)), // Persist
             ColumnConstraint::Absolute(Width::Percentage(12)), // Ports
-            ColumnConstraint::Absolute(Width::Percentage(16)), // Mounts
+            ColumnConstraint::Absolute(Width::Percentage(12)), // Mounts
             ColumnConstraint::Absolute(Width::Percentage(8)),  // Depends
+            ColumnConstraint::Absolute(Width::Percentage(12)), // Limits
         ]);
 
     t.set_header(vec![
@@ -349,6 +1109,9 @@ fn render_summary_table(app: &appcipe_spec::AppCipe) {
         Cell::new("Depends")
             .add_attribute(Attribute::Bold)
             .fg(Color::Green),
+        Cell::new("Limits")
+            .add_attribute(Attribute::Bold)
+            .fg(Color::Green),
     ]);
 
     let total = app.services.len();
@@ -398,12 +1161,29 @@ fn render_summary_table(app: &appcipe_spec::AppCipe) {
         let mounts = if svc.mounts.is_empty() {
             "—".into()
         } else {
-            svc.mounts.join(", ")
+            svc.mounts
+                .iter()
+                .map(|m| m.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
         };
         let depends = if svc.depends_on.is_empty() {
             "—".into()
         } else {
-            svc.depends_on.join(", ")
+            svc.depends_on
+                .entries()
+                .iter()
+                .map(|(n, c)| match c {
+                    appcipe_spec::DependencyCondition::ServiceStarted => n.clone(),
+                    appcipe_spec::DependencyCondition::ServiceHealthy => format!("{n} (healthy)"),
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        let limits = match svc.resources() {
+            r if r.is_empty() => "—".into(),
+            r => r.to_string(),
         };
 
         t.add_row(vec![
@@ -414,6 +1194,7 @@ fn render_summary_table(app: &appcipe_spec::AppCipe) {
             Cell::new(ports).fg(Color::Blue),
             Cell::new(mounts).fg(Color::Blue),
             Cell::new(depends).fg(Color::Magenta),
+            Cell::new(limits).fg(Color::Yellow),
         ]);
 
         if idx + 1 < total {
@@ -425,6 +1206,7 @@ fn render_summary_table(app: &appcipe_spec::AppCipe) {
                 Cell::new(""),
                 Cell::new(""),
                 Cell::new(""),
+                Cell::new(""),
             ]);
         }
     }
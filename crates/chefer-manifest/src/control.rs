@@ -0,0 +1,159 @@
+//! 執行中實例的控制協定（`<data_dir>/control.sock`）：每個連線送一行 JSON 請求，收一行 JSON 回應。
+//! [`Request::Logs`] 的回應之後接著是 log 原文，直到連線關閉。
+use serde::{Deserialize, Serialize};
+
+use crate::Resources;
+
+/// `logs` 預設顯示的行數
+pub const DEFAULT_LOG_LINES: usize = 50;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum Request {
+    /// 第二次啟動：把命令列參數交給執行中的實例
+    Launch {
+        args: Vec<String>,
+    },
+    /// app 與各 service 的狀態
+    Status,
+    /// 啟動已停止或已結束的 service
+    Start {
+        service: String,
+    },
+    /// 送 stop_signal 停止 service，之後不重啟（restart: unless-stopped 時下次開 app 也不啟動）
+    Stop {
+        service: String,
+    },
+    Restart {
+        service: String,
+    },
+    /// 各 service 的 log 最後 `lines` 行（`service | ` 前綴）；`follow` 時持續送出新的
+    Logs {
+        /// None 為全部 service
+        #[serde(default)]
+        service: Option<String>,
+        #[serde(default = "default_log_lines")]
+        lines: usize,
+        #[serde(default)]
+        follow: bool,
+    },
+    /// 停止整個 app（同 Ctrl-C）
+    Shutdown,
+}
+
+fn default_log_lines() -> usize {
+    DEFAULT_LOG_LINES
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Response {
+    pub ok: bool,
+    /// 給使用者看的結果或錯誤
+    #[serde(default)]
+    pub message: String,
+    /// [`Request::Status`] 的結果
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<AppStatus>,
+}
+
+impl Response {
+    pub fn ok(message: impl Into<String>) -> Self {
+        Response {
+            ok: true,
+            message: message.into(),
+            status: None,
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Response {
+            ok: false,
+            message: message.into(),
+            status: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppStatus {
+    pub app: String,
+    #[serde(default)]
+    pub version: Option<String>,
+    /// runtime 的 pid
+    pub pid: u32,
+    pub uptime_secs: u64,
+    /// 依啟動順序
+    pub services: Vec<ServiceStatus>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceStatus {
+    pub name: String,
+    /// pending | running | stopping | backing-off | exited | stopped
+    pub state: String,
+    /// 容器的 shim 行程（host 上的 pid）
+    #[serde(default)]
+    pub pid: Option<u32>,
+    /// 有 healthcheck 時：starting | healthy | unhealthy
+    #[serde(default)]
+    pub health: Option<String>,
+    #[serde(default)]
+    pub uptime_secs: Option<u64>,
+    pub restarts: u32,
+    /// 已結束時的結束狀態
+    #[serde(default)]
+    pub exit: Option<String>,
+    /// `host 位址 -> 容器內的埠/協定`
+    #[serde(default)]
+    pub ports: Vec<String>,
+    #[serde(default)]
+    pub primary: bool,
+    /// 設定的資源上限
+    #[serde(default, skip_serializing_if = "Resources::is_empty")]
+    pub limits: Resources,
+    /// 上限有生效（cgroup 可用且需要的 controller 都有開啟）
+    #[serde(default)]
+    pub limits_enforced: bool,
+    /// 有 cgroup 時的目前用量
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<ResourceUsage>,
+}
+
+/// controller 未開啟的項目為 None
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    /// 100 = 一個核心
+    #[serde(default)]
+    pub cpu_percent: Option<f64>,
+    /// bytes
+    #[serde(default)]
+    pub memory: Option<u64>,
+    #[serde(default)]
+    pub pids: Option<u64>,
+}
+
+/// 送出一個請求並讀回回應；回傳的 reader 接著可讀 [`Request::Logs`] 的 log 原文
+#[cfg(unix)]
+pub fn connect(
+    socket: &std::path::Path,
+    req: &Request,
+) -> anyhow::Result<(Response, std::io::BufReader<std::os::unix::net::UnixStream>)> {
+    use anyhow::Context;
+    use std::io::{BufRead, Write};
+
+    let conn = std::os::unix::net::UnixStream::connect(socket)
+        .with_context(|| format!("connect to {}", socket.display()))?;
+    let mut out = &conn;
+    writeln!(out, "{}", serde_json::to_string(req)?)?;
+    let mut reader = std::io::BufReader::new(conn);
+    let mut line = String::new();
+    reader.read_line(&mut line).context("read the response")?;
+    let response = serde_json::from_str(&line).context("parse the response")?;
+    Ok((response, reader))
+}
+
+/// 送出一個請求並等回應
+#[cfg(unix)]
+pub fn request(socket: &std::path::Path, req: &Request) -> anyhow::Result<Response> {
+    connect(socket, req).map(|(response, _)| response)
+}
@@ -0,0 +1,109 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+use crate::types::Manifest;
+
+/// 資料夾內 persist 資料的子目錄（persist-map.json 的 host_rel 以此開頭）
+pub const DATA_SUBDIR: &str = "data";
+
+/// 資料夾內產生或詢問得到的 secret（`secrets/<name>`，0600）
+pub const SECRETS_SUBDIR: &str = "secrets";
+
+/// 資料夾內 service 輸出的子目錄（`logs/<service>.log`）
+pub const LOGS_SUBDIR: &str = "logs";
+
+/// 執行中的實例持有的鎖（flock）；同一個資料夾同時只能有一個實例
+pub const INSTANCE_LOCK_FILE: &str = "instance.lock";
+
+/// 執行中的實例的控制 socket（Unix domain socket，一行一個 JSON 請求）
+pub const CONTROL_SOCKET: &str = "control.sock";
+
+/// 以 `chefer ctl stop` 停掉、restart 為 unless-stopped 的 service（一行一個）；下次開 app 時不啟動
+pub const STOPPED_SERVICES_FILE: &str = "stopped-services";
+
+/// 資料夾位置從哪裡來
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataDirSource {
+    /// `--chefer-data-dir`
+    CommandLine,
+    /// manifest 的 `data_dir`
+    Manifest,
+    /// 系統預設（Windows %LOCALAPPDATA%、macOS ~/Library/Application Support、Linux ~/.local/share）
+    Default,
+}
+
+#[derive(Debug, Clone)]
+pub struct DataDir {
+    pub path: PathBuf,
+    pub source: DataDirSource,
+}
+
+impl DataDir {
+    /// 解析 app 的資料夾；不建立、不遷移。
+    ///
+    /// 優先順序：`cli_override` > manifest `data_dir` > 系統預設/<app_name>。
+    /// `data_dir` 支援 `~/` 開頭；相對路徑以 exe 所在目錄為準（可攜版用）。
+    pub fn resolve(mani: &Manifest, cli_override: Option<&Path>, exe_dir: &Path) -> Result<Self> {
+        if let Some(p) = cli_override {
+            let path = if p.is_absolute() {
+                p.to_path_buf()
+            } else {
+                std::env::current_dir()?.join(p)
+            };
+            return Ok(DataDir {
+                path,
+                source: DataDirSource::CommandLine,
+            });
+        }
+        if let Some(d) = &mani.data_dir {
+            return Ok(DataDir {
+                path: expand_path(d, exe_dir)?,
+                source: DataDirSource::Manifest,
+            });
+        }
+        Ok(DataDir {
+            path: default_data_dir(&mani.app_name)?,
+            source: DataDirSource::Default,
+        })
+    }
+
+    /// old_names 對應的舊資料夾（與新資料夾同一層），依 old_names 順序
+    pub fn legacy_candidates(&self, mani: &Manifest) -> Vec<PathBuf> {
+        let Some(parent) = self.path.parent() else {
+            return Vec::new();
+        };
+        mani.old_names.iter().map(|old| parent.join(old)).collect()
+    }
+
+    /// data/<service>
+    pub fn service_dir(&self, service: &str) -> PathBuf {
+        self.path.join(DATA_SUBDIR).join(service)
+    }
+}
+
+/// 沒有指定位置時 app 的資料夾：系統預設/<app_name>
+pub fn default_data_dir(app_name: &str) -> Result<PathBuf> {
+    let root = dirs::data_local_dir().context("cannot determine the per-user data directory")?;
+    Ok(root.join(app_name))
+}
+
+/// `<data_dir>/logs/<service>.log`；輪替出的舊檔為 `.log.1`、`.log.2`…
+pub fn log_file(data_dir: &Path, service: &str) -> PathBuf {
+    data_dir.join(LOGS_SUBDIR).join(format!("{service}.log"))
+}
+
+fn expand_path(p: &str, exe_dir: &Path) -> Result<PathBuf> {
+    if p == "~" || p.starts_with("~/") || p.starts_with("~\\") {
+        let home = dirs::home_dir().context("cannot determine the home directory")?;
+        return Ok(match p.get(2..) {
+            Some(rest) if !rest.is_empty() => home.join(rest),
+            _ => home,
+        });
+    }
+    let pb = PathBuf::from(p);
+    Ok(if pb.is_absolute() {
+        pb
+    } else {
+        exe_dir.join(pb)
+    })
+}
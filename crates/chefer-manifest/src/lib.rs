@@ -0,0 +1,10 @@
+//! bundle 的 manifest.json / persist-map.json：chefer-pack 寫、chefer-runtime 讀的正式協定
+pub mod control;
+mod data_dir;
+mod read;
+mod types;
+mod validate;
+
+pub use data_dir::*;
+pub use read::*;
+pub use types::*;
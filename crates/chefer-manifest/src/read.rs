@@ -0,0 +1,270 @@
+use anyhow::{Context, Result, bail};
+use fs_err as fs;
+use std::path::Path;
+
+use crate::types::*;
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+pub const PERSIST_MAP_FILE: &str = "persist-map.json";
+
+/// guest kernel 與 agent 本身的記憶體
+const GUEST_OVERHEAD: ByteSize = ByteSize::mib(128);
+
+impl Manifest {
+    /// 解析並檢查版本相容性與內容
+    pub fn from_slice(bytes: &[u8]) -> Result<Self> {
+        let ident: ManifestIdentity =
+            serde_json::from_slice(bytes).context("parse manifest.json")?;
+        check_compat(ident.manifest_version)?;
+        let mani: Manifest = serde_json::from_slice(bytes).context("parse manifest.json")?;
+        mani.validate()
+            .map_err(|e| anyhow::anyhow!("invalid manifest.json: {e}"))?;
+        Ok(mani)
+    }
+
+    /// 讀 bundle 目錄下的 manifest.json
+    pub fn read(bundle_dir: &Path) -> Result<Self> {
+        let bytes = fs::read(bundle_dir.join(MANIFEST_FILE))?;
+        Self::from_slice(&bytes)
+    }
+
+    pub fn to_vec_pretty(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec_pretty(self)?)
+    }
+
+    pub fn service(&self, name: &str) -> Option<&ServiceManifest> {
+        self.services.iter().find(|s| s.name == name)
+    }
+
+    /// 主要 service（驗證保證至多一個）
+    pub fn primary(&self) -> Option<&ServiceManifest> {
+        self.services.iter().find(|s| s.primary)
+    }
+
+    /// guest 的大小：vCPU 為各 service `cpus` 的最大值（進位），
+    /// 記憶體為各 service `memory` 的總和再加上 guest 本身的預留
+    pub fn guest_size(&self) -> GuestSize {
+        let all = |f: fn(&Resources) -> bool| self.services.iter().all(|s| f(&s.resources));
+        let vcpus = all(|r| r.cpus.is_some()).then(|| {
+            self.services
+                .iter()
+                .filter_map(|s| s.resources.cpus)
+                .fold(1.0, f64::max)
+                .ceil() as u32
+        });
+        let memory = all(|r| r.memory.is_some()).then(|| {
+            let total = self.services.iter().filter_map(|s| s.resources.memory);
+            ByteSize(total.map(|m| m.0).sum::<u64>() + GUEST_OVERHEAD.0)
+        });
+        GuestSize { vcpus, memory }
+    }
+
+    /// interface_mode 為 terminal / both 的 service（驗證保證至多一個）
+    pub fn terminal_service(&self) -> Option<&ServiceManifest> {
+        self.services
+            .iter()
+            .find(|s| s.interface_mode.uses_terminal())
+    }
+}
+
+impl ServiceManifest {
+    /// 展開後的埠對應（範圍拆成單一埠）
+    pub fn port_mappings(&self) -> Result<Vec<PortMapping>> {
+        let mut out = Vec::new();
+        for p in &self.ports {
+            out.extend(
+                appcipe_spec::parse_ports(p)
+                    .map_err(|e| anyhow::anyhow!("service `{}`: {e}", self.name))?,
+            );
+        }
+        Ok(out)
+    }
+}
+
+impl ManifestIdentity {
+    pub fn from_slice(bytes: &[u8]) -> Result<Self> {
+        serde_json::from_slice(bytes).context("parse manifest.json")
+    }
+}
+
+/// 這個版本的程式能否讀某個 manifest_version
+pub fn check_compat(version: u32) -> Result<()> {
+    if version > MANIFEST_VERSION {
+        bail!(
+            "manifest version {version} is newer than this runtime supports ({MANIFEST_VERSION}); \
+             rebuild with a matching chefer or update the runtime"
+        );
+    }
+    Ok(())
+}
+
+pub fn persist_map_from_slice(bytes: &[u8]) -> Result<Vec<PersistEntry>> {
+    let entries: Vec<PersistEntry> =
+        serde_json::from_slice(bytes).context("parse persist-map.json")?;
+    for e in &entries {
+        if e.host_rel != format!("data/{}", e.service) {
+            bail!(
+                "persist-map.json: unexpected host_rel `{}` for service `{}`",
+                e.host_rel,
+                e.service
+            );
+        }
+    }
+    Ok(entries)
+}
+
+/// 讀 bundle 目錄下的 persist-map.json；不存在視為空
+pub fn read_persist_map(bundle_dir: &Path) -> Result<Vec<PersistEntry>> {
+    let p = bundle_dir.join(PERSIST_MAP_FILE);
+    if !p.exists() {
+        return Ok(Vec::new());
+    }
+    persist_map_from_slice(&fs::read(p)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 加入 manifest_version 之前（視為 0）
+    const V0: &str = r#"{
+        "app_name": "App",
+        "spec_version": "0.1",
+        "generated_at_utc": "2025-01-01T00:00:00Z",
+        "services": [{ "name": "web", "rootfs_rel": "services/web/rootfs" }]
+    }"#;
+
+    /// depends_on 只有名稱
+    const V1: &str = r#"{
+        "manifest_version": 1,
+        "app_name": "App",
+        "spec_version": "0.1",
+        "generated_at_utc": "2025-01-01T00:00:00Z",
+        "services": [
+            { "name": "db", "rootfs_rel": "services/db/rootfs" },
+            { "name": "web", "rootfs_rel": "services/web/rootfs", "depends_on": ["db"] }
+        ]
+    }"#;
+
+    /// depends_on 為 {service, condition}，mounts 為 build 時解析好的 "host:container[:ro]"
+    const V2: &str = r#"{
+        "manifest_version": 2,
+        "app_name": "App",
+        "spec_version": "0.1",
+        "generated_at_utc": "2025-01-01T00:00:00Z",
+        "services": [
+            { "name": "db", "rootfs_rel": "services/db/rootfs",
+              "mounts": ["/srv/db:/var/lib/db", "/etc/app:/etc/app:ro"] },
+            { "name": "web", "rootfs_rel": "services/web/rootfs",
+              "depends_on": [{ "service": "db" }, { "service": "db", "condition": "service_started" }] }
+        ]
+    }"#;
+
+    fn as_value(m: &Manifest) -> serde_json::Value {
+        serde_json::from_slice(&m.to_vec_pretty().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn reads_a_manifest_without_version() {
+        let m = Manifest::from_slice(V0.as_bytes()).unwrap();
+        assert_eq!(m.manifest_version, 0);
+        assert_eq!(m.services[0].stop_signal, "SIGTERM");
+        assert!(m.services[0].depends_on.is_empty());
+    }
+
+    #[test]
+    fn reads_v1_string_dependencies() {
+        let m = Manifest::from_slice(V1.as_bytes()).unwrap();
+        assert_eq!(
+            m.service("web").unwrap().depends_on,
+            [Dependency {
+                service: "db".into(),
+                condition: DependencyCondition::ServiceStarted,
+            }]
+        );
+        // 寫出時一律是新格式
+        assert_eq!(
+            as_value(&m)["services"][1]["depends_on"],
+            serde_json::json!([{ "service": "db", "condition": "service_started" }])
+        );
+    }
+
+    #[test]
+    fn reads_v2_string_mounts() {
+        let m = Manifest::from_slice(V2.as_bytes()).unwrap();
+        let db = m.service("db").unwrap();
+        assert_eq!(
+            db.mounts,
+            [
+                Mount {
+                    source: "/srv/db".into(),
+                    target: "/var/lib/db".into(),
+                    read_only: false,
+                    create: false,
+                },
+                Mount {
+                    source: "/etc/app".into(),
+                    target: "/etc/app".into(),
+                    read_only: true,
+                    create: false,
+                },
+            ]
+        );
+        assert_eq!(
+            as_value(&m)["services"][0]["mounts"][1],
+            serde_json::json!({ "source": "/etc/app", "target": "/etc/app", "read_only": true })
+        );
+        // condition 省略時為 service_started
+        let web = m.service("web").unwrap();
+        assert!(
+            web.depends_on
+                .iter()
+                .all(|d| d.condition == DependencyCondition::ServiceStarted)
+        );
+    }
+
+    #[test]
+    fn round_trips_the_current_version() {
+        for old in [V0, V1, V2] {
+            let m = Manifest::from_slice(old.as_bytes()).unwrap();
+            let mut current = m.clone();
+            current.manifest_version = MANIFEST_VERSION;
+            let bytes = current.to_vec_pretty().unwrap();
+            let again = Manifest::from_slice(&bytes).unwrap();
+            assert_eq!(as_value(&again), as_value(&current));
+        }
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        assert!(check_compat(0).is_ok());
+        assert!(check_compat(MANIFEST_VERSION).is_ok());
+        let err = check_compat(MANIFEST_VERSION + 1).unwrap_err().to_string();
+        assert!(
+            err.contains(&format!("manifest version {}", MANIFEST_VERSION + 1)),
+            "{err}"
+        );
+
+        let newer = V0.replacen(
+            '{',
+            &format!("{{\"manifest_version\": {},", MANIFEST_VERSION + 1),
+            1,
+        );
+        let err = Manifest::from_slice(newer.as_bytes())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("newer than this runtime supports"), "{err}");
+        // 識別資訊仍讀得到（自我更新比對用）
+        let ident = ManifestIdentity::from_slice(newer.as_bytes()).unwrap();
+        assert_eq!(ident.manifest_version, MANIFEST_VERSION + 1);
+        assert_eq!(ident.app_name, "App");
+    }
+
+    #[test]
+    fn persist_map_must_point_into_the_service_dir() {
+        let ok = r#"[{ "service": "db", "container_path": "/var/lib/db", "host_rel": "data/db" }]"#;
+        assert_eq!(persist_map_from_slice(ok.as_bytes()).unwrap().len(), 1);
+        let bad = r#"[{ "service": "db", "container_path": "/var/lib/db", "host_rel": "../db" }]"#;
+        assert!(persist_map_from_slice(bad.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,210 @@
+// 與 appcipe.yml 共用的列舉直接沿用 spec 的定義（序列化格式相同）
+pub use appcipe_spec::{
+    ByteSize, Cmd, CrashPolicy, DEFAULT_SECRET_LENGTH, DependencyCondition, Healthcheck,
+    HumanDuration, ImagePlatform, InterfaceMode, Lifetime, Mount, PathAnchor, PortMapping,
+    Protocol, REDACTED, Resources, RestartConfig, RestartPolicy, SECRETS_DIR, Secret,
+    ServiceSecret, UpdateCheck, UpdateConfig, interpolate, path_anchor, signal_number,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// 目前寫出的 manifest 格式版本。
+///
+/// 相容規則：
+/// - 新增選填欄位（帶 `#[serde(default)]`）不升版；讀取端忽略不認得的欄位
+/// - 改名、刪除欄位或改變語意才升版
+/// - 讀取端接受 `0..=MANIFEST_VERSION`；缺欄位視為 0（加入版本號之前的 bundle）
+/// - 比自己新的版本一律拒絕，請使用者更新 runtime
+///
+/// 歷史：
+/// - 1：加入 manifest_version
+/// - 2：depends_on 每項改為 `{service, condition}`（仍可讀舊的字串寫法）
+/// - 3：mounts 每項改為 `{source, target, read_only, create}`，host 路徑在啟動時解析
+///   （相對路徑以執行檔所在目錄為準、可用錨點；仍可讀舊的字串寫法）
+pub const MANIFEST_VERSION: u32 = 3;
+
+/// bundle 根目錄的 manifest.json：runtime 執行 app 所需的一切
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    #[serde(default)]
+    pub manifest_version: u32,
+
+    pub app_name: String,
+
+    #[serde(default)]
+    pub app_version: Option<String>,
+
+    /// 來源 appcipe.yml 的格式版本
+    pub spec_version: String,
+
+    pub generated_at_utc: String,
+
+    /// 舊的資料夾名稱；新資料夾不存在時由 runtime 改名遷移
+    #[serde(default)]
+    pub old_names: Vec<String>,
+
+    /// 覆寫預設的資料夾位置
+    #[serde(default)]
+    pub data_dir: Option<String>,
+
+    #[serde(default)]
+    pub crash: CrashPolicy,
+
+    /// 已套用預設（有主要 service 時為 primary）
+    #[serde(default)]
+    pub lifetime: Lifetime,
+
+    /// runtime 自我更新用
+    #[serde(default)]
+    pub update: Option<UpdateConfig>,
+
+    /// secret 的來源（不含值）；runtime 啟動時取得
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub secrets: BTreeMap<String, Secret>,
+
+    pub services: Vec<ServiceManifest>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceManifest {
+    pub name: String,
+
+    /// 相對 bundle 根目錄，固定為 services/<name>/rootfs
+    pub rootfs_rel: String,
+
+    #[serde(default)]
+    pub persist_path: Option<String>,
+
+    #[serde(default)]
+    pub interface_mode: InterfaceMode,
+
+    /// appcipe 的原字串（`[ip:][host[-end]:]guest[-end][/proto]`），見 [`ServiceManifest::port_mappings`]
+    #[serde(default)]
+    pub ports: Vec<String>,
+
+    /// host 路徑原樣保留（錨點、相對路徑），runtime 啟動時解析
+    #[serde(default)]
+    pub mounts: Vec<Mount>,
+
+    #[serde(default)]
+    pub cmd: Option<Cmd>,
+
+    /// 依 key 排序
+    #[serde(default)]
+    pub env: Vec<(String, String)>,
+
+    /// `--chefer-manifest` 不顯示值的 env：名稱像機密的、值來自 build 機器的（`${VAR}`、env_file）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hidden_env: Vec<String>,
+
+    #[serde(default)]
+    pub workdir: Option<String>,
+
+    /// 依 service 名稱排序
+    #[serde(default)]
+    pub depends_on: Vec<Dependency>,
+
+    /// 已套用 app 層級的預設
+    #[serde(default)]
+    pub restart: RestartConfig,
+
+    /// 已套用 image 的 HEALTHCHECK；None 表示不檢查
+    #[serde(default)]
+    pub healthcheck: Option<Healthcheck>,
+
+    /// 已套用 image 的 StopSignal，正規化成 "SIGTERM" 這種名稱
+    #[serde(default = "default_stop_signal")]
+    pub stop_signal: String,
+
+    #[serde(default = "default_stop_grace_period")]
+    pub stop_grace_period: HumanDuration,
+
+    /// 已解析的主要 service（含由 interface_mode 推得的）；至多一個
+    #[serde(default)]
+    pub primary: bool,
+
+    /// app 已在執行時再次啟動所執行的指令（只在主要 service）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handoff: Option<Cmd>,
+
+    /// CPU、記憶體等上限；namespace 後端以 cgroup v2 套用
+    #[serde(default, skip_serializing_if = "Resources::is_empty")]
+    pub resources: Resources,
+
+    /// 引用的 secrets（檔案或 env）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub secrets: Vec<ServiceSecret>,
+
+    #[serde(default)]
+    pub platform: Option<ImagePlatform>,
+
+    /// "auto" / "docker-archive" / "oci-archive"（僅供顯示）
+    #[serde(default)]
+    pub image_format: Option<String>,
+}
+
+/// microVM 後端配置 guest 的大小；None 由後端決定（有 service 未設定上限時）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GuestSize {
+    pub vcpus: Option<u32>,
+    pub memory: Option<ByteSize>,
+}
+
+/// 啟動前要等 `service` 達到 `condition`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "DependencyRepr")]
+pub struct Dependency {
+    pub service: String,
+    pub condition: DependencyCondition,
+}
+
+/// manifest v1 以前 depends_on 只有名稱
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DependencyRepr {
+    Name(String),
+    Full {
+        service: String,
+        #[serde(default)]
+        condition: DependencyCondition,
+    },
+}
+
+impl From<DependencyRepr> for Dependency {
+    fn from(r: DependencyRepr) -> Self {
+        match r {
+            DependencyRepr::Name(service) => Dependency {
+                service,
+                condition: DependencyCondition::default(),
+            },
+            DependencyRepr::Full { service, condition } => Dependency { service, condition },
+        }
+    }
+}
+
+/// persist-map.json 的一筆：service 的 persist_path 對應到資料夾下的 host_rel
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PersistEntry {
+    pub service: String,
+    pub container_path: String,
+    /// 相對資料夾，固定為 data/<service>
+    pub host_rel: String,
+}
+
+/// 只取識別資訊；不檢查版本，用於比對其他版本的單檔（例如自我更新下載的新版）
+#[derive(Debug, Clone, Deserialize)]
+pub struct ManifestIdentity {
+    #[serde(default)]
+    pub manifest_version: u32,
+    pub app_name: String,
+    #[serde(default)]
+    pub app_version: Option<String>,
+}
+
+fn default_stop_signal() -> String {
+    appcipe_spec::DEFAULT_STOP_SIGNAL.to_string()
+}
+
+fn default_stop_grace_period() -> HumanDuration {
+    appcipe_spec::DEFAULT_STOP_GRACE_PERIOD
+}
@@ -0,0 +1,183 @@
+use std::collections::HashSet;
+
+use crate::types::*;
+
+impl Manifest {
+    /// 結構檢查：寫出前（pack）與讀入後（runtime）都跑一次
+    pub fn validate(&self) -> Result<(), String> {
+        if self.app_name.is_empty() {
+            return Err("app_name is empty".to_string());
+        }
+        if self.services.is_empty() {
+            return Err("services is empty".to_string());
+        }
+        for old in &self.old_names {
+            if old.is_empty() || old.contains(['/', '\\']) || old == "." || old == ".." {
+                return Err(format!(
+                    "old_names entry '{}' is not a plain folder name",
+                    old
+                ));
+            }
+        }
+
+        let mut names = HashSet::new();
+        for s in &self.services {
+            if !names.insert(s.name.as_str()) {
+                return Err(format!("duplicate service '{}'", s.name));
+            }
+        }
+        for s in &self.services {
+            s.validate(&names)?;
+        }
+        appcipe_spec::validate_secrets(self.secrets.iter().map(|(n, s)| (n.as_str(), s)))?;
+        let defined: Vec<&str> = self.secrets.keys().map(String::as_str).collect();
+        for s in &self.services {
+            let env: Vec<&str> = s.env.iter().map(|(k, _)| k.as_str()).collect();
+            appcipe_spec::validate_service_secrets(
+                &format!("service '{}' secrets", s.name),
+                &s.secrets,
+                &defined,
+                &env,
+            )?;
+        }
+        for s in &self.services {
+            for dep in &s.depends_on {
+                let healthcheck = self
+                    .service(&dep.service)
+                    .and_then(|d| d.healthcheck.as_ref());
+                if dep.condition == DependencyCondition::ServiceHealthy && healthcheck.is_none() {
+                    return Err(format!(
+                        "service '{}' waits for '{}' to be healthy, but '{}' has no healthcheck",
+                        s.name, dep.service, dep.service
+                    ));
+                }
+            }
+        }
+        appcipe_spec::validate_ports(
+            self.services
+                .iter()
+                .map(|s| (s.name.as_str(), s.ports.as_slice())),
+        )?;
+        appcipe_spec::validate_web(
+            self.services
+                .iter()
+                .map(|s| (s.name.as_str(), &s.interface_mode, s.ports.as_slice())),
+        )?;
+        let terminal: Vec<&str> = self
+            .services
+            .iter()
+            .filter(|s| s.interface_mode.uses_terminal())
+            .map(|s| s.name.as_str())
+            .collect();
+        if terminal.len() > 1 {
+            return Err(format!(
+                "only one service can use the terminal, found: {}",
+                terminal.join(", ")
+            ));
+        }
+        if self.services.iter().filter(|s| s.primary).count() > 1 {
+            return Err("more than one primary service".to_string());
+        }
+        if let Some(s) = self
+            .services
+            .iter()
+            .find(|s| s.handoff.is_some() && !s.primary)
+        {
+            return Err(format!(
+                "service '{}' has a handoff command but is not the primary service",
+                s.name
+            ));
+        }
+        if self.lifetime == Lifetime::Primary && self.primary().is_none() {
+            return Err("lifetime is primary but no service is primary".to_string());
+        }
+        self.start_order()?;
+        Ok(())
+    }
+
+    /// 依 depends_on 排出的啟動順序（相依的在前）；反過來就是停止順序
+    pub fn start_order(&self) -> Result<Vec<&ServiceManifest>, String> {
+        let graph: Vec<(String, Vec<String>)> = self
+            .services
+            .iter()
+            .map(|s| {
+                let deps = s.depends_on.iter().map(|d| d.service.clone()).collect();
+                (s.name.clone(), deps)
+            })
+            .collect();
+        let order = appcipe_spec::dependency_order(&graph)?;
+        Ok(order.iter().filter_map(|n| self.service(n)).collect())
+    }
+}
+
+impl ServiceManifest {
+    fn validate(&self, services: &HashSet<&str>) -> Result<(), String> {
+        let name = &self.name;
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(format!("invalid service name '{}'", name));
+        }
+        if self.rootfs_rel != format!("services/{}/rootfs", name) {
+            return Err(format!(
+                "service '{}' has unexpected rootfs_rel '{}'",
+                name, self.rootfs_rel
+            ));
+        }
+        if let Some(p) = &self.persist_path
+            && !p.starts_with('/')
+        {
+            return Err(format!(
+                "service '{}' persist_path must be absolute, got '{}'",
+                name, p
+            ));
+        }
+        if signal_number(&self.stop_signal).is_none() {
+            return Err(format!(
+                "service '{}' has unsupported stop_signal '{}'",
+                name, self.stop_signal
+            ));
+        }
+        if let Some(h) = &self.healthcheck {
+            appcipe_spec::validate_healthcheck(&format!("service '{}' healthcheck", name), h)?;
+        }
+        appcipe_spec::validate_resources(
+            &format!("service '{}' resources", name),
+            &self.resources,
+        )?;
+        appcipe_spec::validate_mounts(&format!("service '{}' mounts", name), &self.mounts)?;
+        for dep in &self.depends_on {
+            if !services.contains(dep.service.as_str()) {
+                return Err(format!(
+                    "service '{}' depends on unknown service '{}'",
+                    name, dep.service
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(services: serde_json::Value) -> Manifest {
+        serde_json::from_value(serde_json::json!({
+            "manifest_version": crate::MANIFEST_VERSION,
+            "app_name": "App",
+            "spec_version": "0.1",
+            "generated_at_utc": "2026-01-01T00:00:00Z",
+            "services": services,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn needs_a_service() {
+        assert_eq!(
+            manifest(serde_json::json!([])).validate(),
+            Err("services is empty".to_string())
+        );
+        let one = manifest(serde_json::json!([{ "name": "a", "rootfs_rel": "services/a/rootfs" }]));
+        assert_eq!(one.validate(), Ok(()));
+    }
+}
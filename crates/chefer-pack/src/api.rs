@@ -1,6 +1,6 @@
-use std::path::PathBuf;
 use anyhow::Result;
 use appcipe_spec::AppCipe;
+use std::path::PathBuf;
 
 #[derive(Clone, Debug)]
 pub struct PackOptions {
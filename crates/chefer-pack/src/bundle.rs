@@ -1,10 +1,10 @@
 use anyhow::{Result, bail};
+use appcipe_spec::{AppCipe, ImageFormat, ImageSourceOrPath};
+use chefer_manifest::{Dependency, MANIFEST_VERSION, Manifest, PersistEntry, ServiceManifest};
 use fs_err as fs;
 use std::collections::BTreeMap;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 use time::{OffsetDateTime, format_description::well_known::Rfc3339};
-use serde::Serialize;
-use appcipe_spec::{AppCipe, Cmd, ImageFormat, ImagePlatform, ImageSourceOrPath};
 
 pub struct Layout {
     pub bundle_dir: PathBuf,
@@ -29,117 +29,131 @@ pub fn prepare_layout(app: &AppCipe, opts: &crate::PackOptions) -> Result<Layout
     })
 }
 
-#[derive(Serialize)]
-struct PersistEntry {
-    service: String,
-    container_path: String,
-    host_rel: String,
-}
-
-#[derive(Serialize)]
-struct ServiceManifest {
-    name: String,
-    rootfs_rel: String,
-    persist_path: Option<String>,
-    interface_mode: String,
-    ports: Vec<String>,
-    mounts: Vec<String>,
-    cmd: Option<serde_json::Value>,   // 字串或陣列
-    env: Vec<(String, String)>,       // 排序過
-    workdir: Option<String>,
-    // 追加
-    depends_on: Vec<String>,
-    platform: Option<String>,         // "linux/amd64"...
-    image_format: Option<String>,     // "docker-archive"/"oci-archive"/"auto"
-}
-
-#[derive(Serialize)]
-struct Manifest {
-    app_name: String,
-    spec_version: String,
-    generated_at_utc: String,
-    services: Vec<ServiceManifest>,
-}
-
 pub fn write_metadata(layout: &Layout, app: &AppCipe, opts: &crate::PackOptions) -> Result<()> {
-    // 先做 mounts 主機端存在性驗證
-    for (name, svc) in &app.services {
-        for m in &svc.mounts {
-            if let Some((host, _guest)) = split_mount(m) {
-                let hp = Path::new(host);
-                if !hp.exists() {
-                    bail!("service `{name}` mount host path not found: {host}");
-                }
-            } else {
-                bail!("service `{name}` invalid mount syntax: {m}");
-            }
-        }
-    }
-
     // manifest.json
-    let now = OffsetDateTime::now_utc().format(&Rfc3339).unwrap_or_default();
+    let now = OffsetDateTime::now_utc()
+        .format(&Rfc3339)
+        .unwrap_or_default();
     let mut services = vec![];
+    let primary = app.primary_service();
 
-    for (name, svc) in &app.services {
-        let cmd = match &svc.cmd {
-            Some(Cmd::String(s)) => Some(serde_json::Value::String(s.clone())),
-            Some(Cmd::Array(v))  => Some(serde_json::json!(v)),
-            None => None,
-        };
+    // 依名稱排序，同樣的輸入得到同樣的 manifest
+    let mut names: Vec<&String> = app.services.keys().collect();
+    names.sort();
+    for name in names {
+        let svc = &app.services[name];
 
         // env 排序（穩定輸出）
         let mut env_sorted: BTreeMap<String, String> = BTreeMap::new();
         for (k, v) in &svc.env {
             env_sorted.insert(k.clone(), v.clone());
         }
-        let env_vec: Vec<(String, String)> =
-            env_sorted.into_iter().collect();
+        let env_vec: Vec<(String, String)> = env_sorted.into_iter().collect();
 
         // 讀取 platform / image_format（若有）
         let (platform, image_format) = match &svc.image {
             ImageSourceOrPath::TarPath(_) => (None, Some("auto".to_string())),
-            ImageSourceOrPath::Full { platform, format, .. } => {
-                let pf = match platform {
-                    ImagePlatform::LinuxAmd64   => "linux/amd64",
-                    ImagePlatform::LinuxArm64   => "linux/arm64",
-                    ImagePlatform::WindowsAmd64 => "windows/amd64",
-                }.to_string();
+            ImageSourceOrPath::Full {
+                platform, format, ..
+            } => {
                 let fmt = match format {
-                    ImageFormat::Auto          => "auto",
+                    ImageFormat::Auto => "auto",
                     ImageFormat::DockerArchive => "docker-archive",
-                    ImageFormat::OciArchive    => "oci-archive",
-                }.to_string();
-                (Some(pf), Some(fmt))
+                    ImageFormat::OciArchive => "oci-archive",
+                }
+                .to_string();
+                (Some(*platform), Some(fmt))
             }
         };
 
+        // healthcheck、stop_signal：appcipe 優先，否則沿用 image 的 HEALTHCHECK / StopSignal
+        let image = if svc.healthcheck.is_none() || svc.stop_signal.is_none() {
+            crate::image::image_config(name, svc)?
+        } else {
+            Default::default()
+        };
+        let healthcheck = match &svc.healthcheck {
+            Some(h) if h.disable => None,
+            Some(h) => Some(h.clone()),
+            None => image.healthcheck,
+        };
+        let stop_signal = match (&svc.stop_signal, &image.stop_signal) {
+            (Some(s), _) => appcipe_spec::normalize_signal(s).map_err(anyhow::Error::msg)?,
+            (None, Some(s)) => appcipe_spec::normalize_signal(s).map_err(|e| {
+                anyhow::anyhow!(
+                    "service `{name}` image StopSignal: {e}; set `stop_signal` in appcipe.yml"
+                )
+            })?,
+            (None, None) => appcipe_spec::DEFAULT_STOP_SIGNAL.to_string(),
+        };
+
         services.push(ServiceManifest {
             name: name.clone(),
             rootfs_rel: format!("services/{name}/rootfs"),
             persist_path: svc.persist_path.clone(),
-            interface_mode: format!("{:?}", svc.interface_mode).to_lowercase(),
+            interface_mode: svc.interface_mode.clone(),
             ports: svc.ports.clone(),
             mounts: svc.mounts.clone(),
-            cmd,
+            cmd: svc.cmd.clone(),
+            hidden_env: env_vec
+                .iter()
+                .filter(|(k, _)| svc.hides_env(k))
+                .map(|(k, _)| k.clone())
+                .collect(),
             env: env_vec,
             workdir: svc.workdir.clone(),
-            depends_on: svc.depends_on.clone(),
+            depends_on: svc
+                .depends_on
+                .entries()
+                .into_iter()
+                .map(|(service, condition)| Dependency { service, condition })
+                .collect(),
+            restart: svc
+                .restart
+                .clone()
+                .or_else(|| app.restart.clone())
+                .unwrap_or_default(),
+            healthcheck,
+            stop_signal,
+            stop_grace_period: svc
+                .stop_grace_period
+                .unwrap_or(appcipe_spec::DEFAULT_STOP_GRACE_PERIOD),
+            primary: primary == Some(name.as_str()),
+            handoff: svc.handoff.clone(),
+            resources: svc.resources(),
+            secrets: svc.secrets.clone(),
             platform,
             image_format,
         });
     }
 
     let mani = Manifest {
+        manifest_version: MANIFEST_VERSION,
         app_name: app.name.clone(),
+        app_version: app.app_version.clone(),
         spec_version: app.version.clone(),
         generated_at_utc: now,
+        old_names: app.old_names.clone(),
+        data_dir: app.data_dir.clone(),
+        crash: app.crash,
+        lifetime: app.resolved_lifetime(),
+        update: app.update.clone(),
+        secrets: app
+            .secrets
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect(),
         services,
     };
-    fs::write(&layout.manifest_path, serde_json::to_vec_pretty(&mani)?)?;
+    if let Err(e) = mani.validate() {
+        bail!("generated manifest is invalid: {e}");
+    }
+    fs::write(&layout.manifest_path, mani.to_vec_pretty()?)?;
 
     // persist-map.json（host 相對路徑規則：data/<service>）
     let mut persist = vec![];
-    for (name, svc) in &app.services {
+    for svc in &mani.services {
+        let name = &svc.name;
         if let Some(p) = &svc.persist_path {
             // persist_path 是容器內路徑，必須以 "/" 開頭
             if !p.starts_with('/') {
@@ -152,10 +166,14 @@ pub fn write_metadata(layout: &Layout, app: &AppCipe, opts: &crate::PackOptions)
             });
         }
     }
-    fs::write(&layout.persist_map_path, serde_json::to_vec_pretty(&persist)?)?;
+    fs::write(
+        &layout.persist_map_path,
+        serde_json::to_vec_pretty(&persist)?,
+    )?;
 
     if opts.write_original_yml {
-        let yml = serde_yaml::to_string(app)?;
+        // 僅供參考的副本：機密的 env 值不寫進 bundle（manifest.json 才是 runtime 用的）
+        let yml = serde_yaml::to_string(&app.redacted()?)?;
         fs::write(&layout.appcipe_out_path, yml)?;
     }
     Ok(())
@@ -166,11 +184,3 @@ impl Layout {
         self.services_dir.join(name).join("rootfs")
     }
 }
-
-/// 解析 "<host>:<container>"，從右往左切割，避免 Windows "C:\"
-pub(crate) fn split_mount(s: &str) -> Option<(&str, &str)> {
-    let mut it = s.rsplitn(2, ':');
-    let right = it.next()?;
-    let left  = it.next()?;
-    Some((left, right))
-}
@@ -1,11 +1,14 @@
-use anyhow::{Result, Context, bail};
+use anyhow::{Context, Result, bail};
+use appcipe_spec::{Cmd, Healthcheck, HumanDuration, ImageSourceOrPath, ImageSourceType, Service};
+use flate2::read::GzDecoder;
 use fs_err as fs;
+use serde_json::Value;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
 use std::path::{Path, PathBuf};
-use appcipe_spec::{Service, ImageSourceOrPath, ImageSourceType};
+use std::time::Duration;
 use tar::{Archive, EntryType};
-use flate2::read::GzDecoder;
 
 use crate::bundle::Layout;
 
@@ -14,33 +17,159 @@ pub fn extract_rootfs(layout: &Layout, name: &str, svc: &Service) -> Result<()>
     let out = layout.svc_rootfs_dir(name);
     fs::create_dir_all(&out)?;
     match &svc.image {
-        ImageSourceOrPath::TarPath(p) => unpack_tar_auto(p, &out)
-            .with_context(|| format!("service `{name}` unpack {:?}", p)),
+        ImageSourceOrPath::TarPath(p) => {
+            unpack_tar_auto(p, &out).with_context(|| format!("service `{name}` unpack {:?}", p))
+        }
         ImageSourceOrPath::Full { source, file, .. } => match source {
             ImageSourceType::Tar => unpack_tar_auto(file, &out)
                 .with_context(|| format!("service `{name}` unpack {:?}", file)),
             _ => bail!("MVP only supports image.source=tar for service `{name}`"),
+        },
+    }
+}
+
+/// image 設定中 runtime 用得到的部分
+#[derive(Debug, Default)]
+pub struct ImageConfig {
+    pub healthcheck: Option<Healthcheck>,
+    /// 原樣的 StopSignal（未正規化）
+    pub stop_signal: Option<String>,
+}
+
+/// 讀 docker-archive（manifest.json）或 oci-archive（index.json）裡的 image config；
+/// 單純的 rootfs tar 沒有 config，回傳預設值
+pub fn image_config(name: &str, svc: &Service) -> Result<ImageConfig> {
+    let path = match &svc.image {
+        ImageSourceOrPath::TarPath(p) => p,
+        ImageSourceOrPath::Full { file, .. } => file,
+    };
+    let config = read_config_json(path)
+        .with_context(|| format!("service `{name}` read image config from {:?}", path))?;
+    let Some(config) = config else {
+        return Ok(ImageConfig::default());
+    };
+    let inner = &config["config"];
+    Ok(ImageConfig {
+        healthcheck: healthcheck_from_config(&inner["Healthcheck"]),
+        stop_signal: inner["StopSignal"]
+            .as_str()
+            .filter(|s| !s.is_empty())
+            .map(str::to_string),
+    })
+}
+
+/// config JSON 都很小；layer 不讀
+const MAX_META_SIZE: u64 = 4 << 20;
+
+fn read_config_json(path: &str) -> Result<Option<Value>> {
+    let mut files: HashMap<String, Vec<u8>> = HashMap::new();
+    let mut ar = Archive::new(open_tar_auto(path)?);
+    for entry in ar.entries()? {
+        let mut entry = entry?;
+        let p = entry
+            .path()?
+            .to_string_lossy()
+            .trim_start_matches("./")
+            .to_string();
+        let small = entry.header().size()? <= MAX_META_SIZE;
+        // 只看最上層的 json 與 OCI blobs/；rootfs tar 裡其他的 json 不收
+        let meta = (p.ends_with(".json") && !p.contains('/')) || p.starts_with("blobs/");
+        if small && meta && entry.header().entry_type().is_file() {
+            let mut buf = Vec::new();
+            entry.read_to_end(&mut buf)?;
+            files.insert(p, buf);
+        }
+    }
+    let json = |p: &str| -> Result<Option<Value>> {
+        files
+            .get(p)
+            .map(|b| serde_json::from_slice(b).with_context(|| format!("parse {p}")))
+            .transpose()
+    };
+    let blob = |digest: &Value| -> Result<Option<Value>> {
+        match digest.as_str().and_then(|d| d.split_once(':')) {
+            Some((algo, hex)) => json(&format!("blobs/{algo}/{hex}")),
+            None => Ok(None),
+        }
+    };
+
+    // docker save：manifest.json → [0].Config
+    if let Some(m) = json("manifest.json")? {
+        return match m[0]["Config"].as_str() {
+            Some(cfg) => json(cfg),
+            None => Ok(None),
+        };
+    }
+    // OCI：index.json → manifests[0] →（可能再一層 index）→ config
+    if let Some(mut m) = json("index.json")? {
+        for _ in 0..3 {
+            if let Some(cfg) = m.get("config") {
+                return blob(&cfg["digest"]);
+            }
+            match blob(&m["manifests"][0]["digest"])? {
+                Some(next) => m = next,
+                None => return Ok(None),
+            }
         }
     }
+    Ok(None)
+}
+
+/// Docker 的 Healthcheck：Test 為 ["NONE"] / ["CMD", ...] / ["CMD-SHELL", "..."]，時間單位為 ns
+fn healthcheck_from_config(h: &Value) -> Option<Healthcheck> {
+    let test: Vec<String> = h["Test"]
+        .as_array()?
+        .iter()
+        .filter_map(|v| v.as_str().map(str::to_string))
+        .collect();
+    let cmd = match test.split_first()? {
+        (kind, rest) if kind == "CMD" && !rest.is_empty() => Cmd::Array(rest.to_vec()),
+        (kind, [shell]) if kind == "CMD-SHELL" => Cmd::String(shell.clone()),
+        _ => return None,
+    };
+    let mut hc = Healthcheck::with_cmd(cmd);
+    let ns = |key: &str| {
+        h[key]
+            .as_u64()
+            .filter(|n| *n > 0)
+            .map(|n| HumanDuration(Duration::from_nanos(n)))
+    };
+    if let Some(d) = ns("Interval") {
+        hc.interval = d;
+    }
+    if let Some(d) = ns("Timeout") {
+        hc.timeout = d;
+    }
+    if let Some(d) = ns("StartPeriod") {
+        hc.start_period = d;
+    }
+    if let Some(n) = h["Retries"].as_u64().filter(|n| *n > 0) {
+        hc.retries = n as u32;
+    }
+    Some(hc)
 }
 
 /// 自動判斷 .tar / .tar.gz / .tgz
-fn unpack_tar_auto(path: &str, out_dir: &Path) -> Result<()> {
+fn open_tar_auto(path: &str) -> Result<Box<dyn Read>> {
     let p = Path::new(path);
     let file = File::open(p).with_context(|| format!("open tar {:?}", p))?;
-    let is_gz = p.file_name()
+    let is_gz = p
+        .file_name()
         .and_then(|n| n.to_str())
         .map(|s| s.ends_with(".tar.gz") || s.ends_with(".tgz"))
         .unwrap_or(false);
 
     if is_gz {
-        let gz = GzDecoder::new(file);
-        unpack_tar_from_reader(gz, out_dir)
+        Ok(Box::new(GzDecoder::new(file)))
     } else {
-        unpack_tar_from_reader(file, out_dir)
+        Ok(Box::new(file))
     }
 }
 
+fn unpack_tar_auto(path: &str, out_dir: &Path) -> Result<()> {
+    unpack_tar_from_reader(open_tar_auto(path)?, out_dir)
+}
+
 /// 逐 entry 解包，並做路徑安全檢查
 fn unpack_tar_from_reader<R: Read>(reader: R, out_dir: &Path) -> Result<()> {
     let mut ar = Archive::new(reader);
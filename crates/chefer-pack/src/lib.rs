@@ -18,5 +18,7 @@ pub(crate) fn lib_pack_all(app: &AppCipe, opts: &PackOptions) -> Result<PackResu
     // 寫入 manifest / persist-map / appcipe.yml（可選）
     bundle::write_metadata(&layout, app, opts)?;
 
-    Ok(PackResult { bundle_dir: layout.bundle_dir.clone() })
+    Ok(PackResult {
+        bundle_dir: layout.bundle_dir.clone(),
+    })
 }
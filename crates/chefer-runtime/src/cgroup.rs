@@ -0,0 +1,326 @@
+// src/cgroup.rs
+//! service 的資源上限（cpus、memory、pids_limit、io_weight）以 cgroup v2 套用。
+//!
+//! 需要一個可寫（delegated）的 cgroup：runtime 自己的 cgroup 只有自己時，把自己移進 `runtime/`
+//! 子群組，再替每個 service 開 `svc-<name>/`（root cgroup 則另開 `chefer-<pid>/`）。
+//! 與其他行程共用時（例如從終端機啟動，與 shell 同一個 scope）先透過
+//! `systemd-run --scope -p Delegate=yes` 重新執行自己，取得只有自己的 scope。
+//! 拿不到 cgroup 或缺少 controller 時只警告，app 照常執行但不受限制。
+use anyhow::{Context, Result, bail};
+use chefer_manifest::{Manifest, Resources, ServiceManifest, control::ResourceUsage};
+use std::{
+    cell::Cell,
+    ffi::OsString,
+    os::unix::process::CommandExt,
+    path::{Path, PathBuf},
+    process::Command,
+    time::Instant,
+};
+
+/// 已經在 systemd scope 內重新執行過（避免重複）
+const SCOPE_ENV: &str = "CHEFER_CGROUP_SCOPE";
+
+/// cpu.max 的週期（µs）
+const CPU_PERIOD: u64 = 100_000;
+
+/// app 的 cgroup 子樹
+pub struct Cgroups {
+    root: PathBuf,
+    /// 已對 service 開啟的 controller
+    controllers: Vec<String>,
+    /// 自己建立、結束時要刪的（root cgroup 的情況）
+    created: bool,
+}
+
+/// 一個 service 的 cgroup；重啟時沿用
+pub struct Cgroup {
+    path: PathBuf,
+    /// 需要的 controller 都有開啟
+    pub enforced: bool,
+    /// 上次取樣的 CPU 時間（算使用率）
+    last_cpu: Cell<(Instant, u64)>,
+    /// 已回報過的 OOM kill 次數
+    oom_kills: Cell<u64>,
+}
+
+fn has_limits(mani: &Manifest) -> bool {
+    mani.services.iter().any(|s| !s.resources.is_empty())
+}
+
+/// 需要 cgroup、但自己的 cgroup 與其他行程共用時，在 systemd 的 delegated scope 內重新執行自己。
+/// 成功時不會返回；沒有 systemd 或 exec 失敗就照常繼續（之後 [`setup`] 會警告）
+pub fn reexec_in_scope(mani: &Manifest) {
+    if !has_limits(mani) || std::env::var_os(SCOPE_ENV).is_some() {
+        return;
+    }
+    let Ok((own, dir)) = own_cgroup() else { return };
+    if own == "/" || only_us(&dir) {
+        return;
+    }
+    let user = unsafe { libc::geteuid() } != 0;
+    // 同 sd_booted()：systemd 在管理這個 session
+    let manager = if user {
+        std::env::var_os("XDG_RUNTIME_DIR").map(|d| PathBuf::from(d).join("systemd/private"))
+    } else {
+        Some(PathBuf::from("/run/systemd/system"))
+    };
+    if !manager.is_some_and(|p| p.exists()) {
+        return;
+    }
+    let Ok(exe) = std::env::current_exe() else {
+        return;
+    };
+    tracing::debug!(
+        "cgroup {own} is shared; re-running in a systemd scope to apply resource limits"
+    );
+    let mut cmd = Command::new("systemd-run");
+    if user {
+        cmd.arg("--user");
+    }
+    let err = cmd
+        .args([
+            "--scope",
+            "--quiet",
+            "--collect",
+            "-p",
+            "Delegate=yes",
+            "--",
+        ])
+        .arg(exe)
+        .args(std::env::args_os().skip(1).collect::<Vec<OsString>>())
+        .env(SCOPE_ENV, "1")
+        .exec();
+    tracing::debug!("cannot run systemd-run: {err}");
+}
+
+/// 有 service 設定資源上限時準備 cgroup 子樹；做不到就警告並回傳 None
+pub fn setup(mani: &Manifest) -> Option<Cgroups> {
+    if !has_limits(mani) {
+        return None;
+    }
+    let mut wanted = Vec::new();
+    for s in &mani.services {
+        for c in controllers(&s.resources) {
+            if !wanted.contains(&c) {
+                wanted.push(c);
+            }
+        }
+    }
+    match Cgroups::new(&wanted) {
+        Ok(c) => Some(c),
+        Err(e) => {
+            tracing::warn!("resource limits are not enforced: {e:#}");
+            None
+        }
+    }
+}
+
+/// 上限需要的 controller
+fn controllers(r: &Resources) -> Vec<&'static str> {
+    [
+        (r.cpus.is_some(), "cpu"),
+        (r.memory.is_some(), "memory"),
+        (r.pids_limit.is_some(), "pids"),
+        (r.io_weight.is_some(), "io"),
+    ]
+    .into_iter()
+    .filter_map(|(set, c)| set.then_some(c))
+    .collect()
+}
+
+/// 自己的 cgroup 路徑（"/..."）與它在 cgroup2 掛載點下的目錄
+fn own_cgroup() -> Result<(String, PathBuf)> {
+    let mountinfo = fs_err::read_to_string("/proc/self/mountinfo")?;
+    // 欄位："... <mount point> <options> [optional...] - <fstype> <source> <super options>"
+    let mount = mountinfo
+        .lines()
+        .find_map(|l| {
+            let (pre, post) = l.split_once(" - ")?;
+            (post.split_whitespace().next() == Some("cgroup2"))
+                .then(|| pre.split_whitespace().nth(4).map(PathBuf::from))?
+        })
+        .context("cgroup v2 is not mounted")?;
+    let own = fs_err::read_to_string("/proc/self/cgroup")?
+        .lines()
+        .find_map(|l| l.strip_prefix("0::").map(str::to_string))
+        .context("this process is not in a cgroup v2 hierarchy")?;
+    let dir = mount.join(own.trim_start_matches('/'));
+    Ok((own, dir))
+}
+
+/// cgroup 內只有這個行程
+fn only_us(dir: &Path) -> bool {
+    let me = std::process::id().to_string();
+    std::fs::read_to_string(dir.join("cgroup.procs"))
+        .is_ok_and(|procs| procs.lines().all(|p| p.trim() == me))
+}
+
+fn write(dir: &Path, file: &str, value: &str) -> Result<()> {
+    let path = dir.join(file);
+    std::fs::write(&path, value).with_context(|| format!("write `{value}` to {}", path.display()))
+}
+
+fn read(dir: &Path, file: &str) -> Option<String> {
+    std::fs::read_to_string(dir.join(file)).ok()
+}
+
+/// `key value` 格式的檔案（cpu.stat、memory.events）中的一個值
+fn stat(dir: &Path, file: &str, key: &str) -> Option<u64> {
+    read(dir, file)?.lines().find_map(|l| {
+        let (k, v) = l.split_once(' ')?;
+        (k == key).then(|| v.trim().parse().ok())?
+    })
+}
+
+/// 對 `dir` 的子群組開啟 `wanted` 中可用的 controller；回傳實際開啟的
+fn enable(dir: &Path, wanted: &[&str]) -> Vec<String> {
+    let available = read(dir, "cgroup.controllers").unwrap_or_default();
+    for c in wanted {
+        if available.split_whitespace().any(|a| a == *c)
+            && let Err(e) = write(dir, "cgroup.subtree_control", &format!("+{c}"))
+        {
+            tracing::debug!("{e:#}");
+        }
+    }
+    read(dir, "cgroup.subtree_control")
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(str::to_string)
+        .collect()
+}
+
+impl Cgroups {
+    fn new(wanted: &[&str]) -> Result<Self> {
+        let (own, dir) = own_cgroup()?;
+        let (root, created) = if own == "/" {
+            // root cgroup 可以同時有行程與子群組
+            enable(&dir, wanted);
+            let root = dir.join(format!("chefer-{}", std::process::id()));
+            fs_err::create_dir(&root)?;
+            (root, true)
+        } else if only_us(&dir) {
+            // 有行程的 cgroup 不能對子群組開 controller：先把自己移到葉節點
+            let leaf = dir.join("runtime");
+            if !leaf.is_dir() {
+                fs_err::create_dir(&leaf)
+                    .with_context(|| format!("cgroup {own} is not delegated to this user"))?;
+            }
+            write(&leaf, "cgroup.procs", &std::process::id().to_string())?;
+            (dir, false)
+        } else {
+            bail!(
+                "cgroup {own} is shared with other processes; start the app from its own systemd scope \
+                 (systemd-run --user --scope -p Delegate=yes ...)"
+            );
+        };
+        let controllers = enable(&root, wanted);
+        tracing::debug!(
+            "cgroup {} with controllers: {}",
+            root.display(),
+            controllers.join(" ")
+        );
+        Ok(Cgroups {
+            root,
+            controllers,
+            created,
+        })
+    }
+
+    /// 建立 service 的 cgroup 並寫入上限；缺少的 controller 只警告
+    pub fn service(&self, svc: &ServiceManifest) -> Result<Cgroup> {
+        let path = self.root.join(format!("svc-{}", svc.name));
+        // 上次沒清掉的（例如 runtime 被 SIGKILL）
+        let _ = std::fs::remove_dir(&path);
+        if !path.is_dir() {
+            fs_err::create_dir(&path)?;
+        }
+        let r = &svc.resources;
+        let missing: Vec<&str> = controllers(r)
+            .into_iter()
+            .filter(|c| !self.controllers.iter().any(|e| e == c))
+            .collect();
+        if !missing.is_empty() {
+            tracing::warn!(
+                "service `{}`: the {} controller(s) are not available here; those limits are not enforced",
+                svc.name,
+                missing.join(", ")
+            );
+        }
+        let on = |c: &str| self.controllers.iter().any(|e| e == c);
+        if let Some(cpus) = r.cpus.filter(|_| on("cpu")) {
+            let quota = (cpus * CPU_PERIOD as f64).round() as u64;
+            write(&path, "cpu.max", &format!("{quota} {CPU_PERIOD}"))?;
+        }
+        if let Some(memory) = r.memory.filter(|_| on("memory")) {
+            write(&path, "memory.max", &memory.0.to_string())?;
+        }
+        if let Some(pids) = r.pids_limit.filter(|_| on("pids")) {
+            write(&path, "pids.max", &pids.to_string())?;
+        }
+        if let Some(weight) = r.io_weight.filter(|_| on("io")) {
+            write(&path, "io.weight", &format!("default {weight}"))?;
+        }
+        let cg = Cgroup {
+            enforced: missing.is_empty(),
+            last_cpu: Cell::new((Instant::now(), 0)),
+            oom_kills: Cell::new(0),
+            path,
+        };
+        // 沿用舊目錄時已有累計值
+        cg.last_cpu
+            .set((Instant::now(), cg.cpu_usec().unwrap_or(0)));
+        cg.oom_kills.set(cg.oom_kill_count());
+        Ok(cg)
+    }
+}
+
+impl Drop for Cgroups {
+    fn drop(&mut self) {
+        if self.created {
+            let _ = std::fs::remove_dir(&self.root);
+        }
+    }
+}
+
+impl Cgroup {
+    /// 容器的 shim 啟動時把自己寫進這個目錄的 cgroup.procs
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn cpu_usec(&self) -> Option<u64> {
+        stat(&self.path, "cpu.stat", "usage_usec")
+    }
+
+    fn oom_kill_count(&self) -> u64 {
+        stat(&self.path, "memory.events", "oom_kill").unwrap_or(0)
+    }
+
+    /// 目前的用量；CPU 使用率為上次取樣以來的平均
+    pub fn usage(&self) -> ResourceUsage {
+        let cpu_percent = self.cpu_usec().map(|now| {
+            let (at, before) = self.last_cpu.replace((Instant::now(), now));
+            let wall = at.elapsed().as_micros().max(1) as f64;
+            now.saturating_sub(before) as f64 / wall * 100.0
+        });
+        let number = |file| read(&self.path, file)?.trim().parse().ok();
+        ResourceUsage {
+            cpu_percent,
+            memory: number("memory.current"),
+            pids: number("pids.current"),
+        }
+    }
+
+    /// 上次呼叫以來有行程因超過 memory 上限被 kill
+    pub fn oom_killed(&self) -> bool {
+        let now = self.oom_kill_count();
+        now > self.oom_kills.replace(now)
+    }
+}
+
+impl Drop for Cgroup {
+    fn drop(&mut self) {
+        // 還有行程時刪不掉；runtime 結束時容器都已停止
+        let _ = std::fs::remove_dir(&self.path);
+    }
+}
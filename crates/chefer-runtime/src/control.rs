@@ -0,0 +1,184 @@
+// src/control.rs
+//! 控制 socket 的伺服端：在背景接受連線，請求經 channel 交給 supervisor 的主迴圈處理、由它回應；
+//! `logs` 不經主迴圈，直接讀 logs/<service>.log 送出。協定見 [`chefer_manifest::control`]。
+use anyhow::Result;
+use chefer_manifest::control::{Request, Response};
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    os::{
+        fd::AsRawFd,
+        unix::net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::Duration,
+};
+
+/// 讀取請求的時限
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+/// 等主迴圈處理的時限（停止中的 app 不再處理請求）
+const REPLY_TIMEOUT: Duration = Duration::from_secs(30);
+/// `logs --follow` 檢查新內容的間隔
+const FOLLOW_POLL: Duration = Duration::from_millis(250);
+
+/// 一個請求與送回回應用的 channel
+pub type Pending = (Request, mpsc::Sender<Response>);
+
+/// 在背景接受連線；回傳主迴圈要處理的請求。`services` 依啟動順序
+pub fn serve(
+    listener: UnixListener,
+    data_dir: PathBuf,
+    services: Vec<String>,
+) -> Result<mpsc::Receiver<Pending>> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::Builder::new()
+        .name("control".into())
+        .spawn(move || {
+            for conn in listener.incoming() {
+                let Ok(conn) = conn else { continue };
+                let (tx, data_dir, services) = (tx.clone(), data_dir.clone(), services.clone());
+                std::thread::spawn(move || {
+                    if let Err(e) = handle(conn, &tx, &data_dir, &services) {
+                        tracing::debug!("control socket: {e:#}");
+                    }
+                });
+            }
+        })?;
+    Ok(rx)
+}
+
+fn handle(
+    conn: UnixStream,
+    tx: &mpsc::Sender<Pending>,
+    data_dir: &Path,
+    services: &[String],
+) -> Result<()> {
+    conn.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut line = String::new();
+    BufReader::new(&conn).read_line(&mut line)?;
+    let req = match serde_json::from_str::<Request>(&line) {
+        Ok(req) => req,
+        Err(e) => return respond(&conn, &Response::error(format!("bad request: {e}"))),
+    };
+    if let Request::Logs {
+        service,
+        lines,
+        follow,
+    } = req
+    {
+        let selected: Vec<&str> = match &service {
+            Some(s) if !services.contains(s) => {
+                let msg = format!("unknown service `{s}` (available: {})", services.join(", "));
+                return respond(&conn, &Response::error(msg));
+            }
+            Some(s) => vec![s.as_str()],
+            None => services.iter().map(String::as_str).collect(),
+        };
+        respond(&conn, &Response::ok(""))?;
+        return stream_logs(&conn, data_dir, &selected, lines, follow);
+    }
+
+    let (reply_tx, reply_rx) = mpsc::channel();
+    let response = tx
+        .send((req, reply_tx))
+        .ok()
+        .and_then(|()| reply_rx.recv_timeout(REPLY_TIMEOUT).ok())
+        .unwrap_or_else(|| Response::error("the app is not taking requests (stopping?)"));
+    respond(&conn, &response)
+}
+
+fn respond(conn: &UnixStream, response: &Response) -> Result<()> {
+    let mut out = conn;
+    writeln!(out, "{}", serde_json::to_string(response)?)?;
+    Ok(())
+}
+
+/// 各 service 最後 `lines` 行（每個 service 各自計算）依時間合併送出；
+/// `follow` 時持續送出新寫入的行，直到連線關閉
+fn stream_logs(
+    conn: &UnixStream,
+    data_dir: &Path,
+    services: &[&str],
+    lines: usize,
+    follow: bool,
+) -> Result<()> {
+    let mut out = conn;
+    let width = services.iter().map(|s| s.len()).max().unwrap_or(0);
+    let prefix = |s: &str| format!("{s:<width$} | ");
+
+    let mut tail: Vec<(String, usize)> = Vec::new();
+    let mut offsets = Vec::with_capacity(services.len());
+    for (i, s) in services.iter().enumerate() {
+        let path = chefer_manifest::log_file(data_dir, s);
+        let content = std::fs::read(&path).unwrap_or_default();
+        offsets.push(content.len() as u64);
+        let text = String::from_utf8_lossy(&content);
+        let all: Vec<&str> = text.lines().collect();
+        let start = all.len().saturating_sub(lines);
+        tail.extend(all[start..].iter().map(|l| (l.to_string(), i)));
+    }
+    // 每行以時間戳開頭，字串排序即時間順序
+    tail.sort_by(|a, b| a.0.cmp(&b.0));
+    for (line, i) in &tail {
+        writeln!(out, "{}{line}", prefix(services[*i]))?;
+    }
+    if !follow {
+        return Ok(());
+    }
+
+    loop {
+        std::thread::sleep(FOLLOW_POLL);
+        // 沒有新內容時不會寫入，靠 peek 發現對方已離開
+        if client_gone(conn) {
+            return Ok(());
+        }
+        for (i, s) in services.iter().enumerate() {
+            let path = chefer_manifest::log_file(data_dir, s);
+            let Ok(mut file) = File::open(&path) else {
+                continue;
+            };
+            let len = file.metadata()?.len();
+            // 輪替過：新檔從頭讀
+            if len < offsets[i] {
+                offsets[i] = 0;
+            }
+            if len == offsets[i] {
+                continue;
+            }
+            file.seek(SeekFrom::Start(offsets[i]))?;
+            let mut new = Vec::new();
+            file.take(len - offsets[i]).read_to_end(&mut new)?;
+            // 只送完整的行，寫到一半的留到下次
+            let Some(end) = new.iter().rposition(|&b| b == b'\n') else {
+                continue;
+            };
+            offsets[i] += end as u64 + 1;
+            for line in String::from_utf8_lossy(&new[..end]).lines() {
+                writeln!(out, "{}{line}", prefix(s))?;
+            }
+        }
+    }
+}
+
+/// 對方已關閉連線（peek 讀到 EOF 或錯誤）；還連著但沒送資料時為 false
+fn client_gone(conn: &UnixStream) -> bool {
+    let mut b = [0u8; 1];
+    // SAFETY: buffer 長度正確；MSG_PEEK 不會取走資料
+    let n = unsafe {
+        libc::recv(
+            conn.as_raw_fd(),
+            b.as_mut_ptr().cast(),
+            1,
+            libc::MSG_PEEK | libc::MSG_DONTWAIT,
+        )
+    };
+    match n {
+        0 => true,
+        n if n > 0 => false,
+        _ => !matches!(
+            io::Error::last_os_error().kind(),
+            io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted
+        ),
+    }
+}
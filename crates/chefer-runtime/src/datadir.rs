@@ -0,0 +1,308 @@
+// src/datadir.rs
+//! 準備 app 的資料夾：解析位置 → 從 old_names 遷移 → 建立 data/<service> 與 logs/。
+use anyhow::{Context, Result, bail};
+use chefer_manifest::{DataDir, DataDirSource, LOGS_SUBDIR, Manifest, PersistEntry};
+use fs_err as fs;
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+pub fn prepare(
+    mani: &Manifest,
+    persist: &[PersistEntry],
+    cli_override: Option<&Path>,
+    exe: &Path,
+) -> Result<DataDir> {
+    let exe_dir = exe.parent().unwrap_or(Path::new("."));
+    let dir = DataDir::resolve(mani, cli_override, exe_dir)?;
+
+    // 明確指定位置時不動其他資料夾
+    if dir.source != DataDirSource::CommandLine && !mani.old_names.is_empty() {
+        // 實例鎖在資料夾內，遷移前還拿不到；同時啟動的行程改以資料夾旁的鎖檔排隊
+        let _lock = lock_migration(&dir.path)?;
+        migrate_legacy(mani, &dir)?;
+    }
+
+    fs::create_dir_all(&dir.path)
+        .with_context(|| format!("create data dir {}", dir.path.display()))?;
+    for e in persist {
+        fs::create_dir_all(dir.service_dir(&e.service))?;
+    }
+    fs::create_dir_all(dir.path.join(LOGS_SUBDIR))?;
+    tracing::debug!("data dir: {} ({:?})", dir.path.display(), dir.source);
+    Ok(dir)
+}
+
+/// 遷移用的鎖檔：與資料夾同一層的 `.<名稱>.migrate.lock`，只擋同一個 app 的啟動
+fn migration_lock_path(dir: &Path) -> Option<PathBuf> {
+    let parent = dir.parent().filter(|p| !p.as_os_str().is_empty())?;
+    let mut name = OsString::from(".");
+    name.push(dir.file_name()?);
+    name.push(".migrate.lock");
+    Some(parent.join(name))
+}
+
+/// 鎖住 `dir` 的遷移直到 drop；鎖檔留著不刪（刪掉會讓排隊中的行程鎖到不同的檔案）
+#[cfg(unix)]
+fn lock_migration(dir: &Path) -> Result<Option<std::fs::File>> {
+    use std::os::fd::AsRawFd;
+    let Some(path) = migration_lock_path(dir) else {
+        return Ok(None);
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let f = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .with_context(|| format!("open {}", path.display()))?;
+    // SAFETY: f 是開啟中的 fd
+    if unsafe { libc::flock(f.as_raw_fd(), libc::LOCK_EX) } != 0 {
+        return Err(std::io::Error::last_os_error())
+            .with_context(|| format!("lock {}", path.display()));
+    }
+    Ok(Some(f))
+}
+
+#[cfg(not(unix))]
+fn lock_migration(_dir: &Path) -> Result<Option<std::fs::File>> {
+    Ok(None)
+}
+
+/// 新資料夾不存在時，把 old_names 中第一個存在的舊資料夾改名過來。
+/// 同一層目錄內的 rename 是原子的：不會出現搬一半的狀態。
+/// 有多個舊資料夾時只搬第一個（old_names 由新到舊排列），其餘原封不動並記 log。
+fn migrate_legacy(mani: &Manifest, dir: &DataDir) -> Result<()> {
+    let existing: Vec<_> = dir
+        .legacy_candidates(mani)
+        .into_iter()
+        .filter(|p| p.is_dir() && *p != dir.path)
+        .collect();
+    let Some((src, rest)) = existing.split_first() else {
+        return Ok(());
+    };
+
+    if dir.path.exists() {
+        for p in &existing {
+            tracing::debug!(
+                "legacy data folder {} left untouched: {} already exists",
+                p.display(),
+                dir.path.display()
+            );
+        }
+        return Ok(());
+    }
+    for p in rest {
+        tracing::warn!(
+            "several legacy data folders found; migrating {} and leaving {} untouched",
+            src.display(),
+            p.display()
+        );
+    }
+
+    match std::fs::rename(src, &dir.path) {
+        Ok(()) => {
+            tracing::info!(
+                "migrated data folder {} -> {}",
+                src.display(),
+                dir.path.display()
+            );
+            Ok(())
+        }
+        // 另一個同時啟動的行程先搬好了（沒有 flock 的平台）
+        Err(_) if dir.path.is_dir() && !src.exists() => Ok(()),
+        Err(e) => bail!(
+            "cannot migrate data folder {} -> {}: {e}",
+            src.display(),
+            dir.path.display()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Barrier, mpsc};
+    use std::time::Duration;
+
+    /// 資料夾指定在 `root/App`（manifest 的 data_dir）
+    fn manifest(root: &Path, old_names: &[&str]) -> Manifest {
+        Manifest::from_slice(
+            serde_json::json!({
+                "manifest_version": chefer_manifest::MANIFEST_VERSION,
+                "app_name": "App",
+                "spec_version": "0.1",
+                "generated_at_utc": "2026-01-01T00:00:00Z",
+                "data_dir": root.join("App"),
+                "old_names": old_names,
+                "services": [{ "name": "db", "rootfs_rel": "services/db/rootfs" }],
+            })
+            .to_string()
+            .as_bytes(),
+        )
+        .unwrap()
+    }
+
+    fn persist() -> Vec<PersistEntry> {
+        vec![PersistEntry {
+            service: "db".into(),
+            container_path: "/var/lib/db".into(),
+            host_rel: "data/db".into(),
+        }]
+    }
+
+    /// 舊資料夾內放一個標記檔，內容為資料夾名稱
+    fn legacy(root: &Path, name: &str) {
+        fs::create_dir_all(root.join(name)).unwrap();
+        fs::write(root.join(name).join("marker"), name).unwrap();
+    }
+
+    fn marker(dir: &Path) -> String {
+        fs::read_to_string(dir.join("marker")).unwrap()
+    }
+
+    fn prepare_in(root: &Path, old_names: &[&str]) -> DataDir {
+        prepare(
+            &manifest(root, old_names),
+            &persist(),
+            None,
+            &root.join("App.run"),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn creates_a_fresh_data_dir() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = prepare_in(root.path(), &["Old"]);
+        assert_eq!(dir.path, root.path().join("App"));
+        assert_eq!(dir.source, DataDirSource::Manifest);
+        assert!(dir.service_dir("db").is_dir());
+        assert!(dir.path.join(LOGS_SUBDIR).is_dir());
+        assert!(!root.path().join("Old").exists());
+    }
+
+    #[test]
+    fn migrates_a_legacy_dir() {
+        let root = tempfile::tempdir().unwrap();
+        legacy(root.path(), "Old");
+        let dir = prepare_in(root.path(), &["Old"]);
+        assert_eq!(marker(&dir.path), "Old");
+        assert!(!root.path().join("Old").exists());
+        assert!(dir.service_dir("db").is_dir());
+    }
+
+    #[test]
+    fn migrates_only_the_newest_legacy_dir() {
+        let root = tempfile::tempdir().unwrap();
+        legacy(root.path(), "Beta");
+        legacy(root.path(), "Alpha");
+        // old_names 由新到舊；不存在的略過
+        let dir = prepare_in(root.path(), &["Missing", "Beta", "Alpha"]);
+        assert_eq!(marker(&dir.path), "Beta");
+        assert!(!root.path().join("Beta").exists());
+        assert_eq!(marker(&root.path().join("Alpha")), "Alpha");
+    }
+
+    #[test]
+    fn leaves_legacy_dirs_when_the_target_exists() {
+        let root = tempfile::tempdir().unwrap();
+        legacy(root.path(), "App");
+        legacy(root.path(), "Old");
+        let dir = prepare_in(root.path(), &["Old"]);
+        assert_eq!(marker(&dir.path), "App");
+        assert_eq!(marker(&root.path().join("Old")), "Old");
+    }
+
+    #[test]
+    fn command_line_dir_does_not_migrate() {
+        let root = tempfile::tempdir().unwrap();
+        legacy(root.path(), "Old");
+        let cli = root.path().join("App");
+        let dir = prepare(
+            &manifest(root.path(), &["Old"]),
+            &persist(),
+            Some(&cli),
+            &root.path().join("App.run"),
+        )
+        .unwrap();
+        assert_eq!(dir.source, DataDirSource::CommandLine);
+        assert!(!dir.path.join("marker").exists());
+        assert_eq!(marker(&root.path().join("Old")), "Old");
+    }
+
+    #[test]
+    fn concurrent_launches_migrate_once() {
+        let root = tempfile::tempdir().unwrap();
+        legacy(root.path(), "Old");
+        let barrier = Arc::new(Barrier::new(8));
+        let launches: Vec<_> = (0..8)
+            .map(|_| {
+                let (root, barrier) = (root.path().to_path_buf(), barrier.clone());
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    prepare(
+                        &manifest(&root, &["Old"]),
+                        &persist(),
+                        None,
+                        &root.join("App.run"),
+                    )
+                })
+            })
+            .collect();
+        for l in launches {
+            l.join().unwrap().unwrap();
+        }
+        assert_eq!(marker(&root.path().join("App")), "Old");
+        assert!(!root.path().join("Old").exists());
+    }
+
+    #[test]
+    fn racing_migrations_without_the_lock_succeed() {
+        // 沒有 flock 的平台：rename 輸給另一個行程時，目標已在、來源已不在，不算錯誤
+        let root = tempfile::tempdir().unwrap();
+        legacy(root.path(), "Old");
+        let mani = Arc::new(manifest(root.path(), &["Old"]));
+        let dir = DataDir::resolve(&mani, None, root.path()).unwrap();
+        let barrier = Arc::new(Barrier::new(8));
+        let launches: Vec<_> = (0..8)
+            .map(|_| {
+                let (mani, dir, barrier) = (mani.clone(), dir.clone(), barrier.clone());
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    migrate_legacy(&mani, &dir)
+                })
+            })
+            .collect();
+        for l in launches {
+            l.join().unwrap().unwrap();
+        }
+        assert_eq!(marker(&dir.path), "Old");
+        assert!(!root.path().join("Old").exists());
+    }
+
+    #[test]
+    fn migration_lock_is_per_app() {
+        let root = tempfile::tempdir().unwrap();
+        let a = root.path().join("AppA");
+        let b = root.path().join("AppB");
+        assert_eq!(
+            migration_lock_path(&a).unwrap(),
+            root.path().join(".AppA.migrate.lock")
+        );
+        let held = lock_migration(&a).unwrap();
+        // 另一個 app 不必等
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || tx.send(lock_migration(&b).unwrap().is_some()).unwrap());
+        assert!(rx.recv_timeout(Duration::from_secs(5)).unwrap());
+        // 同一個 app 要等
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || tx.send(lock_migration(&a).unwrap().is_some()).unwrap());
+        assert!(rx.recv_timeout(Duration::from_millis(300)).is_err());
+        drop(held);
+        assert!(rx.recv_timeout(Duration::from_secs(5)).unwrap());
+    }
+}
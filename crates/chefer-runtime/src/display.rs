@@ -0,0 +1,423 @@
+// src/display.rs
+//! interface_mode 為 gui / both 的 service 共用 host 的桌面：Wayland / X11 的 socket、音效（PipeWire、
+//! PulseAudio）與字型，並設定對應的環境變數。service 的 env 已設定的變數不覆寫。
+//!
+//! socket 一律掛到容器內的 [`RUNTIME_DIR`]（在容器的 /tmp tmpfs 上，唯讀的 rootfs 也能建掛載點）。
+//! X11 的 Xauthority 會改寫成不限 hostname 的副本（容器有自己的 hostname，原本的 cookie 對不上）。
+//! 一個顯示都沒有時在啟動前就報錯；沒有桌面的環境可用 `Xvfb :99 &` 再以 `DISPLAY=:99` 執行。
+use anyhow::{Context, Result, bail};
+use std::{
+    env,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+use tempfile::TempDir;
+
+use crate::ns::{Bind, Container};
+
+/// 容器內的 XDG_RUNTIME_DIR
+const RUNTIME_DIR: &str = "/tmp/runtime-root";
+/// Xauthority 的 FamilyWild：不比對位址，任何 hostname 都適用
+const FAMILY_WILD: u16 = 0xffff;
+
+/// 讀取環境變數；測試時換成固定的值
+type Env<'a> = &'a dyn Fn(&str) -> Option<OsString>;
+
+pub struct Display {
+    binds: Vec<Bind>,
+    env: Vec<(String, String)>,
+    /// 共用了什麼，給 log 看
+    shared: Vec<String>,
+    /// 改寫過的 Xauthority；容器都結束後才可刪除
+    _xauth: Option<TempDir>,
+}
+
+impl Display {
+    /// 依 runtime 的環境找出可共用的東西；Wayland 與 X11 都不能用時回傳錯誤
+    pub fn detect() -> Result<Display> {
+        Self::detect_with(&|key| env::var_os(key))
+    }
+
+    fn detect_with(env: Env) -> Result<Display> {
+        let mut d = Display {
+            binds: Vec::new(),
+            env: Vec::new(),
+            shared: Vec::new(),
+            _xauth: None,
+        };
+        let runtime_dir = env("XDG_RUNTIME_DIR").map(PathBuf::from);
+        let home = env("HOME").map(PathBuf::from);
+
+        let wayland = d.wayland(env, runtime_dir.as_deref())?;
+        let x11 = d.x11(env, home.as_deref())?;
+        if !wayland && !x11 {
+            bail!(
+                "no display to share: neither WAYLAND_DISPLAY nor DISPLAY points to a running display server \
+                 (run it from a desktop session, or start one with `Xvfb :99 &` and set DISPLAY=:99)"
+            );
+        }
+        d.audio(env, runtime_dir.as_deref(), home.as_deref())?;
+        d.fonts(home.as_deref())?;
+        if d.binds.iter().any(|b| b.target.starts_with(RUNTIME_DIR)) {
+            d.set("XDG_RUNTIME_DIR", RUNTIME_DIR);
+        }
+        Ok(d)
+    }
+
+    /// 加到 service 的容器
+    pub fn apply(&self, c: &mut Container) {
+        c.binds.extend(self.binds.iter().cloned());
+        for (k, v) in &self.env {
+            if !c.env.iter().any(|(ck, _)| ck == k) {
+                c.env.push((k.clone(), v.clone()));
+            }
+        }
+        // image 沒有 fontconfig 設定時借用 host 的，host 字型才找得到
+        if !c.rootfs.join("etc/fonts/fonts.conf").exists()
+            && let Ok(b) = Bind::new(Path::new("/etc/fonts"), "/etc/fonts")
+        {
+            c.binds.push(b.readonly().optional());
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        self.shared.join(", ")
+    }
+
+    fn set(&mut self, key: &str, value: &str) {
+        self.env.push((key.into(), value.into()));
+    }
+
+    fn bind(&mut self, host: &Path, target: &str) -> Result<()> {
+        self.binds.push(Bind::new(host, target)?);
+        Ok(())
+    }
+
+    fn wayland(&mut self, env: Env, runtime_dir: Option<&Path>) -> Result<bool> {
+        let Some(name) = env("WAYLAND_DISPLAY") else {
+            return Ok(false);
+        };
+        // 也可以是絕對路徑
+        let socket = match runtime_dir {
+            _ if Path::new(&name).is_absolute() => PathBuf::from(&name),
+            Some(dir) => dir.join(&name),
+            None => {
+                tracing::warn!(
+                    "WAYLAND_DISPLAY is set but XDG_RUNTIME_DIR is not; skipping Wayland"
+                );
+                return Ok(false);
+            }
+        };
+        if !socket.exists() {
+            tracing::warn!(
+                "WAYLAND_DISPLAY={} but {} does not exist; skipping Wayland",
+                name.to_string_lossy(),
+                socket.display()
+            );
+            return Ok(false);
+        }
+        self.bind(&socket, &format!("{RUNTIME_DIR}/wayland-0"))?;
+        self.set("WAYLAND_DISPLAY", "wayland-0");
+        self.shared
+            .push(format!("Wayland ({})", name.to_string_lossy()));
+        Ok(true)
+    }
+
+    fn x11(&mut self, env: Env, home: Option<&Path>) -> Result<bool> {
+        let Some(name) = env("DISPLAY").and_then(|v| v.into_string().ok()) else {
+            return Ok(false);
+        };
+        let Some((host, number)) = parse_display(&name) else {
+            tracing::warn!("cannot parse DISPLAY={name}; skipping X11");
+            return Ok(false);
+        };
+        if host.is_empty() || host == "unix" {
+            let socket = PathBuf::from(format!("/tmp/.X11-unix/X{number}"));
+            if !socket.exists() {
+                tracing::warn!(
+                    "DISPLAY={name} but {} does not exist; skipping X11",
+                    socket.display()
+                );
+                return Ok(false);
+            }
+            self.bind(&socket, &socket.to_string_lossy())?;
+            self.set("DISPLAY", &format!(":{}", name.rsplit_once(':').unwrap().1));
+            self.shared.push(format!("X11 ({name})"));
+        } else {
+            // TCP 的顯示：容器需與 host 共用網路才連得到
+            self.set("DISPLAY", &name);
+            self.shared.push(format!("X11 over TCP ({name})"));
+        }
+
+        let xauthority = env("XAUTHORITY")
+            .map(PathBuf::from)
+            .or_else(|| home.map(|h| h.join(".Xauthority")))
+            .filter(|p| p.is_file());
+        if let Some(src) = xauthority {
+            let data = fs_err::read(&src)?;
+            let entries = rewrite_xauthority(&data, number)
+                .with_context(|| format!("read {}", src.display()))?;
+            if entries.is_empty() {
+                tracing::debug!("{} has no cookie for name {number}", src.display());
+            } else {
+                let dir = tempfile::Builder::new().prefix("chefer-xauth-").tempdir()?;
+                let copy = dir.path().join("Xauthority");
+                fs_err::write(&copy, entries)?;
+                self.bind(&copy, &format!("{RUNTIME_DIR}/Xauthority"))?;
+                self.set("XAUTHORITY", &format!("{RUNTIME_DIR}/Xauthority"));
+                self._xauth = Some(dir);
+            }
+        }
+        Ok(true)
+    }
+
+    fn audio(&mut self, env: Env, runtime_dir: Option<&Path>, home: Option<&Path>) -> Result<()> {
+        if let Some(socket) = runtime_dir
+            .map(|d| d.join("pipewire-0"))
+            .filter(|p| p.exists())
+        {
+            self.bind(&socket, &format!("{RUNTIME_DIR}/pipewire-0"))?;
+            self.shared.push("PipeWire".into());
+        }
+
+        // PULSE_SERVER 只處理本機 socket；其他形式（tcp:host）原樣傳入
+        let pulse = match env("PULSE_SERVER").and_then(|v| v.into_string().ok()) {
+            Some(server) => match server.strip_prefix("unix:") {
+                Some(path) => Some(PathBuf::from(path)),
+                None => {
+                    self.set("PULSE_SERVER", &server);
+                    self.shared.push(format!("PulseAudio ({server})"));
+                    None
+                }
+            },
+            None => runtime_dir.map(|d| d.join("pulse/native")),
+        };
+        if let Some(socket) = pulse.filter(|p| p.exists()) {
+            let target = format!("{RUNTIME_DIR}/pulse/native");
+            self.bind(&socket, &target)?;
+            self.set("PULSE_SERVER", &format!("unix:{target}"));
+            self.shared.push("PulseAudio".into());
+
+            let cookie = env("PULSE_COOKIE")
+                .map(PathBuf::from)
+                .or_else(|| home.map(|h| h.join(".config/pulse/cookie")))
+                .filter(|p| p.is_file());
+            if let Some(cookie) = cookie {
+                let target = format!("{RUNTIME_DIR}/pulse/cookie");
+                self.bind(&cookie, &target)?;
+                self.set("PULSE_COOKIE", &target);
+            }
+        }
+        Ok(())
+    }
+
+    /// host 的字型掛在 /usr/share/fonts 底下，fontconfig 的預設設定會一併掃到
+    fn fonts(&mut self, home: Option<&Path>) -> Result<()> {
+        let mut dirs = vec![
+            (
+                PathBuf::from("/usr/share/fonts"),
+                "/usr/share/fonts/chefer-host",
+            ),
+            (
+                PathBuf::from("/usr/local/share/fonts"),
+                "/usr/share/fonts/chefer-host-local",
+            ),
+        ];
+        if let Some(home) = home {
+            dirs.push((
+                home.join(".local/share/fonts"),
+                "/usr/share/fonts/chefer-user",
+            ));
+            dirs.push((home.join(".fonts"), "/usr/share/fonts/chefer-user-legacy"));
+        }
+        let mut any = false;
+        for (host, target) in dirs {
+            if host.is_dir() {
+                self.binds
+                    .push(Bind::new(&host, target)?.readonly().optional());
+                any = true;
+            }
+        }
+        if any {
+            self.shared.push("fonts".into());
+        }
+        Ok(())
+    }
+}
+
+/// `[host]:number[.screen]` → (host, number)
+fn parse_display(display: &str) -> Option<(&str, &str)> {
+    let (host, rest) = display.rsplit_once(':')?;
+    let number = rest.split('.').next()?;
+    (!number.is_empty() && number.bytes().all(|b| b.is_ascii_digit())).then_some((host, number))
+}
+
+/// 留下 `number` 這個顯示的 Xauthority 項目並改成 FamilyWild。
+/// 每筆為 family（u16 big-endian）接著 address、number、name、data 四個「u16 長度 + 內容」
+fn rewrite_xauthority(data: &[u8], number: &str) -> Result<Vec<u8>> {
+    fn take<'d>(data: &mut &'d [u8], n: usize) -> Result<&'d [u8]> {
+        if data.len() < n {
+            bail!("truncated Xauthority entry");
+        }
+        let (head, rest) = data.split_at(n);
+        *data = rest;
+        Ok(head)
+    }
+    fn field<'d>(data: &mut &'d [u8]) -> Result<&'d [u8]> {
+        let len = take(data, 2)?;
+        take(data, u16::from_be_bytes([len[0], len[1]]) as usize)
+    }
+
+    let mut rest = data;
+    let mut out = Vec::new();
+    while !rest.is_empty() {
+        take(&mut rest, 2)?;
+        let _address = field(&mut rest)?;
+        let num = field(&mut rest)?;
+        let name = field(&mut rest)?;
+        let cookie = field(&mut rest)?;
+        if num != number.as_bytes() {
+            continue;
+        }
+        out.extend(FAMILY_WILD.to_be_bytes());
+        for f in [&[][..], num, name, cookie] {
+            out.extend((f.len() as u16).to_be_bytes());
+            out.extend(f);
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        collections::HashMap,
+        os::unix::net::UnixStream,
+        process::{Child, Command, Stdio},
+        time::{Duration, Instant},
+    };
+
+    fn env_of(vars: &[(&str, &Path)]) -> impl Fn(&str) -> Option<OsString> {
+        let vars: HashMap<String, OsString> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.as_os_str().to_owned()))
+            .collect();
+        move |k| vars.get(k).cloned()
+    }
+
+    /// family、address、number、name、data
+    fn xauth_entry(family: u16, address: &str, number: &str, cookie: &[u8]) -> Vec<u8> {
+        let mut out = family.to_be_bytes().to_vec();
+        for f in [
+            address.as_bytes(),
+            number.as_bytes(),
+            b"MIT-MAGIC-COOKIE-1",
+            cookie,
+        ] {
+            out.extend((f.len() as u16).to_be_bytes());
+            out.extend(f);
+        }
+        out
+    }
+
+    #[test]
+    fn display_names() {
+        assert_eq!(parse_display(":0"), Some(("", "0")));
+        assert_eq!(parse_display("unix:12.1"), Some(("unix", "12")));
+        assert_eq!(parse_display("host.example:3"), Some(("host.example", "3")));
+        assert_eq!(parse_display(":x"), None);
+        assert_eq!(parse_display("0"), None);
+    }
+
+    #[test]
+    fn xauthority_keeps_only_the_display_as_wild() {
+        let mut data = xauth_entry(256, "myhost", "0", b"cookie0");
+        data.extend(xauth_entry(256, "myhost", "1", b"cookie1"));
+        let out = rewrite_xauthority(&data, "1").unwrap();
+        assert_eq!(out, xauth_entry(FAMILY_WILD, "", "1", b"cookie1"));
+        assert!(rewrite_xauthority(&data[..data.len() - 1], "1").is_err());
+    }
+
+    #[test]
+    fn no_display_is_an_error() {
+        let e = Display::detect_with(&env_of(&[])).err().unwrap();
+        assert!(e.to_string().contains("no display to share"), "{e}");
+        // 指向不存在的 socket 也一樣
+        let e = Display::detect_with(&env_of(&[("DISPLAY", Path::new(":65000"))]))
+            .err()
+            .unwrap();
+        assert!(e.to_string().contains("Xvfb"), "{e}");
+    }
+
+    /// 啟動 Xvfb；沒有安裝時回傳 None（略過測試）
+    fn xvfb() -> Option<(Child, u32)> {
+        let number = (100..600).find(|n| {
+            !Path::new(&format!("/tmp/.X11-unix/X{n}")).exists()
+                && !Path::new(&format!("/tmp/.X{n}-lock")).exists()
+        })?;
+        let child = match Command::new("Xvfb")
+            .args([format!(":{number}").as_str(), "-nolisten", "tcp"])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+            Err(e) => panic!("start Xvfb: {e}"),
+        };
+        Some((child, number))
+    }
+
+    #[test]
+    fn shares_an_xvfb_display() {
+        let Some((mut xvfb, number)) = xvfb() else {
+            eprintln!("Xvfb is not installed; skipping");
+            return;
+        };
+        let socket = PathBuf::from(format!("/tmp/.X11-unix/X{number}"));
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while UnixStream::connect(&socket).is_err() {
+            assert!(Instant::now() < deadline, "Xvfb :{number} did not start");
+            std::thread::sleep(Duration::from_millis(50));
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let xauthority = dir.path().join("Xauthority");
+        let mut data = xauth_entry(256, "otherhost", &number.to_string(), b"cookie");
+        data.extend(xauth_entry(256, "otherhost", "0", b"not this one"));
+        fs_err::write(&xauthority, data).unwrap();
+        let display = format!(":{number}");
+        let d = Display::detect_with(&env_of(&[
+            ("DISPLAY", Path::new(&display)),
+            ("XAUTHORITY", &xauthority),
+            ("HOME", dir.path()),
+        ]));
+        // SIGTERM：讓 Xvfb 自己清掉 socket 與 lock 檔
+        unsafe { libc::kill(xvfb.id() as i32, libc::SIGTERM) };
+        let _ = xvfb.wait();
+        let d = d.unwrap();
+
+        assert!(d.describe().starts_with(&format!("X11 ({display})")));
+        let env: HashMap<&str, &str> = d
+            .env
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        assert_eq!(env["DISPLAY"], display);
+        assert_eq!(env["XAUTHORITY"], format!("{RUNTIME_DIR}/Xauthority"));
+        assert_eq!(env["XDG_RUNTIME_DIR"], RUNTIME_DIR);
+        assert!(d.binds.iter().any(|b| b.target == socket.to_string_lossy()));
+
+        // 容器內用的是只含這個顯示、不限 hostname 的副本
+        let copy = d
+            .binds
+            .iter()
+            .find(|b| b.target.ends_with("/Xauthority"))
+            .unwrap();
+        assert_eq!(
+            fs_err::read(&copy.host).unwrap(),
+            xauth_entry(FAMILY_WILD, "", &number.to_string(), b"cookie")
+        );
+    }
+}
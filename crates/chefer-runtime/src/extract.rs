@@ -1,38 +1,16 @@
 // src/extract.rs
-use anyhow::{Context, Result, bail};
+use anyhow::Result;
 use fs_err as fs;
-use sha2::{Digest, Sha256};
-use std::{
-    fs::File,
-    io::{Read, Seek, SeekFrom},
-    path::{Path, PathBuf},
-};
-use tar::Archive;
+use std::path::{Path, PathBuf};
 use tempfile::TempDir;
 
-use crate::footer::Footer;
-
 pub struct Extracted {
-    pub tempdir: TempDir,
+    // drop 時刪除暫存目錄；keep_tmp 時為 None
+    _tempdir: Option<TempDir>,
     pub bundle_dir: PathBuf,
 }
 
-pub fn extract_bundle(exe: &Path, ft: &Footer, keep_dir: Option<&Path>) -> Result<Extracted> {
-    // 1) 讀出 bundle bytes
-    let mut f = File::open(exe)?;
-    f.seek(SeekFrom::Start(ft.offset))?;
-    let mut bundle = vec![0u8; ft.length as usize];
-    f.read_exact(&mut bundle)?;
-
-    // 2) 驗證 sha256
-    let mut hasher = Sha256::new();
-    hasher.update(&bundle);
-    let got = hasher.finalize();
-    if got.as_slice() != &ft.sha256 {
-        bail!("bundle sha256 mismatch");
-    }
-
-    // 3) 解壓到 temp
+pub fn extract_bundle(exe: &Path, keep_dir: Option<&Path>, keep_tmp: bool) -> Result<Extracted> {
     let tempdir = match keep_dir {
         Some(dir) => {
             fs::create_dir_all(dir)?;
@@ -41,20 +19,23 @@ pub fn extract_bundle(exe: &Path, ft: &Footer, keep_dir: Option<&Path>) -> Resul
         None => tempfile::tempdir()?,
     };
     let out = tempdir.path().join("bundle");
-    fs::create_dir_all(&out)?;
+    unpack_bundle(exe, &out)?;
 
-    // 支援：tar 或 zstd(tar)
-    if ft.compressed_is_zstd() {
-        let mut d = zstd::stream::read::Decoder::new(&bundle[..]).context("zstd decode")?;
-        let mut tar_buf = Vec::new();
-        d.read_to_end(&mut tar_buf)?;
-        Archive::new(&tar_buf[..]).unpack(&out)?;
+    let tempdir = if keep_tmp {
+        let kept = tempdir.keep();
+        tracing::info!("keeping extracted bundle at {}", kept.display());
+        None
     } else {
-        Archive::new(&bundle[..]).unpack(&out)?;
-    }
-
+        Some(tempdir)
+    };
     Ok(Extracted {
-        tempdir,
+        _tempdir: tempdir,
         bundle_dir: out,
     })
 }
+
+/// 驗證 payload 後解到 out（不存在會自動建立）
+pub fn unpack_bundle(exe: &Path, out: &Path) -> Result<()> {
+    chefer_assembler::unpack_payload(exe, out)?;
+    Ok(())
+}
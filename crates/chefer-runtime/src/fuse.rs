@@ -0,0 +1,585 @@
+// src/fuse.rs
+//! 唯讀 FUSE 檔案系統：直接從 exe 內的未壓縮 tar 區段提供 bundle 檔案樹，
+//! 不需解壓、不占額外磁碟（類似 AppImage 掛載自己）。
+//! 只實作唯讀所需的 opcode，其餘回 ENOSYS。
+//! payload 的 sha256 在背景驗證，不擋啟動；不符時之後的讀取一律回 EIO 並要求 app 停止。
+use anyhow::{Context, Result, bail};
+use std::{
+    ffi::{CString, OsStr},
+    fs::File,
+    io::{Read, Write},
+    os::{
+        fd::{AsRawFd, FromRawFd},
+        unix::{ffi::OsStrExt, fs::FileExt},
+    },
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+use tempfile::TempDir;
+
+use crate::tarfs::{NodeKind, TarIndex};
+
+const FUSE_KERNEL_VERSION: u32 = 7;
+const FUSE_KERNEL_MINOR_VERSION: u32 = 31;
+const MAX_WRITE: u32 = 128 * 1024;
+const BUF_SIZE: usize = MAX_WRITE as usize + 4096;
+/// bundle 內容不會變，屬性與名稱快取可以放很久
+const TTL_SECS: u64 = 3600;
+
+const FUSE_ASYNC_READ: u32 = 1 << 0;
+const FUSE_BIG_WRITES: u32 = 1 << 5;
+const FUSE_PARALLEL_DIROPS: u32 = 1 << 18;
+const FUSE_MAX_PAGES: u32 = 1 << 22;
+const FUSE_CACHE_SYMLINKS: u32 = 1 << 23;
+const FOPEN_KEEP_CACHE: u32 = 1 << 1;
+
+const OP_LOOKUP: u32 = 1;
+const OP_FORGET: u32 = 2;
+const OP_GETATTR: u32 = 3;
+const OP_READLINK: u32 = 5;
+const OP_OPEN: u32 = 14;
+const OP_READ: u32 = 15;
+const OP_STATFS: u32 = 17;
+const OP_RELEASE: u32 = 18;
+const OP_FLUSH: u32 = 25;
+const OP_INIT: u32 = 26;
+const OP_OPENDIR: u32 = 27;
+const OP_READDIR: u32 = 28;
+const OP_RELEASEDIR: u32 = 29;
+const OP_INTERRUPT: u32 = 36;
+const OP_DESTROY: u32 = 38;
+const OP_BATCH_FORGET: u32 = 42;
+
+/// 卸載後等 server thread 結束的上限（lazy unmount 時仍有人開著檔案就不會立刻結束）
+const SERVER_JOIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+const IN_HEADER_LEN: usize = 40;
+const OUT_HEADER_LEN: usize = 16;
+
+/// 已掛載的 bundle；drop 時自動卸載
+pub struct Mounted {
+    pub bundle_dir: PathBuf,
+    via_fusermount: Option<&'static str>,
+    server: Option<JoinHandle<Result<()>>>,
+    /// 背景驗證發現 payload 與 footer 的 sha256 不符
+    tampered: Arc<AtomicBool>,
+    // 必須最後 drop：卸載後才能刪除掛載點
+    _mountpoint: TempDir,
+}
+
+impl Drop for Mounted {
+    fn drop(&mut self) {
+        let res = match self.via_fusermount {
+            Some(bin) => Command::new(bin)
+                .arg("-u")
+                .arg("-z")
+                .arg(&self.bundle_dir)
+                .status()
+                .map(|s| s.success()),
+            None => {
+                let c = CString::new(self.bundle_dir.as_os_str().as_bytes()).unwrap_or_default();
+                // SAFETY: c 是合法的 NUL 結尾字串
+                Ok(unsafe { libc::umount2(c.as_ptr(), libc::MNT_DETACH) } == 0)
+            }
+        };
+        if !matches!(res, Ok(true)) {
+            tracing::warn!("failed to unmount {}", self.bundle_dir.display());
+        }
+        if let Some(server) = self.server.take() {
+            join_server(server);
+        }
+    }
+}
+
+impl Mounted {
+    /// 背景驗證失敗時回傳錯誤（還沒驗完視為通過）
+    pub fn verified(&self) -> Result<()> {
+        if self.tampered.load(Ordering::Relaxed) {
+            bail!("payload sha256 mismatch");
+        }
+        Ok(())
+    }
+}
+
+/// 收回 server thread，回報它的錯誤或 panic
+fn join_server(server: JoinHandle<Result<()>>) {
+    let deadline = Instant::now() + SERVER_JOIN_TIMEOUT;
+    while !server.is_finished() {
+        if Instant::now() >= deadline {
+            tracing::warn!("fuse server did not stop after unmount");
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    match server.join() {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => tracing::warn!("fuse server stopped: {e:#}"),
+        Err(_) => tracing::warn!("fuse server panicked"),
+    }
+}
+
+/// 把 exe 內 [offset, offset+length) 的未壓縮 tar 掛成唯讀檔案系統；
+/// 背景驗證 payload 不符時設定 `stop`
+pub fn mount_bundle(
+    exe: &Path,
+    offset: u64,
+    length: u64,
+    parent: Option<&Path>,
+    stop: Arc<AtomicBool>,
+) -> Result<Mounted> {
+    let index = TarIndex::build(exe, offset, length).context("index bundle tar")?;
+    let data = File::open(exe).with_context(|| format!("open exe {:?}", exe))?;
+
+    let mountpoint = match parent {
+        Some(dir) => {
+            fs_err::create_dir_all(dir)?;
+            TempDir::new_in(dir)?
+        }
+        None => tempfile::tempdir()?,
+    };
+    let target = mountpoint.path().to_path_buf();
+
+    // root 直接 mount(2)；一般使用者透過 setuid 的 fusermount
+    // SAFETY: geteuid 沒有前置條件
+    let (dev, via_fusermount) = if unsafe { libc::geteuid() } == 0 {
+        (mount_direct(&target)?, None)
+    } else {
+        let (dev, bin) = mount_fusermount(&target)?;
+        (dev, Some(bin))
+    };
+
+    let tampered = Arc::new(AtomicBool::new(false));
+    let server = {
+        let tampered = tampered.clone();
+        std::thread::Builder::new()
+            .name("chefer-fuse".into())
+            .spawn(move || {
+                TarFs {
+                    index,
+                    data,
+                    tampered,
+                }
+                .serve(dev)
+            })?
+    };
+    spawn_verify(exe.to_path_buf(), tampered.clone(), stop)?;
+
+    Ok(Mounted {
+        bundle_dir: target,
+        via_fusermount,
+        server: Some(server),
+        tampered,
+        _mountpoint: mountpoint,
+    })
+}
+
+/// 與解壓路徑相同的整段 sha256 檢查，但在背景做，不延後啟動
+fn spawn_verify(exe: PathBuf, tampered: Arc<AtomicBool>, stop: Arc<AtomicBool>) -> Result<()> {
+    std::thread::Builder::new()
+        .name("chefer-verify".into())
+        .spawn(move || {
+            if let Err(e) = chefer_assembler::verify_payload(&exe) {
+                tracing::error!("bundle verification failed ({e:#}); stopping the app");
+                tampered.store(true, Ordering::Relaxed);
+                stop.store(true, Ordering::Relaxed);
+            }
+        })?;
+    Ok(())
+}
+
+fn mount_direct(target: &Path) -> Result<File> {
+    let dev = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/fuse")
+        .context("open /dev/fuse")?;
+    // SAFETY: getuid/getgid 沒有前置條件
+    let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+    let opts = format!(
+        "fd={},rootmode=40000,user_id={uid},group_id={gid}",
+        dev.as_raw_fd()
+    );
+    let src = CString::new("chefer")?;
+    let fstype = CString::new("fuse.chefer")?;
+    let tgt = CString::new(target.as_os_str().as_bytes())?;
+    let data = CString::new(opts)?;
+    // SAFETY: 所有指標都是合法的 NUL 結尾字串
+    let rc = unsafe {
+        libc::mount(
+            src.as_ptr(),
+            tgt.as_ptr(),
+            fstype.as_ptr(),
+            libc::MS_NOSUID | libc::MS_NODEV | libc::MS_RDONLY,
+            data.as_ptr().cast(),
+        )
+    };
+    if rc != 0 {
+        bail!("mount fuse: {}", std::io::Error::last_os_error());
+    }
+    Ok(dev)
+}
+
+/// 與 libfuse 相同的協定：透過 _FUSE_COMMFD socket 收回 /dev/fuse 的 fd
+fn mount_fusermount(target: &Path) -> Result<(File, &'static str)> {
+    let mut last_err = None;
+    for bin in ["fusermount3", "fusermount"] {
+        match fusermount_once(bin, target) {
+            Ok(dev) => return Ok((dev, bin)),
+            Err(e) => last_err = Some(e),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| anyhow::anyhow!("fusermount not found")))
+}
+
+fn fusermount_once(bin: &'static str, target: &Path) -> Result<File> {
+    let mut fds = [0i32; 2];
+    // SAFETY: fds 是長度 2 的陣列
+    if unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, fds.as_mut_ptr()) } != 0 {
+        bail!("socketpair: {}", std::io::Error::last_os_error());
+    }
+    // SAFETY: 兩個 fd 剛由 socketpair 建立，所有權轉交給 File
+    let (ours, theirs) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+    // SAFETY: ours 是合法 fd
+    unsafe { libc::fcntl(ours.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) };
+
+    let status = Command::new(bin)
+        .arg("-o")
+        .arg("ro,nosuid,nodev,fsname=chefer,subtype=chefer")
+        .arg("--")
+        .arg(target)
+        .env("_FUSE_COMMFD", theirs.as_raw_fd().to_string())
+        .status()
+        .with_context(|| format!("spawn {bin}"))?;
+    drop(theirs);
+    if !status.success() {
+        bail!("{bin} failed: {status}");
+    }
+    recv_fd(&ours).with_context(|| format!("receive /dev/fuse fd from {bin}"))
+}
+
+fn recv_fd(sock: &File) -> Result<File> {
+    let mut byte = [0u8; 1];
+    let mut iov = libc::iovec {
+        iov_base: byte.as_mut_ptr().cast(),
+        iov_len: 1,
+    };
+    // SAFETY: CMSG_SPACE 只做算術
+    let space = unsafe { libc::CMSG_SPACE(std::mem::size_of::<i32>() as u32) } as usize;
+    let mut cbuf = vec![0u8; space];
+    // SAFETY: msghdr 全零是合法初值
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = cbuf.as_mut_ptr().cast();
+    msg.msg_controllen = space as _;
+    // SAFETY: msg 指向的緩衝在呼叫期間皆有效
+    let n = unsafe { libc::recvmsg(sock.as_raw_fd(), &mut msg, 0) };
+    if n <= 0 {
+        bail!("recvmsg: {}", std::io::Error::last_os_error());
+    }
+    // SAFETY: msg 由 recvmsg 填好，CMSG_* 只在 cbuf 範圍內走訪
+    unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        if cmsg.is_null() || (*cmsg).cmsg_type != libc::SCM_RIGHTS {
+            bail!("no fd received");
+        }
+        let fd = std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const i32);
+        Ok(File::from_raw_fd(fd))
+    }
+}
+
+struct TarFs {
+    index: TarIndex,
+    data: File,
+    tampered: Arc<AtomicBool>,
+}
+
+impl TarFs {
+    fn serve(&mut self, mut dev: File) -> Result<()> {
+        let mut buf = vec![0u8; BUF_SIZE];
+        loop {
+            let n = match dev.read(&mut buf) {
+                Ok(n) => n,
+                Err(e) => match e.raw_os_error() {
+                    // 請求被中斷或暫時無資料：重試
+                    Some(libc::ENOENT) | Some(libc::EINTR) | Some(libc::EAGAIN) => continue,
+                    // 已卸載
+                    Some(libc::ENODEV) => return Ok(()),
+                    _ => return Err(e.into()),
+                },
+            };
+            if n < IN_HEADER_LEN {
+                bail!("short fuse request ({n} bytes)");
+            }
+            let hdr = &buf[..IN_HEADER_LEN];
+            let opcode = u32_at(hdr, 4);
+            let unique = u64_at(hdr, 8);
+            let nodeid = u64_at(hdr, 16);
+            let body = &buf[IN_HEADER_LEN..n];
+
+            let reply = match opcode {
+                // 不需要回覆
+                OP_FORGET | OP_BATCH_FORGET | OP_INTERRUPT => continue,
+                OP_DESTROY => {
+                    write_reply(&mut dev, unique, Ok(Vec::new()))?;
+                    return Ok(());
+                }
+                OP_INIT => self.init(body),
+                OP_LOOKUP => self.lookup(nodeid, body),
+                OP_GETATTR => self.getattr(nodeid),
+                // 驗證失敗後不再交出內容
+                OP_READ | OP_READLINK if self.tampered.load(Ordering::Relaxed) => Err(libc::EIO),
+                OP_READLINK => self.readlink(nodeid),
+                OP_OPEN => self.open(nodeid, libc::S_IFREG),
+                OP_OPENDIR => self.open(nodeid, libc::S_IFDIR),
+                OP_READ => self.read(nodeid, body),
+                OP_READDIR => self.readdir(nodeid, body),
+                OP_RELEASE | OP_RELEASEDIR | OP_FLUSH => Ok(Vec::new()),
+                OP_STATFS => Ok(self.statfs()),
+                _ => Err(libc::ENOSYS),
+            };
+            write_reply(&mut dev, unique, reply)?;
+        }
+    }
+
+    fn init(&self, body: &[u8]) -> Reply {
+        if body.len() < 16 {
+            return Err(libc::EINVAL);
+        }
+        let major = u32_at(body, 0);
+        let max_readahead = u32_at(body, 8);
+        let kflags = u32_at(body, 12);
+        if major < FUSE_KERNEL_VERSION {
+            return Err(libc::EPROTO);
+        }
+        let mut out = Vec::with_capacity(64);
+        put_u32(&mut out, FUSE_KERNEL_VERSION);
+        put_u32(&mut out, FUSE_KERNEL_MINOR_VERSION);
+        let want = FUSE_ASYNC_READ
+            | FUSE_BIG_WRITES
+            | FUSE_PARALLEL_DIROPS
+            | FUSE_MAX_PAGES
+            | FUSE_CACHE_SYMLINKS;
+        put_u32(&mut out, max_readahead);
+        put_u32(&mut out, kflags & want);
+        put_u16(&mut out, 16); // max_background
+        put_u16(&mut out, 12); // congestion_threshold
+        put_u32(&mut out, MAX_WRITE);
+        put_u32(&mut out, 1); // time_gran
+        put_u16(&mut out, (MAX_WRITE / 4096) as u16); // max_pages
+        put_u16(&mut out, 0); // map_alignment
+        put_u32(&mut out, 0); // flags2
+        out.resize(64, 0);
+        Ok(out)
+    }
+
+    fn lookup(&self, parent: u64, body: &[u8]) -> Reply {
+        let name = cstr(body);
+        let node = self
+            .index
+            .lookup(parent, OsStr::from_bytes(name))
+            .ok_or(libc::ENOENT)?;
+        let mut out = Vec::with_capacity(128);
+        put_u64(&mut out, node.ino);
+        put_u64(&mut out, 0); // generation
+        put_u64(&mut out, TTL_SECS); // entry_valid
+        put_u64(&mut out, TTL_SECS); // attr_valid
+        put_u32(&mut out, 0);
+        put_u32(&mut out, 0);
+        self.put_attr(&mut out, node.ino)?;
+        Ok(out)
+    }
+
+    fn getattr(&self, ino: u64) -> Reply {
+        let mut out = Vec::with_capacity(104);
+        put_u64(&mut out, TTL_SECS);
+        put_u32(&mut out, 0);
+        put_u32(&mut out, 0);
+        self.put_attr(&mut out, ino)?;
+        Ok(out)
+    }
+
+    fn put_attr(&self, out: &mut Vec<u8>, ino: u64) -> Result<(), i32> {
+        let n = self.index.get(ino).ok_or(libc::ENOENT)?;
+        let fmt = match n.kind {
+            NodeKind::Dir => libc::S_IFDIR,
+            NodeKind::File => libc::S_IFREG,
+            NodeKind::Symlink => libc::S_IFLNK,
+            NodeKind::CharDev => libc::S_IFCHR,
+            NodeKind::BlockDev => libc::S_IFBLK,
+            NodeKind::Fifo => libc::S_IFIFO,
+        };
+        put_u64(out, n.ino);
+        put_u64(out, n.size);
+        put_u64(out, n.size.div_ceil(512)); // blocks
+        put_u64(out, n.mtime); // atime
+        put_u64(out, n.mtime);
+        put_u64(out, n.mtime); // ctime
+        put_u32(out, 0);
+        put_u32(out, 0);
+        put_u32(out, 0);
+        put_u32(out, fmt | n.perm);
+        put_u32(out, n.nlink);
+        put_u32(out, n.uid);
+        put_u32(out, n.gid);
+        put_u32(out, n.rdev);
+        put_u32(out, 4096); // blksize
+        put_u32(out, 0); // flags
+        Ok(())
+    }
+
+    fn readlink(&self, ino: u64) -> Reply {
+        let n = self.index.get(ino).ok_or(libc::ENOENT)?;
+        match &n.link {
+            Some(target) => Ok(target.as_bytes().to_vec()),
+            None => Err(libc::EINVAL),
+        }
+    }
+
+    fn open(&self, ino: u64, want: u32) -> Reply {
+        let n = self.index.get(ino).ok_or(libc::ENOENT)?;
+        match (n.kind, want) {
+            (NodeKind::Dir, libc::S_IFDIR) => {}
+            (NodeKind::Dir, _) => return Err(libc::EISDIR),
+            (_, libc::S_IFDIR) => return Err(libc::ENOTDIR),
+            _ => {}
+        }
+        let mut out = Vec::with_capacity(16);
+        put_u64(&mut out, 0); // fh：用不到，直接用 nodeid
+        put_u32(&mut out, FOPEN_KEEP_CACHE);
+        put_u32(&mut out, 0);
+        Ok(out)
+    }
+
+    fn read(&self, ino: u64, body: &[u8]) -> Reply {
+        if body.len() < 24 {
+            return Err(libc::EINVAL);
+        }
+        let offset = u64_at(body, 8);
+        let size = u32_at(body, 16) as u64;
+        let n = self.index.get(ino).ok_or(libc::ENOENT)?;
+        if n.kind != NodeKind::File {
+            return Err(libc::EISDIR);
+        }
+        if offset >= n.size {
+            return Ok(Vec::new());
+        }
+        let len = size.min(n.size - offset) as usize;
+        let mut out = vec![0u8; len];
+        self.data
+            .read_exact_at(&mut out, n.data_offset + offset)
+            .map_err(|e| e.raw_os_error().unwrap_or(libc::EIO))?;
+        Ok(out)
+    }
+
+    fn readdir(&self, ino: u64, body: &[u8]) -> Reply {
+        if body.len() < 24 {
+            return Err(libc::EINVAL);
+        }
+        let offset = u64_at(body, 8) as usize;
+        let size = u32_at(body, 16) as usize;
+        let dir = self.index.get(ino).ok_or(libc::ENOENT)?;
+        if dir.kind != NodeKind::Dir {
+            return Err(libc::ENOTDIR);
+        }
+
+        let dots = [(dir.ino, b".".as_slice()), (dir.parent, b"..".as_slice())];
+        let entries = dots.into_iter().chain(
+            dir.children
+                .iter()
+                .map(|(name, &child)| (child, name.as_bytes())),
+        );
+
+        let mut out = Vec::with_capacity(size);
+        for (i, (child, name)) in entries.enumerate().skip(offset) {
+            let rec = (24 + name.len()).next_multiple_of(8);
+            if out.len() + rec > size {
+                break;
+            }
+            let dtype = match self.index.get(child).map(|n| n.kind) {
+                Some(NodeKind::Dir) => libc::DT_DIR,
+                Some(NodeKind::File) => libc::DT_REG,
+                Some(NodeKind::Symlink) => libc::DT_LNK,
+                Some(NodeKind::CharDev) => libc::DT_CHR,
+                Some(NodeKind::BlockDev) => libc::DT_BLK,
+                Some(NodeKind::Fifo) => libc::DT_FIFO,
+                None => libc::DT_UNKNOWN,
+            };
+            let start = out.len();
+            put_u64(&mut out, child);
+            put_u64(&mut out, (i + 1) as u64); // 下一筆的 offset
+            put_u32(&mut out, name.len() as u32);
+            put_u32(&mut out, dtype as u32);
+            out.extend_from_slice(name);
+            out.resize(start + rec, 0);
+        }
+        Ok(out)
+    }
+
+    fn statfs(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(80);
+        put_u64(&mut out, 0); // blocks
+        put_u64(&mut out, 0); // bfree
+        put_u64(&mut out, 0); // bavail
+        put_u64(&mut out, self.index.len() as u64); // files
+        put_u64(&mut out, 0); // ffree
+        put_u32(&mut out, 4096); // bsize
+        put_u32(&mut out, 255); // namelen
+        put_u32(&mut out, 4096); // frsize
+        out.resize(80, 0);
+        out
+    }
+}
+
+/// Ok(payload) 或 Err(errno)
+type Reply = std::result::Result<Vec<u8>, i32>;
+
+fn write_reply(dev: &mut File, unique: u64, reply: Reply) -> Result<()> {
+    let (error, payload) = match reply {
+        Ok(p) => (0i32, p),
+        Err(errno) => (-errno, Vec::new()),
+    };
+    let mut out = Vec::with_capacity(OUT_HEADER_LEN + payload.len());
+    put_u32(&mut out, (OUT_HEADER_LEN + payload.len()) as u32);
+    out.extend_from_slice(&error.to_ne_bytes());
+    put_u64(&mut out, unique);
+    out.extend_from_slice(&payload);
+    match dev.write_all(&out) {
+        // 請求在回覆前已被中斷
+        Err(e) if e.raw_os_error() == Some(libc::ENOENT) => Ok(()),
+        other => other.context("write fuse reply"),
+    }
+}
+
+fn cstr(b: &[u8]) -> &[u8] {
+    match b.iter().position(|&c| c == 0) {
+        Some(i) => &b[..i],
+        None => b,
+    }
+}
+
+fn u32_at(b: &[u8], at: usize) -> u32 {
+    u32::from_ne_bytes(b[at..at + 4].try_into().unwrap())
+}
+
+fn u64_at(b: &[u8], at: usize) -> u64 {
+    u64::from_ne_bytes(b[at..at + 8].try_into().unwrap())
+}
+
+fn put_u16(out: &mut Vec<u8>, v: u16) {
+    out.extend_from_slice(&v.to_ne_bytes());
+}
+
+fn put_u32(out: &mut Vec<u8>, v: u32) {
+    out.extend_from_slice(&v.to_ne_bytes());
+}
+
+fn put_u64(out: &mut Vec<u8>, v: u64) {
+    out.extend_from_slice(&v.to_ne_bytes());
+}
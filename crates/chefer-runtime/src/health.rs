@@ -0,0 +1,214 @@
+// src/health.rs
+//! 每個有 healthcheck 的 service 一條背景 thread：每隔 interval 檢查一次，
+//! 連續失敗 retries 次（start_period 內不計）即 unhealthy。
+use anyhow::{Result, bail};
+use chefer_manifest::Healthcheck;
+use std::{
+    fmt,
+    io::{BufRead, BufReader, Write},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{
+    net::Upstream,
+    ns::{self, Container},
+};
+
+const TICK: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Health {
+    /// 還沒有成功過
+    Starting,
+    Healthy,
+    Unhealthy,
+}
+
+impl fmt::Display for Health {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Health::Starting => "starting",
+            Health::Healthy => "healthy",
+            Health::Unhealthy => "unhealthy",
+        })
+    }
+}
+
+/// drop 時通知 thread 結束（不等它，進行中的檢查最多再跑一個 timeout）
+pub struct Monitor {
+    state: Arc<Mutex<Health>>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Monitor {
+    pub fn start(
+        hc: &Healthcheck,
+        container: &Container,
+        shim: u32,
+        upstream: Upstream,
+    ) -> Monitor {
+        let state = Arc::new(Mutex::new(Health::Starting));
+        let cancel = Arc::new(AtomicBool::new(false));
+        let probe = Probe {
+            hc: hc.clone(),
+            container: container.clone(),
+            shim,
+            upstream,
+        };
+        let (s, c) = (state.clone(), cancel.clone());
+        std::thread::Builder::new()
+            .name(format!("health-{}", container.name))
+            .spawn(move || probe.run(&s, &c))
+            .expect("spawn healthcheck thread");
+        Monitor { state, cancel }
+    }
+
+    pub fn health(&self) -> Health {
+        *self.state.lock().unwrap()
+    }
+}
+
+impl Drop for Monitor {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+struct Probe {
+    hc: Healthcheck,
+    container: Container,
+    shim: u32,
+    upstream: Upstream,
+}
+
+impl Probe {
+    fn run(&self, state: &Mutex<Health>, cancel: &AtomicBool) {
+        let name = &self.container.name;
+        let started = Instant::now();
+        let mut failures = 0;
+        loop {
+            let wake = Instant::now() + self.hc.interval.0;
+            while Instant::now() < wake {
+                if cancel.load(Ordering::Relaxed) {
+                    return;
+                }
+                std::thread::sleep(TICK);
+            }
+
+            let result = self.check();
+            if cancel.load(Ordering::Relaxed) {
+                return;
+            }
+            let mut current = state.lock().unwrap();
+            match result {
+                Ok(()) => {
+                    failures = 0;
+                    if *current != Health::Healthy {
+                        tracing::info!("service `{name}` is healthy");
+                    }
+                    *current = Health::Healthy;
+                }
+                Err(e) if started.elapsed() < self.hc.start_period.0 => {
+                    tracing::debug!(
+                        "service `{name}` healthcheck failed during start period: {e:#}"
+                    );
+                }
+                Err(e) => {
+                    failures += 1;
+                    tracing::debug!(
+                        "service `{name}` healthcheck failed ({failures}/{}): {e:#}",
+                        self.hc.retries
+                    );
+                    if failures >= self.hc.retries && *current != Health::Unhealthy {
+                        tracing::warn!(
+                            "service `{name}` is unhealthy after {failures} failed check(s): {e:#}"
+                        );
+                        *current = Health::Unhealthy;
+                    }
+                }
+            }
+        }
+    }
+
+    fn check(&self) -> Result<()> {
+        let timeout = self.hc.timeout.0;
+        if let Some(cmd) = &self.hc.cmd {
+            let mut child = self.container.exec_in(self.shim, &ns::argv(cmd))?;
+            let deadline = Instant::now() + timeout;
+            loop {
+                if let Some(status) = child.try_wait()? {
+                    if status.success() {
+                        return Ok(());
+                    }
+                    bail!("command exited with {status}");
+                }
+                if Instant::now() >= deadline {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    bail!("command timed out after {timeout:?}");
+                }
+                std::thread::sleep(Duration::from_millis(20));
+            }
+        }
+        if let Some(port) = self.hc.tcp {
+            return match self.upstream.connect_tcp(port, timeout) {
+                Ok(_) => Ok(()),
+                Err(e) => bail!("cannot connect to port {port}: {e}"),
+            };
+        }
+        if let Some(url) = &self.hc.http
+            && let Upstream::Pod(_) = self.upstream
+        {
+            return self.http_in_pod(url, timeout);
+        }
+        if let Some(url) = &self.hc.http {
+            let agent: ureq::Agent = ureq::Agent::config_builder()
+                .timeout_global(Some(timeout))
+                .http_status_as_error(false)
+                .build()
+                .into();
+            let status = agent.get(url).call()?.status();
+            if status.is_success() || status.is_redirection() {
+                return Ok(());
+            }
+            bail!("GET {url} returned {status}");
+        }
+        bail!("healthcheck has no probe")
+    }
+
+    /// app network 內的 http：runtime 連不到它的 localhost，只能經 holder 取得連線後自己送 GET
+    fn http_in_pod(&self, url: &str, timeout: Duration) -> Result<()> {
+        let Some(rest) = url.strip_prefix("http://") else {
+            bail!("only http:// healthchecks are supported on the app network; use tcp instead");
+        };
+        let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        let port = match authority.rsplit_once(':') {
+            Some((_, p)) if !p.ends_with(']') => p.parse()?,
+            _ => 80,
+        };
+        let path = if path.is_empty() { "/" } else { path };
+
+        let mut stream = self.upstream.connect_tcp(port, timeout)?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+        let request = format!(
+            "GET {path} HTTP/1.0\r\nHost: {authority}\r\nUser-Agent: chefer-healthcheck\r\n\r\n"
+        );
+        stream.write_all(request.as_bytes())?;
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line)?;
+        let status: u16 = line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| anyhow::anyhow!("GET {url}: not an HTTP response"))?;
+        if (200..400).contains(&status) {
+            return Ok(());
+        }
+        bail!("GET {url} returned {status}");
+    }
+}
@@ -0,0 +1,240 @@
+// src/inspect.rs
+//! 單檔自我檢視：`--chefer-info` / `--chefer-verify` / `--chefer-extract` /
+//! `--chefer-manifest` / `--chefer-list`。全部不會啟動 app。
+use anyhow::{Context, Result, bail};
+use chefer_assembler::{Located, Placement};
+use fs_err as fs;
+use serde_json::Value;
+use std::path::Path;
+
+use crate::extract;
+
+/// 顯示用：不做版本檢查，比 runtime 新的 manifest 也能看
+fn manifest(exe: &Path) -> Result<Value> {
+    let bytes = chefer_assembler::read_bundle_file(exe, chefer_manifest::MANIFEST_FILE)?
+        .context("bundle has no manifest.json")?;
+    serde_json::from_slice(&bytes).context("parse manifest.json")
+}
+
+fn str_field<'a>(v: &'a Value, key: &str) -> &'a str {
+    v.get(key).and_then(Value::as_str).unwrap_or("-")
+}
+
+/// `--chefer-info`
+pub fn info(exe: &Path, loc: &Located, data_dir: Option<&Path>) -> Result<()> {
+    let ft = &loc.footer;
+    let mani = manifest(exe)?;
+    let exe_size = fs::metadata(exe)?.len();
+
+    println!("app:          {}", str_field(&mani, "app_name"));
+    println!("app version:  {}", str_field(&mani, "app_version"));
+    println!("spec version: {}", str_field(&mani, "spec_version"));
+    println!(
+        "manifest:     v{} (runtime supports up to v{})",
+        mani.get("manifest_version")
+            .and_then(Value::as_u64)
+            .unwrap_or(0),
+        chefer_manifest::MANIFEST_VERSION
+    );
+    println!("built (UTC):  {}", str_field(&mani, "generated_at_utc"));
+    println!("runtime:      chefer-runtime {}", env!("CARGO_PKG_VERSION"));
+    println!("executable:   {} ({} bytes)", exe.display(), exe_size);
+    let table = chefer_assembler::section::read_section_table(exe, ft)?;
+    let compression = match &table {
+        Some(_) => "per-section",
+        None if ft.compressed_is_zstd() => "zstd",
+        None => "none",
+    };
+    println!(
+        "payload:      offset={} length={} compression={} footer=v{}",
+        ft.offset, ft.length, compression, ft.version
+    );
+    println!("sha256:       {}", hex::encode(ft.sha256));
+    match loc.placement {
+        Placement::Appended if loc.trailing > 0 => println!(
+            "placement:    appended; {} bytes of trailing data skipped",
+            loc.trailing
+        ),
+        Placement::Appended => println!("placement:    appended"),
+        Placement::ElfSection => println!("placement:    ELF section .chefer"),
+    }
+    if let Some(table) = &table {
+        if let Some(d) = table.dictionary {
+            println!("  zstd dictionary: {} bytes", d.length);
+        }
+        for s in &table.sections {
+            let level = s.level.map(|l| format!(" -{l}")).unwrap_or_default();
+            let dict = if s.dictionary { " +dict" } else { "" };
+            println!(
+                "  {:<24} {}{}{} {} -> {} bytes",
+                s.name,
+                s.codec.as_str(),
+                level,
+                dict,
+                s.raw_length,
+                s.length
+            );
+        }
+    }
+    if let Ok(typed) = serde_json::from_value::<chefer_manifest::Manifest>(mani.clone()) {
+        let exe_dir = exe.parent().unwrap_or(Path::new("."));
+        let dir = chefer_manifest::DataDir::resolve(&typed, data_dir, exe_dir)?;
+        println!("data dir:     {} ({:?})", dir.path.display(), dir.source);
+        if !typed.old_names.is_empty() {
+            println!("old names:    {}", typed.old_names.join(", "));
+        }
+    }
+    if let Some(up) = mani.get("update").filter(|u| !u.is_null()) {
+        println!("update feed:  {}", str_field(up, "feed"));
+    }
+
+    let services = mani
+        .get("services")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    println!("services:     {}", services.len());
+    for s in services {
+        let primary = s.get("primary").and_then(Value::as_bool) == Some(true);
+        // web 模式是物件：{"web": {"port": 8080, "path": "/"}}
+        let interface = match s.get("interface_mode").and_then(|m| m.get("web")) {
+            Some(web) => format!(
+                "web (port {}, {})",
+                web.get("port").and_then(Value::as_u64).unwrap_or_default(),
+                str_field(web, "path")
+            ),
+            None => str_field(s, "interface_mode").to_string(),
+        };
+        let limits = s
+            .get("resources")
+            .and_then(|r| serde_json::from_value::<chefer_manifest::Resources>(r.clone()).ok())
+            .filter(|r| !r.is_empty())
+            .map(|r| format!(" limits: {r}"))
+            .unwrap_or_default();
+        let secrets = s
+            .get("secrets")
+            .and_then(|r| {
+                serde_json::from_value::<Vec<chefer_manifest::ServiceSecret>>(r.clone()).ok()
+            })
+            .filter(|r| !r.is_empty())
+            .map(|r| {
+                let r: Vec<String> = r.iter().map(|s| s.to_string()).collect();
+                format!(" secrets: {}", r.join(", "))
+            })
+            .unwrap_or_default();
+        println!(
+            "  - {} [{}] interface={}{}{limits}{secrets}",
+            str_field(s, "name"),
+            str_field(s, "platform"),
+            interface,
+            if primary { " (primary)" } else { "" }
+        );
+    }
+    Ok(())
+}
+
+/// `--chefer-verify`：payload digest、manifest 與每個 service 的 rootfs 都要在
+pub fn verify(exe: &Path) -> Result<()> {
+    chefer_assembler::verify_payload(exe)?;
+    println!("payload sha256: ok");
+
+    let bytes = chefer_assembler::read_bundle_file(exe, chefer_manifest::MANIFEST_FILE)?
+        .context("bundle has no manifest.json")?;
+    let mani = chefer_manifest::Manifest::from_slice(&bytes)?;
+    println!("manifest.json:  ok (version {})", mani.manifest_version);
+
+    let entries = chefer_assembler::list_bundle(exe)?;
+    let has = |p: &str| {
+        let p = p.trim_end_matches('/');
+        entries
+            .iter()
+            .any(|e| e.path.trim_start_matches("./").trim_end_matches('/') == p)
+    };
+    let mut missing = Vec::new();
+    for s in &mani.services {
+        if has(&s.rootfs_rel) {
+            println!("service {}: ok", s.name);
+        } else {
+            println!("service {}: missing {}", s.name, s.rootfs_rel);
+            missing.push(s.name.clone());
+        }
+    }
+    if !missing.is_empty() {
+        bail!("bundle is incomplete: {}", missing.join(", "));
+    }
+    println!("verified {}", exe.display());
+    Ok(())
+}
+
+/// `--chefer-extract <dir>`：解出 bundle 但不執行
+pub fn extract_to(exe: &Path, dir: &Path) -> Result<()> {
+    if dir.exists() && fs::read_dir(dir)?.next().is_some() {
+        bail!(
+            "refusing to extract into non-empty directory {}",
+            dir.display()
+        );
+    }
+    extract::unpack_bundle(exe, dir)?;
+    println!("extracted bundle to {}", dir.display());
+    Ok(())
+}
+
+/// `--chefer-manifest`：hidden_env 列出的 env 值以 `***` 顯示
+pub fn print_manifest(exe: &Path) -> Result<()> {
+    let mut mani = manifest(exe)?;
+    if let Some(services) = mani.get_mut("services").and_then(Value::as_array_mut) {
+        services.iter_mut().for_each(redact_env);
+    }
+    println!("{}", serde_json::to_string_pretty(&mani)?);
+    Ok(())
+}
+
+fn redact_env(svc: &mut Value) {
+    let hidden: Vec<String> = svc
+        .get("hidden_env")
+        .and_then(|h| serde_json::from_value(h.clone()).ok())
+        .unwrap_or_default();
+    let Some(env) = svc.get_mut("env").and_then(Value::as_array_mut) else {
+        return;
+    };
+    for pair in env.iter_mut() {
+        if let Some([k, v]) = pair.as_array_mut().map(Vec::as_mut_slice)
+            && k.as_str().is_some_and(|k| hidden.iter().any(|h| h == k))
+        {
+            *v = chefer_manifest::REDACTED.into();
+        }
+    }
+}
+
+/// `--chefer-list`：類似 `tar tv`
+pub fn list(exe: &Path) -> Result<()> {
+    for e in chefer_assembler::list_bundle(exe)? {
+        match &e.link {
+            Some(target) => println!(
+                "{}{:04o} {:>12} {} -> {}",
+                e.kind, e.mode, e.size, e.path, target
+            ),
+            None => println!("{}{:04o} {:>12} {}", e.kind, e.mode, e.size, e.path),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifest_output_hides_listed_env() {
+        let mut svc = serde_json::json!({
+            "name": "db",
+            "env": [["DB_PASSWORD", "pw"], ["DB_USER", "bob"], ["PLAIN", "x"]],
+            "hidden_env": ["DB_PASSWORD", "DB_USER"],
+        });
+        redact_env(&mut svc);
+        assert_eq!(
+            svc["env"],
+            serde_json::json!([["DB_PASSWORD", "***"], ["DB_USER", "***"], ["PLAIN", "x"]])
+        );
+    }
+}
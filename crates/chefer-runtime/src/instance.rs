@@ -0,0 +1,212 @@
+// src/instance.rs
+//! 同一個 app 資料夾同時只有一個實例：以 flock 鎖住 `instance.lock`（行程結束即釋放，不會殘留），
+//! 並在 `control.sock` 接受控制請求。已有實例在跑時，第二次啟動把自己的參數交給它後就結束。
+//! `--chefer-new-instance` 不交接，但資料夾仍不可共用，要搭配 `--chefer-data-dir` 另開一份資料。
+use anyhow::{Result, bail};
+use chefer_manifest::{
+    CONTROL_SOCKET, INSTANCE_LOCK_FILE,
+    control::{Request, Response},
+};
+use fs_err as fs;
+use std::{
+    ffi::OsString,
+    io::{self, Write},
+    os::{fd::AsRawFd, unix::net::UnixListener},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// 對方剛啟動、還沒開好 socket 時重試的時間
+const CONNECT_WAIT: Duration = Duration::from_secs(5);
+
+pub enum Launch {
+    /// 這是唯一的實例
+    First(Instance),
+    /// 已交給執行中的實例
+    HandedOver,
+}
+
+pub struct Instance {
+    /// 持有期間鎖住資料夾
+    _lock: std::fs::File,
+    socket: PathBuf,
+    /// 交給 supervisor；開不了 socket 時為 None
+    pub listener: Option<UnixListener>,
+}
+
+impl Drop for Instance {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.socket);
+    }
+}
+
+/// 只有自己能連線的 socket：建立時就是 0600（bind 後再 chmod 會有別人先連上的空檔）
+fn bind_private(path: &Path) -> io::Result<UnixListener> {
+    // SAFETY: umask 沒有前置條件；這時只有主執行緒會建立檔案
+    let old = unsafe { libc::umask(0o177) };
+    let res = UnixListener::bind(path);
+    // SAFETY: 同上，還原原本的 umask
+    unsafe { libc::umask(old) };
+    res
+}
+
+/// 鎖住 `data_dir`；已被鎖住時把 `args` 交給執行中的實例（`new_instance` 時改為報錯）
+pub fn acquire(data_dir: &Path, new_instance: bool, args: &[OsString]) -> Result<Launch> {
+    let path = data_dir.join(INSTANCE_LOCK_FILE);
+    let mut lock = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)?
+        .into_parts()
+        .0;
+    let socket = data_dir.join(CONTROL_SOCKET);
+
+    // SAFETY: lock 是剛開好的合法 fd，flock 不碰記憶體；LOCK_NB 讓呼叫不會阻塞
+    if unsafe { libc::flock(lock.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        // 內容只給人看：目前實例的 pid
+        lock.set_len(0)?;
+        writeln!(lock, "{}", std::process::id())?;
+        let _ = std::fs::remove_file(&socket);
+        let listener = match bind_private(&socket) {
+            Ok(l) => Some(l),
+            Err(e) => {
+                tracing::warn!(
+                    "cannot open the control socket {} ({e}); a second launch will not be handed over",
+                    socket.display()
+                );
+                None
+            }
+        };
+        return Ok(Launch::First(Instance {
+            _lock: lock,
+            socket,
+            listener,
+        }));
+    }
+    let err = io::Error::last_os_error();
+    if err.raw_os_error() != Some(libc::EWOULDBLOCK) {
+        return Err(anyhow::Error::new(err).context(format!("lock {}", path.display())));
+    }
+
+    let owner = std::fs::read_to_string(&path)
+        .ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .map(|pid| format!(" (pid {pid})"))
+        .unwrap_or_default();
+    if new_instance {
+        bail!(
+            "{} is in use by another instance{owner}; pass --chefer-data-dir <dir> to run a separate copy",
+            data_dir.display()
+        );
+    }
+
+    let req = Request::Launch {
+        args: args
+            .iter()
+            .map(|a| a.to_string_lossy().into_owned())
+            .collect(),
+    };
+    let deadline = Instant::now() + CONNECT_WAIT;
+    let response = loop {
+        match chefer_manifest::control::request(&socket, &req) {
+            Ok(r) => break r,
+            Err(e) if Instant::now() >= deadline => {
+                bail!("the app is already running{owner} but does not answer: {e:#}")
+            }
+            Err(_) => std::thread::sleep(Duration::from_millis(100)),
+        }
+    };
+    let Response { ok, message, .. } = response;
+    if !ok {
+        bail!("the running instance{owner} refused the launch: {message}");
+    }
+    tracing::info!("already running{owner}: {message}");
+    Ok(Launch::HandedOver)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::fs::PermissionsExt;
+
+    /// bind_private 會暫時改 umask；在有自己 umask 的執行緒上跑，才不會影響同時建立檔案的其他測試
+    fn isolated<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+        std::thread::spawn(move || {
+            // SAFETY: CLONE_FS 只讓這個執行緒有自己的 umask 與 cwd
+            assert_eq!(unsafe { libc::unshare(libc::CLONE_FS) }, 0);
+            f()
+        })
+        .join()
+        .unwrap()
+    }
+
+    fn first(dir: &Path) -> Instance {
+        let dir = dir.to_path_buf();
+        match isolated(move || acquire(&dir, false, &[]).unwrap()) {
+            Launch::First(i) => i,
+            Launch::HandedOver => panic!("nothing else holds the lock"),
+        }
+    }
+
+    #[test]
+    fn second_lock_in_the_same_dir_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let instance = first(dir.path());
+
+        // flock 屬於開啟的檔案，同一個行程再開一次也會衝突
+        let e = acquire(dir.path(), true, &[])
+            .err()
+            .expect("already locked");
+        let msg = e.to_string();
+        assert!(msg.contains("in use by another instance"), "{msg}");
+        assert!(
+            msg.contains(&format!("(pid {})", std::process::id())),
+            "{msg}"
+        );
+
+        // 釋放後可以再拿到，舊的 socket 也清掉了
+        drop(instance);
+        assert!(!dir.path().join(CONTROL_SOCKET).exists());
+        let again = first(dir.path());
+        assert!(again.listener.is_some());
+    }
+
+    #[test]
+    fn control_socket_is_private() {
+        let dir = tempfile::tempdir().unwrap();
+        let _instance = first(dir.path());
+        let mode = std::fs::metadata(dir.path().join(CONTROL_SOCKET))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn second_launch_is_handed_over() {
+        let dir = tempfile::tempdir().unwrap();
+        let instance = first(dir.path());
+        let listener = instance.listener.as_ref().unwrap().try_clone().unwrap();
+        let server = std::thread::spawn(move || {
+            let (conn, _) = listener.accept().unwrap();
+            let mut line = String::new();
+            BufReader::new(&conn).read_line(&mut line).unwrap();
+            let mut out = &conn;
+            let response = Response::ok("brought to front");
+            writeln!(out, "{}", serde_json::to_string(&response).unwrap()).unwrap();
+            serde_json::from_str::<Request>(&line).unwrap()
+        });
+
+        let args = [OsString::from("open"), OsString::from("file.txt")];
+        let launch = acquire(dir.path(), false, &args).unwrap();
+        assert!(matches!(launch, Launch::HandedOver));
+        match server.join().unwrap() {
+            Request::Launch { args } => assert_eq!(args, ["open", "file.txt"]),
+            other => panic!("unexpected request {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,270 @@
+// src/logs.rs
+//! service 的 stdout/stderr：每行加上時間戳寫進 `<data_dir>/logs/<service>.log`（超過大小就輪替），
+//! 有終端機或指定 `--chefer-logs` 時，同時以 compose 風格（彩色、`name |` 前綴）合併印到 stdout。
+//! 有 service 接上終端機（terminal.rs）時終端機歸它，其他 service 的輸出只在指定 `--chefer-logs` 時才印。
+//! secrets 的值在寫出前換成 `***`（接上終端機的 service 直接輸出到終端機，不經過這裡）。
+use anyhow::{Result, bail};
+use chrono::{SecondsFormat, Utc};
+use fs_err::{self as fs, File};
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+    process::Child,
+    sync::{Arc, Mutex},
+    thread::JoinHandle,
+};
+
+/// 單一 log 檔的上限；超過就輪替
+const MAX_SIZE: u64 = 10 * 1024 * 1024;
+/// 保留幾個舊檔（.log.1 最新）
+const KEEP: usize = 5;
+/// 比這短的 secret 不遮蔽（遮掉 "1" 會毀掉整份 log）
+const MIN_REDACT_LEN: usize = 4;
+/// compose 的配色順序
+const COLORS: &[&str] = &["36", "33", "32", "35", "34", "96", "93", "92", "95", "94"];
+
+pub struct Logs {
+    files: HashMap<String, Arc<Mutex<LogFile>>>,
+    console: Option<Arc<Console>>,
+    /// 要遮蔽的值，長的在前（一個值包含另一個時先遮長的）
+    redact: Arc<Vec<String>>,
+}
+
+struct Console {
+    /// 空 = 全部
+    only: Vec<String>,
+    color: bool,
+    width: usize,
+    colors: HashMap<String, &'static str>,
+}
+
+impl Logs {
+    /// `filter` 為 `--chefer-logs` 指定的 service（可逗號分隔）；指定了就一定印到 console。
+    /// `terminal`：有 service 接上終端機
+    pub fn new(
+        data_dir: &Path,
+        services: &[&str],
+        filter: &[String],
+        terminal: bool,
+    ) -> Result<Logs> {
+        let only: Vec<String> = filter
+            .iter()
+            .flat_map(|f| f.split(','))
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+        if let Some(unknown) = only.iter().find(|s| !services.contains(&s.as_str())) {
+            bail!(
+                "--chefer-logs: unknown service `{unknown}` (available: {})",
+                services.join(", ")
+            );
+        }
+
+        let mut files = HashMap::new();
+        for name in services {
+            let path = chefer_manifest::log_file(data_dir, name);
+            files.insert(name.to_string(), Arc::new(Mutex::new(LogFile::open(path)?)));
+        }
+
+        let stdout = std::io::stdout().is_terminal();
+        let console = ((stdout && !terminal) || !only.is_empty()).then(|| {
+            Arc::new(Console {
+                width: services.iter().map(|s| s.len()).max().unwrap_or(0),
+                colors: services
+                    .iter()
+                    .enumerate()
+                    .map(|(i, s)| (s.to_string(), COLORS[i % COLORS.len()]))
+                    .collect(),
+                only,
+                color: stdout,
+            })
+        });
+        Ok(Logs {
+            files,
+            console,
+            redact: Arc::default(),
+        })
+    }
+
+    /// 之後 attach 的輸出中遮蔽這些值
+    pub fn redact<'a>(&mut self, values: impl IntoIterator<Item = &'a str>) {
+        let mut values: Vec<String> = values
+            .into_iter()
+            .filter(|v| v.len() >= MIN_REDACT_LEN)
+            .map(str::to_string)
+            .collect();
+        values.sort_by_key(|v| std::cmp::Reverse(v.len()));
+        values.dedup();
+        self.redact = Arc::new(values);
+    }
+
+    /// 接手 child 的 stdout/stderr（spawn 時需為 piped），各開一條 thread 逐行轉寫。
+    /// 容器結束時所有寫端都會關閉，thread 隨之結束；join 它們可確保輸出已寫完。
+    pub fn attach(&self, service: &str, child: &mut Child) -> Vec<JoinHandle<()>> {
+        let mut pumps = Vec::new();
+        if let Some(out) = child.stdout.take() {
+            pumps.push(self.pump(service, "stdout", out));
+        }
+        if let Some(err) = child.stderr.take() {
+            pumps.push(self.pump(service, "stderr", err));
+        }
+        pumps
+    }
+
+    fn pump(
+        &self,
+        service: &str,
+        stream: &'static str,
+        pipe: impl Read + Send + 'static,
+    ) -> JoinHandle<()> {
+        let file = self.files[service].clone();
+        let console = self.console.clone();
+        let redact = self.redact.clone();
+        let service = service.to_string();
+        std::thread::Builder::new()
+            .name(format!("log-{service}-{stream}"))
+            .spawn(move || {
+                let mut reader = BufReader::new(pipe);
+                let mut buf = Vec::new();
+                loop {
+                    buf.clear();
+                    match reader.read_until(b'\n', &mut buf) {
+                        Ok(0) | Err(_) => break,
+                        Ok(_) => {}
+                    }
+                    let mut line = String::from_utf8_lossy(&buf);
+                    for v in redact.iter() {
+                        if line.contains(v.as_str()) {
+                            line = line.replace(v.as_str(), "***").into();
+                        }
+                    }
+                    let line = line.trim_end_matches(['\n', '\r']);
+                    file.lock().unwrap().line(stream, line);
+                    if let Some(c) = &console {
+                        c.print(&service, line);
+                    }
+                }
+            })
+            .expect("spawn log thread")
+    }
+
+    /// runtime 對某個 service 的事件（啟動、結束）也記進它的 log 檔
+    pub fn event(&self, service: &str, message: &str) {
+        if let Some(f) = self.files.get(service) {
+            f.lock().unwrap().line("chefer", message);
+        }
+    }
+}
+
+impl Console {
+    fn print(&self, service: &str, line: &str) {
+        if !self.only.is_empty() && !self.only.iter().any(|s| s == service) {
+            return;
+        }
+        let end = crate::terminal::line_end();
+        let out = if self.color {
+            let c = self.colors.get(service).copied().unwrap_or("0");
+            format!(
+                "\x1b[{c}m{service:<width$} |\x1b[0m {line}{end}",
+                width = self.width
+            )
+        } else {
+            format!("{service:<width$} | {line}{end}", width = self.width)
+        };
+        // 一次寫完整行，避免不同 service 交錯
+        let _ = std::io::stdout().lock().write_all(out.as_bytes());
+    }
+}
+
+struct LogFile {
+    path: PathBuf,
+    file: Option<File>,
+    size: u64,
+}
+
+impl LogFile {
+    fn open(path: PathBuf) -> Result<LogFile> {
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(LogFile {
+            path,
+            file: Some(file),
+            size,
+        })
+    }
+
+    /// 寫不進去（磁碟滿等）就放棄這一行，不影響 service
+    fn line(&mut self, stream: &str, line: &str) {
+        let ts = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+        let entry = format!("{ts} {stream} {line}\n");
+        if self.size > 0 && self.size + entry.len() as u64 > MAX_SIZE {
+            self.rotate();
+        }
+        if let Some(f) = &mut self.file
+            && f.write_all(entry.as_bytes()).is_ok()
+        {
+            self.size += entry.len() as u64;
+        }
+    }
+
+    /// .log → .log.1 → … → .log.KEEP（最舊的丟掉）
+    fn rotate(&mut self) {
+        self.file = None;
+        let numbered = |n: usize| {
+            let mut p = self.path.clone().into_os_string();
+            p.push(format!(".{n}"));
+            PathBuf::from(p)
+        };
+        for n in (1..KEEP).rev() {
+            let _ = fs::rename(numbered(n), numbered(n + 1));
+        }
+        if let Err(e) = fs::rename(&self.path, numbered(1)) {
+            tracing::warn!("cannot rotate {}: {e}", self.path.display());
+        }
+        match LogFile::open(self.path.clone()) {
+            Ok(f) => *self = f,
+            Err(e) => tracing::warn!("cannot reopen {}: {e:#}", self.path.display()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pump_lines(redact: &[&str], input: &str) -> String {
+        let dir = tempfile::tempdir().unwrap();
+        let path = chefer_manifest::log_file(dir.path(), "app");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let mut logs = Logs::new(dir.path(), &["app"], &[], false).unwrap();
+        logs.redact(redact.iter().copied());
+        logs.pump("app", "stdout", std::io::Cursor::new(input.to_string()))
+            .join()
+            .unwrap();
+        fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn secrets_are_redacted() {
+        let log = pump_lines(
+            &["hunter2", "hunter22", "abc"],
+            "password=hunter22\nother=hunter2!\nshort=abc\n",
+        );
+        let lines: Vec<&str> = log
+            .lines()
+            .map(|l| l.split_once(" stdout ").unwrap().1)
+            .collect();
+        // 長的值先遮，短於 MIN_REDACT_LEN 的不遮
+        assert_eq!(lines, ["password=***", "other=***!", "short=abc"]);
+    }
+
+    #[test]
+    fn partial_last_line_is_kept() {
+        let log = pump_lines(&["s3cret"], "token s3cret");
+        assert!(log.trim_end().ends_with("stdout token ***"), "{log}");
+    }
+}
@@ -1,62 +1,339 @@
 // src/main.rs
+#[cfg(target_os = "linux")]
+mod cgroup;
+#[cfg(target_os = "linux")]
+mod control;
+mod datadir;
+#[cfg(target_os = "linux")]
+mod display;
 mod extract;
-mod footer;
+#[cfg(target_os = "linux")]
+mod fuse;
+#[cfg(target_os = "linux")]
+mod health;
+mod inspect;
+#[cfg(target_os = "linux")]
+mod instance;
+#[cfg(target_os = "linux")]
+mod logs;
+#[cfg(target_os = "linux")]
+mod net;
+#[cfg(target_os = "linux")]
+mod ns;
+#[cfg(target_os = "linux")]
+mod publish;
 mod run;
+#[cfg(target_os = "linux")]
+mod secrets;
+#[cfg(target_os = "linux")]
+mod supervise;
+#[cfg(target_os = "linux")]
+mod tarfs;
+#[cfg(target_os = "linux")]
+mod terminal;
+mod update;
 mod util;
+mod vars;
+#[cfg(target_os = "linux")]
+mod web;
 
-use anyhow::Result;
-use std::path::PathBuf;
+use anyhow::{Context, Result};
+use chefer_assembler::{Footer, Placement};
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+    sync::{Arc, atomic::AtomicBool},
+};
 use tracing_subscriber::FmtSubscriber;
 
+/// runtime 自己的旗標一律以 `--chefer-` 開頭，其餘參數原樣交給 app。
 #[derive(clap::Parser, Debug)]
-#[command(name = "chefer-runtime", version, about = "Chefer Runtime Stub")]
+#[command(
+    name = "chefer-runtime",
+    version,
+    about = "Chefer Runtime Stub",
+    disable_help_flag = true,
+    disable_version_flag = true
+)]
 struct Args {
-    /// 指定暫存解壓目錄（預設使用系統 temp）
-    #[arg(long)]
-    extract_dir: Option<PathBuf>,
+    /// 顯示 runtime 旗標說明
+    #[arg(long = "chefer-help", action = clap::ArgAction::Help)]
+    help: Option<bool>,
+
+    /// 顯示 runtime 版本
+    #[arg(long = "chefer-version", action = clap::ArgAction::Version)]
+    version: Option<bool>,
+
+    /// 指定暫存解壓（或掛載點）目錄（預設使用系統 temp）
+    #[arg(long = "chefer-tmp-dir", alias = "extract-dir", value_name = "DIR")]
+    tmp_dir: Option<PathBuf>,
+
+    /// 指定 app 資料夾（覆寫 appcipe 的 data_dir 與系統預設，且不做 old_names 遷移）
+    #[arg(long = "chefer-data-dir", value_name = "DIR")]
+    data_dir: Option<PathBuf>,
 
     /// 保留 temp 目錄（預設退出即刪）
-    #[arg(long)]
+    #[arg(long = "chefer-keep-tmp", alias = "keep-tmp")]
     keep_tmp: bool,
 
+    /// 不使用 FUSE 直接掛載 exe，一律解壓
+    #[arg(long = "chefer-no-mount")]
+    no_mount: bool,
+
     /// 僅顯示 footer 資訊後退出（除錯用）
-    #[arg(long)]
+    #[arg(long = "chefer-dump-footer", alias = "dump-footer")]
     dump_footer: bool,
+
+    /// 顯示 app 名稱、版本、建置時間、runtime 版本、footer 與 services
+    #[arg(long = "chefer-info")]
+    info: bool,
+
+    /// 驗證 payload digest、manifest 與各 service rootfs
+    #[arg(long = "chefer-verify")]
+    verify: bool,
+
+    /// 把 bundle 解到指定目錄後退出（不執行 app）
+    #[arg(long = "chefer-extract", value_name = "DIR")]
+    extract: Option<PathBuf>,
+
+    /// 印出內嵌的 manifest.json
+    #[arg(long = "chefer-manifest")]
+    manifest: bool,
+
+    /// 列出 payload 內容
+    #[arg(long = "chefer-list")]
+    list: bool,
+
+    /// 檢查更新 feed，有新版就下載、驗證並替換執行檔後退出
+    #[arg(long = "chefer-update")]
+    update: bool,
+
+    /// 只檢查是否有新版，不安裝
+    #[arg(long = "chefer-check-update")]
+    check_update: bool,
+
+    /// console 只顯示指定 service 的輸出（可重複或以逗號分隔；未指定時有終端機才顯示全部）
+    #[arg(long = "chefer-logs", value_name = "SERVICE")]
+    logs: Vec<String>,
+
+    /// 已有實例在執行時不把參數交給它；資料夾不可共用，需搭配 --chefer-data-dir
+    #[arg(long = "chefer-new-instance")]
+    new_instance: bool,
+
+    /// web 模式開啟瀏覽器的指令（URL 接在最後或取代 %s；預設 $BROWSER、xdg-open；none 不開）
+    #[arg(long = "chefer-browser", value_name = "COMMAND")]
+    browser: Option<String>,
+
+    /// 不屬於 runtime 的參數，交給 app
+    #[arg(skip)]
+    app_args: Vec<OsString>,
+}
+
+/// 改名前的旗標：仍然接受（clap 的隱藏 alias），但提示改用新名稱
+const LEGACY_FLAGS: [(&str, &str); 3] = [
+    ("--extract-dir", "--chefer-tmp-dir"),
+    ("--keep-tmp", "--chefer-keep-tmp"),
+    ("--dump-footer", "--chefer-dump-footer"),
+];
+
+impl Args {
+    /// 從 argv 挑出 `--chefer-*` 與舊名稱（連同它們的值），其餘依序留給 app；
+    /// `--` 之後全部屬於 app，讓 app 也能收到字面上的 `--chefer-…`。
+    fn parse_split() -> Self {
+        let mut argv = std::env::args_os();
+        let mut ours = vec![argv.next().unwrap_or_else(|| "chefer-runtime".into())];
+        let mut app_args = Vec::new();
+
+        let cmd = <Args as clap::CommandFactory>::command();
+        let takes_value = |flag: &str| {
+            cmd.get_arguments().any(|a| {
+                (a.get_long() == Some(flag)
+                    || a.get_all_aliases().is_some_and(|v| v.contains(&flag)))
+                    && a.get_action().takes_values()
+            })
+        };
+        let legacy = |s: &str| {
+            let name = s.split_once('=').map_or(s, |(n, _)| n);
+            LEGACY_FLAGS.iter().find(|(old, _)| *old == name)
+        };
+
+        while let Some(arg) = argv.next() {
+            let Some(s) = arg
+                .to_str()
+                .filter(|s| s.starts_with("--chefer-") || legacy(s).is_some())
+            else {
+                if arg == "--" {
+                    app_args.extend(argv.by_ref());
+                } else {
+                    app_args.push(arg);
+                }
+                continue;
+            };
+            if let Some((old, new)) = legacy(s) {
+                tracing::warn!("{old} is deprecated; use {new}");
+            }
+            let needs_next = !s.contains('=') && takes_value(&s[2..]);
+            ours.push(arg);
+            if needs_next && let Some(v) = argv.next() {
+                ours.push(v);
+            }
+        }
+
+        let mut args = <Args as clap::Parser>::parse_from(ours);
+        args.app_args = app_args;
+        args
+    }
+}
+
+/// bundle 的檔案樹來源：FUSE 掛載（免解壓）或解壓到暫存目錄
+enum Bundle {
+    Extracted(extract::Extracted),
+    #[cfg(target_os = "linux")]
+    Mounted(fuse::Mounted),
+}
+
+impl Bundle {
+    fn dir(&self) -> &Path {
+        match self {
+            Bundle::Extracted(e) => &e.bundle_dir,
+            #[cfg(target_os = "linux")]
+            Bundle::Mounted(m) => &m.bundle_dir,
+        }
+    }
+
+    /// 掛載時 payload 在背景驗證；結束前確認它沒有失敗
+    fn verified(&self) -> Result<()> {
+        match self {
+            Bundle::Extracted(_) => Ok(()),
+            #[cfg(target_os = "linux")]
+            Bundle::Mounted(m) => m.verified(),
+        }
+    }
 }
 
 fn main() -> Result<()> {
-    let subscriber = FmtSubscriber::builder().with_target(false).finish();
-    tracing::subscriber::set_global_default(subscriber).ok();
+    let subscriber = FmtSubscriber::builder().with_target(false);
+    #[cfg(target_os = "linux")]
+    let subscriber = subscriber.with_writer(terminal::LogWriter);
+    tracing::subscriber::set_global_default(subscriber.finish()).ok();
 
-    let args = <Args as clap::Parser>::parse();
+    let args = Args::parse_split();
     let exe = std::env::current_exe()?;
 
-    let ft = footer::Footer::read_from_exe(&exe)?;
+    let loc = Footer::locate(&exe)?;
+    let ft = loc.footer;
     if args.dump_footer {
         println!(
-            "footer: version={} flags={:#010b} offset={} length={} sha256={}",
+            "footer: version={} flags={:#010b} offset={} length={} sha256={} at={} placement={:?} trailing={}",
             ft.version,
             ft.flags,
             ft.offset,
             ft.length,
-            hex::encode(ft.sha256)
+            hex::encode(ft.sha256),
+            loc.footer_at,
+            loc.placement,
+            loc.trailing
         );
         return Ok(());
     }
+    if loc.placement == Placement::Appended && loc.trailing > 0 {
+        tracing::info!(
+            "skipped {} bytes of trailing data after the payload footer",
+            loc.trailing
+        );
+    }
+    if args.info {
+        return inspect::info(&exe, &loc, args.data_dir.as_deref());
+    }
+    if args.verify {
+        return inspect::verify(&exe);
+    }
+    if let Some(dir) = &args.extract {
+        return inspect::extract_to(&exe, dir);
+    }
+    if args.manifest {
+        return inspect::print_manifest(&exe);
+    }
+    if args.list {
+        return inspect::list(&exe);
+    }
 
-    let keep_dir = if args.keep_tmp {
-        args.extract_dir.as_deref()
-    } else {
-        args.extract_dir.as_deref()
+    update::cleanup_previous(&exe);
+    let manifest = chefer_assembler::read_bundle_file(&exe, chefer_manifest::MANIFEST_FILE)?
+        .context("bundle has no manifest.json")?;
+    let mut manifest = chefer_manifest::Manifest::from_slice(&manifest)?;
+    #[cfg(target_os = "linux")]
+    if manifest.terminal_service().is_some() {
+        terminal::quiet();
+    }
+    let update_settings = update::UpdateSettings::from_manifest(&manifest);
+    if args.update || args.check_update {
+        let Some(s) = &update_settings else {
+            anyhow::bail!("this app has no `update` section configured");
+        };
+        return update::run_manual(s, &exe, args.check_update);
+    }
+
+    // 在遷移資料夾、取得實例鎖之前：exec 會放掉 flock，重新執行到再次上鎖之間別的啟動可能搶走鎖
+    #[cfg(target_os = "linux")]
+    cgroup::reexec_in_scope(&manifest);
+
+    let persist = match chefer_assembler::read_bundle_file(&exe, chefer_manifest::PERSIST_MAP_FILE)?
+    {
+        Some(bytes) => chefer_manifest::persist_map_from_slice(&bytes)?,
+        None => Vec::new(),
+    };
+    let data_dir = datadir::prepare(&manifest, &persist, args.data_dir.as_deref(), &exe)?;
+    vars::expand(&mut manifest, &data_dir.path, &exe)?;
+    #[cfg(target_os = "linux")]
+    let mut instance = match instance::acquire(&data_dir.path, args.new_instance, &args.app_args)? {
+        instance::Launch::First(i) => i,
+        instance::Launch::HandedOver => return Ok(()),
     };
-    let extracted = extract::extract_bundle(&exe, &ft, keep_dir)?;
-    tracing::info!("bundle extracted at {}", extracted.bundle_dir.display());
+
+    // 收到訊號或 bundle 驗證失敗時要求 app 停止
+    let stop = Arc::new(AtomicBool::new(false));
+    let bundle = open_bundle(&exe, &ft, &args, stop.clone())?;
+    let update_check = update_settings
+        .filter(|s| s.on_startup)
+        .map(|s| update::spawn_startup_check(s, exe.clone()));
+    tracing::info!("bundle ready at {}", bundle.dir().display());
 
     let ctx = run::RuntimeContext {
-        bundle_dir: camino::Utf8PathBuf::from_path_buf(extracted.bundle_dir.clone()).unwrap(),
+        bundle_dir: camino::Utf8PathBuf::from_path_buf(bundle.dir().to_path_buf()).unwrap(),
+        manifest,
+        data_dir: data_dir.path,
+        app_args: args.app_args.clone(),
+        log_filter: args.logs.clone(),
+        browser: args.browser.clone(),
+        stop,
+        #[cfg(target_os = "linux")]
+        control: instance.listener.take(),
     };
-    run::run(&ctx)?;
+    let code = run::run(&ctx)?;
+    bundle.verified()?;
 
-    // TempDir 會在 drop 時清除；若 keep_tmp=true，你可以改成不釋放讓它保留
+    // Bundle drop 時卸載 / 刪除 temp（keep_tmp 時保留解壓結果）；process::exit 不會跑解構子，先 drop
+    drop(bundle);
+    drop(update_check);
+    #[cfg(target_os = "linux")]
+    drop(instance);
+    if code != 0 {
+        std::process::exit(code);
+    }
     Ok(())
 }
+
+/// 優先以 FUSE 掛載未壓縮的 payload；壓縮過或不可用時退回解壓
+fn open_bundle(exe: &Path, ft: &Footer, args: &Args, stop: Arc<AtomicBool>) -> Result<Bundle> {
+    #[cfg(target_os = "linux")]
+    if !args.no_mount && !args.keep_tmp && ft.is_plain_tar() {
+        match fuse::mount_bundle(exe, ft.offset, ft.length, args.tmp_dir.as_deref(), stop) {
+            Ok(m) => return Ok(Bundle::Mounted(m)),
+            Err(e) => tracing::info!("FUSE mount unavailable ({e:#}), falling back to extraction"),
+        }
+    }
+
+    let extracted = extract::extract_bundle(exe, args.tmp_dir.as_deref(), args.keep_tmp)?;
+    Ok(Bundle::Extracted(extracted))
+}
@@ -0,0 +1,444 @@
+// src/net.rs
+//! app 的網路：
+//! - host 上找得到 slirp4netns：所有 service 共用一個獨立的 network namespace（類似 pod），
+//!   彼此以 localhost 互連；對外連線走 slirp4netns，published port 由 runtime 的 proxy 轉進來
+//! - 找不到：沿用 host 網路；host 埠與容器埠相同的對應由 service 自己 bind
+//!
+//! namespace 由一個 holder 行程建立並持有，它同時負責在 namespace 內連到 service，
+//! 把連好的 socket 以 SCM_RIGHTS 傳回 runtime（runtime 自己沒有權限 setns 進去）。
+use anyhow::{Context, Result, bail};
+use std::{
+    ffi::CString,
+    fs::File,
+    io::{self, Read},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, UdpSocket},
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+        unix::process::CommandExt,
+    },
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tempfile::TempDir;
+
+use crate::ns::{close_from, die_with, fail, write_file};
+
+const SLIRP: &str = "slirp4netns";
+/// slirp4netns 內建的 DNS 轉送
+const SLIRP_DNS: &str = "10.0.2.3";
+
+/// 連到 service 的方式；tcp/udp 的連線目標都是該網路的 localhost
+#[derive(Debug, Clone)]
+pub enum Upstream {
+    Host,
+    Pod(Arc<Pod>),
+}
+
+impl Upstream {
+    /// 建立 app 的網路；沒有 slirp4netns 或建立失敗時退回 host 網路
+    pub fn create() -> Upstream {
+        let Some(slirp) = find_in_path(SLIRP) else {
+            tracing::info!("{SLIRP} not found; services share the host network");
+            return Upstream::Host;
+        };
+        match Pod::create(&slirp) {
+            Ok(pod) => {
+                tracing::debug!("app network namespace held by pid {}", pod.holder.id());
+                Upstream::Pod(Arc::new(pod))
+            }
+            Err(e) => {
+                tracing::warn!("cannot create the app network ({e:#}); using the host network");
+                Upstream::Host
+            }
+        }
+    }
+
+    pub fn pod(&self) -> Option<Arc<Pod>> {
+        match self {
+            Upstream::Host => None,
+            Upstream::Pod(p) => Some(p.clone()),
+        }
+    }
+
+    pub fn connect_tcp(&self, port: u16, timeout: Duration) -> io::Result<TcpStream> {
+        match self {
+            Upstream::Host => {
+                let mut last = None;
+                for ip in [Ipv4Addr::LOCALHOST.into(), Ipv6Addr::LOCALHOST.into()] {
+                    match TcpStream::connect_timeout(&SocketAddr::new(ip, port), timeout) {
+                        Ok(s) => return Ok(s),
+                        Err(e) => last = Some(e),
+                    }
+                }
+                Err(last.expect("tried at least one address"))
+            }
+            Upstream::Pod(p) => {
+                let s = TcpStream::from(p.connect(libc::SOCK_STREAM, port)?);
+                s.set_nonblocking(false)?;
+                Ok(s)
+            }
+        }
+    }
+
+    pub fn connect_udp(&self, port: u16) -> io::Result<UdpSocket> {
+        match self {
+            Upstream::Host => {
+                let s = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))?;
+                s.connect((Ipv4Addr::LOCALHOST, port))?;
+                Ok(s)
+            }
+            Upstream::Pod(p) => Ok(UdpSocket::from(p.connect(libc::SOCK_DGRAM, port)?)),
+        }
+    }
+}
+
+/// 持有 app network namespace 的行程與 slirp4netns；drop 時一併結束
+#[derive(Debug)]
+pub struct Pod {
+    holder: Child,
+    slirp: Child,
+    /// holder 的 user / network namespace，給容器 setns
+    pub user: File,
+    pub net: File,
+    /// 與 holder 的 SOCK_SEQPACKET；一次一個請求
+    helper: Mutex<OwnedFd>,
+    /// 容器內的 /etc/resolv.conf（指向 slirp 的 DNS）
+    pub resolv_conf: PathBuf,
+    _dir: TempDir,
+}
+
+impl Pod {
+    fn create(slirp: &Path) -> Result<Pod> {
+        let (ours, theirs) = seqpacket_pair()?;
+        let (uid, gid) = unsafe { (libc::geteuid(), libc::getegid()) };
+        let uid_map = CString::new(format!("0 {uid} 1"))?;
+        let gid_map = CString::new(format!("0 {gid} 1"))?;
+        let parent = std::process::id() as libc::pid_t;
+        let keep = theirs.as_raw_fd();
+
+        // 程式路徑不會用到：pre_exec 成功後就留在 serve，不會 exec
+        let mut cmd = Command::new("/proc/self/exe");
+        cmd.stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .process_group(0);
+        // SAFETY: 只做 syscall，字串都已事先配置
+        unsafe {
+            cmd.pre_exec(move || hold(parent, keep, &uid_map, &gid_map));
+        }
+        let mut holder = cmd.spawn().context("create network namespace")?;
+        drop(theirs);
+
+        let ns = |name: &str| File::open(format!("/proc/{}/ns/{name}", holder.id()));
+        let (user, net) = match (ns("user"), ns("net")) {
+            (Ok(u), Ok(n)) => (u, n),
+            (Err(e), _) | (_, Err(e)) => {
+                let _ = holder.kill();
+                let _ = holder.wait();
+                return Err(e).context("open the app network namespace");
+            }
+        };
+
+        let slirp = match start_slirp(slirp, holder.id()) {
+            Ok(s) => s,
+            Err(e) => {
+                let _ = holder.kill();
+                let _ = holder.wait();
+                return Err(e);
+            }
+        };
+
+        let dir = tempfile::Builder::new().prefix("chefer-net-").tempdir()?;
+        let resolv_conf = dir.path().join("resolv.conf");
+        fs_err::write(&resolv_conf, format!("nameserver {SLIRP_DNS}\n"))?;
+
+        Ok(Pod {
+            holder,
+            slirp,
+            user,
+            net,
+            helper: Mutex::new(ours),
+            resolv_conf,
+            _dir: dir,
+        })
+    }
+
+    /// 請 holder 在 namespace 內連到 localhost:`port`
+    fn connect(&self, kind: libc::c_int, port: u16) -> io::Result<OwnedFd> {
+        let sock = self.helper.lock().unwrap();
+        let [hi, lo] = port.to_be_bytes();
+        let req = [(kind == libc::SOCK_DGRAM) as u8, hi, lo];
+        let n = unsafe {
+            libc::send(
+                sock.as_raw_fd(),
+                req.as_ptr().cast(),
+                req.len(),
+                libc::MSG_NOSIGNAL,
+            )
+        };
+        if n != req.len() as isize {
+            return Err(io::Error::last_os_error());
+        }
+        recv_fd(sock.as_raw_fd())
+    }
+}
+
+impl Drop for Pod {
+    fn drop(&mut self) {
+        for c in [&mut self.slirp, &mut self.holder] {
+            let _ = c.kill();
+            let _ = c.wait();
+        }
+    }
+}
+
+fn find_in_path(name: &str) -> Option<PathBuf> {
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|d| d.join(name))
+        .find(|p| p.is_file())
+}
+
+fn seqpacket_pair() -> io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+    let r = unsafe {
+        libc::socketpair(
+            libc::AF_UNIX,
+            libc::SOCK_SEQPACKET | libc::SOCK_CLOEXEC,
+            0,
+            fds.as_mut_ptr(),
+        )
+    };
+    if r != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) })
+}
+
+/// slirp4netns 接上 holder 的 namespace（建立 tap0、設定位址與預設路由），設定好才返回
+fn start_slirp(slirp: &Path, holder: u32) -> Result<Child> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    let (ready_r, ready_w) =
+        unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+    let w = ready_w.as_raw_fd();
+    let parent = std::process::id() as libc::pid_t;
+
+    let mut cmd = Command::new(slirp);
+    cmd.args([
+        "--configure",
+        "--mtu=65520",
+        "--disable-host-loopback",
+        &format!("--ready-fd={w}"),
+        &holder.to_string(),
+        "tap0",
+    ])
+    .stdin(Stdio::null())
+    .stdout(Stdio::null())
+    .process_group(0);
+    // SAFETY: 只做 syscall
+    unsafe {
+        cmd.pre_exec(move || {
+            die_with(Some(parent))?;
+            // ready fd 要留給 slirp4netns
+            if libc::fcntl(w, libc::F_SETFD, 0) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let mut child = cmd
+        .spawn()
+        .with_context(|| format!("start {}", slirp.display()))?;
+    drop(ready_w);
+
+    let mut buf = [0u8; 1];
+    if File::from(ready_r).read(&mut buf)? == 0 {
+        let status = child.wait()?;
+        bail!("{SLIRP} exited before it was ready ({status})");
+    }
+    Ok(child)
+}
+
+/// holder（pre_exec 內）：建立 user + network namespace、啟用 lo，之後只服務連線請求
+fn hold(parent: libc::pid_t, sock: RawFd, uid_map: &CString, gid_map: &CString) -> io::Result<()> {
+    die_with(Some(parent))?;
+    if unsafe { libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNET) } != 0 {
+        return Err(fail(c"unshare network namespace"));
+    }
+    write_file(c"/proc/self/setgroups", c"deny")?;
+    write_file(c"/proc/self/uid_map", uid_map)?;
+    write_file(c"/proc/self/gid_map", gid_map)?;
+    loopback_up()?;
+
+    // 關掉 std 的錯誤回報 pipe 等其他 fd，spawn 才會返回
+    unsafe { libc::syscall(libc::SYS_close_range, 3, sock - 1, 0) };
+    close_from(sock as libc::c_uint + 1);
+    serve(sock)
+}
+
+fn loopback_up() -> io::Result<()> {
+    unsafe {
+        let fd = libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0);
+        if fd < 0 {
+            return Err(fail(c"socket for lo"));
+        }
+        let mut req: libc::ifreq = std::mem::zeroed();
+        for (d, s) in req.ifr_name.iter_mut().zip(b"lo\0") {
+            *d = *s as libc::c_char;
+        }
+        let ok = libc::ioctl(fd, libc::SIOCGIFFLAGS as _, &mut req) == 0 && {
+            req.ifr_ifru.ifru_flags |= (libc::IFF_UP | libc::IFF_RUNNING) as libc::c_short;
+            libc::ioctl(fd, libc::SIOCSIFFLAGS as _, &req) == 0
+        };
+        let err = (!ok).then(|| fail(c"bring up lo"));
+        libc::close(fd);
+        err.map_or(Ok(()), Err)
+    }
+}
+
+/// 請求：[udp?, port_hi, port_lo]；回覆：errno（0 = 成功）加上 SCM_RIGHTS 的 socket
+fn serve(sock: RawFd) -> ! {
+    loop {
+        let mut req = [0u8; 3];
+        let n = unsafe { libc::recv(sock, req.as_mut_ptr().cast(), req.len(), 0) };
+        if n < 0 && io::Error::last_os_error().raw_os_error() == Some(libc::EINTR) {
+            continue;
+        }
+        if n <= 0 {
+            // runtime 關掉了
+            unsafe { libc::_exit(0) };
+        }
+        if n as usize != req.len() {
+            continue;
+        }
+        let kind = if req[0] == 1 {
+            libc::SOCK_DGRAM
+        } else {
+            libc::SOCK_STREAM
+        };
+        let port = u16::from_be_bytes([req[1], req[2]]);
+        match connect_local(kind, port) {
+            Ok(fd) => {
+                send_fd(sock, fd, 0);
+                unsafe { libc::close(fd) };
+            }
+            Err(errno) => send_fd(sock, -1, errno),
+        }
+    }
+}
+
+/// 先試 127.0.0.1，tcp 被拒再試 ::1
+fn connect_local(kind: libc::c_int, port: u16) -> Result<RawFd, i32> {
+    let v4 = libc::sockaddr_in {
+        sin_family: libc::AF_INET as libc::sa_family_t,
+        sin_port: port.to_be(),
+        sin_addr: libc::in_addr {
+            s_addr: u32::from(Ipv4Addr::LOCALHOST).to_be(),
+        },
+        sin_zero: [0; 8],
+    };
+    let v6 = libc::sockaddr_in6 {
+        sin6_family: libc::AF_INET6 as libc::sa_family_t,
+        sin6_port: port.to_be(),
+        sin6_flowinfo: 0,
+        sin6_addr: libc::in6_addr {
+            s6_addr: Ipv6Addr::LOCALHOST.octets(),
+        },
+        sin6_scope_id: 0,
+    };
+    let attempts: [(libc::c_int, *const libc::sockaddr, libc::socklen_t); 2] = [
+        (
+            libc::AF_INET,
+            (&v4 as *const libc::sockaddr_in).cast(),
+            size_of::<libc::sockaddr_in>() as libc::socklen_t,
+        ),
+        (
+            libc::AF_INET6,
+            (&v6 as *const libc::sockaddr_in6).cast(),
+            size_of::<libc::sockaddr_in6>() as libc::socklen_t,
+        ),
+    ];
+    let mut errno = libc::ECONNREFUSED;
+    for (family, addr, len) in attempts {
+        unsafe {
+            let fd = libc::socket(family, kind | libc::SOCK_CLOEXEC, 0);
+            if fd < 0 {
+                errno = *libc::__errno_location();
+                continue;
+            }
+            if libc::connect(fd, addr, len) == 0 {
+                return Ok(fd);
+            }
+            errno = *libc::__errno_location();
+            libc::close(fd);
+        }
+        // udp 的 connect 不會被拒，第一個就夠了
+        if kind == libc::SOCK_DGRAM || errno != libc::ECONNREFUSED {
+            break;
+        }
+    }
+    Err(errno)
+}
+
+/// 控制訊息的空間（一個 fd 對齊後遠小於此）
+const CMSG_BUF: usize = 64;
+
+fn send_fd(sock: RawFd, fd: RawFd, errno: i32) {
+    unsafe {
+        let mut data = errno.to_ne_bytes();
+        let mut iov = libc::iovec {
+            iov_base: data.as_mut_ptr().cast(),
+            iov_len: data.len(),
+        };
+        let mut control = [0u64; CMSG_BUF / 8];
+        let mut msg: libc::msghdr = std::mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        if fd >= 0 {
+            msg.msg_control = control.as_mut_ptr().cast();
+            msg.msg_controllen = libc::CMSG_SPACE(size_of::<RawFd>() as u32) as _;
+            let c = libc::CMSG_FIRSTHDR(&msg);
+            (*c).cmsg_level = libc::SOL_SOCKET;
+            (*c).cmsg_type = libc::SCM_RIGHTS;
+            (*c).cmsg_len = libc::CMSG_LEN(size_of::<RawFd>() as u32) as _;
+            std::ptr::write_unaligned(libc::CMSG_DATA(c).cast::<RawFd>(), fd);
+        }
+        libc::sendmsg(sock, &msg, libc::MSG_NOSIGNAL);
+    }
+}
+
+fn recv_fd(sock: RawFd) -> io::Result<OwnedFd> {
+    unsafe {
+        let mut data = [0u8; 4];
+        let mut iov = libc::iovec {
+            iov_base: data.as_mut_ptr().cast(),
+            iov_len: data.len(),
+        };
+        let mut control = [0u64; CMSG_BUF / 8];
+        let mut msg: libc::msghdr = std::mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr().cast();
+        msg.msg_controllen = CMSG_BUF as _;
+        let n = libc::recvmsg(sock, &mut msg, libc::MSG_CMSG_CLOEXEC);
+        if n < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if n as usize != data.len() {
+            return Err(io::Error::other("network helper exited"));
+        }
+        let errno = i32::from_ne_bytes(data);
+        if errno != 0 {
+            return Err(io::Error::from_raw_os_error(errno));
+        }
+        let c = libc::CMSG_FIRSTHDR(&msg);
+        if c.is_null() || (*c).cmsg_type != libc::SCM_RIGHTS {
+            return Err(io::Error::other("network helper sent no socket"));
+        }
+        let fd = std::ptr::read_unaligned(libc::CMSG_DATA(c).cast::<RawFd>());
+        Ok(OwnedFd::from_raw_fd(fd))
+    }
+}
@@ -0,0 +1,938 @@
+// src/ns.rs
+//! Linux namespace backend：不需要 KVM，直接以 unprivileged user/mount/PID/UTS/IPC
+//! namespace 從 service 的 rootfs 執行 cmd。
+//!
+//! 每個 service 的行程樹：
+//! ```text
+//! runtime ── shim（unshare 後 fork；留在 host PID namespace，轉送訊號、回報 exit code）
+//!             └─ init（新 PID namespace 的 PID 1：掛載、pivot_root、收屍、轉送訊號）
+//!                 └─ app（exec cmd）
+//! ```
+//! rootfs 以 overlay 疊一層 tmpfs（可寫、退出即丟）；kernel 不支援時退回直接 bind。
+//! 有 app network namespace（net.rs）時 shim 先加入它的 user / network namespace，
+//! 否則自己建立 user namespace、與 host 共用網路。
+//! 接上終端機的 service 以 PTY 的 slave 端當 stdio 與控制終端（terminal.rs），app 在其中當前景。
+//! 任何一層的父行程死掉都會帶走子行程（PR_SET_PDEATHSIG），PID 1 結束時 kernel 會清掉整個 namespace。
+//! 有資源上限時 shim 在 unshare 之前先加入 service 的 cgroup（cgroup.rs），整棵行程樹都受限制。
+use anyhow::{Context, Result, bail};
+use chefer_manifest::{Cmd, DATA_SUBDIR, ServiceManifest};
+use std::{
+    ffi::{CStr, CString, OsString},
+    io,
+    os::{
+        fd::{AsRawFd, OwnedFd},
+        unix::{ffi::OsStrExt, process::CommandExt},
+    },
+    path::{Component, Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::{
+        Arc,
+        atomic::{AtomicI32, Ordering},
+    },
+};
+
+use crate::net::Pod;
+
+/// pivot_root 後舊的根暫時掛在這裡，用來 bind host 的路徑；設定完就卸載
+const HOST_DIR: &str = "/.chefer-host";
+
+const DEFAULT_PATH: &str = "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+
+/// 由 host 的 /dev 逐一 bind 進容器的裝置
+const DEVICES: &[&str] = &["null", "zero", "full", "random", "urandom", "tty"];
+
+/// 轉送給下一層的訊號
+const FORWARDED: &[libc::c_int] = &[
+    libc::SIGTERM,
+    libc::SIGINT,
+    libc::SIGHUP,
+    libc::SIGQUIT,
+    libc::SIGUSR1,
+    libc::SIGUSR2,
+    libc::SIGWINCH,
+    libc::SIGPWR,
+];
+
+/// 一個 service 在容器中的執行設定（由 manifest 轉換而來）
+#[derive(Debug, Clone)]
+pub struct Container {
+    pub name: String,
+    pub rootfs: PathBuf,
+    pub argv: Vec<String>,
+    /// 接在 argv 後面的參數（執行檔的命令列參數，只給主要 service）
+    pub args: Vec<OsString>,
+    pub env: Vec<(String, String)>,
+    /// 容器內的絕對路徑
+    pub workdir: String,
+    pub binds: Vec<Bind>,
+    /// app 的 network namespace；None 時與 host 共用網路
+    pub pod: Option<Arc<Pod>>,
+    /// service 的 cgroup 目錄；None 時留在 runtime 的 cgroup
+    pub cgroup: Option<PathBuf>,
+    /// 測試用的假容器：不建立 namespace，直接在 host 上執行 argv
+    #[cfg(test)]
+    pub on_host: bool,
+}
+
+/// app 的 stdio
+pub enum Io {
+    /// stdin 為 /dev/null，stdout/stderr 以 pipe 交給 logs.rs
+    Piped,
+    /// 沿用 runtime 的 stdio（接終端機的 service，但 runtime 的 stdin 不是終端機）
+    Inherit,
+    /// PTY 的 slave 端：app 的 stdio 與控制終端
+    Pty(OwnedFd),
+}
+
+/// host 路徑 bind 到容器內
+#[derive(Debug, Clone)]
+pub struct Bind {
+    /// 已 canonicalize 的 host 絕對路徑
+    pub host: PathBuf,
+    /// 容器內的絕對路徑
+    pub target: String,
+    pub is_dir: bool,
+    pub readonly: bool,
+    /// 掛不上就略過（可有可無的共用，例如字型）
+    pub optional: bool,
+}
+
+impl Container {
+    /// persist_path 對應到 `<data_dir>/data/<service>`；mounts 不存在的 host 路徑依 `create` 建立或報錯
+    pub fn from_manifest(
+        svc: &ServiceManifest,
+        bundle_dir: &Path,
+        data_dir: &Path,
+    ) -> Result<Self> {
+        let argv = match svc.cmd.as_ref().map(argv) {
+            Some(a) if !a.is_empty() => a,
+            _ => bail!(
+                "service `{}` has no cmd; set `cmd` in appcipe.yml",
+                svc.name
+            ),
+        };
+
+        let mut env: Vec<(String, String)> = vec![
+            ("PATH".into(), DEFAULT_PATH.into()),
+            ("HOME".into(), "/root".into()),
+            ("HOSTNAME".into(), svc.name.clone()),
+        ];
+        if let Ok(term) = std::env::var("TERM") {
+            env.push(("TERM".into(), term));
+        }
+        for (k, v) in &svc.env {
+            env.retain(|(ek, _)| ek != k);
+            env.push((k.clone(), v.clone()));
+        }
+
+        let workdir = match &svc.workdir {
+            Some(w) => {
+                container_path(w).with_context(|| format!("service `{}` workdir", svc.name))?
+            }
+            None => "/".into(),
+        };
+
+        let mut binds = Vec::new();
+        if let Some(p) = &svc.persist_path {
+            let host = data_dir.join(DATA_SUBDIR).join(&svc.name);
+            binds.push(
+                Bind::new(&host, p)
+                    .with_context(|| format!("service `{}` persist_path", svc.name))?,
+            );
+        }
+        // host 路徑已由 vars::expand 解析成絕對路徑
+        for m in &svc.mounts {
+            let host = Path::new(&m.source);
+            if !host.exists() {
+                if !m.create {
+                    bail!(
+                        "service `{}` mount host path not found: {} (add `create` to the mount to create it)",
+                        svc.name,
+                        host.display()
+                    );
+                }
+                fs_err::create_dir_all(host)
+                    .with_context(|| format!("service `{}` mount {m}", svc.name))?;
+            }
+            let bind = Bind::new(host, &m.target)
+                .with_context(|| format!("service `{}` mount {m}", svc.name))?;
+            binds.push(if m.read_only { bind.readonly() } else { bind });
+        }
+
+        Ok(Container {
+            name: svc.name.clone(),
+            rootfs: bundle_dir.join(&svc.rootfs_rel),
+            argv,
+            args: Vec::new(),
+            env,
+            workdir,
+            binds,
+            pod: None,
+            cgroup: None,
+            #[cfg(test)]
+            on_host: false,
+        })
+    }
+
+    /// 啟動容器；`scratch` 是 host 上一個空目錄（放 overlay 的 tmpfs），容器結束前不可刪除。
+    /// 回傳的 Child 是 shim：它的 exit code 就是 app 的（被訊號結束時為 128+signal）。
+    pub fn spawn(&self, scratch: &Path, io: Io) -> Result<Child> {
+        let controlling_tty = matches!(io, Io::Pty(_));
+        let mut cmd = Command::new(&self.argv[0]);
+        cmd.args(&self.argv[1..])
+            .args(&self.args)
+            .env_clear()
+            .envs(self.env.iter().map(|(k, v)| (k, v)))
+            // 終端的 Ctrl-C 只送到 runtime，由 runtime 決定怎麼停
+            .process_group(0);
+        match io {
+            Io::Piped => {
+                cmd.stdin(Stdio::null())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped());
+            }
+            Io::Inherit => {
+                cmd.stdin(Stdio::inherit())
+                    .stdout(Stdio::inherit())
+                    .stderr(Stdio::inherit());
+            }
+            Io::Pty(slave) => {
+                cmd.stdin(slave.try_clone()?)
+                    .stdout(slave.try_clone()?)
+                    .stderr(slave);
+            }
+        }
+        #[cfg(test)]
+        if self.on_host {
+            return Ok(cmd.spawn()?);
+        }
+        let mut plan = Plan::new(self, scratch)?;
+        plan.controlling_tty = controlling_tty;
+        // SAFETY: closure 只做 syscall，所需字串都已在 Plan 事先配置好
+        unsafe {
+            cmd.pre_exec(move || plan.enter());
+        }
+        cmd.spawn()
+            .with_context(|| format!("start service `{}` in namespace container", self.name))
+    }
+
+    /// 在執行中的容器內執行 `argv`（healthcheck 用）：進入 init 的 namespaces 後再 fork，
+    /// 指令才會在容器的 PID namespace 內。`shim` 是 [`Container::spawn`] 回傳的行程；
+    /// 回傳的 Child 留在外面，exit code 同指令，kill 它會帶走指令。
+    pub fn exec_in(&self, shim: u32, argv: &[String]) -> Result<Child> {
+        let Some((prog, args)) = argv.split_first() else {
+            bail!("empty command");
+        };
+        let init =
+            child_of(shim).with_context(|| format!("service `{}` is not running", self.name))?;
+        // user 要先進，之後才有權限進其他的；mnt 會把根目錄換成容器的
+        let mut names = vec!["user", "ipc", "uts", "pid", "mnt"];
+        if self.pod.is_some() {
+            names.insert(1, "net");
+        }
+        let fds = names
+            .iter()
+            .map(|ns| std::fs::File::open(format!("/proc/{init}/ns/{ns}")))
+            .collect::<io::Result<Vec<_>>>()
+            .with_context(|| format!("open namespaces of service `{}`", self.name))?;
+        let workdir = cstr(&self.workdir)?;
+        let parent = std::process::id() as libc::pid_t;
+        let cgroup = self.cgroup.as_deref().map(cgroup_procs).transpose()?;
+
+        let mut cmd = Command::new(prog);
+        cmd.args(args)
+            .env_clear()
+            .envs(self.env.iter().map(|(k, v)| (k, v)))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .process_group(0);
+        // SAFETY: closure 只做 syscall；fds、workdir、cgroup 都在 fork 前準備好，setns 的 fd 在 closure 內有效
+        unsafe {
+            cmd.pre_exec(move || {
+                die_with(Some(parent))?;
+                if let Some(procs) = &cgroup {
+                    write_file(procs, c"0")?;
+                }
+                for f in &fds {
+                    if libc::setns(f.as_raw_fd(), 0) != 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                libc::chdir(workdir.as_ptr());
+                match libc::fork() {
+                    -1 => Err(io::Error::last_os_error()),
+                    0 => die_with(None),
+                    child => supervise(child, true),
+                }
+            });
+        }
+        cmd.spawn()
+            .with_context(|| format!("run `{prog}` in service `{}`", self.name))
+    }
+}
+
+/// 寫入 "0" 即把自己移進這個 cgroup
+fn cgroup_procs(dir: &Path) -> Result<CString> {
+    cstr(dir.join("cgroup.procs"))
+}
+
+/// 找 `pid` 的子行程（shim 只有一個子行程：容器的 init）
+fn child_of(pid: u32) -> Result<u32> {
+    let children = fs_err::read_to_string(format!("/proc/{pid}/task/{pid}/children"));
+    if let Ok(s) = children
+        && let Some(c) = s.split_whitespace().next()
+    {
+        return Ok(c.parse()?);
+    }
+    // 沒有 CONFIG_PROC_CHILDREN 時掃 /proc
+    for e in fs_err::read_dir("/proc")? {
+        let e = e?;
+        let Some(candidate) = e.file_name().to_str().and_then(|n| n.parse::<u32>().ok()) else {
+            continue;
+        };
+        let Ok(stat) = std::fs::read_to_string(e.path().join("stat")) else {
+            continue;
+        };
+        // "pid (comm) state ppid ..."；comm 可能含空白，從最後一個 ')' 之後算
+        let ppid = stat
+            .rsplit_once(')')
+            .and_then(|(_, rest)| rest.split_whitespace().nth(1))
+            .and_then(|p| p.parse::<u32>().ok());
+        if ppid == Some(pid) {
+            return Ok(candidate);
+        }
+    }
+    bail!("process {pid} has no child")
+}
+
+impl Bind {
+    pub fn new(host: &Path, target: &str) -> Result<Self> {
+        let host = fs_err::canonicalize(host)?;
+        let is_dir = host.is_dir();
+        Ok(Bind {
+            host,
+            target: container_path(target)?,
+            is_dir,
+            readonly: false,
+            optional: false,
+        })
+    }
+
+    pub fn readonly(mut self) -> Self {
+        self.readonly = true;
+        self
+    }
+
+    pub fn optional(mut self) -> Self {
+        self.optional = true;
+        self
+    }
+}
+
+/// 字串寫法交給 /bin/sh -c
+pub fn argv(cmd: &Cmd) -> Vec<String> {
+    match cmd {
+        Cmd::String(s) => vec!["/bin/sh".into(), "-c".into(), s.clone()],
+        Cmd::Array(a) => a.clone(),
+    }
+}
+
+/// 容器內路徑：相對路徑視為從根開始（"./app" → "/app"），不允許 ".."
+fn container_path(p: &str) -> Result<String> {
+    let mut out = String::new();
+    for c in Path::new(p).components() {
+        match c {
+            Component::RootDir | Component::CurDir => {}
+            Component::Normal(s) => {
+                out.push('/');
+                out.push_str(&s.to_string_lossy());
+            }
+            _ => bail!("container path `{p}` must not contain `..`"),
+        }
+    }
+    if out.is_empty() {
+        out.push('/');
+    }
+    Ok(out)
+}
+
+fn cstr(p: impl AsRef<Path>) -> Result<CString> {
+    CString::new(p.as_ref().as_os_str().as_bytes()).context("path contains NUL byte")
+}
+
+/// host 路徑在 pivot_root 之後的位置
+fn via_host(p: &Path) -> PathBuf {
+    Path::new(HOST_DIR).join(p.strip_prefix("/").unwrap_or(p))
+}
+
+/// 來源掛載點的這些旗標在 user namespace 內是鎖住的；重新掛成唯讀時要一併帶上，否則會被拒絕
+fn locked_flags(p: &Path) -> libc::c_ulong {
+    let Ok(path) = cstr(p) else { return 0 };
+    // SAFETY: statvfs 全零是合法初值；path 是合法的 NUL 結尾字串，st 在呼叫期間有效
+    let mut st: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut st) } != 0 {
+        return 0;
+    }
+    [
+        (libc::ST_NOSUID, libc::MS_NOSUID),
+        (libc::ST_NODEV, libc::MS_NODEV),
+        (libc::ST_NOEXEC, libc::MS_NOEXEC),
+        (libc::ST_NOATIME, libc::MS_NOATIME),
+        (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+        (libc::ST_RELATIME, libc::MS_RELATIME),
+    ]
+    .into_iter()
+    .filter(|(st_flag, _)| st.f_flag & st_flag != 0)
+    .fold(0, |flags, (_, ms)| flags | ms)
+}
+
+/// 容器內建立 `target` 的所有上層目錄，最後建立目錄或空檔當掛載點
+fn mountpoint(ops: &mut Vec<Op>, target: &str, is_dir: bool) -> Result<()> {
+    let mut cur = String::new();
+    let parts: Vec<&str> = target.split('/').filter(|s| !s.is_empty()).collect();
+    for (i, part) in parts.iter().enumerate() {
+        cur.push('/');
+        cur.push_str(part);
+        if i + 1 < parts.len() || is_dir {
+            ops.push(Op::Mkdir(cstr(&cur)?));
+        } else {
+            ops.push(Op::Touch(cstr(&cur)?));
+        }
+    }
+    Ok(())
+}
+
+/// 進容器前的步驟。建目錄、建檔、symlink 失敗都忽略（已存在或唯讀時由後面的 mount 報錯）
+enum Op {
+    Mkdir(CString),
+    Touch(CString),
+    Symlink(CString, CString),
+    Mount(Mount),
+}
+
+struct Mount {
+    src: CString,
+    dst: CString,
+    fstype: Option<CString>,
+    flags: libc::c_ulong,
+    data: Option<CString>,
+    /// 失敗時略過（例如 devpts、/sys）
+    optional: bool,
+    /// 失敗時印到 stderr 的說明
+    what: CString,
+}
+
+impl Mount {
+    fn new(
+        src: &str,
+        dst: &str,
+        fstype: Option<&str>,
+        flags: libc::c_ulong,
+        data: Option<&str>,
+    ) -> Result<Self> {
+        Ok(Mount {
+            src: cstr(src)?,
+            dst: cstr(dst)?,
+            fstype: fstype.map(cstr).transpose()?,
+            flags,
+            data: data.map(cstr).transpose()?,
+            optional: false,
+            what: CString::new(format!("mount {dst}"))?,
+        })
+    }
+    fn bind(src: &Path, dst: &str) -> Result<Self> {
+        Ok(Mount {
+            src: cstr(src)?,
+            dst: cstr(dst)?,
+            fstype: None,
+            flags: libc::MS_BIND | libc::MS_REC,
+            data: None,
+            optional: false,
+            what: CString::new(format!("mount {dst}"))?,
+        })
+    }
+    fn optional(mut self) -> Self {
+        self.optional = true;
+        self
+    }
+
+    /// 失敗時回傳 Err（不論 optional）
+    fn try_run(&self) -> io::Result<()> {
+        let opt = |c: &Option<CString>| c.as_ref().map_or(std::ptr::null(), |c| c.as_ptr());
+        // SAFETY: src/dst/fstype/data 都是 self 持有的 NUL 結尾字串或 null，呼叫期間有效
+        let r = unsafe {
+            libc::mount(
+                self.src.as_ptr(),
+                self.dst.as_ptr(),
+                opt(&self.fstype),
+                self.flags,
+                opt(&self.data).cast(),
+            )
+        };
+        if r == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    fn run(&self) -> io::Result<()> {
+        match self.try_run() {
+            Err(_) if !self.optional => Err(fail(&self.what)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// 所有字串都在 fork 前配置好；子行程內只做 syscall
+struct Plan {
+    parent: libc::pid_t,
+    /// 要加入的 app network namespace（持有到 spawn 結束）
+    pod: Option<Arc<Pod>>,
+    uid_map: CString,
+    gid_map: CString,
+    hostname: CString,
+    scratch: CString,
+    upper: CString,
+    work: CString,
+    root: CString,
+    host_dir: CString,
+    /// 依序嘗試：overlay（userxattr）→ overlay → 直接 bind
+    root_mounts: Vec<Mount>,
+    /// pivot_root 之後、卸載舊根之前
+    ops: Vec<Op>,
+    workdir: CString,
+    /// app 的 stdio 是 PTY：init 開新 session 設為控制終端，app 當前景
+    controlling_tty: bool,
+    /// 要加入的 service cgroup 的 cgroup.procs
+    cgroup: Option<CString>,
+}
+
+impl Plan {
+    fn new(c: &Container, scratch: &Path) -> Result<Self> {
+        let rootfs = fs_err::canonicalize(&c.rootfs)?;
+        // SAFETY: geteuid/getegid 沒有前置條件
+        let (uid, gid) = unsafe { (libc::geteuid(), libc::getegid()) };
+        let root = scratch.join("root");
+        let lower = rootfs.to_string_lossy();
+
+        let mut root_mounts = Vec::new();
+        // overlay 的選項以 ',' 分隔、lowerdir 以 ':' 分隔；路徑含這些字元就只能 bind
+        if !lower.contains([',', ':']) {
+            let data = format!(
+                "lowerdir={lower},upperdir={},workdir={}",
+                scratch.join("upper").display(),
+                scratch.join("work").display()
+            );
+            let dst = root.to_string_lossy();
+            for d in [format!("{data},userxattr"), data] {
+                root_mounts.push(Mount::new("overlay", &dst, Some("overlay"), 0, Some(&d))?);
+            }
+        }
+        let mut bind = Mount::bind(&rootfs, &root.to_string_lossy())?;
+        bind.what = CString::new(format!("bind rootfs {}", rootfs.display()))?;
+        root_mounts.push(bind);
+
+        let nosuid_nodev = libc::MS_NOSUID | libc::MS_NODEV;
+        let mut ops = vec![
+            Op::Mkdir(cstr("/proc")?),
+            Op::Mount(Mount::new(
+                "proc",
+                "/proc",
+                Some("proc"),
+                nosuid_nodev | libc::MS_NOEXEC,
+                None,
+            )?),
+            Op::Mkdir(cstr("/dev")?),
+            Op::Mount(Mount::new(
+                "tmpfs",
+                "/dev",
+                Some("tmpfs"),
+                libc::MS_NOSUID | libc::MS_STRICTATIME,
+                Some("mode=755,size=65536k"),
+            )?),
+        ];
+        for d in DEVICES {
+            let host = Path::new("/dev").join(d);
+            if host.exists() {
+                let dst = format!("/dev/{d}");
+                ops.push(Op::Touch(cstr(&dst)?));
+                ops.push(Op::Mount(Mount::bind(&via_host(&host), &dst)?));
+            }
+        }
+        ops.extend([
+            Op::Mkdir(cstr("/dev/pts")?),
+            Op::Mount(
+                Mount::new(
+                    "devpts",
+                    "/dev/pts",
+                    Some("devpts"),
+                    libc::MS_NOSUID | libc::MS_NOEXEC,
+                    Some("newinstance,ptmxmode=0666,mode=0620"),
+                )?
+                .optional(),
+            ),
+            Op::Symlink(cstr("pts/ptmx")?, cstr("/dev/ptmx")?),
+            Op::Mkdir(cstr("/dev/shm")?),
+            Op::Mount(Mount::new(
+                "shm",
+                "/dev/shm",
+                Some("tmpfs"),
+                nosuid_nodev,
+                Some("mode=1777"),
+            )?),
+            Op::Symlink(cstr("/proc/self/fd")?, cstr("/dev/fd")?),
+            Op::Symlink(cstr("/proc/self/fd/0")?, cstr("/dev/stdin")?),
+            Op::Symlink(cstr("/proc/self/fd/1")?, cstr("/dev/stdout")?),
+            Op::Symlink(cstr("/proc/self/fd/2")?, cstr("/dev/stderr")?),
+            Op::Mkdir(cstr("/tmp")?),
+            Op::Mount(Mount::new(
+                "tmpfs",
+                "/tmp",
+                Some("tmpfs"),
+                nosuid_nodev,
+                Some("mode=1777"),
+            )?),
+            // sysfs 只有在自己的 network namespace 內才能重新掛載；一律沿用 host 的
+            Op::Mkdir(cstr("/sys")?),
+            Op::Mount(Mount::bind(&via_host(Path::new("/sys")), "/sys")?.optional()),
+        ]);
+        // 名稱解析跟 host 一致；app network 的 DNS 由 slirp4netns 轉送
+        let resolv_conf = match &c.pod {
+            Some(pod) => pod.resolv_conf.clone(),
+            None => PathBuf::from("/etc/resolv.conf"),
+        };
+        for (f, src) in [
+            ("/etc/resolv.conf", resolv_conf.as_path()),
+            ("/etc/hosts", Path::new("/etc/hosts")),
+        ] {
+            if let Ok(host) = fs_err::canonicalize(src) {
+                ops.push(Op::Mkdir(cstr("/etc")?));
+                ops.push(Op::Touch(cstr(f)?));
+                ops.push(Op::Mount(Mount::bind(&via_host(&host), f)?.optional()));
+            }
+        }
+        for b in &c.binds {
+            mountpoint(&mut ops, &b.target, b.is_dir)?;
+            let mut m = Mount::bind(&via_host(&b.host), &b.target)?;
+            m.what = CString::new(format!("bind {} -> {}", b.host.display(), b.target))?;
+            m.optional = b.optional;
+            ops.push(Op::Mount(m));
+            if b.readonly {
+                let flags =
+                    libc::MS_BIND | libc::MS_REMOUNT | libc::MS_RDONLY | locked_flags(&b.host);
+                ops.push(Op::Mount(
+                    Mount::new("none", &b.target, None, flags, None)?.optional(),
+                ));
+            }
+        }
+
+        Ok(Plan {
+            parent: std::process::id() as libc::pid_t,
+            pod: c.pod.clone(),
+            uid_map: CString::new(format!("0 {uid} 1"))?,
+            gid_map: CString::new(format!("0 {gid} 1"))?,
+            hostname: CString::new(c.name.as_str())?,
+            scratch: cstr(scratch)?,
+            upper: cstr(scratch.join("upper"))?,
+            work: cstr(scratch.join("work"))?,
+            root: cstr(&root)?,
+            host_dir: cstr(HOST_DIR)?,
+            root_mounts,
+            ops,
+            workdir: cstr(&c.workdir)?,
+            controlling_tty: false,
+            cgroup: c.cgroup.as_deref().map(cgroup_procs).transpose()?,
+        })
+    }
+
+    /// pre_exec 內執行（shim 行程）。只有 app 會從這裡返回去 exec；
+    /// shim 與 init 留在這裡直到結束。init 設定失敗時回傳 Err，由 std 回報給 spawn。
+    fn enter(&self) -> io::Result<()> {
+        die_with(Some(self.parent))?;
+        // 進 user namespace 之前：cgroup 目錄屬於 host 上的使用者
+        if let Some(procs) = &self.cgroup {
+            write_file(procs, c"0")?;
+        }
+        let mut flags =
+            libc::CLONE_NEWNS | libc::CLONE_NEWPID | libc::CLONE_NEWUTS | libc::CLONE_NEWIPC;
+        match &self.pod {
+            // 它的 user namespace 已經有 uid/gid 對應
+            // SAFETY: pod 持有兩個 namespace fd 直到 spawn 結束，fd 在這裡都有效
+            Some(pod) => unsafe {
+                if libc::setns(pod.user.as_raw_fd(), libc::CLONE_NEWUSER) != 0
+                    || libc::setns(pod.net.as_raw_fd(), libc::CLONE_NEWNET) != 0
+                {
+                    return Err(fail(c"join the app network namespace"));
+                }
+            },
+            None => flags |= libc::CLONE_NEWUSER,
+        }
+        // SAFETY: unshare 只影響呼叫的行程（fork 出來、尚未 exec 的 shim）
+        if unsafe { libc::unshare(flags) } != 0 {
+            return Err(fail(
+                c"unshare namespaces (are unprivileged user namespaces enabled?)",
+            ));
+        }
+        if self.pod.is_none() {
+            write_file(c"/proc/self/setgroups", c"deny")?;
+            write_file(c"/proc/self/uid_map", &self.uid_map)?;
+            write_file(c"/proc/self/gid_map", &self.gid_map)?;
+        }
+
+        // SAFETY: 在 pre_exec 內：子行程只做 syscall、不配置記憶體也不碰鎖，直到 exec 或 _exit
+        match unsafe { libc::fork() } {
+            -1 => Err(io::Error::last_os_error()),
+            0 => {
+                die_with(None)?;
+                self.init()
+            }
+            child => supervise(child, true),
+        }
+    }
+
+    /// 新 PID namespace 的 PID 1
+    fn init(&self) -> io::Result<()> {
+        self.setup_root()?;
+        // init 當 PTY 的 session leader，app 另開 process group 當前景：
+        // app 的 process group 在 session 內有父行程、不是 orphaned，Ctrl-Z（SIGTSTP）才停得下來
+        // SAFETY: setsid/ioctl 只作用在目前行程與 fd 0（spawn 時設為 PTY slave）
+        if self.controlling_tty
+            && unsafe { libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY, 0) != 0 }
+        {
+            return Err(fail(c"make the pty the controlling terminal"));
+        }
+
+        // SAFETY: getpid 沒有前置條件；fork 同上，子行程在 exec 前只做 syscall
+        let init = unsafe { libc::getpid() };
+        match unsafe { libc::fork() } {
+            -1 => Err(io::Error::last_os_error()),
+            0 => {
+                die_with(Some(init))?;
+                if self.controlling_tty {
+                    foreground()?;
+                }
+                Ok(())
+            }
+            app => supervise(app, false),
+        }
+    }
+
+    fn setup_root(&self) -> io::Result<()> {
+        // SAFETY: 所有指標都是 c"" 常數或 self 持有的 NUL 結尾字串；此時已在自己的 mount namespace 內
+        unsafe {
+            let none = c"none".as_ptr();
+            if libc::mount(
+                none,
+                c"/".as_ptr(),
+                std::ptr::null(),
+                libc::MS_REC | libc::MS_PRIVATE,
+                std::ptr::null(),
+            ) != 0
+            {
+                return Err(fail(c"make mounts private"));
+            }
+            if libc::mount(
+                c"tmpfs".as_ptr(),
+                self.scratch.as_ptr(),
+                c"tmpfs".as_ptr(),
+                0,
+                c"mode=755".as_ptr().cast(),
+            ) != 0
+            {
+                return Err(fail(c"mount scratch tmpfs"));
+            }
+            for d in [&self.upper, &self.work, &self.root] {
+                libc::mkdir(d.as_ptr(), 0o755);
+            }
+        }
+        // 最後一個（bind）才報錯
+        let (last, overlays) = self
+            .root_mounts
+            .split_last()
+            .expect("bind is always planned");
+        if !overlays.iter().any(|m| m.try_run().is_ok()) {
+            last.run()?;
+        }
+
+        // SAFETY: 同上，指標都指向常數或 self 持有的字串；pivot_root 只影響這個 mount namespace
+        unsafe {
+            if libc::chdir(self.root.as_ptr()) != 0 {
+                return Err(fail(c"chdir to new root"));
+            }
+            libc::mkdir(c".chefer-host".as_ptr(), 0o700);
+            if libc::syscall(
+                libc::SYS_pivot_root,
+                c".".as_ptr(),
+                c".chefer-host".as_ptr(),
+            ) != 0
+            {
+                return Err(fail(
+                    c"pivot_root (try --chefer-no-mount if the rootfs is read-only)",
+                ));
+            }
+            libc::chdir(c"/".as_ptr());
+        }
+
+        for op in &self.ops {
+            match op {
+                // SAFETY: 以下 p、target、link 都是 Plan 事先配置好的 NUL 結尾字串；open 成功的 fd 立即關閉
+                Op::Mkdir(p) => unsafe {
+                    libc::mkdir(p.as_ptr(), 0o755);
+                },
+                Op::Touch(p) => unsafe {
+                    let fd = libc::open(
+                        p.as_ptr(),
+                        libc::O_WRONLY | libc::O_CREAT | libc::O_CLOEXEC,
+                        0o644,
+                    );
+                    if fd >= 0 {
+                        libc::close(fd);
+                    }
+                },
+                Op::Symlink(target, link) => unsafe {
+                    libc::symlink(target.as_ptr(), link.as_ptr());
+                },
+                Op::Mount(m) => m.run()?,
+            }
+        }
+
+        // SAFETY: host_dir/hostname/workdir 由 self 持有；sethostname 帶明確長度，不需 NUL 結尾
+        unsafe {
+            if libc::umount2(self.host_dir.as_ptr(), libc::MNT_DETACH) != 0 {
+                return Err(fail(c"detach host root"));
+            }
+            libc::rmdir(self.host_dir.as_ptr());
+            let name = self.hostname.as_bytes();
+            libc::sethostname(name.as_ptr().cast(), name.len());
+            if libc::chdir(self.workdir.as_ptr()) != 0 {
+                return Err(fail(c"chdir to workdir"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 自成一個 process group 並設為終端機的前景（背景呼叫 tcsetpgrp 要先忽略 SIGTTOU）
+fn foreground() -> io::Result<()> {
+    // SAFETY: 只作用在目前行程與 fd 0；SIGTTOU 的處理在返回前還原
+    unsafe {
+        libc::setpgid(0, 0);
+        libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+        let ok = libc::tcsetpgrp(0, libc::getpid()) == 0;
+        libc::signal(libc::SIGTTOU, libc::SIG_DFL);
+        if !ok {
+            return Err(fail(c"take the terminal foreground"));
+        }
+    }
+    Ok(())
+}
+
+/// 父行程結束時自己也收到 SIGKILL；設定前父行程已經不在就直接結束。
+/// 新 PID namespace 的 PID 1 看不到父行程（getppid 為 0），無法檢查，傳 None。
+pub fn die_with(parent: Option<libc::pid_t>) -> io::Result<()> {
+    // SAFETY: prctl/getppid 沒有指標參數；_exit 不跑 atexit，在 fork 後的子行程中是安全的
+    unsafe {
+        if libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if parent.is_some_and(|p| libc::getppid() != p) {
+            libc::_exit(1);
+        }
+    }
+    Ok(())
+}
+
+pub fn write_file(path: &CStr, content: &CStr) -> io::Result<()> {
+    // SAFETY: path 是 NUL 結尾字串；寫入範圍就是 content 的位元組；fd 用完即關
+    unsafe {
+        let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+        if fd < 0 {
+            return Err(fail(path));
+        }
+        let bytes = content.to_bytes();
+        let n = libc::write(fd, bytes.as_ptr().cast(), bytes.len());
+        libc::close(fd);
+        if n != bytes.len() as isize {
+            return Err(fail(path));
+        }
+    }
+    Ok(())
+}
+
+/// 保留 errno，並把哪一步失敗印到 stderr（spawn 的錯誤只帶得回 errno）
+pub fn fail(what: &CStr) -> io::Error {
+    let err = io::Error::last_os_error();
+    let mut line = [0u8; 512];
+    let mut n = 0;
+    for part in [
+        b"chefer-runtime: container setup failed: ".as_slice(),
+        what.to_bytes(),
+        b"\n",
+    ] {
+        let take = part.len().min(line.len() - n);
+        line[n..n + take].copy_from_slice(&part[..take]);
+        n += take;
+    }
+    // SAFETY: n 不超過 line 的長度；write 是 async-signal-safe，fork 後也能用
+    unsafe { libc::write(2, line.as_ptr().cast(), n) };
+    err
+}
+
+/// 訊號要轉送到的行程（fork 後各行程有各自的一份）
+static FORWARD_TO: AtomicI32 = AtomicI32::new(0);
+
+extern "C" fn forward(sig: libc::c_int) {
+    let pid = FORWARD_TO.load(Ordering::Relaxed);
+    if pid > 0 {
+        // SAFETY: kill 是 async-signal-safe，可以在訊號處理函式內呼叫
+        unsafe { libc::kill(pid, sig) };
+    }
+}
+
+/// shim 與 init 共用：轉送訊號給 `child`、等它結束並以相同的 exit code 結束自己。
+/// init 另外負責收養孤兒行程（reap）。
+fn supervise(child: libc::pid_t, is_shim: bool) -> ! {
+    FORWARD_TO.store(child, Ordering::Relaxed);
+    // SAFETY: forward 只讀 atomic 並呼叫 kill，可當訊號處理函式；status 在 waitpid 期間有效；
+    // 這個函式不返回，_exit 不會跑到 std 的清理
+    unsafe {
+        for &sig in FORWARDED {
+            libc::signal(
+                sig,
+                forward as extern "C" fn(libc::c_int) as libc::sighandler_t,
+            );
+        }
+        // 不保留 std 的錯誤回報 pipe 等任何繼承來的 fd，spawn 才會在 app exec 後返回
+        close_from(3);
+        loop {
+            let mut status = 0;
+            let pid = libc::waitpid(if is_shim { child } else { -1 }, &mut status, 0);
+            if pid == child {
+                let code = if libc::WIFSIGNALED(status) {
+                    128 + libc::WTERMSIG(status)
+                } else {
+                    libc::WEXITSTATUS(status)
+                };
+                libc::_exit(code);
+            }
+            if pid == -1 && io::Error::last_os_error().raw_os_error() != Some(libc::EINTR) {
+                libc::_exit(1);
+            }
+        }
+    }
+}
+
+pub fn close_from(first: libc::c_uint) {
+    // SAFETY: 只關閉 fd；呼叫端（fork 後、exec 前）不再使用 first 以上的 fd
+    unsafe {
+        if libc::syscall(libc::SYS_close_range, first, libc::c_uint::MAX, 0) == 0 {
+            return;
+        }
+        let max = libc::sysconf(libc::_SC_OPEN_MAX).clamp(0, 65536) as libc::c_int;
+        for fd in first as libc::c_int..max {
+            libc::close(fd);
+        }
+    }
+}
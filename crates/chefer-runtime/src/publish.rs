@@ -0,0 +1,182 @@
+// src/publish.rs
+//! published port：在 host 上開 listener，每個連線（udp 為每個來源位址）經 [`Upstream`]
+//! 連到 service 再雙向轉送。啟動任何 service 前先全部 bind，埠被占用就直接報錯。
+use anyhow::{Context, Result, anyhow};
+use chefer_manifest::{Manifest, PortMapping, Protocol};
+use std::{
+    collections::HashMap,
+    io,
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream, UdpSocket},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::net::Upstream;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// udp 沒有連線的概念：這麼久沒有回應就丟掉對應
+const UDP_IDLE: Duration = Duration::from_secs(60);
+
+/// host 上實際 bind 的位址（host 埠為 0 時由系統挑）
+pub struct Published {
+    pub service: String,
+    pub mapping: PortMapping,
+    pub addr: SocketAddr,
+}
+
+/// bind 所有 published port 並開始轉送；listener 跟著 thread 活到 runtime 結束
+pub fn publish(mani: &Manifest, upstream: &Upstream) -> Result<Vec<Published>> {
+    let mut published = Vec::new();
+    for svc in &mani.services {
+        for m in svc.port_mappings()? {
+            // 共用 host 網路時 service 自己會 bind 同一個埠；只確認現在沒被占用
+            if matches!(upstream, Upstream::Host) && m.host == m.guest {
+                bind(&m, &svc.name)?;
+                tracing::debug!("service `{}` publishes {m} itself (host network)", svc.name);
+                published.push(Published {
+                    service: svc.name.clone(),
+                    addr: SocketAddr::new(m.bind, m.host),
+                    mapping: m,
+                });
+                continue;
+            }
+            let listener = bind(&m, &svc.name)?;
+            published.push(Published {
+                service: svc.name.clone(),
+                addr: match &listener {
+                    Listener::Tcp(l) => l.local_addr()?,
+                    Listener::Udp(s) => s.local_addr()?,
+                },
+                mapping: m,
+            });
+            match listener {
+                Listener::Tcp(l) => {
+                    tracing::info!(
+                        "service `{}`: {} -> {}/tcp",
+                        svc.name,
+                        l.local_addr()?,
+                        m.guest
+                    );
+                    let up = upstream.clone();
+                    std::thread::Builder::new()
+                        .name(format!("publish-{}", m.guest))
+                        .spawn(move || serve_tcp(l, up, m.guest))?;
+                }
+                Listener::Udp(s) => {
+                    tracing::info!(
+                        "service `{}`: {} -> {}/udp",
+                        svc.name,
+                        s.local_addr()?,
+                        m.guest
+                    );
+                    let up = upstream.clone();
+                    std::thread::Builder::new()
+                        .name(format!("publish-{}", m.guest))
+                        .spawn(move || serve_udp(s, up, m.guest))?;
+                }
+            }
+        }
+    }
+    Ok(published)
+}
+
+enum Listener {
+    Tcp(TcpListener),
+    Udp(UdpSocket),
+}
+
+fn bind(m: &PortMapping, service: &str) -> Result<Listener> {
+    let addr = SocketAddr::new(m.bind, m.host);
+    let result = match m.proto {
+        Protocol::Tcp => TcpListener::bind(addr).map(Listener::Tcp),
+        Protocol::Udp => UdpSocket::bind(addr).map(Listener::Udp),
+    };
+    result.map_err(|e| {
+        let what = format!("{addr}/{}", m.proto.as_str());
+        match e.kind() {
+            io::ErrorKind::AddrInUse => {
+                anyhow!("host port {what} for service `{service}` is already in use")
+            }
+            io::ErrorKind::PermissionDenied => anyhow!(
+                "not allowed to bind host port {what} for service `{service}` (ports below 1024 need privileges)"
+            ),
+            _ => anyhow::Error::new(e)
+                .context(format!("bind host port {what} for service `{service}`")),
+        }
+    })
+}
+
+fn serve_tcp(listener: TcpListener, upstream: Upstream, port: u16) {
+    for conn in listener.incoming() {
+        let Ok(client) = conn else { continue };
+        let up = upstream.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = proxy_tcp(client, &up, port) {
+                tracing::debug!("port {port}/tcp: {e:#}");
+            }
+        });
+    }
+}
+
+fn proxy_tcp(client: TcpStream, upstream: &Upstream, port: u16) -> Result<()> {
+    let server = upstream
+        .connect_tcp(port, CONNECT_TIMEOUT)
+        .context("connect to service")?;
+    let (mut c_read, mut s_write) = (client.try_clone()?, server.try_clone()?);
+    let up = std::thread::spawn(move || {
+        let _ = io::copy(&mut c_read, &mut s_write);
+        let _ = s_write.shutdown(Shutdown::Write);
+    });
+    let (mut s_read, mut c_write) = (server, client);
+    let _ = io::copy(&mut s_read, &mut c_write);
+    let _ = c_write.shutdown(Shutdown::Write);
+    let _ = up.join();
+    Ok(())
+}
+
+fn serve_udp(socket: UdpSocket, upstream: Upstream, port: u16) {
+    let socket = Arc::new(socket);
+    let sessions: Arc<Mutex<HashMap<SocketAddr, Arc<UdpSocket>>>> = Default::default();
+    let mut buf = vec![0u8; 65536];
+    loop {
+        let Ok((n, client)) = socket.recv_from(&mut buf) else {
+            continue;
+        };
+        let existing = sessions.lock().unwrap().get(&client).cloned();
+        let server = match existing {
+            Some(s) => s,
+            None => match udp_session(&socket, &upstream, port, client, &sessions) {
+                Ok(s) => s,
+                Err(e) => {
+                    tracing::debug!("port {port}/udp: {e:#}");
+                    continue;
+                }
+            },
+        };
+        let _ = server.send(&buf[..n]);
+    }
+}
+
+/// 新的來源位址：連一個 udp socket 到 service，回應由另一條 thread 送回
+fn udp_session(
+    socket: &Arc<UdpSocket>,
+    upstream: &Upstream,
+    port: u16,
+    client: SocketAddr,
+    sessions: &Arc<Mutex<HashMap<SocketAddr, Arc<UdpSocket>>>>,
+) -> Result<Arc<UdpSocket>> {
+    let server = Arc::new(upstream.connect_udp(port).context("connect to service")?);
+    server.set_read_timeout(Some(UDP_IDLE))?;
+    sessions.lock().unwrap().insert(client, server.clone());
+
+    let (socket, sessions, reply) = (socket.clone(), sessions.clone(), server.clone());
+    std::thread::spawn(move || {
+        let mut buf = vec![0u8; 65536];
+        // 逾時或 service 不在（ECONNREFUSED）就結束這個對應
+        while let Ok(n) = reply.recv(&mut buf) {
+            let _ = socket.send_to(&buf[..n], client);
+        }
+        sessions.lock().unwrap().remove(&client);
+    });
+    Ok(server)
+}
@@ -1,24 +1,57 @@
 // src/run.rs
 use anyhow::Result;
 use camino::Utf8PathBuf;
-use fs_err as fs;
+use chefer_manifest::Manifest;
+use std::{
+    ffi::OsString,
+    path::PathBuf,
+    sync::{Arc, atomic::AtomicBool},
+};
 
 #[derive(Debug)]
 pub struct RuntimeContext {
     pub bundle_dir: Utf8PathBuf,
+    /// 已驗證過的 manifest.json
+    pub manifest: Manifest,
+    /// app 資料夾（已建立，含 data/<service>）
+    pub data_dir: PathBuf,
+    /// 非 `--chefer-*` 的命令列參數，原樣交給 app
+    pub app_args: Vec<OsString>,
+    /// `--chefer-logs`：console 只顯示這些 service 的輸出
+    pub log_filter: Vec<String>,
+    /// `--chefer-browser`：web 模式開啟瀏覽器的指令
+    pub browser: Option<String>,
+    /// 設定後 app 整體停止（訊號、bundle 背景驗證失敗）
+    pub stop: Arc<AtomicBool>,
+    /// 本實例的控制 socket（見 instance.rs）
+    #[cfg(target_os = "linux")]
+    pub control: Option<std::os::unix::net::UnixListener>,
 }
 
-pub fn run(ctx: &RuntimeContext) -> Result<()> {
-    // 後面會：
-    // 1) 讀 ctx.bundle_dir/manifest.json
-    // 2) 起 microVM（vmm-backend），mount services/*/rootfs、注入 rt/kernel/initrd/agent
-    // 3) 檢查 service depends_on、interface_mode，配好網路/port
-    // 4) 監控 guest-agent 回報的 service 狀態
+pub fn run(ctx: &RuntimeContext) -> Result<i32> {
+    // 執行後端：
+    // - Linux：namespace 容器（ns.rs），不需要 KVM
+    // - 之後：microVM（vmm-backend + guest-agent），guest 的大小取自 Manifest::guest_size
 
-    let mani = ctx.bundle_dir.join("manifest.json");
-    if !fs::metadata(&mani).is_ok() {
-        anyhow::bail!("manifest.json not found in {}", mani);
+    let mani = &ctx.manifest;
+    for svc in &mani.services {
+        let rootfs = ctx.bundle_dir.join(&svc.rootfs_rel);
+        if !rootfs.is_dir() {
+            anyhow::bail!("service `{}` rootfs not found at {}", svc.name, rootfs);
+        }
     }
-    tracing::info!("(stub) manifest OK at {}", mani);
-    Ok(())
+    tracing::info!(
+        "{} {}: starting {} service(s)",
+        mani.app_name,
+        mani.app_version.as_deref().unwrap_or("-"),
+        mani.services.len()
+    );
+    tracing::debug!("data dir: {}", ctx.data_dir.display());
+    tracing::debug!("app args: {:?}", ctx.app_args);
+
+    #[cfg(target_os = "linux")]
+    return crate::supervise::run(ctx);
+
+    #[cfg(not(target_os = "linux"))]
+    anyhow::bail!("no execution backend is available on this platform yet");
 }
@@ -0,0 +1,256 @@
+// src/secrets.rs
+//! secrets：啟動時取得值，以唯讀檔案掛進容器（預設 `/run/secrets/<name>`）或設成 env。
+//!
+//! - generate：第一次啟動時以 /dev/urandom 產生英數字，存在 `{data_dir}/secrets/<name>`（0600），之後沿用
+//! - prompt：第一次啟動時在終端機詢問（不回顯），同樣存起來；沒有終端機就報錯並指出檔案位置
+//! - file：使用者機器上的檔案（vars.rs 已解析成絕對路徑），每次啟動讀取
+//!
+//! 值不含檔案結尾的換行（`echo key > file` 的情況），檔案與 env 都一樣：掛進容器的不是來源檔，
+//! 而是只含值的副本（暫存資料夾內、0600，結束時刪除）。
+//!
+//! 值不會出現在 manifest 或 runtime 的 log；service 輸出到 log 的值以 `***` 遮蔽（logs.rs）。
+use crate::ns::{Bind, Container};
+use anyhow::{Context, Result, bail};
+use chefer_manifest::{DEFAULT_SECRET_LENGTH, Manifest, SECRETS_SUBDIR, Secret, ServiceManifest};
+use std::{
+    collections::BTreeMap,
+    io::{IsTerminal, Read, Write},
+    os::unix::fs::{DirBuilderExt, OpenOptionsExt},
+    path::Path,
+};
+use tempfile::TempDir;
+
+/// 產生的字元
+const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// 取得的 secret 與掛進容器用的副本
+pub struct Secrets {
+    values: BTreeMap<String, String>,
+    /// 每個 secret 一個檔案 `<name>`，內容即值；drop 時刪除
+    dir: TempDir,
+}
+
+impl Secrets {
+    /// 取得 service 有引用的 secret（沒被引用的不產生、不詢問）
+    pub fn load(mani: &Manifest, data_dir: &Path) -> Result<Secrets> {
+        let mut values = BTreeMap::new();
+        for name in mani
+            .services
+            .iter()
+            .flat_map(|s| &s.secrets)
+            .map(|r| &r.source)
+        {
+            if values.contains_key(name) {
+                continue;
+            }
+            let secret = mani
+                .secrets
+                .get(name)
+                .with_context(|| format!("unknown secret `{name}`"))?;
+            let v = load(name, secret, data_dir).with_context(|| format!("secret `{name}`"))?;
+            values.insert(name.clone(), v);
+        }
+        // 有 XDG_RUNTIME_DIR（tmpfs、只有自己可進入）就放那裡，不落到磁碟
+        let mut dir = tempfile::Builder::new();
+        dir.prefix("chefer-secrets-");
+        let dir = match std::env::var_os("XDG_RUNTIME_DIR").filter(|d| Path::new(d).is_dir()) {
+            Some(run) => dir.tempdir_in(run)?,
+            None => dir.tempdir()?,
+        };
+        for (name, value) in &values {
+            write_private(&dir.path().join(name), value)?;
+        }
+        Ok(Secrets { values, dir })
+    }
+
+    /// 值（給 log 遮蔽用）
+    pub fn values(&self) -> impl Iterator<Item = &str> {
+        self.values.values().map(String::as_str)
+    }
+
+    /// 把 service 引用的 secret 加進容器：檔案以唯讀 bind 掛上，env 直接設定
+    pub fn apply(&self, svc: &ServiceManifest, c: &mut Container) -> Result<()> {
+        for r in &svc.secrets {
+            let Some(value) = self.values.get(&r.source) else {
+                bail!(
+                    "service `{}`: secret `{}` was not loaded",
+                    svc.name,
+                    r.source
+                );
+            };
+            match (&r.env, r.file_target()) {
+                (Some(env), _) => {
+                    c.env.retain(|(k, _)| k != env);
+                    c.env.push((env.clone(), value.clone()));
+                }
+                (None, Some(target)) => c.binds.push(
+                    Bind::new(&self.dir.path().join(&r.source), &target)
+                        .with_context(|| format!("service `{}` secret {r}", svc.name))?
+                        .readonly(),
+                ),
+                (None, None) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+fn load(name: &str, secret: &Secret, data_dir: &Path) -> Result<String> {
+    if let Some(file) = &secret.file {
+        return Ok(trim(fs_err::read_to_string(file)?));
+    }
+    let path = data_dir.join(SECRETS_SUBDIR).join(name);
+    if path.is_file() {
+        return Ok(trim(fs_err::read_to_string(&path)?));
+    }
+    let value = match &secret.prompt {
+        Some(prompt) => ask(prompt, &path)?,
+        None => generate(secret.length.unwrap_or(DEFAULT_SECRET_LENGTH) as usize)?,
+    };
+    store(&path, &value)?;
+    tracing::info!("secret `{name}` saved to {}", path.display());
+    Ok(value)
+}
+
+/// 檔案結尾的換行不算在值內（`echo key > file` 的情況）
+fn trim(mut value: String) -> String {
+    while value.ends_with(['\n', '\r']) {
+        value.pop();
+    }
+    value
+}
+
+fn generate(len: usize) -> Result<String> {
+    let mut random = fs_err::File::open("/dev/urandom")?;
+    let mut out = String::with_capacity(len);
+    let mut buf = [0u8; 64];
+    while out.len() < len {
+        random.read_exact(&mut buf)?;
+        // 拒絕取樣：只用 < 62*4 的 byte，避免分布偏差
+        for b in buf.iter().filter(|b| (**b as usize) < ALPHABET.len() * 4) {
+            if out.len() < len {
+                out.push(ALPHABET[*b as usize % ALPHABET.len()] as char);
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// 在終端機詢問，不回顯輸入
+fn ask(prompt: &str, path: &Path) -> Result<String> {
+    if !(std::io::stdin().is_terminal() && std::io::stderr().is_terminal()) {
+        bail!(
+            "it has no value yet; start the app from a terminal once to enter it, or write it to {}",
+            path.display()
+        );
+    }
+    let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+    let echo_off = unsafe { libc::tcgetattr(0, &mut saved) } == 0 && {
+        let mut t = saved;
+        t.c_lflag &= !libc::ECHO;
+        t.c_lflag |= libc::ECHONL;
+        unsafe { libc::tcsetattr(0, libc::TCSADRAIN, &t) == 0 }
+    };
+    eprint!("{}: ", prompt.trim_end_matches([':', ' ']));
+    let _ = std::io::stderr().flush();
+    let mut line = String::new();
+    let read = std::io::stdin().read_line(&mut line);
+    if echo_off {
+        unsafe { libc::tcsetattr(0, libc::TCSADRAIN, &saved) };
+    }
+    read?;
+    let value = trim(line);
+    if value.is_empty() {
+        bail!("no value entered");
+    }
+    Ok(value)
+}
+
+/// 只有自己可讀寫；寫到暫存檔再改名，不會留下寫一半的檔案
+fn store(path: &Path, value: &str) -> Result<()> {
+    let dir = path.parent().context("secret path has no parent")?;
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+        .with_context(|| format!("create {}", dir.display()))?;
+    // secret 名稱不以 '.' 開頭，不會撞名
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = path.with_file_name(format!(".{name}.tmp"));
+    write_private(&tmp, value)?;
+    fs_err::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// 建立（或覆寫）只有自己可讀寫的檔案
+fn write_private(path: &Path, value: &str) -> Result<()> {
+    let mut f = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+        .with_context(|| format!("create {}", path.display()))?;
+    f.write_all(value.as_bytes())?;
+    f.sync_all()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn manifest(key_file: &Path) -> Manifest {
+        let json = serde_json::json!({
+            "app_name": "App",
+            "spec_version": "0.1",
+            "generated_at_utc": "2026-01-01T00:00:00Z",
+            "secrets": {
+                "key": { "file": key_file },
+                "pw": { "generate": true, "length": 12 },
+                "unused": { "prompt": "never asked" },
+            },
+            "services": [{
+                "name": "app",
+                "rootfs_rel": "services/app/rootfs",
+                "secrets": ["key", { "source": "pw", "env": "PW" }],
+            }],
+        });
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn file_values_are_trimmed_and_copied() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = dir.path().join("key.txt");
+        fs_err::write(&key, "sk-123\r\n").unwrap();
+        let secrets = Secrets::load(&manifest(&key), dir.path()).unwrap();
+
+        assert_eq!(secrets.values["key"], "sk-123");
+        // 掛進容器的是只含值的副本
+        let copy = secrets.dir.path().join("key");
+        assert_eq!(fs_err::read_to_string(&copy).unwrap(), "sk-123");
+        let mode = fs_err::metadata(&copy).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        // 沒被引用的不詢問
+        assert!(!secrets.values.contains_key("unused"));
+    }
+
+    #[test]
+    fn generated_values_are_stored_and_reused() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = dir.path().join("key.txt");
+        fs_err::write(&key, "k").unwrap();
+        let mani = manifest(&key);
+
+        let first = Secrets::load(&mani, dir.path()).unwrap().values["pw"].clone();
+        assert_eq!(first.len(), 12);
+        assert!(first.bytes().all(|b| ALPHABET.contains(&b)));
+        let stored = dir.path().join(SECRETS_SUBDIR).join("pw");
+        assert_eq!(fs_err::read_to_string(&stored).unwrap(), first);
+
+        let again = Secrets::load(&mani, dir.path()).unwrap();
+        assert_eq!(again.values["pw"], first);
+    }
+}
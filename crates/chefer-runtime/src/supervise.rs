@@ -0,0 +1,1258 @@
+// src/supervise.rs
+//! 依 depends_on 的順序啟動 service（每個相依先達到指定的 condition）、監看結束、
+//! 依 restart 策略重啟（unhealthy 也算失敗）、依 lifetime 與 crash 策略決定要不要整體停止；
+//! 停止時反過來，依賴別人的先停。控制 socket 的請求（狀態、單一 service 的啟停）也在主迴圈處理。
+use anyhow::{Context, Result, bail};
+use chefer_manifest::{
+    Cmd, CrashPolicy, DependencyCondition, Lifetime, Manifest, RestartConfig, RestartPolicy,
+    STOPPED_SERVICES_FILE, ServiceManifest,
+    control::{AppStatus, Request, Response, ServiceStatus},
+};
+use std::{
+    os::unix::process::ExitStatusExt,
+    path::Path,
+    process::{Child, ExitStatus},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+use tempfile::TempDir;
+
+use crate::{
+    cgroup::{self, Cgroup, Cgroups},
+    control::{self, Pending},
+    display::Display,
+    health::{Health, Monitor},
+    logs::Logs,
+    net::Upstream,
+    ns::{self, Container, Io},
+    run::RuntimeContext,
+    secrets::Secrets,
+    terminal::Terminal,
+    web::Web,
+};
+
+const POLL: Duration = Duration::from_millis(100);
+
+/// 回傳 runtime 的結束碼：主要 service 的結束碼（被訊號終止為 128+訊號）
+pub fn run(ctx: &RuntimeContext) -> Result<i32> {
+    let stop = ctx.stop.clone();
+    for sig in [
+        signal_hook::consts::SIGINT,
+        signal_hook::consts::SIGTERM,
+        signal_hook::consts::SIGHUP,
+    ] {
+        signal_hook::flag::register(sig, stop.clone())?;
+    }
+
+    let upstream = Upstream::create();
+    let published = crate::publish::publish(&ctx.manifest, &upstream)?;
+    let web = crate::web::start(
+        &ctx.manifest,
+        &published,
+        &upstream,
+        ctx.browser.as_deref(),
+        stop.clone(),
+    )?;
+
+    let mut sup = Supervisor::new(ctx, stop, upstream)?;
+    sup.web = web;
+    for p in &published {
+        let i = sup.index(&p.service);
+        sup.services[i].ports.push(format!(
+            "{} -> {}/{}",
+            p.addr,
+            p.mapping.guest,
+            p.mapping.proto.as_str()
+        ));
+    }
+    if let Some(l) = &ctx.control {
+        let names = sup
+            .services
+            .iter()
+            .map(|s| s.manifest.name.clone())
+            .collect();
+        sup.requests = Some(control::serve(l.try_clone()?, ctx.data_dir.clone(), names)?);
+    }
+    let result = sup.start_all().and_then(|()| sup.wait_all());
+    sup.stop_all();
+    result?;
+
+    let primary = &sup.services[sup.primary];
+    let code = match primary.state {
+        State::Exited(status) => exit_code(status),
+        // 以 `chefer ctl stop` 停掉的
+        State::Stopped => 0,
+        _ => 1,
+    };
+    if !sup.failed.is_empty() {
+        tracing::error!("service(s) exited with an error: {}", sup.failed.join(", "));
+        if code == 0 {
+            return Ok(1);
+        }
+    }
+    Ok(code)
+}
+
+fn exit_code(status: ExitStatus) -> i32 {
+    status
+        .code()
+        .unwrap_or_else(|| 128 + status.signal().unwrap_or(0))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Pending,
+    Running,
+    /// 已結束，等到時間再重啟
+    BackingOff(Instant),
+    /// 以控制 socket 停止中：已送 stop_signal，等它結束；`deadline` 到了 SIGKILL（之後為 None）
+    Stopping {
+        deadline: Option<Instant>,
+        /// 結束後再啟動（restart）
+        restart: bool,
+    },
+    Exited(ExitStatus),
+    /// 以控制 socket 停掉的：不依 restart 策略重啟
+    Stopped,
+}
+
+impl State {
+    fn as_str(self) -> &'static str {
+        match self {
+            State::Pending => "pending",
+            State::Running => "running",
+            State::BackingOff(_) => "backing-off",
+            State::Stopping { .. } => "stopping",
+            State::Exited(_) => "exited",
+            State::Stopped => "stopped",
+        }
+    }
+}
+
+struct Service<'a> {
+    manifest: &'a ServiceManifest,
+    container: Container,
+    state: State,
+    proc: Option<Running>,
+    /// stop_signal 的編號
+    stop_signal: i32,
+    /// 連續重啟次數；執行超過 reset_after 才結束時歸零
+    restarts: u32,
+    /// 發布到 host 的埠（`host 位址 -> 容器內的埠/協定`）
+    ports: Vec<String>,
+    /// 有資源上限且 cgroup 可用時才有
+    cgroup: Option<Cgroup>,
+}
+
+struct Running {
+    child: Child,
+    started_at: Instant,
+    /// 沒有 healthcheck 時為 None
+    health: Option<Monitor>,
+    /// 因 unhealthy 被停掉
+    killed_unhealthy: bool,
+    /// 轉寫輸出的 thread
+    pumps: Vec<JoinHandle<()>>,
+    /// overlay 的暫存掛載點；容器結束後才可刪除
+    _scratch: TempDir,
+}
+
+struct Supervisor<'a> {
+    manifest: &'a Manifest,
+    data_dir: &'a Path,
+    started_at: Instant,
+    /// 依啟動順序（相依的在前）
+    services: Vec<Service<'a>>,
+    /// 決定 runtime 結束碼的 service：主要 service，沒有時為啟動順序的最後一個
+    primary: usize,
+    lifetime: Lifetime,
+    stop: Arc<AtomicBool>,
+    upstream: Upstream,
+    logs: Logs,
+    /// 有接終端機的 service 且 stdin 是終端機時才有
+    terminal: Option<Terminal>,
+    crash: CrashPolicy,
+    /// fail_fast 觸發：停止所有 service
+    teardown: bool,
+    failed: Vec<String>,
+    /// 有 gui service 時才有；持有改寫過的 Xauthority
+    _display: Option<Display>,
+    /// 持有 secret 檔案的副本；service 重啟時還會再掛一次
+    _secrets: Secrets,
+    /// 有 web service 時才有
+    web: Option<Web>,
+    /// 控制 socket 收到的請求；沒有 socket 時為 None
+    requests: Option<mpsc::Receiver<Pending>>,
+    /// 在 services（各自的 cgroup）之後 drop
+    _cgroups: Option<Cgroups>,
+}
+
+impl<'a> Supervisor<'a> {
+    fn new(ctx: &'a RuntimeContext, stop: Arc<AtomicBool>, upstream: Upstream) -> Result<Self> {
+        let order = ctx.manifest.start_order().map_err(anyhow::Error::msg)?;
+        let names: Vec<&str> = order.iter().map(|s| s.name.as_str()).collect();
+        let has_terminal = ctx.manifest.terminal_service().is_some();
+        // 詢問 secret 要在終端機交給 service 之前
+        let secrets = Secrets::load(&ctx.manifest, &ctx.data_dir)?;
+        let mut logs = Logs::new(&ctx.data_dir, &names, &ctx.log_filter, has_terminal)?;
+        logs.redact(secrets.values());
+        let terminal = if has_terminal {
+            Terminal::open()?
+        } else {
+            None
+        };
+        let display = match order.iter().find(|s| s.interface_mode.uses_display()) {
+            Some(svc) => Some(
+                Display::detect()
+                    .with_context(|| format!("service `{}` has a graphical interface", svc.name))?,
+            ),
+            None => None,
+        };
+        if let Some(d) = &display {
+            tracing::info!("sharing the host desktop: {}", d.describe());
+        }
+        let stopped = read_stopped(&ctx.data_dir.join(STOPPED_SERVICES_FILE));
+        let cgroups = cgroup::setup(&ctx.manifest);
+        let mut services = Vec::with_capacity(order.len());
+        for svc in order {
+            let mut container =
+                Container::from_manifest(svc, ctx.bundle_dir.as_std_path(), &ctx.data_dir)?;
+            container.pod = upstream.pod();
+            if let Some(d) = display
+                .as_ref()
+                .filter(|_| svc.interface_mode.uses_display())
+            {
+                d.apply(&mut container);
+            }
+            secrets.apply(svc, &mut container)?;
+            let cgroup = match &cgroups {
+                Some(c) if !svc.resources.is_empty() => match c.service(svc) {
+                    Ok(cg) => Some(cg),
+                    Err(e) => {
+                        tracing::warn!(
+                            "service `{}`: resource limits are not enforced: {e:#}",
+                            svc.name
+                        );
+                        None
+                    }
+                },
+                _ => None,
+            };
+            container.cgroup = cgroup.as_ref().map(|c| c.path().to_path_buf());
+            if let Some(cg) = cgroup.as_ref().filter(|c| c.enforced) {
+                tracing::info!("service `{}` limited to {}", svc.name, svc.resources);
+                tracing::debug!("service `{}` cgroup {}", svc.name, cg.path().display());
+            }
+            if svc.primary && !ctx.app_args.is_empty() {
+                // `sh -c` 之後的第一個參數是 $0
+                if matches!(svc.cmd, Some(Cmd::String(_))) {
+                    container.args.push(svc.name.clone().into());
+                }
+                container.args.extend(ctx.app_args.iter().cloned());
+            }
+            // restart: unless-stopped 的 service 上次被手動停掉，就維持停止
+            let state = if svc.restart.policy == RestartPolicy::UnlessStopped
+                && stopped.contains(&svc.name)
+            {
+                tracing::info!(
+                    "service `{}` was stopped with `chefer ctl stop`; not starting it (restart: unless-stopped)",
+                    svc.name
+                );
+                State::Stopped
+            } else {
+                State::Pending
+            };
+            services.push(Service {
+                manifest: svc,
+                container,
+                state,
+                proc: None,
+                stop_signal: chefer_manifest::signal_number(&svc.stop_signal)
+                    .expect("stop_signal was validated with the manifest"),
+                restarts: 0,
+                ports: Vec::new(),
+                cgroup,
+            });
+        }
+        if ctx.manifest.primary().is_none() && !ctx.app_args.is_empty() {
+            tracing::warn!(
+                "no primary service to pass the arguments to; ignoring {:?}",
+                ctx.app_args
+            );
+        }
+        // manifest 驗證保證至少有一個 service
+        let primary = services
+            .iter()
+            .position(|s| s.manifest.primary)
+            .unwrap_or(services.len() - 1);
+        Ok(Supervisor {
+            manifest: &ctx.manifest,
+            data_dir: &ctx.data_dir,
+            started_at: Instant::now(),
+            primary,
+            lifetime: ctx.manifest.lifetime,
+            services,
+            stop,
+            upstream,
+            logs,
+            terminal,
+            crash: ctx.manifest.crash,
+            teardown: false,
+            failed: Vec::new(),
+            _display: display,
+            _secrets: secrets,
+            web: None,
+            requests: None,
+            _cgroups: cgroups,
+        })
+    }
+
+    fn stopping(&self) -> bool {
+        self.teardown || self.stop.load(Ordering::Relaxed)
+    }
+
+    fn index(&self, name: &str) -> usize {
+        self.services
+            .iter()
+            .position(|s| s.manifest.name == name)
+            .expect("depends_on was validated against the manifest")
+    }
+
+    fn handle(&mut self, req: Request) -> Response {
+        match req {
+            Request::Launch { args } => self.second_launch(args),
+            Request::Status => Response {
+                status: Some(self.status()),
+                ..Response::ok("")
+            },
+            Request::Start { service } => self.start_service(&service),
+            Request::Stop { service } => self.stop_service(&service),
+            Request::Restart { service } => self.restart_service(&service),
+            // control.rs 自己處理，不會送到這裡
+            Request::Logs { .. } => Response::error("logs are not handled by the supervisor"),
+            Request::Shutdown => {
+                tracing::info!("shutdown requested over the control socket");
+                self.stop.store(true, Ordering::Relaxed);
+                Response::ok(format!("stopping {}", self.manifest.app_name))
+            }
+        }
+    }
+
+    fn status(&self) -> AppStatus {
+        AppStatus {
+            app: self.manifest.app_name.clone(),
+            version: self.manifest.app_version.clone(),
+            pid: std::process::id(),
+            uptime_secs: self.started_at.elapsed().as_secs(),
+            services: self
+                .services
+                .iter()
+                .map(|s| ServiceStatus {
+                    name: s.manifest.name.clone(),
+                    state: s.state.as_str().to_string(),
+                    pid: s.proc.as_ref().map(|p| p.child.id()),
+                    health: s
+                        .proc
+                        .as_ref()
+                        .and_then(|p| p.health.as_ref())
+                        .map(|m| m.health().to_string()),
+                    uptime_secs: s.proc.as_ref().map(|p| p.started_at.elapsed().as_secs()),
+                    restarts: s.restarts,
+                    exit: match s.state {
+                        State::Exited(status) => Some(status.to_string()),
+                        _ => None,
+                    },
+                    ports: s.ports.clone(),
+                    primary: s.manifest.primary,
+                    limits: s.manifest.resources,
+                    limits_enforced: s.cgroup.as_ref().is_some_and(|c| c.enforced),
+                    usage: s.cgroup.as_ref().map(Cgroup::usage),
+                })
+                .collect(),
+        }
+    }
+
+    fn find(&self, name: &str) -> Result<usize, Response> {
+        self.services
+            .iter()
+            .position(|s| s.manifest.name == name)
+            .ok_or_else(|| {
+                let names: Vec<&str> = self
+                    .services
+                    .iter()
+                    .map(|s| s.manifest.name.as_str())
+                    .collect();
+                Response::error(format!(
+                    "unknown service `{name}` (available: {})",
+                    names.join(", ")
+                ))
+            })
+    }
+
+    /// 啟動已停止或已結束的 service；不等它的相依
+    fn start_service(&mut self, name: &str) -> Response {
+        let i = match self.find(name) {
+            Ok(i) => i,
+            Err(r) => return r,
+        };
+        match self.services[i].state {
+            State::Running => {
+                return Response::error(format!("service `{name}` is already running"));
+            }
+            State::Stopping { .. } => {
+                return Response::error(format!(
+                    "service `{name}` is still stopping; try again once it has stopped"
+                ));
+            }
+            State::Pending => {
+                return Response::error(format!(
+                    "service `{name}` is still waiting for its dependencies"
+                ));
+            }
+            State::BackingOff(_) | State::Exited(_) | State::Stopped => {}
+        }
+        if self.stopping() {
+            return Response::error(format!("{} is stopping", self.manifest.app_name));
+        }
+        self.remember_stopped(i, false);
+        self.services[i].restarts = 0;
+        match self.start(i) {
+            Ok(()) => Response::ok(format!("service `{name}` started")),
+            Err(e) => Response::error(format!("service `{name}`: {e:#}")),
+        }
+    }
+
+    /// 送 stop_signal，結束後不重啟；不等它結束（超過 stop_grace_period 由 poll SIGKILL）
+    fn stop_service(&mut self, name: &str) -> Response {
+        let i = match self.find(name) {
+            Ok(i) => i,
+            Err(r) => return r,
+        };
+        self.remember_stopped(i, true);
+        let svc = &mut self.services[i];
+        match &mut svc.state {
+            State::Running => {
+                self.signal(i, false);
+                Response::ok(format!("stopping service `{name}`"))
+            }
+            State::Stopping { restart, .. } => {
+                *restart = false;
+                Response::ok(format!("service `{name}` is already stopping"))
+            }
+            State::Pending | State::BackingOff(_) => {
+                svc.state = State::Stopped;
+                Response::ok(format!("service `{name}` will not be started"))
+            }
+            State::Exited(_) | State::Stopped => {
+                svc.state = State::Stopped;
+                Response::ok(format!("service `{name}` is not running"))
+            }
+        }
+    }
+
+    /// 執行中的停掉後再啟動；沒在執行的直接啟動
+    fn restart_service(&mut self, name: &str) -> Response {
+        let i = match self.find(name) {
+            Ok(i) => i,
+            Err(r) => return r,
+        };
+        match &mut self.services[i].state {
+            State::Running => {
+                self.remember_stopped(i, false);
+                self.signal(i, true);
+                Response::ok(format!("restarting service `{name}`"))
+            }
+            State::Stopping { restart, .. } => {
+                *restart = true;
+                self.remember_stopped(i, false);
+                Response::ok(format!(
+                    "service `{name}` is stopping; it will start again once it has stopped"
+                ))
+            }
+            _ => self.start_service(name),
+        }
+    }
+
+    /// 送 stop_signal 給執行中的 service，轉為 Stopping
+    fn signal(&mut self, i: usize, restart: bool) {
+        let svc = &mut self.services[i];
+        let Some(p) = &svc.proc else { return };
+        let m = svc.manifest;
+        tracing::info!(
+            "{} service `{}` ({}) as requested over the control socket",
+            if restart { "restarting" } else { "stopping" },
+            m.name,
+            m.stop_signal
+        );
+        // SAFETY: kill 沒有指標參數；child 還沒被 wait，pid 不會被回收給別的行程
+        unsafe { libc::kill(p.child.id() as libc::pid_t, svc.stop_signal) };
+        svc.state = State::Stopping {
+            deadline: Some(Instant::now() + m.stop_grace_period.0),
+            restart,
+        };
+    }
+
+    /// 記下（或移除）手動停掉的 unless-stopped service，下次開 app 時沿用
+    fn remember_stopped(&self, i: usize, stopped: bool) {
+        let svc = &self.services[i];
+        if stopped && svc.manifest.restart.policy != RestartPolicy::UnlessStopped {
+            return;
+        }
+        let path = self.data_dir.join(STOPPED_SERVICES_FILE);
+        let mut names = read_stopped(&path);
+        let name = &svc.manifest.name;
+        if names.contains(name) == stopped {
+            return;
+        }
+        if stopped {
+            names.push(name.clone());
+        } else {
+            names.retain(|n| n != name);
+        }
+        let result = if names.is_empty() {
+            std::fs::remove_file(&path)
+        } else {
+            std::fs::write(&path, names.join("\n") + "\n")
+        };
+        if let Err(e) = result {
+            tracing::warn!("cannot update {}: {e}", path.display());
+        }
+    }
+
+    /// 第二次啟動：主要 service 有 handoff 指令就在它的容器內執行（接上參數），
+    /// web 模式則重新開啟瀏覽器
+    fn second_launch(&mut self, args: Vec<String>) -> Response {
+        let svc = &self.services[self.primary];
+        let name = &svc.manifest.name;
+        let Some(handoff) = &svc.manifest.handoff else {
+            if let Some(web) = &self.web {
+                return Response::ok(web.reopen());
+            }
+            if !args.is_empty() {
+                tracing::warn!("second launch with {args:?}, but no service takes it over");
+            }
+            return Response::ok("no service takes over a second launch; nothing to do");
+        };
+        let Some(p) = &svc.proc else {
+            return Response::error(format!("service `{name}` is not running"));
+        };
+        let mut argv = ns::argv(handoff);
+        // 同主要 service 的參數：`sh -c` 之後的第一個參數是 $0
+        if matches!(handoff, Cmd::String(_)) {
+            argv.push(name.clone());
+        }
+        argv.extend(args);
+        match svc.container.exec_in(p.child.id(), &argv) {
+            Ok(mut child) => {
+                tracing::info!("second launch handed over to service `{name}`: {argv:?}");
+                let name = name.clone();
+                std::thread::spawn(move || match child.wait() {
+                    Ok(status) if !status.success() => {
+                        tracing::warn!("handoff in service `{name}` exited with {status}")
+                    }
+                    _ => {}
+                });
+                Response::ok(format!("handed over to service `{}`", svc.manifest.name))
+            }
+            Err(e) => Response::error(format!("{e:#}")),
+        }
+    }
+
+    /// 依序啟動；中途收到停止訊號就不再啟動後面的
+    fn start_all(&mut self) -> Result<()> {
+        for i in 0..self.services.len() {
+            // 上次手動停掉的（unless-stopped），或等待相依時被停掉的
+            if self.services[i].state == State::Stopped {
+                continue;
+            }
+            for dep in &self.services[i].manifest.depends_on {
+                if !self.wait_for(i, &dep.service, dep.condition)? {
+                    return Ok(());
+                }
+            }
+            // 等待中被 `chefer ctl stop` 停掉的
+            if self.services[i].state == State::Stopped {
+                continue;
+            }
+            self.start(i)?;
+        }
+        Ok(())
+    }
+
+    fn start(&mut self, i: usize) -> Result<()> {
+        let (upstream, logs) = (&self.upstream, &self.logs);
+        let svc = &mut self.services[i];
+        let scratch = tempfile::Builder::new().prefix("chefer-ns-").tempdir()?;
+        let (io, tty_pump) = match &self.terminal {
+            _ if !svc.manifest.interface_mode.uses_terminal() => (Io::Piped, None),
+            Some(t) => {
+                let (io, pump) = t.attach()?;
+                (io, Some(pump))
+            }
+            None => (Io::Inherit, None),
+        };
+        let mut child = svc.container.spawn(scratch.path(), io)?;
+        let mut pumps = logs.attach(&svc.manifest.name, &mut child);
+        pumps.extend(tty_pump);
+        logs.event(&svc.manifest.name, &format!("started (pid {})", child.id()));
+        tracing::info!(
+            "service `{}` started (pid {})",
+            svc.manifest.name,
+            child.id()
+        );
+        let health = svc
+            .manifest
+            .healthcheck
+            .as_ref()
+            .map(|hc| Monitor::start(hc, &svc.container, child.id(), upstream.clone()));
+        svc.state = State::Running;
+        svc.proc = Some(Running {
+            child,
+            started_at: Instant::now(),
+            health,
+            killed_unhealthy: false,
+            pumps,
+            _scratch: scratch,
+        });
+        Ok(())
+    }
+
+    /// 等 `dep` 達到 `condition`，或 service `i` 在等待中被停掉；收到停止訊號時回傳 false
+    fn wait_for(&mut self, i: usize, dep: &str, condition: DependencyCondition) -> Result<bool> {
+        let d = self.index(dep);
+        let mut logged = false;
+        loop {
+            self.poll()?;
+            if self.stopping() {
+                return Ok(false);
+            }
+            if self.services[i].state == State::Stopped {
+                return Ok(true);
+            }
+            let state = self.services[d].state;
+            if state == State::Stopped {
+                tracing::warn!(
+                    "service `{}` depends on `{dep}`, which was stopped; starting it anyway",
+                    self.services[i].manifest.name
+                );
+                return Ok(true);
+            }
+            let ready = match condition {
+                // 啟動過就算（例如只跑一次的初始化 service）
+                DependencyCondition::ServiceStarted => state != State::Pending,
+                DependencyCondition::ServiceHealthy => self.is_healthy(d),
+            };
+            if ready {
+                return Ok(true);
+            }
+            if condition == DependencyCondition::ServiceHealthy
+                && self.health(d) == Some(Health::Unhealthy)
+                && self.services[d].manifest.restart.policy == RestartPolicy::No
+            {
+                bail!(
+                    "service `{}` cannot start: dependency `{dep}` is unhealthy",
+                    self.services[i].manifest.name
+                );
+            }
+            // 等待重啟中的相依仍可能變成 ready；放棄了才算失敗
+            if let State::Exited(status) = state {
+                bail!(
+                    "service `{}` cannot start: dependency `{dep}` exited ({status}) before it was {}",
+                    self.services[i].manifest.name,
+                    condition.as_str()
+                );
+            }
+            if !logged {
+                tracing::info!(
+                    "service `{}` waiting for `{dep}` ({})",
+                    self.services[i].manifest.name,
+                    condition.as_str()
+                );
+                logged = true;
+            }
+            std::thread::sleep(POLL);
+        }
+    }
+
+    fn health(&self, i: usize) -> Option<Health> {
+        self.services[i]
+            .proc
+            .as_ref()?
+            .health
+            .as_ref()
+            .map(Monitor::health)
+    }
+
+    /// 沒有 healthcheck 的 service 執行中即視為 healthy（manifest 驗證已擋掉 service_healthy 的情況）
+    fn is_healthy(&self, i: usize) -> bool {
+        let svc = &self.services[i];
+        match &svc.proc {
+            Some(p) => p
+                .health
+                .as_ref()
+                .is_none_or(|m| m.health() == Health::Healthy),
+            None => false,
+        }
+    }
+
+    /// 收割已結束的 service，並啟動到了重啟時間的
+    fn poll(&mut self) -> Result<()> {
+        while let Some((req, reply)) = self.requests.as_ref().and_then(|r| r.try_recv().ok()) {
+            let _ = reply.send(self.handle(req));
+        }
+        for i in 0..self.services.len() {
+            let Some(p) = &mut self.services[i].proc else {
+                continue;
+            };
+            if let Some(status) = p.child.try_wait()? {
+                let ran = p.started_at.elapsed();
+                if let Some(p) = self.services[i].proc.take() {
+                    p.finish();
+                }
+                match self.services[i].state {
+                    State::Stopping { restart, .. } => self.stopped(i, status, restart)?,
+                    _ => self.exited(i, status, ran),
+                }
+                continue;
+            }
+
+            let svc = &mut self.services[i];
+            let Some(p) = &mut svc.proc else { continue };
+            if let State::Stopping { deadline, restart } = svc.state {
+                if deadline.is_some_and(|d| d <= Instant::now()) {
+                    tracing::warn!(
+                        "service `{}` did not stop within {}, killing",
+                        svc.manifest.name,
+                        svc.manifest.stop_grace_period
+                    );
+                    let _ = p.child.kill();
+                    svc.state = State::Stopping {
+                        deadline: None,
+                        restart,
+                    };
+                }
+                continue;
+            }
+
+            // unhealthy：會重啟的 service 直接砍掉（可能已卡死），交給 restart 策略
+            let unhealthy = p
+                .health
+                .as_ref()
+                .is_some_and(|m| m.health() == Health::Unhealthy);
+            if unhealthy && !p.killed_unhealthy && svc.manifest.restart.policy != RestartPolicy::No
+            {
+                tracing::warn!(
+                    "service `{}` is unhealthy; killing it so it restarts",
+                    svc.manifest.name
+                );
+                let _ = p.child.kill();
+                p.killed_unhealthy = true;
+            }
+        }
+
+        let now = Instant::now();
+        for i in 0..self.services.len() {
+            if let State::BackingOff(at) = self.services[i].state
+                && at <= now
+                && !self.stopping()
+            {
+                self.start(i)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 以控制 socket 停止的 service 已結束：不經 restart、lifetime 與 crash 策略
+    fn stopped(&mut self, i: usize, status: ExitStatus, restart: bool) -> Result<()> {
+        let name = &self.services[i].manifest.name;
+        self.logs.event(name, &format!("stopped ({status})"));
+        tracing::info!("service `{name}` stopped ({status})");
+        if restart && !self.stopping() {
+            self.services[i].restarts = 0;
+            return self.start(i);
+        }
+        self.services[i].state = State::Stopped;
+        Ok(())
+    }
+
+    /// 依 restart 策略決定重啟或放棄；放棄的失敗再依 crash 策略處理
+    fn exited(&mut self, i: usize, status: ExitStatus, ran: Duration) {
+        let stopping = self.stopping();
+        let ends_app = match self.lifetime {
+            Lifetime::Primary => i == self.primary,
+            Lifetime::Any => true,
+            Lifetime::All => false,
+        };
+        // 主要 service 結束就是使用者要離開：結束碼交給 runtime，不算 crash
+        let hands_over = ends_app && i == self.primary;
+        self.logs.event(
+            &self.services[i].manifest.name,
+            &format!("exited ({status})"),
+        );
+        let svc = &mut self.services[i];
+        let name = &svc.manifest.name;
+        if let Some(memory) = svc.manifest.resources.memory
+            && svc.cgroup.as_ref().is_some_and(Cgroup::oom_killed)
+        {
+            tracing::warn!("service `{name}` ran out of memory (limit {memory}) and was killed");
+        }
+        let r = &svc.manifest.restart;
+        if ran >= r.reset_after.0 {
+            svc.restarts = 0;
+        }
+
+        let wants = match r.policy {
+            RestartPolicy::No => false,
+            RestartPolicy::OnFailure => !status.success(),
+            RestartPolicy::Always | RestartPolicy::UnlessStopped => true,
+        };
+        if wants && !stopping {
+            if r.max_retries.is_none_or(|max| svc.restarts < max) {
+                svc.restarts += 1;
+                let delay = backoff(r, svc.restarts);
+                let limit = r.max_retries.map(|m| format!("/{m}")).unwrap_or_default();
+                tracing::info!(
+                    "service `{name}` exited ({status}); restarting in {delay:?} (restart {}{limit}, policy {})",
+                    svc.restarts,
+                    r.policy.as_str()
+                );
+                svc.state = State::BackingOff(Instant::now() + delay);
+                return;
+            }
+            tracing::warn!(
+                "service `{name}` exited ({status}); giving up after {} restart(s)",
+                svc.restarts
+            );
+        } else if status.success() {
+            tracing::info!("service `{name}` exited");
+        } else if hands_over {
+            tracing::info!("service `{name}` exited with {status}");
+        } else {
+            tracing::warn!("service `{name}` exited with {status}");
+        }
+        svc.state = State::Exited(status);
+
+        if ends_app && !stopping {
+            tracing::info!(
+                "lifetime {}: service `{name}` ended, stopping the app",
+                self.lifetime.as_str()
+            );
+            self.teardown = true;
+        }
+        if status.success() || stopping || hands_over {
+            return;
+        }
+        self.failed.push(name.clone());
+        match self.crash {
+            CrashPolicy::FailFast => {
+                tracing::warn!("crash policy fail_fast: stopping all services");
+                self.teardown = true;
+            }
+            CrashPolicy::Degrade => {
+                tracing::warn!("crash policy degrade: keeping the other services running");
+            }
+        }
+    }
+
+    /// 等到全部結束（不再有執行中或等待重啟的）或收到停止訊號
+    fn wait_all(&mut self) -> Result<()> {
+        let active = |s: &Service| s.proc.is_some() || matches!(s.state, State::BackingOff(_));
+        while self.services.iter().any(active) && !self.stopping() {
+            self.poll()?;
+            std::thread::sleep(POLL);
+        }
+        Ok(())
+    }
+
+    /// 依啟動順序反過來逐一停止：送 stop_signal（shim 會轉給 app），超過 stop_grace_period
+    /// 再 SIGKILL（shim 死掉會帶走整個 namespace）。停止中再收到一次停止訊號就不再等，全部 SIGKILL。
+    fn stop_all(&mut self) {
+        let count = self.services.iter().filter(|s| s.proc.is_some()).count();
+        if count == 0 {
+            return;
+        }
+        tracing::info!("stopping {count} service(s)");
+        self.teardown = true;
+        // 重新武裝：之後再被設起來就是第二次停止訊號
+        self.stop.store(false, Ordering::Relaxed);
+        let mut force = false;
+
+        for i in (0..self.services.len()).rev() {
+            let svc = &mut self.services[i];
+            let Some(mut p) = svc.proc.take() else {
+                continue;
+            };
+            let m = svc.manifest;
+            if !force {
+                tracing::debug!("sending {} to service `{}`", m.stop_signal, m.name);
+                // SAFETY: 同上，child 還沒被 wait，pid 仍屬於它
+                unsafe { libc::kill(p.child.id() as libc::pid_t, svc.stop_signal) };
+            }
+            let deadline = Instant::now() + m.stop_grace_period.0;
+            let status = loop {
+                match p.child.try_wait() {
+                    Ok(Some(status)) => break Some(status),
+                    Ok(None) => {
+                        if !force && self.stop.load(Ordering::Relaxed) {
+                            tracing::warn!("stop requested again: killing the remaining services");
+                            force = true;
+                        }
+                        if !force && Instant::now() < deadline {
+                            std::thread::sleep(POLL);
+                            continue;
+                        }
+                        if !force {
+                            tracing::warn!(
+                                "service `{}` did not stop within {}, killing",
+                                m.name,
+                                m.stop_grace_period
+                            );
+                        }
+                        let _ = p.child.kill();
+                        break p.child.wait().ok();
+                    }
+                    Err(_) => break None,
+                }
+            };
+            if let Some(status) = status {
+                let health = p
+                    .health
+                    .as_ref()
+                    .map(|m| format!(", last {}", m.health()))
+                    .unwrap_or_default();
+                p.finish();
+                self.logs.event(&m.name, &format!("stopped ({status})"));
+                tracing::info!("service `{}` stopped ({status}{health})", m.name);
+                svc.state = State::Exited(status);
+            }
+        }
+    }
+}
+
+impl Running {
+    /// 容器已結束：等輸出寫完，並停掉 healthcheck
+    fn finish(self) {
+        for t in self.pumps {
+            let _ = t.join();
+        }
+    }
+}
+
+/// 手動停掉的 unless-stopped service 名稱
+fn read_stopped(path: &Path) -> Vec<String> {
+    std::fs::read_to_string(path)
+        .map(|s| {
+            s.lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// 第 n 次重啟前的等待：backoff × 2^(n-1)，上限 max_backoff
+fn backoff(r: &RestartConfig, n: u32) -> Duration {
+    let factor = 1u32.checked_shl(n.saturating_sub(1)).unwrap_or(u32::MAX);
+    r.backoff
+        .0
+        .checked_mul(factor)
+        .map_or(r.max_backoff.0, |d| d.min(r.max_backoff.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chefer_manifest::HumanDuration;
+
+    /// `app` 為 manifest 的頂層欄位（services 至少要有 name 與 cmd）；service 的 $DIR 為 `dir`
+    fn context(dir: &Path, mut app: serde_json::Value) -> RuntimeContext {
+        for svc in app["services"].as_array_mut().unwrap() {
+            let name = svc["name"].as_str().unwrap().to_string();
+            svc["rootfs_rel"] = format!("services/{name}/rootfs").into();
+            svc["env"] = serde_json::json!([["DIR", dir]]);
+        }
+        let defaults = serde_json::json!({
+            "manifest_version": chefer_manifest::MANIFEST_VERSION,
+            "app_name": "App",
+            "spec_version": "0.1",
+            "generated_at_utc": "2026-01-01T00:00:00Z",
+        });
+        for (k, v) in defaults.as_object().unwrap() {
+            app[k] = v.clone();
+        }
+        std::fs::create_dir_all(dir.join(chefer_manifest::LOGS_SUBDIR)).unwrap();
+        RuntimeContext {
+            bundle_dir: camino::Utf8PathBuf::from_path_buf(dir.to_path_buf()).unwrap(),
+            manifest: Manifest::from_slice(app.to_string().as_bytes()).unwrap(),
+            data_dir: dir.to_path_buf(),
+            app_args: Vec::new(),
+            log_filter: Vec::new(),
+            browser: None,
+            stop: Arc::new(AtomicBool::new(false)),
+            control: None,
+        }
+    }
+
+    /// service 都跑在假容器裡：host 上的 sh，不建立 namespace
+    fn supervisor(ctx: &RuntimeContext) -> Supervisor<'_> {
+        let mut sup = Supervisor::new(ctx, ctx.stop.clone(), Upstream::Host).unwrap();
+        for s in &mut sup.services {
+            s.container.on_host = true;
+        }
+        sup
+    }
+
+    fn state<'a>(sup: &'a Supervisor, name: &str) -> &'a State {
+        &sup.services[sup.index(name)].state
+    }
+
+    /// 等 service 寫出 `<dir>/<name>.up`（trap 已設好）
+    fn wait_up(dir: &Path, names: &[&str]) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !names.iter().all(|n| dir.join(format!("{n}.up")).exists()) {
+            assert!(Instant::now() < deadline, "services did not come up");
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    #[test]
+    fn stop_while_pending_keeps_the_service_stopped() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = context(
+            dir.path(),
+            serde_json::json!({
+                "services": [
+                    // 不會變成 healthy：第一次檢查在一小時後
+                    { "name": "db", "cmd": "exec sleep 30",
+                      "healthcheck": { "tcp": 1, "interval": "1h" } },
+                    { "name": "app", "cmd": "touch $DIR/app.up; exec sleep 30",
+                      "depends_on": [{ "service": "db", "condition": "service_healthy" }] },
+                ],
+            }),
+        );
+        let mut sup = supervisor(&ctx);
+        let (tx, rx) = mpsc::channel();
+        let (reply_tx, reply) = mpsc::channel();
+        tx.send((
+            Request::Stop {
+                service: "app".into(),
+            },
+            reply_tx,
+        ))
+        .unwrap();
+        sup.requests = Some(rx);
+
+        sup.start_all().unwrap();
+        assert_eq!(
+            reply.recv().unwrap().message,
+            "service `app` will not be started"
+        );
+        assert_eq!(*state(&sup, "app"), State::Stopped);
+        assert!(sup.services[sup.index("app")].proc.is_none());
+        assert_eq!(*state(&sup, "db"), State::Running);
+        sup.stop_all();
+    }
+
+    fn config(backoff_ms: u64, max_ms: u64) -> RestartConfig {
+        RestartConfig {
+            backoff: HumanDuration(Duration::from_millis(backoff_ms)),
+            max_backoff: HumanDuration(Duration::from_millis(max_ms)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let r = config(200, 1000);
+        let waits: Vec<u128> = (1..=6).map(|n| backoff(&r, n).as_millis()).collect();
+        assert_eq!(waits, [200, 400, 800, 1000, 1000, 1000]);
+    }
+
+    #[test]
+    fn backoff_does_not_overflow() {
+        let r = config(1000, 60_000);
+        assert_eq!(backoff(&r, 0), Duration::from_secs(1));
+        assert_eq!(backoff(&r, 40), Duration::from_secs(60));
+        assert_eq!(backoff(&r, u32::MAX), Duration::from_secs(60));
+    }
+
+    /// 收到 SIGTERM 時把自己的名稱記到 $DIR/stopped
+    fn traps_term(name: &str) -> String {
+        format!(
+            "trap 'echo {name} >> $DIR/stopped; exit 0' TERM; touch $DIR/{name}.up; \
+             while :; do sleep 0.05; done"
+        )
+    }
+
+    #[test]
+    fn stops_in_reverse_dependency_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = context(
+            dir.path(),
+            serde_json::json!({
+                "services": [
+                    { "name": "web", "cmd": traps_term("web"), "depends_on": ["api"] },
+                    { "name": "db", "cmd": traps_term("db") },
+                    { "name": "api", "cmd": traps_term("api"), "depends_on": ["db"] },
+                ],
+            }),
+        );
+        let mut sup = supervisor(&ctx);
+        sup.start_all().unwrap();
+        wait_up(dir.path(), &["db", "api", "web"]);
+        sup.stop_all();
+
+        let stopped = std::fs::read_to_string(dir.path().join("stopped")).unwrap();
+        assert_eq!(stopped, "web\napi\ndb\n");
+        for s in &sup.services {
+            assert!(matches!(s.state, State::Exited(st) if st.success()));
+        }
+    }
+
+    #[test]
+    fn kills_after_the_grace_period() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = context(
+            dir.path(),
+            serde_json::json!({
+                "services": [{
+                    "name": "stubborn",
+                    // 忽略 SIGTERM（exec 之後仍然忽略）
+                    "cmd": "trap '' TERM; touch $DIR/stubborn.up; exec sleep 30",
+                    "stop_grace_period": "300ms",
+                }],
+            }),
+        );
+        let mut sup = supervisor(&ctx);
+        sup.start_all().unwrap();
+        wait_up(dir.path(), &["stubborn"]);
+
+        let t = Instant::now();
+        sup.stop_all();
+        let took = t.elapsed();
+        assert!(took >= Duration::from_millis(300), "{took:?}");
+        assert!(took < Duration::from_secs(5), "{took:?}");
+        assert!(
+            matches!(*state(&sup, "stubborn"), State::Exited(st) if st.signal() == Some(libc::SIGKILL))
+        );
+    }
+
+    #[test]
+    fn fail_fast_stops_the_app() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = context(
+            dir.path(),
+            serde_json::json!({
+                "services": [
+                    { "name": "db", "cmd": "exec sleep 30" },
+                    { "name": "job", "cmd": "exit 3" },
+                ],
+            }),
+        );
+        let mut sup = supervisor(&ctx);
+        sup.start_all().unwrap();
+        sup.wait_all().unwrap();
+        assert!(sup.teardown);
+        assert_eq!(sup.failed, ["job"]);
+        assert!(matches!(*state(&sup, "job"), State::Exited(st) if st.code() == Some(3)));
+        assert_eq!(*state(&sup, "db"), State::Running);
+        sup.stop_all();
+    }
+
+    #[test]
+    fn degrade_keeps_the_others_running() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = context(
+            dir.path(),
+            serde_json::json!({
+                "crash": "degrade",
+                "services": [
+                    { "name": "db", "cmd": "sleep 0.5" },
+                    { "name": "job", "cmd": "exit 3" },
+                ],
+            }),
+        );
+        let mut sup = supervisor(&ctx);
+        sup.start_all().unwrap();
+        // lifetime all：兩個都結束才返回
+        sup.wait_all().unwrap();
+        assert!(!sup.teardown);
+        assert_eq!(sup.failed, ["job"]);
+        assert!(matches!(*state(&sup, "db"), State::Exited(st) if st.success()));
+    }
+
+    #[test]
+    fn restart_gives_up_after_max_retries() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = context(
+            dir.path(),
+            serde_json::json!({
+                "services": [{
+                    "name": "flaky",
+                    "cmd": "echo run >> $DIR/runs; exit 1",
+                    "restart": { "policy": "on-failure", "max_retries": 2, "backoff": "10ms" },
+                }],
+            }),
+        );
+        let mut sup = supervisor(&ctx);
+        sup.start_all().unwrap();
+        sup.wait_all().unwrap();
+        let runs = std::fs::read_to_string(dir.path().join("runs")).unwrap();
+        assert_eq!(runs.lines().count(), 3);
+        assert_eq!(sup.services[0].restarts, 2);
+        assert_eq!(sup.failed, ["flaky"]);
+    }
+
+    #[test]
+    fn lifetime_primary_ends_with_the_primary() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = context(
+            dir.path(),
+            serde_json::json!({
+                "lifetime": "primary",
+                "services": [
+                    { "name": "db", "cmd": "exec sleep 30" },
+                    // 以錯誤結束也不算 crash：結束碼交給 runtime
+                    { "name": "cli", "cmd": "exit 4", "primary": true },
+                ],
+            }),
+        );
+        let mut sup = supervisor(&ctx);
+        assert_eq!(sup.primary, sup.index("cli"));
+        sup.start_all().unwrap();
+        sup.wait_all().unwrap();
+        assert!(sup.teardown);
+        assert!(sup.failed.is_empty());
+        assert!(matches!(*state(&sup, "cli"), State::Exited(st) if exit_code(st) == 4));
+        sup.stop_all();
+        assert!(matches!(*state(&sup, "db"), State::Exited(_)));
+    }
+
+    #[test]
+    fn lifetime_any_ends_with_any_service() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = context(
+            dir.path(),
+            serde_json::json!({
+                "lifetime": "any",
+                "services": [
+                    { "name": "db", "cmd": "exec sleep 30" },
+                    { "name": "once", "cmd": "true" },
+                ],
+            }),
+        );
+        let mut sup = supervisor(&ctx);
+        sup.start_all().unwrap();
+        sup.wait_all().unwrap();
+        assert!(sup.teardown);
+        assert!(sup.failed.is_empty());
+        assert_eq!(*state(&sup, "db"), State::Running);
+        sup.stop_all();
+    }
+}
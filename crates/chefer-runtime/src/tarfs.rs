@@ -0,0 +1,404 @@
+// src/tarfs.rs
+use anyhow::{Context, Result, bail};
+use chefer_assembler::ExeRegion;
+use std::{
+    collections::BTreeMap,
+    ffi::{OsStr, OsString},
+    fs::File,
+    path::{Component, Path},
+};
+use tar::{Archive, EntryType};
+
+pub const ROOT_INO: u64 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    Dir,
+    File,
+    Symlink,
+    CharDev,
+    BlockDev,
+    Fifo,
+}
+
+/// 一個 inode：檔案內容不複製，只記錄在 exe 內的絕對位移
+#[derive(Debug)]
+pub struct Node {
+    pub ino: u64,
+    pub parent: u64,
+    pub kind: NodeKind,
+    pub perm: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub mtime: u64,
+    pub size: u64,
+    pub nlink: u32,
+    pub rdev: u32,
+    /// 檔案資料在 exe 內的絕對 offset（僅 File）
+    pub data_offset: u64,
+    /// symlink 目標
+    pub link: Option<OsString>,
+    /// 目錄內容（僅 Dir）
+    pub children: BTreeMap<OsString, u64>,
+}
+
+/// 以 exe 內未壓縮 tar 區段建立的唯讀檔案樹索引
+pub struct TarIndex {
+    nodes: Vec<Node>,
+}
+
+impl TarIndex {
+    /// 掃描 exe 內 [offset, offset+length) 的 tar，只讀 header（以 seek 跳過檔案資料）
+    pub fn build(exe: &Path, offset: u64, length: u64) -> Result<Self> {
+        let file = File::open(exe).with_context(|| format!("open exe {:?}", exe))?;
+        let region = ExeRegion::new(file, offset, length);
+
+        let mut idx = TarIndex {
+            nodes: vec![Node::dir(ROOT_INO, ROOT_INO, 0o755)],
+        };
+
+        let mut ar = Archive::new(region);
+        for entry in ar.entries_with_seek()? {
+            let entry = entry?;
+            let raw_path = entry.path()?.into_owned();
+            let Some(rel) = clean_rel_path(&raw_path) else {
+                bail!("unsafe path in bundle: {:?}", raw_path);
+            };
+            let hdr = entry.header();
+            let perm = hdr.mode().unwrap_or(0o644) & 0o7777;
+            let uid = hdr.uid().unwrap_or(0) as u32;
+            let gid = hdr.gid().unwrap_or(0) as u32;
+            let mtime = hdr.mtime().unwrap_or(0);
+
+            let kind = match hdr.entry_type() {
+                EntryType::Directory => NodeKind::Dir,
+                EntryType::Regular | EntryType::Continuous => NodeKind::File,
+                EntryType::Symlink => NodeKind::Symlink,
+                EntryType::Char => NodeKind::CharDev,
+                EntryType::Block => NodeKind::BlockDev,
+                EntryType::Fifo => NodeKind::Fifo,
+                EntryType::Link => {
+                    // 硬連結：指向既有 inode
+                    let target = entry
+                        .link_name()?
+                        .and_then(|p| clean_rel_path(&p))
+                        .with_context(|| format!("bad hard link target for {:?}", raw_path))?;
+                    let ino = idx
+                        .resolve(&target)
+                        .with_context(|| format!("hard link target not found: {:?}", target))?;
+                    let parent = idx.ensure_dirs(rel.parent().unwrap_or(Path::new("")))?;
+                    let name = file_name(&rel)?;
+                    idx.nodes[(ino - 1) as usize].nlink += 1;
+                    idx.nodes[(parent - 1) as usize].children.insert(name, ino);
+                    continue;
+                }
+                EntryType::GNUSparse => bail!("sparse files are not supported: {:?}", raw_path),
+                // pax / gnu longname 等由 tar crate 處理；其他類型忽略
+                _ => continue,
+            };
+
+            if rel.as_os_str().is_empty() {
+                // 根目錄本身（"./"）
+                if kind == NodeKind::Dir {
+                    let root = &mut idx.nodes[0];
+                    root.perm = perm;
+                    root.mtime = mtime;
+                }
+                continue;
+            }
+
+            let parent = idx.ensure_dirs(rel.parent().unwrap_or(Path::new("")))?;
+            let name = file_name(&rel)?;
+
+            // 目錄可能先被隱式建立，這裡只更新屬性
+            if kind == NodeKind::Dir
+                && let Some(&existing) = idx.nodes[(parent - 1) as usize].children.get(&name)
+                && idx.nodes[(existing - 1) as usize].kind == NodeKind::Dir
+            {
+                let n = &mut idx.nodes[(existing - 1) as usize];
+                n.perm = perm;
+                n.uid = uid;
+                n.gid = gid;
+                n.mtime = mtime;
+                continue;
+            }
+
+            let ino = idx.nodes.len() as u64 + 1;
+            let mut node = Node::dir(ino, parent, perm);
+            node.kind = kind;
+            node.uid = uid;
+            node.gid = gid;
+            node.mtime = mtime;
+            match kind {
+                NodeKind::File => {
+                    node.size = entry.size();
+                    node.data_offset = offset + entry.raw_file_position();
+                }
+                NodeKind::Symlink => {
+                    let target = entry
+                        .link_name()?
+                        .map(|p| p.into_owned().into_os_string())
+                        .unwrap_or_default();
+                    node.size = target.len() as u64;
+                    node.link = Some(target);
+                }
+                NodeKind::CharDev | NodeKind::BlockDev => {
+                    let major = hdr.device_major()?.unwrap_or(0);
+                    let minor = hdr.device_minor()?.unwrap_or(0);
+                    node.rdev = (major << 8) | (minor & 0xff) | ((minor & !0xff) << 12);
+                }
+                _ => {}
+            }
+            if kind != NodeKind::Dir {
+                node.nlink = 1;
+            }
+            idx.nodes.push(node);
+            idx.nodes[(parent - 1) as usize].children.insert(name, ino);
+        }
+        Ok(idx)
+    }
+
+    pub fn get(&self, ino: u64) -> Option<&Node> {
+        ino.checked_sub(1).and_then(|i| self.nodes.get(i as usize))
+    }
+
+    pub fn lookup(&self, parent: u64, name: &OsStr) -> Option<&Node> {
+        let dir = self.get(parent)?;
+        dir.children.get(name).and_then(|&ino| self.get(ino))
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    fn resolve(&self, rel: &Path) -> Option<u64> {
+        let mut cur = ROOT_INO;
+        for comp in rel.components() {
+            cur = *self.get(cur)?.children.get(comp.as_os_str())?;
+        }
+        Some(cur)
+    }
+
+    /// 逐層建立（或取得）目錄 inode，回傳最末層目錄的 ino
+    fn ensure_dirs(&mut self, rel: &Path) -> Result<u64> {
+        let mut cur = ROOT_INO;
+        for comp in rel.components() {
+            let name = comp.as_os_str().to_os_string();
+            let next = match self.nodes[(cur - 1) as usize].children.get(&name) {
+                Some(&ino) => {
+                    if self.nodes[(ino - 1) as usize].kind != NodeKind::Dir {
+                        bail!("path component is not a directory: {:?}", rel);
+                    }
+                    ino
+                }
+                None => {
+                    let ino = self.nodes.len() as u64 + 1;
+                    self.nodes.push(Node::dir(ino, cur, 0o755));
+                    self.nodes[(cur - 1) as usize].children.insert(name, ino);
+                    ino
+                }
+            };
+            cur = next;
+        }
+        Ok(cur)
+    }
+}
+
+impl Node {
+    fn dir(ino: u64, parent: u64, perm: u32) -> Self {
+        Node {
+            ino,
+            parent,
+            kind: NodeKind::Dir,
+            perm,
+            uid: 0,
+            gid: 0,
+            mtime: 0,
+            size: 0,
+            nlink: 2,
+            rdev: 0,
+            data_offset: 0,
+            link: None,
+            children: BTreeMap::new(),
+        }
+    }
+}
+
+/// 去掉 "./"、"/"；含 ".." 視為不安全
+fn clean_rel_path(p: &Path) -> Option<std::path::PathBuf> {
+    let mut buf = std::path::PathBuf::new();
+    for comp in p.components() {
+        match comp {
+            Component::Prefix(_) | Component::RootDir | Component::CurDir => continue,
+            Component::ParentDir => return None,
+            Component::Normal(seg) => buf.push(seg),
+        }
+    }
+    Some(buf)
+}
+
+fn file_name(rel: &Path) -> Result<OsString> {
+    rel.file_name()
+        .map(|n| n.to_os_string())
+        .with_context(|| format!("bad entry path {:?}", rel))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Seek, SeekFrom, Write};
+    use tar::{Builder, Header};
+
+    /// exe 前面的假 runtime 長度，確認 offset 有算進去
+    const PREFIX: usize = 1000;
+
+    fn header(kind: EntryType, mode: u32, size: u64) -> Header {
+        let mut h = Header::new_gnu();
+        h.set_entry_type(kind);
+        h.set_mode(mode);
+        h.set_size(size);
+        h.set_mtime(1_700_000_000);
+        h
+    }
+
+    /// 把 tar 接在 PREFIX 個位元組之後，回傳 (檔案, tar 長度)
+    fn exe_with_tar(build: impl FnOnce(&mut Builder<Vec<u8>>)) -> (tempfile::NamedTempFile, u64) {
+        let mut b = Builder::new(Vec::new());
+        build(&mut b);
+        let tar = b.into_inner().unwrap();
+        let mut exe = tempfile::NamedTempFile::new().unwrap();
+        exe.write_all(&[0x7f; PREFIX]).unwrap();
+        exe.write_all(&tar).unwrap();
+        (exe, tar.len() as u64)
+    }
+
+    fn resolve<'a>(idx: &'a TarIndex, path: &str) -> &'a Node {
+        let ino = idx.resolve(Path::new(path)).expect(path);
+        idx.get(ino).unwrap()
+    }
+
+    fn content(exe: &Path, node: &Node) -> Vec<u8> {
+        let mut f = File::open(exe).unwrap();
+        f.seek(SeekFrom::Start(node.data_offset)).unwrap();
+        let mut buf = vec![0; node.size as usize];
+        f.read_exact(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn indexes_dirs_files_links_and_long_names() {
+        let long = format!("srv/{}/data.txt", "d".repeat(120));
+        let (exe, len) = exe_with_tar(|b| {
+            b.append_data(&mut header(EntryType::Directory, 0o700, 0), "./", &[][..])
+                .unwrap();
+            b.append_data(&mut header(EntryType::Directory, 0o750, 0), "etc/", &[][..])
+                .unwrap();
+            b.append_data(
+                &mut header(EntryType::Regular, 0o640, 5),
+                "./etc/hosts",
+                &b"hello"[..],
+            )
+            .unwrap();
+            // 父目錄沒有自己的 entry：隱式建立
+            b.append_data(
+                &mut header(EntryType::Regular, 0o644, 4),
+                &long,
+                &b"long"[..],
+            )
+            .unwrap();
+            b.append_link(
+                &mut header(EntryType::Link, 0o640, 0),
+                "bin/hosts",
+                "etc/hosts",
+            )
+            .unwrap();
+            b.append_link(
+                &mut header(EntryType::Symlink, 0o777, 0),
+                "etc/alias",
+                "hosts",
+            )
+            .unwrap();
+            // 目錄在內容之後才出現：只更新屬性
+            b.append_data(&mut header(EntryType::Directory, 0o711, 0), "srv/", &[][..])
+                .unwrap();
+        });
+        let idx = TarIndex::build(exe.path(), PREFIX as u64, len).unwrap();
+
+        let root = idx.get(ROOT_INO).unwrap();
+        assert_eq!(root.perm, 0o700);
+        assert_eq!(
+            root.children.keys().collect::<Vec<_>>(),
+            ["bin", "etc", "srv"]
+        );
+        assert_eq!(resolve(&idx, "etc").perm, 0o750);
+        assert_eq!(resolve(&idx, "srv").perm, 0o711);
+        assert_eq!(resolve(&idx, "srv").kind, NodeKind::Dir);
+
+        let hosts = resolve(&idx, "etc/hosts");
+        assert_eq!(hosts.kind, NodeKind::File);
+        assert_eq!(hosts.perm, 0o640);
+        assert_eq!(hosts.mtime, 1_700_000_000);
+        assert_eq!(content(exe.path(), hosts), b"hello");
+        // 硬連結共用同一個 inode
+        assert_eq!(resolve(&idx, "bin/hosts").ino, hosts.ino);
+        assert_eq!(hosts.nlink, 2);
+
+        let data = resolve(&idx, &long);
+        assert_eq!(content(exe.path(), data), b"long");
+        assert_eq!(
+            data.parent,
+            resolve(&idx, &format!("srv/{}", "d".repeat(120))).ino
+        );
+
+        let alias = resolve(&idx, "etc/alias");
+        assert_eq!(alias.kind, NodeKind::Symlink);
+        assert_eq!(alias.link.as_deref(), Some(OsStr::new("hosts")));
+        assert_eq!(alias.size, 5);
+
+        let etc = resolve(&idx, "etc");
+        assert_eq!(
+            idx.lookup(etc.ino, OsStr::new("hosts")).unwrap().ino,
+            hosts.ino
+        );
+        assert!(idx.lookup(etc.ino, OsStr::new("missing")).is_none());
+    }
+
+    #[test]
+    fn rejects_bad_entries() {
+        let (exe, len) = exe_with_tar(|b| {
+            b.append_link(&mut header(EntryType::Link, 0o644, 0), "a", "missing")
+                .unwrap();
+        });
+        let err = TarIndex::build(exe.path(), PREFIX as u64, len)
+            .err()
+            .expect("rejected");
+        assert!(
+            format!("{err:#}").contains("hard link target not found"),
+            "{err:#}"
+        );
+
+        // 檔案底下不能再有東西
+        let (exe, len) = exe_with_tar(|b| {
+            b.append_data(&mut header(EntryType::Regular, 0o644, 1), "f", &b"x"[..])
+                .unwrap();
+            b.append_data(&mut header(EntryType::Regular, 0o644, 1), "f/g", &b"y"[..])
+                .unwrap();
+        });
+        let err = TarIndex::build(exe.path(), PREFIX as u64, len)
+            .err()
+            .expect("rejected");
+        assert!(err.to_string().contains("not a directory"), "{err:#}");
+    }
+
+    #[test]
+    fn clean_rel_path_strips_roots_and_rejects_parents() {
+        let clean = |p: &str| clean_rel_path(Path::new(p)).map(|p| p.display().to_string());
+        assert_eq!(clean("./usr/bin/sh").as_deref(), Some("usr/bin/sh"));
+        assert_eq!(clean("/etc/passwd").as_deref(), Some("etc/passwd"));
+        assert_eq!(clean("a/./b/").as_deref(), Some("a/b"));
+        assert_eq!(clean("./").as_deref(), Some(""));
+        assert_eq!(clean("../etc/passwd"), None);
+        assert_eq!(clean("a/../../b"), None);
+        assert_eq!(clean("/a/.."), None);
+    }
+}
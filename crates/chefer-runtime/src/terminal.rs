@@ -0,0 +1,334 @@
+// src/terminal.rs
+//! interface_mode 為 terminal / both 的 service 接上使用者的終端機（一個 app 至多一個）。
+//!
+//! runtime 的 stdin 是終端機時，每次啟動都配一個新的 PTY 給 app（slave 端成為它的控制終端），
+//! runtime 把自己的終端機切到 raw mode 轉送輸入輸出，並同步視窗大小（SIGWINCH）。
+//! Ctrl-C、Ctrl-\ 由 PTY 依 app 自己的 termios 產生訊號給 app；Ctrl-Z 讓 app 停下後 runtime 也跟著
+//! 暫停、把終端機還給 shell，`fg` 之後一起繼續。stdin 不是終端機（pipe、檔案）時直接沿用 runtime 的 stdio。
+//! 有這種 service 時 runtime 自己的訊息只印 warn 以上（見 [`LogWriter`]），輸出就跟一般指令一樣乾淨。
+use anyhow::{Context, Result};
+use std::{
+    fs::File,
+    io::{self, IsTerminal, Read, Write},
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::ns::Io;
+
+/// Ctrl-Z 之後等 app 停下的時間；app 攔下 SIGTSTP 不停就不暫停 runtime
+const SUSPEND_WAIT: Duration = Duration::from_secs(1);
+
+/// runtime 的終端機目前在 raw mode、交給 app 使用
+static RAW: AtomicBool = AtomicBool::new(false);
+/// app 有接終端機的 service：runtime 的訊息只印 warn 以上
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// 讀到 manifest、知道有接終端機的 service 後呼叫
+pub fn quiet() {
+    QUIET.store(true, Ordering::Relaxed);
+}
+
+pub struct Terminal {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    /// 進入 raw mode 前的設定，app 結束或 runtime 暫停時還原
+    saved: libc::termios,
+    /// 目前 app 的 PTY master；app 不在（等待重啟、已結束）時為 None
+    master: Mutex<Option<Arc<File>>>,
+}
+
+impl Terminal {
+    /// stdin 不是終端機時回傳 None：app 直接沿用 runtime 的 stdio
+    pub fn open() -> Result<Option<Terminal>> {
+        if !io::stdin().is_terminal() {
+            return Ok(None);
+        }
+        let mut saved = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(0, &mut saved) } != 0 {
+            return Err(io::Error::last_os_error()).context("read terminal settings");
+        }
+        let shared = Arc::new(Shared {
+            saved,
+            master: Mutex::new(None),
+        });
+
+        let s = shared.clone();
+        std::thread::Builder::new()
+            .name("terminal-input".into())
+            .spawn(move || s.forward_input())?;
+        let s = shared.clone();
+        let mut signals = signal_hook::iterator::Signals::new([libc::SIGWINCH])?;
+        std::thread::Builder::new()
+            .name("terminal-resize".into())
+            .spawn(move || {
+                for _ in signals.forever() {
+                    s.resize();
+                }
+            })?;
+        Ok(Some(Terminal { shared }))
+    }
+
+    /// 為即將啟動的 app 配一個新的 PTY 並切到 raw mode。回傳 app 的 stdio 與轉送輸出的 thread：
+    /// app 結束、slave 端全部關閉後它會讀完剩下的輸出、還原終端機再結束。
+    pub fn attach(&self) -> Result<(Io, JoinHandle<()>)> {
+        let (mut master, mut slave) = (0, 0);
+        let size = window_size();
+        if unsafe {
+            libc::openpty(
+                &mut master,
+                &mut slave,
+                std::ptr::null_mut(),
+                std::ptr::null(),
+                &size,
+            )
+        } != 0
+        {
+            return Err(io::Error::last_os_error()).context("allocate a pty");
+        }
+        let (master, slave) =
+            unsafe { (OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave)) };
+        // 不可漏進其他 service
+        for fd in [&master, &slave] {
+            unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) };
+        }
+
+        let master = Arc::new(File::from(master));
+        *self.shared.master.lock().unwrap() = Some(master.clone());
+        self.shared.raw()?;
+        let s = self.shared.clone();
+        let pump = std::thread::Builder::new()
+            .name("terminal-output".into())
+            .spawn(move || s.forward_output(master))?;
+        Ok((Io::Pty(slave), pump))
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        self.shared.restore();
+    }
+}
+
+impl Shared {
+    fn current(&self) -> Option<Arc<File>> {
+        self.master.lock().unwrap().clone()
+    }
+
+    fn raw(&self) -> Result<()> {
+        let mut t = self.saved;
+        unsafe { libc::cfmakeraw(&mut t) };
+        if unsafe { libc::tcsetattr(0, libc::TCSADRAIN, &t) } != 0 {
+            return Err(io::Error::last_os_error()).context("switch the terminal to raw mode");
+        }
+        RAW.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn restore(&self) {
+        if RAW.swap(false, Ordering::Relaxed) {
+            unsafe { libc::tcsetattr(0, libc::TCSADRAIN, &self.saved) };
+        }
+    }
+
+    fn resize(&self) {
+        if let Some(m) = self.current() {
+            let size = window_size();
+            unsafe { libc::ioctl(m.as_raw_fd(), libc::TIOCSWINSZ, &size) };
+        }
+    }
+
+    /// 使用者的輸入一律轉給目前的 app；app 不在時丟掉
+    fn forward_input(&self) {
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = unsafe { libc::read(0, buf.as_mut_ptr().cast(), buf.len()) };
+            if n < 0 && io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            if n <= 0 {
+                return;
+            }
+            let Some(master) = self.current() else {
+                continue;
+            };
+            let mut rest = &buf[..n as usize];
+            while let Some(i) = suspend_key(&master, rest) {
+                // Ctrl-Z 本身照送，由 PTY 發 SIGTSTP 給 app
+                let _ = (&*master).write_all(&rest[..=i]);
+                self.suspend(&master);
+                rest = &rest[i + 1..];
+            }
+            let _ = (&*master).write_all(rest);
+        }
+    }
+
+    fn forward_output(&self, master: Arc<File>) {
+        let mut buf = [0u8; 8192];
+        loop {
+            match (&*master).read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => {
+                    let mut out = io::stdout().lock();
+                    let _ = out.write_all(&buf[..n]);
+                    let _ = out.flush();
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                // EIO：slave 端都關了
+                Err(_) => break,
+            }
+        }
+        let mut current = self.master.lock().unwrap();
+        if current.as_ref().is_some_and(|m| Arc::ptr_eq(m, &master)) {
+            *current = None;
+            self.restore();
+        }
+    }
+
+    /// app 真的停下（沒有攔下 SIGTSTP）就還原終端機並暫停 runtime；
+    /// 繼續後回到 raw mode、同步視窗大小，再讓 app 繼續
+    fn suspend(&self, master: &File) {
+        let pgrp = unsafe { libc::tcgetpgrp(master.as_raw_fd()) };
+        if pgrp <= 0 {
+            return;
+        }
+        let deadline = Instant::now() + SUSPEND_WAIT;
+        while !stopped(pgrp) {
+            if Instant::now() >= deadline {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        // 讓輸出 thread 先送完 app 停下前印的東西（例如還原畫面）
+        while pending(master) > 0 && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        self.restore();
+        unsafe { libc::raise(libc::SIGTSTP) };
+        if self
+            .current()
+            .is_some_and(|m| m.as_raw_fd() == master.as_raw_fd())
+        {
+            if let Err(e) = self.raw() {
+                tracing::warn!("{e:#}");
+            }
+            self.resize();
+        }
+        unsafe { libc::kill(-pgrp, libc::SIGCONT) };
+    }
+}
+
+/// bytes 中第一個會讓 PTY 發 SIGTSTP 的字元（app 關掉 ISIG 或 VSUSP 時沒有）
+fn suspend_key(master: &File, bytes: &[u8]) -> Option<usize> {
+    let mut t: libc::termios = unsafe { std::mem::zeroed() };
+    // master 上的 termios 操作作用在 slave 端
+    if unsafe { libc::tcgetattr(master.as_raw_fd(), &mut t) } != 0 || t.c_lflag & libc::ISIG == 0 {
+        return None;
+    }
+    let key = t.c_cc[libc::VSUSP];
+    // _POSIX_VDISABLE
+    if key == 0 {
+        return None;
+    }
+    bytes.iter().position(|&b| b == key)
+}
+
+/// /proc/<pid>/stat 的狀態為 T（stopped）
+fn stopped(pid: libc::pid_t) -> bool {
+    let Ok(stat) = std::fs::read_to_string(format!("/proc/{pid}/stat")) else {
+        return false;
+    };
+    stat.rsplit_once(')')
+        .and_then(|(_, rest)| rest.trim_start().chars().next())
+        == Some('T')
+}
+
+/// master 上還沒讀走的位元組數
+fn pending(master: &File) -> libc::c_int {
+    let mut n: libc::c_int = 0;
+    unsafe { libc::ioctl(master.as_raw_fd(), libc::FIONREAD, &mut n) };
+    n
+}
+
+/// runtime 終端機的大小；查不到時為 0（PTY 保持預設）
+fn window_size() -> libc::winsize {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    for fd in [0, 1] {
+        if unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut size) } == 0 {
+            break;
+        }
+    }
+    size
+}
+
+/// 印到 runtime 終端機的換行：raw mode 下要自己補 \r
+pub fn line_end() -> &'static str {
+    if RAW.load(Ordering::Relaxed) {
+        "\r\n"
+    } else {
+        "\n"
+    }
+}
+
+/// runtime 自己的 tracing 輸出：有接終端機的 service 時只印 warn 以上，raw mode 下補上 \r
+pub struct LogWriter;
+
+pub enum LogOut {
+    Plain(io::Stdout),
+    Raw(io::Stdout),
+    Discard,
+}
+
+impl<'a> MakeWriter<'a> for LogWriter {
+    type Writer = LogOut;
+
+    fn make_writer(&'a self) -> LogOut {
+        LogOut::Plain(io::stdout())
+    }
+
+    fn make_writer_for(&'a self, meta: &tracing::Metadata<'_>) -> LogOut {
+        let quiet = QUIET.load(Ordering::Relaxed) || RAW.load(Ordering::Relaxed);
+        if quiet && *meta.level() > tracing::Level::WARN {
+            LogOut::Discard
+        } else if RAW.load(Ordering::Relaxed) {
+            LogOut::Raw(io::stdout())
+        } else {
+            LogOut::Plain(io::stdout())
+        }
+    }
+}
+
+impl Write for LogOut {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            LogOut::Plain(out) => out.write(buf),
+            LogOut::Raw(out) => {
+                let mut out = out.lock();
+                for (i, part) in buf.split(|&b| b == b'\n').enumerate() {
+                    if i > 0 {
+                        out.write_all(b"\r\n")?;
+                    }
+                    out.write_all(part)?;
+                }
+                Ok(buf.len())
+            }
+            LogOut::Discard => Ok(buf.len()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            LogOut::Plain(out) | LogOut::Raw(out) => out.flush(),
+            LogOut::Discard => Ok(()),
+        }
+    }
+}
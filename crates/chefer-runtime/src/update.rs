@@ -0,0 +1,324 @@
+// src/update.rs
+//! 自我更新：讀 feed → 比版本 → 下載到 exe 同目錄 → 驗證 → 原子替換。
+//! 只替換執行檔本身，資料目錄不受影響；新版在下次啟動生效。
+use anyhow::{Context, Result, bail};
+use chefer_assembler::update::{self as feed, Feed, Release};
+use chefer_manifest::{Manifest, ManifestIdentity};
+use fs_err as fs;
+use sha2::{Digest, Sha256};
+use std::{
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+#[derive(Debug, Clone)]
+pub struct UpdateSettings {
+    pub app_name: String,
+    pub current_version: String,
+    pub feed: String,
+    pub public_key: String,
+    pub on_startup: bool,
+}
+
+impl UpdateSettings {
+    /// 從 manifest 取出 update 區段；未設定時回傳 None
+    pub fn from_manifest(mani: &Manifest) -> Option<Self> {
+        let up = mani.update.as_ref()?;
+        Some(UpdateSettings {
+            app_name: mani.app_name.clone(),
+            current_version: mani.app_version.clone().unwrap_or_default(),
+            feed: up.feed.clone(),
+            public_key: up.public_key.clone(),
+            on_startup: matches!(up.check, chefer_manifest::UpdateCheck::Startup),
+        })
+    }
+}
+
+/// 找到的新版
+pub struct Available {
+    pub version: String,
+    pub release: Release,
+}
+
+fn agent() -> ureq::Agent {
+    ureq::Agent::config_builder()
+        .timeout_connect(Some(Duration::from_secs(10)))
+        .timeout_recv_body(Some(Duration::from_secs(600)))
+        .build()
+        .into()
+}
+
+/// 讀 feed，有比目前新的版本才回傳
+pub fn check(s: &UpdateSettings) -> Result<Option<Available>> {
+    let body = agent()
+        .get(&s.feed)
+        .call()
+        .with_context(|| format!("fetch update feed {}", s.feed))?
+        .body_mut()
+        .read_to_string()?;
+    let feed: Feed = serde_json::from_str(&body).context("parse update feed")?;
+    if feed.app != s.app_name {
+        bail!("update feed is for `{}`, not `{}`", feed.app, s.app_name);
+    }
+    if !feed::is_newer(&feed.version, &s.current_version) {
+        return Ok(None);
+    }
+    let platform = feed::platform_key();
+    let Some(release) = feed.platforms.get(&platform) else {
+        bail!("update {} has no build for {platform}", feed.version);
+    };
+    // 下載前先驗簽，避免白白下載被竄改的 feed
+    feed::verify(
+        &s.public_key,
+        &s.app_name,
+        &feed.version,
+        &platform,
+        release,
+    )?;
+    Ok(Some(Available {
+        version: feed.version.clone(),
+        release: release.clone(),
+    }))
+}
+
+/// 下載、驗證並替換 exe
+pub fn install(s: &UpdateSettings, exe: &Path, avail: &Available) -> Result<()> {
+    let staged = staging_path(exe);
+    let res = download(s, avail, &staged).and_then(|_| verify_staged(s, avail, &staged));
+    if let Err(e) = res {
+        let _ = fs::remove_file(&staged);
+        return Err(e);
+    }
+    replace_exe(&staged, exe)
+}
+
+fn download(s: &UpdateSettings, avail: &Available, staged: &Path) -> Result<()> {
+    let url = feed::resolve_url(&s.feed, &avail.release.url);
+    let resp = agent()
+        .get(&url)
+        .call()
+        .with_context(|| format!("download {url}"))?;
+    let mut reader = resp.into_body().into_reader();
+    let mut out = io::BufWriter::new(fs::File::create(staged)?);
+    // 多讀 1 byte 以偵測超出宣告大小
+    let n = io::copy(&mut (&mut reader).take(avail.release.size + 1), &mut out)?;
+    out.flush()?;
+    if n != avail.release.size {
+        bail!("downloaded {n} bytes, feed says {}", avail.release.size);
+    }
+    Ok(())
+}
+
+fn verify_staged(s: &UpdateSettings, avail: &Available, staged: &Path) -> Result<()> {
+    let mut f = fs::File::open(staged)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut f, &mut hasher)?;
+    let got = hex::encode(hasher.finalize());
+    if !got.eq_ignore_ascii_case(&avail.release.sha256) {
+        bail!("downloaded update sha256 mismatch");
+    }
+
+    // 必須是完整的 chefer 單檔，而且是同一個 app
+    chefer_assembler::verify_payload(staged)
+        .context("downloaded update is not a valid executable")?;
+    let mani = chefer_assembler::read_bundle_file(staged, chefer_manifest::MANIFEST_FILE)?
+        .context("downloaded update has no manifest.json")?;
+    // 新版的 manifest_version 可能比目前 runtime 新，只比對身分
+    let head = ManifestIdentity::from_slice(&mani)?;
+    if head.app_name != s.app_name {
+        bail!("downloaded update is for `{}`", head.app_name);
+    }
+    Ok(())
+}
+
+fn staging_path(exe: &Path) -> PathBuf {
+    let name = exe.file_name().unwrap_or_default().to_string_lossy();
+    exe.with_file_name(format!(".{name}.update"))
+}
+
+#[cfg(unix)]
+fn replace_exe(staged: &Path, exe: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = fs::metadata(exe)?.permissions().mode() | 0o111;
+    fs::set_permissions(staged, std::fs::Permissions::from_mode(mode))?;
+    // 同目錄 rename 是原子的；執行中的行程仍持有舊 inode
+    fs::rename(staged, exe).with_context(|| format!("replace {:?}", exe))?;
+    Ok(())
+}
+
+#[cfg(windows)]
+fn replace_exe(staged: &Path, exe: &Path) -> Result<()> {
+    // 執行中的 exe 不能覆寫，但可以改名；舊檔於下次啟動時清掉
+    let old = exe.with_extension("old");
+    let _ = fs::remove_file(&old);
+    fs::rename(exe, &old)?;
+    if let Err(e) = fs::rename(staged, exe) {
+        let _ = fs::rename(&old, exe);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// 清掉上次更新留下的舊檔（僅 Windows 會有）
+pub fn cleanup_previous(exe: &Path) {
+    if cfg!(windows) {
+        let _ = std::fs::remove_file(exe.with_extension("old"));
+    }
+}
+
+/// `--chefer-update` / `--chefer-check-update`
+pub fn run_manual(s: &UpdateSettings, exe: &Path, check_only: bool) -> Result<()> {
+    match check(s)? {
+        None => println!("{} {} is up to date", s.app_name, s.current_version),
+        Some(avail) if check_only => println!(
+            "update available: {} → {}",
+            s.current_version, avail.version
+        ),
+        Some(avail) => {
+            install(s, exe, &avail)?;
+            println!(
+                "updated {} {} → {}; restart to use the new version",
+                s.app_name, s.current_version, avail.version
+            );
+        }
+    }
+    Ok(())
+}
+
+/// 背景更新；drop 時（app 結束）若還沒完成，刪掉下載到一半的暫存檔
+pub struct StartupCheck {
+    staged: PathBuf,
+    done: Arc<AtomicBool>,
+}
+
+impl Drop for StartupCheck {
+    fn drop(&mut self) {
+        if !self.done.load(Ordering::Acquire) {
+            let _ = std::fs::remove_file(&self.staged);
+        }
+    }
+}
+
+/// 啟動時在背景檢查；失敗只記 log，不影響 app 執行
+pub fn spawn_startup_check(s: UpdateSettings, exe: PathBuf) -> StartupCheck {
+    let staged = staging_path(&exe);
+    let done = Arc::new(AtomicBool::new(false));
+    let finished = done.clone();
+    // 上次被強制結束（SIGKILL）時留下的暫存檔
+    let _ = std::fs::remove_file(&staged);
+    let _ = std::thread::Builder::new()
+        .name("chefer-update".into())
+        .spawn(move || {
+            match check(&s) {
+                Ok(None) => tracing::debug!("no update available"),
+                Ok(Some(avail)) => match install(&s, &exe, &avail) {
+                    Ok(()) => tracing::info!(
+                        "update {} installed; it will be used on next launch",
+                        avail.version
+                    ),
+                    Err(e) => tracing::warn!("update {} failed: {e:#}", avail.version),
+                },
+                Err(e) => tracing::warn!("update check failed: {e:#}"),
+            }
+            finished.store(true, Ordering::Release);
+        });
+    StartupCheck { staged, done }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chefer_assembler::update::{generate_keypair, platform_key, sign};
+    use std::{collections::BTreeMap, net::TcpListener};
+
+    const SHA: &str = "ab12000000000000000000000000000000000000000000000000000000000000";
+
+    /// 本機 HTTP stand-in：每個請求都回同一份 feed
+    fn serve(body: String) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/feed.json", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for mut conn in listener.incoming().flatten() {
+                let mut req = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !req.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match conn.read(&mut buf) {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => req.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let _ = write!(
+                    conn,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+            }
+        });
+        url
+    }
+
+    fn feed(app: &str, version: &str, secret: &str) -> String {
+        let platform = platform_key();
+        let release = Release {
+            url: "App.run".into(),
+            size: 1,
+            sha256: SHA.into(),
+            signature: sign(secret, app, version, &platform, SHA).unwrap(),
+        };
+        serde_json::to_string(&Feed {
+            app: app.into(),
+            version: version.into(),
+            platforms: BTreeMap::from([(platform, release)]),
+        })
+        .unwrap()
+    }
+
+    fn settings(feed: String, public_key: String) -> UpdateSettings {
+        UpdateSettings {
+            app_name: "App".into(),
+            current_version: "1.0.0".into(),
+            feed,
+            public_key,
+            on_startup: false,
+        }
+    }
+
+    #[test]
+    fn newer_signed_release_is_offered() {
+        let (secret, public) = generate_keypair().unwrap();
+        let s = settings(serve(feed("App", "1.1.0", &secret)), public);
+        let avail = check(&s).unwrap().expect("update available");
+        assert_eq!(avail.version, "1.1.0");
+        assert_eq!(avail.release.sha256, SHA);
+    }
+
+    #[test]
+    fn release_signed_with_other_key_is_rejected() {
+        let (secret, _) = generate_keypair().unwrap();
+        let (_, other) = generate_keypair().unwrap();
+        let s = settings(serve(feed("App", "1.1.0", &secret)), other);
+        let err = check(&s).err().expect("bad signature");
+        assert!(format!("{err:#}").contains("signature"), "{err:#}");
+    }
+
+    #[test]
+    fn same_or_older_version_is_not_offered() {
+        let (secret, public) = generate_keypair().unwrap();
+        for version in ["1.0.0", "0.9.0", "nightly"] {
+            let s = settings(serve(feed("App", version, &secret)), public.clone());
+            assert!(check(&s).unwrap().is_none(), "{version}");
+        }
+    }
+
+    #[test]
+    fn feed_for_another_app_is_rejected() {
+        let (secret, public) = generate_keypair().unwrap();
+        let s = settings(serve(feed("Other", "1.1.0", &secret)), public);
+        assert!(check(&s).is_err());
+    }
+}
@@ -0,0 +1,113 @@
+// src/vars.rs
+//! 代入 appcipe.yml 中留到啟動時才解析的變數（`${CHEFER_DATA_DIR}`、`${CHEFER_EXE_DIR}`、`${HOME}`），
+//! 並把 mounts 的 host 路徑與 secrets 的 file 解析成絕對路徑。
+//!
+//! `chefer build` 已展開其餘的 `${VAR}`，只在 service 的 env、cmd、handoff 保留這些變數與 `$$`；
+//! 這裡再展開一次，`$$` 成為字面的 `$`。路徑用的是錨點（`$DATA_DIR/...`），見 [`PathAnchor`]。
+use anyhow::{Context, Result, anyhow};
+use chefer_manifest::{Cmd, Manifest, PathAnchor, interpolate, path_anchor};
+use std::path::{Path, PathBuf};
+
+/// 展開 manifest 中各 service 的 env、cmd、handoff，解析 mounts 的 host 路徑與 secrets 的 file
+pub fn expand(mani: &mut Manifest, data_dir: &Path, exe: &Path) -> Result<()> {
+    let exe_dir = exe.parent().unwrap_or(Path::new("."));
+    // 與 appcipe_spec::RUNTIME_VARS 一致
+    let lookup = |name: &str| match name {
+        "CHEFER_DATA_DIR" => Some(data_dir.display().to_string()),
+        "CHEFER_EXE_DIR" => Some(exe_dir.display().to_string()),
+        "HOME" => std::env::var("HOME").ok(),
+        _ => None,
+    };
+    for svc in &mut mani.services {
+        let name = &svc.name;
+        for (k, v) in &mut svc.env {
+            *v = interpolate(v, lookup).map_err(|e| anyhow!("service `{name}` env {k}: {e}"))?;
+        }
+        for (field, cmd) in [("cmd", &mut svc.cmd), ("handoff", &mut svc.handoff)] {
+            if let Some(cmd) = cmd {
+                expand_cmd(cmd, lookup).map_err(|e| anyhow!("service `{name}` {field}: {e}"))?;
+            }
+        }
+        for m in &mut svc.mounts {
+            let (anchor, rest) = m.anchor().map_err(|e| anyhow!("service `{name}`: {e}"))?;
+            let host = resolve(anchor, rest, data_dir, exe_dir)
+                .with_context(|| format!("service `{name}` mount {m}"))?;
+            m.source = host.display().to_string();
+        }
+    }
+    for (name, secret) in &mut mani.secrets {
+        if let Some(file) = &mut secret.file {
+            let (anchor, rest) =
+                path_anchor(file).map_err(|e| anyhow!("secret `{name}` file: {e}"))?;
+            let path = resolve(anchor, rest, data_dir, exe_dir)
+                .with_context(|| format!("secret `{name}` file {file}"))?;
+            *file = path.display().to_string();
+        }
+    }
+    Ok(())
+}
+
+/// 錨點換成使用者機器上的資料夾
+fn resolve(
+    anchor: Option<PathAnchor>,
+    rest: &str,
+    data_dir: &Path,
+    exe_dir: &Path,
+) -> Result<PathBuf> {
+    let base = match anchor {
+        None => PathBuf::new(),
+        Some(PathAnchor::ExeDir) => exe_dir.to_path_buf(),
+        Some(PathAnchor::DataDir) => data_dir.to_path_buf(),
+        Some(PathAnchor::Home) => std::env::var_os("HOME")
+            .map(PathBuf::from)
+            .context("HOME is not set")?,
+        Some(PathAnchor::Cwd) => std::env::current_dir()?,
+    };
+    // join("") 會多一個結尾的 '/'
+    Ok(if rest.is_empty() {
+        base
+    } else {
+        base.join(rest)
+    })
+}
+
+fn expand_cmd(cmd: &mut Cmd, lookup: impl Fn(&str) -> Option<String> + Copy) -> Result<(), String> {
+    match cmd {
+        Cmd::String(s) => *s = interpolate(s, lookup)?,
+        Cmd::Array(a) => {
+            for s in a {
+                *s = interpolate(s, lookup)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_anchors() {
+        let (data, exe) = (Path::new("/data/App"), Path::new("/opt/app"));
+        let r = |a, rest| resolve(a, rest, data, exe).unwrap();
+        assert_eq!(
+            r(Some(PathAnchor::DataDir), "db"),
+            Path::new("/data/App/db")
+        );
+        assert_eq!(r(Some(PathAnchor::ExeDir), ""), Path::new("/opt/app"));
+        assert_eq!(r(None, "/srv/x"), Path::new("/srv/x"));
+        assert_eq!(
+            r(Some(PathAnchor::Cwd), "in"),
+            std::env::current_dir().unwrap().join("in")
+        );
+    }
+
+    #[test]
+    fn expand_cmd_keeps_escapes_literal() {
+        let lookup = |name: &str| (name == "CHEFER_DATA_DIR").then(|| "/data/App".to_string());
+        let mut cmd = Cmd::Array(vec!["--root=${CHEFER_DATA_DIR}".into(), "$${PATH}".into()]);
+        expand_cmd(&mut cmd, lookup).unwrap();
+        assert!(matches!(&cmd, Cmd::Array(a) if a == &["--root=/data/App", "${PATH}"]));
+    }
+}
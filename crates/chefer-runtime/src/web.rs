@@ -0,0 +1,359 @@
+// src/web.rs
+//! interface_mode web：service 在容器內的埠接受連線後，以瀏覽器開啟它發布到 host 的位址。
+//!
+//! 開啟的指令依序為 `--chefer-browser`、`$BROWSER`、`xdg-open`；URL 接在最後，
+//! 或取代指令中的 `%s`。`--chefer-browser none` 不開瀏覽器，只印出網址。
+//! app 執行期間另在 127.0.0.1 提供一個小頁面，顯示 app 是否在執行並附上開啟的連結。
+//! 再次啟動單檔（見 instance.rs）時重新開啟瀏覽器。
+use anyhow::{Context, Result, bail};
+use chefer_manifest::{Manifest, Protocol};
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream},
+    process::{Command, Stdio},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use crate::{net::Upstream, publish::Published};
+
+/// 預設的開啟指令
+const DEFAULT_OPENER: &str = "xdg-open";
+/// 等 service 開好埠時每次嘗試的間隔
+const POLL: Duration = Duration::from_millis(250);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+/// 狀態頁讀取請求的時限
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct WebUi {
+    app: String,
+    service: String,
+    /// service 在容器內聽的埠
+    port: u16,
+    /// 給瀏覽器的網址（host 上發布的位址）
+    url: String,
+    upstream: Upstream,
+    /// 開啟瀏覽器的指令；"none" 不開
+    opener: String,
+}
+
+/// 執行中的 web UI
+pub struct Web {
+    ui: Arc<WebUi>,
+}
+
+/// 有 web service 時開狀態頁，並在背景等它開好後開啟瀏覽器
+pub fn start(
+    mani: &Manifest,
+    published: &[Published],
+    upstream: &Upstream,
+    browser: Option<&str>,
+    stop: Arc<AtomicBool>,
+) -> Result<Option<Web>> {
+    let Some((svc, port, path)) = mani
+        .services
+        .iter()
+        .find_map(|s| s.interface_mode.web().map(|(port, path)| (s, port, path)))
+    else {
+        return Ok(None);
+    };
+    let Some(p) = published.iter().find(|p| {
+        p.service == svc.name && p.mapping.guest == port && p.mapping.proto == Protocol::Tcp
+    }) else {
+        bail!("service `{}`: web port {port} is not published", svc.name);
+    };
+    let opener = match browser {
+        Some(cmd) => Some(cmd.to_string()),
+        None => std::env::var("BROWSER")
+            .ok()
+            // $BROWSER 可以是以 : 分隔的候選清單，取第一個
+            .and_then(|b| b.split(':').next().map(str::to_string))
+            .filter(|b| !b.trim().is_empty()),
+    }
+    .unwrap_or_else(|| DEFAULT_OPENER.to_string());
+    let ui = Arc::new(WebUi {
+        app: mani.app_name.clone(),
+        service: svc.name.clone(),
+        port,
+        url: format!("http://{}{path}", reachable(p.addr)),
+        upstream: upstream.clone(),
+        opener,
+    });
+
+    match serve_status(ui.clone()) {
+        Ok(addr) => tracing::info!("status page at http://{addr}/"),
+        Err(e) => tracing::warn!("cannot serve the status page: {e}"),
+    }
+
+    let waiter = ui.clone();
+    std::thread::Builder::new()
+        .name("web-open".into())
+        .spawn(move || waiter.wait_and_open(&stop))?;
+    Ok(Some(Web { ui }))
+}
+
+impl Web {
+    /// 再開一次瀏覽器；還沒開好時等它開好會自己開
+    pub fn reopen(&self) -> String {
+        let ui = &self.ui;
+        if !ui.ready(CONNECT_TIMEOUT) {
+            return format!(
+                "{} is still starting; the browser opens once it is ready",
+                ui.app
+            );
+        }
+        if ui.opener != "none" {
+            let ui = ui.clone();
+            std::thread::spawn(move || ui.open());
+        }
+        format!("{} is at {}", ui.app, ui.url)
+    }
+}
+
+/// 在 127.0.0.1 的任意埠開狀態頁，回傳它的位址
+fn serve_status(ui: Arc<WebUi>) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+    let addr = listener.local_addr()?;
+    std::thread::Builder::new()
+        .name("web-status".into())
+        .spawn(move || ui.serve(listener))?;
+    Ok(addr)
+}
+
+/// 瀏覽器連得到的位址：bind 在任意位址時改用 loopback
+fn reachable(addr: SocketAddr) -> SocketAddr {
+    match addr.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => {
+            SocketAddr::new(Ipv4Addr::LOCALHOST.into(), addr.port())
+        }
+        IpAddr::V6(ip) if ip.is_unspecified() => {
+            SocketAddr::new(Ipv6Addr::LOCALHOST.into(), addr.port())
+        }
+        _ => addr,
+    }
+}
+
+impl WebUi {
+    fn ready(&self, timeout: Duration) -> bool {
+        self.upstream.connect_tcp(self.port, timeout).is_ok()
+    }
+
+    fn wait_and_open(&self, stop: &AtomicBool) {
+        while !self.ready(CONNECT_TIMEOUT) {
+            if stop.load(Ordering::Relaxed) {
+                return;
+            }
+            std::thread::sleep(POLL);
+        }
+        if stop.load(Ordering::Relaxed) {
+            return;
+        }
+        tracing::info!("service `{}` is ready at {}", self.service, self.url);
+        if self.opener != "none" {
+            self.open();
+        }
+    }
+
+    fn open(&self) {
+        if let Err(e) = launch_browser(&self.opener, &self.url) {
+            tracing::warn!("cannot open a browser ({e:#}); open {} yourself", self.url);
+        }
+    }
+
+    fn serve(&self, listener: TcpListener) {
+        for conn in listener.incoming() {
+            let Ok(conn) = conn else { continue };
+            if let Err(e) = self.respond(conn) {
+                tracing::debug!("status page: {e}");
+            }
+        }
+    }
+
+    fn respond(&self, conn: TcpStream) -> io::Result<()> {
+        conn.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+        let mut reader = BufReader::new(&conn);
+        let mut request = String::new();
+        reader.read_line(&mut request)?;
+        // 讀完標頭再回應
+        let mut line = String::new();
+        while reader.read_line(&mut line)? > 2 {
+            line.clear();
+        }
+        let path = request.split_whitespace().nth(1).unwrap_or("");
+        let (status, body) = match path {
+            "/" => ("200 OK", self.page()),
+            _ => ("404 Not Found", "not found\n".to_string()),
+        };
+        let mut out = &conn;
+        write!(
+            out,
+            "HTTP/1.1 {status}\r\nContent-Type: text/html; charset=utf-8\r\n\
+             Content-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    fn page(&self) -> String {
+        let (refresh, status) = if self.ready(Duration::from_millis(500)) {
+            (
+                "",
+                format!(
+                    "<p>{} is running.</p><p><a href=\"{}\">Open {}</a></p>",
+                    self.app, self.url, self.app
+                ),
+            )
+        } else {
+            // 還沒開好（或正在重啟）：每秒重新整理
+            (
+                "<meta http-equiv=\"refresh\" content=\"1\">",
+                format!("<p>{} is starting…</p>", self.app),
+            )
+        };
+        format!(
+            "<!doctype html><html><head><meta charset=\"utf-8\">{refresh}<title>{}</title>\
+             <style>body{{font-family:sans-serif;text-align:center;margin-top:20vh}}</style>\
+             </head><body><h1>{}</h1>{status}</body></html>\n",
+            self.app, self.app
+        )
+    }
+}
+
+/// 以 `opener` 開啟網址；指令有 `%s` 時取代它，否則接在最後
+fn launch_browser(opener: &str, url: &str) -> Result<()> {
+    let mut argv: Vec<String> = opener.split_whitespace().map(str::to_string).collect();
+    if argv.is_empty() {
+        bail!("the browser command is empty");
+    }
+    if !argv.iter().any(|a| a.contains("%s")) {
+        argv.push("%s".into());
+    }
+    let argv: Vec<String> = argv.iter().map(|a| a.replace("%s", url)).collect();
+    let status = Command::new(&argv[0])
+        .args(&argv[1..])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .with_context(|| format!("run `{}`", argv[0]))?;
+    if !status.success() {
+        bail!("`{}` {status}", argv[0]);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, io::Read, path::Path, time::Instant};
+
+    /// 假的瀏覽器：把收到的參數寫進 `out`
+    fn stub_opener(dir: &Path, out: &Path) -> String {
+        let script = dir.join("browser.sh");
+        fs::write(&script, format!("echo \"$@\" > {}\n", out.display())).unwrap();
+        format!("sh {}", script.display())
+    }
+
+    fn wait_for(path: &Path) -> String {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            if let Ok(s) = fs::read_to_string(path)
+                && s.ends_with('\n')
+            {
+                return s;
+            }
+            assert!(Instant::now() < deadline, "the browser was not opened");
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    fn get(addr: SocketAddr, path: &str) -> String {
+        let mut conn = TcpStream::connect(addr).unwrap();
+        write!(conn, "GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        conn.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn opener_gets_the_url() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("args");
+        let stub = stub_opener(dir.path(), &out);
+
+        launch_browser(&format!("{stub} --url=%s --new"), "http://127.0.0.1:1/x").unwrap();
+        assert_eq!(
+            fs::read_to_string(&out).unwrap(),
+            "--url=http://127.0.0.1:1/x --new\n"
+        );
+        launch_browser(&stub, "http://127.0.0.1:1/").unwrap();
+        assert_eq!(fs::read_to_string(&out).unwrap(), "http://127.0.0.1:1/\n");
+
+        assert!(launch_browser("false", "http://127.0.0.1:1/").is_err());
+        assert!(launch_browser("  ", "http://127.0.0.1:1/").is_err());
+    }
+
+    #[test]
+    fn opens_the_published_url_and_serves_the_status_page() {
+        // 以 host 網路代替容器：service 直接聽 127.0.0.1，發布的位址就是它本身
+        let service = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = service.local_addr().unwrap().port();
+        let mani = chefer_manifest::Manifest::from_slice(
+            serde_json::json!({
+                "manifest_version": 3,
+                "app_name": "App",
+                "spec_version": "0.1",
+                "generated_at_utc": "2026-01-01T00:00:00Z",
+                "services": [{
+                    "name": "ui",
+                    "rootfs_rel": "services/ui/rootfs",
+                    "ports": [format!("{port}:{port}")],
+                    "interface_mode": { "web": { "port": port, "path": "/app" } },
+                }],
+            })
+            .to_string()
+            .as_bytes(),
+        )
+        .unwrap();
+        let published = [Published {
+            service: "ui".into(),
+            mapping: chefer_manifest::PortMapping {
+                bind: Ipv4Addr::UNSPECIFIED.into(),
+                host: port,
+                guest: port,
+                proto: Protocol::Tcp,
+            },
+            addr: SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port),
+        }];
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("args");
+        let browser = format!("{} --app=%s", stub_opener(dir.path(), &out));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let web = start(
+            &mani,
+            &published,
+            &Upstream::Host,
+            Some(&browser),
+            stop.clone(),
+        )
+        .unwrap()
+        .expect("a web service");
+        let url = format!("http://127.0.0.1:{port}/app");
+        assert_eq!(wait_for(&out), format!("--app={url}\n"));
+
+        let status = serve_status(web.ui.clone()).unwrap();
+        let page = get(status, "/");
+        assert!(page.starts_with("HTTP/1.1 200 OK\r\n"), "{page}");
+        assert!(page.contains("App is running."), "{page}");
+        assert!(page.contains(&format!("href=\"{url}\"")), "{page}");
+        assert!(get(status, "/favicon.ico").starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+        drop(service);
+        let page = get(status, "/");
+        assert!(page.contains("App is starting…"), "{page}");
+        stop.store(true, Ordering::Relaxed);
+    }
+}